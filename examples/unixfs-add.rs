@@ -51,9 +51,13 @@ async fn main() -> anyhow::Result<()> {
                     anyhow::bail!("Unknown error while writting to blockstore");
                 }
             }
+            UnixfsStatus::EntryStatus { path, cid, .. } => {
+                println!("stored {} as {cid}", path.display());
+            }
             UnixfsStatus::CompletedStatus { path, written, .. } => {
                 println!("{written} been stored with path {path}");
             }
+            _ => {}
         }
     }
 
@@ -67,11 +67,15 @@ async fn main() -> anyhow::Result<()> {
                     anyhow::bail!("Unknown error while writting to disk");
                 }
             }
+            UnixfsStatus::EntryStatus { path, cid, .. } => {
+                println!("written {cid} to {}", path.display());
+            }
             UnixfsStatus::CompletedStatus { written, .. } => {
                 let path = dest;
                 println!("{written} been written successfully to {}", path.display());
                 break;
             }
+            _ => {}
         }
     }
 
@@ -0,0 +1,168 @@
+//! go-ipfs compatible configuration file handling and setup.
+
+use anyhow::Context;
+use base64::Engine;
+use rust_ipfs::{Keypair, Multiaddr};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+
+/// Defines the configuration types supported by the API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Profile {
+    /// Runs the daemon using ephemeral ports on the loopback interface
+    Test,
+    /// Runs the daemon on the ports of kubo, 4001 for the swarm and 5001 for the API
+    Default,
+}
+
+/// The configuration of the node read from the file.
+#[derive(Debug)]
+pub struct Config {
+    pub keypair: Keypair,
+    pub swarm: Vec<Multiaddr>,
+    pub api_addr: Multiaddr,
+}
+
+/// Creates the IPFS_PATH directory structure and creates a new compatible configuration file with
+/// an Ed25519 key. Returns the Peer ID.
+pub fn init(ipfs_path: &Path, profile: Profile) -> anyhow::Result<String> {
+    use std::fs::OpenOptions;
+    use std::io::{BufWriter, Write};
+
+    let (api, swarm) = match profile {
+        Profile::Test => ("/ip4/127.0.0.1/tcp/0", "/ip4/127.0.0.1/tcp/0"),
+        Profile::Default => ("/ip4/127.0.0.1/tcp/5001", "/ip4/0.0.0.0/tcp/4001"),
+    };
+
+    let keypair = Keypair::generate_ed25519();
+    let peer_id = keypair.public().to_peer_id().to_string();
+    let private_key =
+        base64::engine::general_purpose::STANDARD.encode(keypair.to_protobuf_encoding()?);
+
+    let config_contents = CompatibleConfigFile {
+        identity: Identity {
+            peer_id: peer_id.clone(),
+            private_key,
+        },
+        addresses: Addresses {
+            swarm: vec![swarm.parse()?],
+            api: api.parse()?,
+        },
+    };
+
+    std::fs::create_dir_all(ipfs_path)
+        .with_context(|| format!("failed to create repository path {ipfs_path:?}"))?;
+
+    let config_path = ipfs_path.join("config");
+    let config_file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&config_path)
+        .with_context(|| format!("failed to create the configuration file {config_path:?}"))?;
+
+    let mut writer = BufWriter::new(config_file);
+    serde_json::to_writer_pretty(&mut writer, &config_contents)?;
+    writer.flush()?;
+
+    Ok(peer_id)
+}
+
+/// Loads a `go-ipfs` compatible configuration file from the given file.
+///
+/// Returns only the keypair and the addresses, the rest of the configuration of kubo is ignored.
+pub fn load(config: File) -> anyhow::Result<Config> {
+    use std::io::BufReader;
+
+    let config_file: CompatibleConfigFile =
+        serde_json::from_reader(BufReader::new(config)).context("invalid configuration file")?;
+
+    let keypair = config_file.identity.load_keypair()?;
+
+    let peer_id = keypair.public().to_peer_id().to_string();
+
+    anyhow::ensure!(
+        peer_id == config_file.identity.peer_id,
+        "peer id mismatch: the private key is for {peer_id} but {} was configured",
+        config_file.identity.peer_id
+    );
+
+    Ok(Config {
+        keypair,
+        swarm: config_file.addresses.swarm,
+        api_addr: config_file.addresses.api,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CompatibleConfigFile {
+    identity: Identity,
+    addresses: Addresses,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Addresses {
+    swarm: Vec<Multiaddr>,
+    #[serde(rename = "API")]
+    api: Multiaddr,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Identity {
+    #[serde(rename = "PeerID")]
+    peer_id: String,
+    #[serde(rename = "PrivKey")]
+    private_key: String,
+}
+
+impl Identity {
+    fn load_keypair(&self) -> anyhow::Result<Keypair> {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(&self.private_key)
+            .context("private key is not base64")?;
+
+        Keypair::from_protobuf_encoding(&bytes).context("failed to load the private key")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{init, load, Profile};
+
+    #[test]
+    fn init_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let peer_id = init(dir.path(), Profile::Test).unwrap();
+        let config = load(std::fs::File::open(dir.path().join("config")).unwrap()).unwrap();
+
+        assert_eq!(config.keypair.public().to_peer_id().to_string(), peer_id);
+        assert_eq!(config.api_addr.to_string(), "/ip4/127.0.0.1/tcp/0");
+
+        // the keys are not replaced
+        assert!(init(dir.path(), Profile::Test).is_err());
+    }
+
+    #[test]
+    fn read_private_key_from_goipfs() {
+        use super::Identity;
+
+        // generated with go-ipfs 0.4.23, init --bits 2048
+        let input = Identity {
+            peer_id: String::from("QmVNXj4TENKBjaUmQQzYMawDXu5LcEEzLyf4K6Ds3WgcF3"),
+            private_key: String::from("CAASqQkwggSlAgEAAoIBAQDVwj2MoXUccztSbZarmjQusB+7dZw1ZDycnGlOtLTjsc/Fl7keESwQB+nSXvt3DjV+ftTmK3nPODNVY2c+nooyX3k9svQogSmHxfQIwHkKe11VmrMNTdsYwfswcDq4PgNWrGX8/vUBtfvVb0qzgevBXwc4C9+SDIhRtjiHRNSexc2vFx59tQv03VTfj3sbxdBTwWN+ReeCTyf+7nE3Mg7NdHQ78mysMDFT3w1HDwZ+qt4dpyH5mZRm0anNWQUBtQue7IwzUsHzVCUzm+NeYXJf/miSNw2CCQUfA245+H6zu1F0SJFvTVTKCEmZ7D2ZkseRG73Srm0rdD1jajLiBhUtAgMBAAECggEBALy/mHOuOefWRGKDjBCYyE0Vjd+MeVOX4AF2B3LNFBEeeFWEpJxNE3hQVIJDBo7ZCBlbSwi3CQcWHBXhAVCE04ipTzhQ5VFCw/Y0sEhuFDNSPVcSk9pCjh1tZC0gXGlFsNL+xcvBIXzSQb30WKTrKs6D567wpQikclacrYucFpbee5/wE6GdE9mtrXK69vP5vgAtLQmg0TZljDI78agPwbEUlTVoVxA1JCcroWBfVjuY/xPjBcHUO+8fKsh2P5vqsiwcvbd8Pc4BwqJsct1LbE4sFvHjUvj6XQR3bS38z7XsaqWV9y65s/xgNQdw5zpt+wlRwNjN6+7djPKYRrSZO6ECgYEA6Fdv54Z4Yk7JRwjWSe2aWR3Mz/4o7UM2ILhMhb6DxEpiXfcErbPTqdFdnAuZ3Yp8cEyR2TZB4PYEAxh9zmS+akO1CqG9XaD6ZX76pvM/5p+Kpd6M/wbDNtYFY7tTuLX7J7IXA6vsUaMF4nZxsEp0EvF1wXB29ZiRp4oan7C/FYUCgYEA64ZlbjYb7LSfFGyJl/VaH5ka2Y1L9XWApgY+YphV6e2gCT7kaOKjxve0t0quYQMnpPJKw9MWPSNh2TE9XjJJcpR/EgkEX9/rBMg8VScqyxtItS/voUrW79qCwrHhRR5iY77a9rAZwVkl0EDyIx+cq7ebyK0OCz6891//FBWdnYkCgYBhfxeBU0c/EYqa2VV6zk7fqIainSe1cGfNUSkjUm/etcwTXC3FalmewDGE4sVdVtijEy58tKzuZq4GUoewTUwuMV1OKdLZ8ExCvQcXeanN8BLxSbNm7QKMB0FZuWkHcK4E2VGZA9L16u/0OPm6HXQZ4uMkGjqBEtXENUq4yiVVNQKBgQCzshydU+dGWCCvYogwSl/yj8vuhGGZ64a2JTlf3D5gdo6Nv1BhvdmbKs7UscQN/Gw46yuj8N+c0ewL3AeoYNGs/CNfTUXrKFqVkXiGt5Vs1WpJ40L/WqxW3+64QSNQqvgChlFlucJMxImXNJYJukq8sR/IolB+v+VJEBL77eoNkQKBgQCFQYL064rQZqEBc1dWy2Cucf5eWH5VFBHxCPC5Y6orxpmljYuduAIO0+InoVC+KEAkRPjHU3gFGdBvlDif3x2a8eFsZl//RCd9QdpTToynhl+WNKqQH87kfjsBoFW1L5QYLTbKK558QUp9yR6siKW0viXDbOvB7lK8WaDdYX8lcA=="),
+        };
+
+        let peer_id = input
+            .load_keypair()
+            .unwrap()
+            .public()
+            .to_peer_id()
+            .to_string();
+
+        assert_eq!(peer_id, input.peer_id);
+    }
+}
@@ -0,0 +1,12 @@
+//! Kubo compatible `/api/v0` HTTP RPC API on top of `rust-ipfs`.
+//!
+//! The [`v0::routes`] can be served next to an application's own routes, and the binary of this
+//! crate runs a node with the API much like `ipfs daemon` does, so that tooling speaking kubo's
+//! RPC can be pointed at a rust-ipfs node.
+
+#[macro_use]
+extern crate tracing;
+
+pub mod v0;
+
+pub mod config;
@@ -0,0 +1,184 @@
+use clap::{Parser, Subcommand};
+use std::future::IntoFuture;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use rust_ipfs::{Ipfs, Multiaddr, Protocol, UninitializedIpfsNoop};
+use rust_ipfs_http::{config, v0};
+
+#[macro_use]
+extern crate tracing;
+
+/// A kubo compatible HTTP RPC API on top of rust-ipfs. The repository is at `IPFS_PATH`, or at
+/// `~/.rust-ipfs` when unset.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Options {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Initializes the repository with a new identity
+    Init {
+        #[arg(long, value_enum, default_value = "default")]
+        profile: config::Profile,
+    },
+    /// Runs the node, serving the API until shut down
+    Daemon,
+}
+
+fn main() {
+    if std::env::var_os("RUST_LOG").is_none() {
+        std::env::set_var("RUST_LOG", "rust_ipfs_http=info,rust_ipfs=info");
+    }
+
+    tracing_subscriber::fmt::init();
+
+    let opts = Options::parse();
+
+    let home = std::env::var_os("IPFS_PATH")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|tilde| {
+                let mut path = PathBuf::from(tilde);
+                path.push(".rust-ipfs");
+                path
+            })
+        });
+
+    let home = home.unwrap_or_else(|| {
+        eprintln!("IPFS_PATH and HOME unset");
+        std::process::exit(1);
+    });
+
+    let config_path = home.join("config");
+
+    let config = match opts.command {
+        Command::Init { profile } => {
+            println!("initializing IPFS node at {home:?}");
+
+            if config_path.is_file() {
+                eprintln!("Error: ipfs configuration file already exists!");
+                eprintln!("Reinitializing would override your keys.");
+                std::process::exit(1);
+            }
+
+            match config::init(&home, profile) {
+                Ok(peer_id) => {
+                    println!("peer identity: {peer_id}");
+                    std::process::exit(0);
+                }
+                Err(e) => {
+                    eprintln!("Error: {e:#}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Daemon => {
+            if !config_path.is_file() {
+                eprintln!("Error: no IPFS repo found in {home:?}");
+                eprintln!("please run: 'ipfs init'");
+                std::process::exit(1);
+            }
+
+            let loaded = std::fs::File::open(&config_path)
+                .map_err(anyhow::Error::from)
+                .and_then(config::load);
+
+            match loaded {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("Error: failed to load {config_path:?}: {e:#}");
+                    std::process::exit(1);
+                }
+            }
+        }
+    };
+
+    println!("IPFS_PATH: {home:?}");
+    println!("Process id: {}", std::process::id());
+
+    let rt = tokio::runtime::Runtime::new().expect("Failed to create event loop");
+
+    rt.block_on(async move {
+        let ipfs: Ipfs = UninitializedIpfsNoop::new()
+            .with_default()
+            .set_keypair(&config.keypair)
+            .set_path(&home)
+            .set_listening_addrs(config.swarm)
+            .start()
+            .await
+            .expect("Initialization failed");
+
+        let api_link_file = home.join("api");
+
+        let (addr, server) = match serve(&ipfs, &config.api_addr).await {
+            Ok(serving) => serving,
+            Err(e) => {
+                eprintln!("Error: failed to serve the API: {e:#}");
+                ipfs.exit_daemon().await;
+                std::process::exit(1);
+            }
+        };
+
+        let api_multiaddr = Multiaddr::from(addr.ip()).with(Protocol::Tcp(addr.port()));
+
+        let wrote = tokio::fs::write(&api_link_file, api_multiaddr.to_string())
+            .await
+            .is_ok();
+
+        println!("API listening on {api_multiaddr}");
+        println!("daemon is running");
+
+        if let Err(e) = server.await {
+            error!("API server failed: {e}");
+        }
+
+        ipfs.exit_daemon().await;
+
+        if wrote {
+            let _ = tokio::fs::remove_file(&api_link_file)
+                .await
+                .map_err(|e| info!("Failed to remove {:?}: {}", api_link_file, e));
+        }
+    });
+
+    info!("Shutdown complete");
+}
+
+/// Binds the API to the address, returning the bound address and the server which runs until
+/// either `/api/v0/shutdown` is requested or the process is interrupted.
+async fn serve(
+    ipfs: &Ipfs,
+    listening_addr: &Multiaddr,
+) -> anyhow::Result<(
+    SocketAddr,
+    impl std::future::Future<Output = std::io::Result<()>>,
+)> {
+    let (shutdown_tx, mut shutdown_rx) = tokio::sync::mpsc::channel::<()>(1);
+
+    let routes = v0::routes(ipfs, shutdown_tx);
+
+    let components = listening_addr.iter().collect::<Vec<_>>();
+
+    let socket_addr = match components.as_slice() {
+        [Protocol::Ip4(ip), Protocol::Tcp(port)] => SocketAddr::new((*ip).into(), *port),
+        [Protocol::Ip6(ip), Protocol::Tcp(port)] => SocketAddr::new((*ip).into(), *port),
+        _ => anyhow::bail!("Couldn't convert MultiAddr into SocketAddr: {listening_addr}"),
+    };
+
+    let listener = tokio::net::TcpListener::bind(socket_addr).await?;
+    let addr = listener.local_addr()?;
+
+    let server = axum::serve(listener, routes).with_graceful_shutdown(async move {
+        tokio::select! {
+            _ = shutdown_rx.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+        info!("Shutdown trigger received; starting shutdown");
+    });
+
+    Ok((addr, server.into_future()))
+}
@@ -0,0 +1,167 @@
+//! Implementation of `/api/v0` HTTP endpoints.
+//!
+//! See https://docs.ipfs.tech/reference/kubo/rpc/ for more information.
+
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::Router;
+use rust_ipfs::Ipfs;
+
+pub mod bitswap;
+pub mod block;
+pub mod bootstrap;
+pub mod dag;
+pub mod filestore;
+pub mod id;
+pub mod ipns;
+pub mod key;
+pub mod name;
+pub mod pin;
+pub mod pubsub;
+pub mod refs;
+pub mod root_files;
+pub mod routing;
+pub mod stats;
+pub mod swarm;
+pub mod version;
+
+pub mod support;
+pub use support::{ApiError, ApiResult, MessageResponse};
+
+/// Supported routes of the crate. Like kubo, all of the endpoints are only served for `POST`
+/// requests.
+pub fn routes(ipfs: &Ipfs, shutdown_tx: tokio::sync::mpsc::Sender<()>) -> Router {
+    let api = Router::new()
+        .route(
+            "/shutdown",
+            post(move || handle_shutdown(shutdown_tx.clone())),
+        )
+        .route("/id", post(id::identity))
+        .route("/version", post(version::version))
+        .route("/add", post(root_files::add))
+        .route("/cat", post(root_files::cat))
+        .route("/get", post(root_files::get))
+        .route("/ls", post(root_files::ls))
+        .route("/refs", post(refs::refs))
+        .route("/refs/local", post(refs::local))
+        .route("/resolve", post(ipns::resolve))
+        .route("/dns", post(ipns::dns))
+        .route("/block/get", post(block::get))
+        .route("/block/put", post(block::put))
+        .route("/block/rm", post(block::rm))
+        .route("/block/stat", post(block::stat))
+        .route("/dag/get", post(dag::get))
+        .route("/dag/put", post(dag::put))
+        .route("/dag/resolve", post(dag::resolve))
+        .route("/filestore/ls", post(filestore::list))
+        .route("/filestore/verify", post(filestore::verify))
+        .route("/pin/add", post(pin::add))
+        .route("/pin/ls", post(pin::list))
+        .route("/pin/rm", post(pin::rm))
+        .route("/pin/update", post(pin::update))
+        .route("/name/publish", post(name::publish))
+        .route("/name/resolve", post(name::resolve))
+        .route("/key/gen", post(key::generate))
+        .route("/key/list", post(key::list))
+        .route("/key/rm", post(key::rm))
+        .route("/key/rename", post(key::rename))
+        .route("/key/export", post(key::export))
+        .route("/key/import", post(key::import))
+        .route("/routing/findpeer", post(routing::find_peer))
+        .route("/routing/findprovs", post(routing::find_providers))
+        .route("/routing/provide", post(routing::provide))
+        .route("/routing/get", post(routing::get))
+        .route("/routing/put", post(routing::put))
+        // the deprecated dht commands of kubo, which are now routing commands
+        .route("/dht/findpeer", post(routing::find_peer))
+        .route("/dht/findprovs", post(routing::find_providers))
+        .route("/dht/provide", post(routing::provide))
+        .route("/dht/get", post(routing::get))
+        .route("/dht/put", post(routing::put))
+        .route("/dht/query", post(routing::query))
+        .route("/pubsub/ls", post(pubsub::ls))
+        .route("/pubsub/peers", post(pubsub::peers))
+        .route("/pubsub/pub", post(pubsub::publish))
+        .route("/pubsub/sub", post(pubsub::subscribe))
+        .route("/bootstrap", post(bootstrap::list))
+        .route("/bootstrap/list", post(bootstrap::list))
+        .route("/bootstrap/add", post(bootstrap::add))
+        .route("/bootstrap/add/default", post(bootstrap::add_default))
+        .route("/bootstrap/rm", post(bootstrap::rm))
+        .route("/bootstrap/rm/all", post(bootstrap::rm_all))
+        .route("/bitswap/wantlist", post(bitswap::wantlist))
+        .route("/bitswap/stat", post(stats::bitswap))
+        .route("/stats/repo", post(stats::repo))
+        .route("/stats/bitswap", post(stats::bitswap))
+        .route("/stats/provide", post(stats::provide))
+        .route("/swarm/peers", post(swarm::peers))
+        .route("/swarm/addrs", post(swarm::addrs))
+        .route("/swarm/addrs/local", post(swarm::addrs_local))
+        .route("/swarm/addrs/listen", post(swarm::addrs_listen))
+        .route("/swarm/connect", post(swarm::connect))
+        .route("/swarm/disconnect", post(swarm::disconnect));
+
+    Router::new()
+        .nest("/api/v0", api)
+        .fallback(not_found)
+        .with_state(ipfs.clone())
+}
+
+pub(crate) async fn handle_shutdown(tx: tokio::sync::mpsc::Sender<()>) -> StatusCode {
+    match tx.send(()).await {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::NOT_IMPLEMENTED,
+    }
+}
+
+async fn not_found() -> (StatusCode, &'static str) {
+    // from go-ipfs
+    (StatusCode::NOT_FOUND, "404 page not found")
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use axum::Router;
+    use rust_ipfs::Node;
+    use tower::ServiceExt;
+
+    /// Creates routes for tests on top of a node without peers.
+    async fn testing_routes() -> (Node, Router) {
+        let node = Node::new("test_node").await;
+        let (shutdown_tx, _) = tokio::sync::mpsc::channel::<()>(1);
+        let routes = super::routes(&node, shutdown_tx);
+        (node, routes)
+    }
+
+    #[tokio::test]
+    async fn not_found_as_plaintext() {
+        let (_node, routes) = testing_routes().await;
+        let request = Request::post("/api/v0/id_foobar")
+            .body(Body::empty())
+            .unwrap();
+        let response = routes.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), 404);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        // from go-ipfs
+        assert_eq!(&body[..], b"404 page not found");
+    }
+
+    #[tokio::test]
+    async fn invalid_peer_id_as_messageresponse() {
+        let (_node, routes) = testing_routes().await;
+        let request = Request::post("/api/v0/id?arg=foobar")
+            .body(Body::empty())
+            .unwrap();
+        let response = routes.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), 400);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            &body[..],
+            br#"{"Message":"invalid peer id","Code":1,"Type":"error"}"#
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use super::support::{ApiError, ApiResult, Params};
+use axum::extract::State;
+use axum::Json;
+use rust_ipfs::{Ipfs, PeerId};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct WantlistResponse {
+    // the cids as dag-json links, {"/": cid}
+    keys: Vec<BTreeMap<&'static str, String>>,
+}
+
+/// `bitswap/wantlist` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-bitswap-wantlist,
+/// listing the wantlist of the node or of the `peer`.
+pub async fn wantlist(
+    State(ipfs): State<Ipfs>,
+    params: Params,
+) -> ApiResult<Json<WantlistResponse>> {
+    let peer = params
+        .get("peer")
+        .map(|peer| {
+            peer.parse::<PeerId>()
+                .map_err(|_| ApiError::bad_request("invalid peer id"))
+        })
+        .transpose()?;
+
+    let keys = ipfs
+        .bitswap_wantlist(peer)
+        .await?
+        .into_iter()
+        .map(|cid| BTreeMap::from([("/", cid.to_string())]))
+        .collect();
+
+    Ok(Json(WantlistResponse { keys }))
+}
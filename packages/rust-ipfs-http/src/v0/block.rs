@@ -0,0 +1,142 @@
+use super::support::{
+    ndjson, next_file, parse_codec, parse_hash, parse_path, resolve_cid, streamed, ApiError,
+    ApiResult, Params,
+};
+use axum::body::Body;
+use axum::extract::{Multipart, State};
+use axum::Json;
+use futures::stream;
+use libipld::cid::Version;
+use libipld::multihash::{Code, MultihashDigest};
+use libipld::{Cid, IpldCodec};
+use rust_ipfs::{Block, Ipfs};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StatResponse {
+    key: String,
+    size: usize,
+}
+
+/// Loads the block of the path given as the first `arg`.
+async fn load(ipfs: &Ipfs, params: &Params) -> ApiResult<Block> {
+    let cid = resolve_cid(ipfs, parse_path(params.arg("cid")?)?).await?;
+    Ok(ipfs.get_block(&cid).await?)
+}
+
+/// `block/get` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-block-get.
+pub async fn get(State(ipfs): State<Ipfs>, params: Params) -> ApiResult {
+    let block = load(&ipfs, &params).await?;
+    let (_, data) = block.into_inner();
+
+    Ok(streamed(Body::from(data), "text/plain"))
+}
+
+/// `block/stat` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-block-stat.
+pub async fn stat(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<StatResponse>> {
+    let block = load(&ipfs, &params).await?;
+
+    Ok(Json(StatResponse {
+        key: block.cid().to_string(),
+        size: block.data().len(),
+    }))
+}
+
+/// `block/put` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-block-put, storing every part
+/// of the multipart body as a block. The deprecated `format` option is supported the same way as
+/// kubo, with `v0` and `protobuf` creating version 0 dag-pb Cids.
+pub async fn put(State(ipfs): State<Ipfs>, params: Params, mut multipart: Multipart) -> ApiResult {
+    let hash = parse_hash(params.get("mhtype").unwrap_or("sha2-256"))?;
+    let (version, codec) = match (params.get("format"), params.get("cid-codec")) {
+        (Some(_), Some(_)) => {
+            return Err(ApiError::bad_request(
+                "unable to use \"format\" (deprecated) and \"cid-codec\" at the same time",
+            ))
+        }
+        (Some("v0" | "protobuf"), None) => (Version::V0, IpldCodec::DagPb),
+        (Some("cbor"), None) => (Version::V1, IpldCodec::DagCbor),
+        (Some(format), None) => (Version::V1, parse_codec(format)?),
+        (None, codec) => (Version::V1, parse_codec(codec.unwrap_or("raw"))?),
+    };
+    if version == Version::V0 && hash != Code::Sha2_256 {
+        return Err(ApiError::bad_request(
+            "cid version 0 only supports sha2-256",
+        ));
+    }
+    let pin = params.flag("pin", false)?;
+
+    let mut responses = Vec::new();
+    while let Some(data) = next_file(&mut multipart).await? {
+        let digest = hash.digest(&data);
+        let cid = match version {
+            Version::V0 => Cid::new_v0(digest).map_err(ApiError::internal)?,
+            Version::V1 => Cid::new_v1(codec.into(), digest),
+        };
+
+        let size = data.len();
+        let block = Block::new(cid, data.to_vec()).map_err(ApiError::bad_request)?;
+        ipfs.put_block(block).await?;
+        if pin {
+            ipfs.insert_pin(&cid).await?;
+        }
+
+        responses.push(Ok(StatResponse {
+            key: cid.to_string(),
+            size,
+        }));
+    }
+
+    if responses.is_empty() {
+        return Err(ApiError::bad_request("file argument \"data\" is required"));
+    }
+
+    Ok(ndjson(stream::iter(responses)))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RmResponse {
+    hash: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    error: String,
+}
+
+/// `block/rm` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-block-rm, reporting the
+/// failures of the blocks in the `Error` of their lines. Missing blocks are not reported with
+/// `force`, and only the failures are reported with `quiet`.
+pub async fn rm(State(ipfs): State<Ipfs>, params: Params) -> ApiResult {
+    let force = params.flag("force", false)?;
+    let quiet = params.flag("quiet", false)?;
+
+    let cids = params
+        .args()
+        .map(|arg| {
+            Cid::try_from(arg)
+                .map_err(|e| ApiError::bad_request(format!("invalid cid {arg:?}: {e}")))
+        })
+        .collect::<ApiResult<Vec<_>>>()?;
+    if cids.is_empty() {
+        return Err(ApiError::bad_request("argument \"cid\" is required"));
+    }
+
+    let mut responses = Vec::new();
+    for cid in cids {
+        let error = match ipfs.remove_block(cid, false).await {
+            Ok(removed) if removed.contains(&cid) => String::new(),
+            Ok(_) if force => String::new(),
+            Ok(_) => "blockstore: block not found".to_string(),
+            Err(e) => e.to_string(),
+        };
+
+        if quiet && error.is_empty() {
+            continue;
+        }
+        responses.push(Ok(RmResponse {
+            hash: cid.to_string(),
+            error,
+        }));
+    }
+
+    Ok(ndjson(stream::iter(responses)))
+}
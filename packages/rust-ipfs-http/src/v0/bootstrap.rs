@@ -0,0 +1,87 @@
+use super::support::{ApiError, ApiResult, Params};
+use axum::extract::State;
+use axum::Json;
+use rust_ipfs::{Ipfs, Multiaddr};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PeersResponse {
+    peers: Vec<String>,
+}
+
+impl PeersResponse {
+    fn new(addrs: &[Multiaddr]) -> Json<Self> {
+        Json(PeersResponse {
+            peers: addrs.iter().map(Multiaddr::to_string).collect(),
+        })
+    }
+}
+
+/// Parses the `arg` parameters as the addresses of bootstrap peers.
+fn addresses(params: &Params) -> ApiResult<Vec<Multiaddr>> {
+    params
+        .args()
+        .map(|arg| {
+            arg.parse::<Multiaddr>()
+                .map_err(|e| ApiError::bad_request(format!("invalid address {arg:?}: {e}")))
+        })
+        .collect()
+}
+
+/// `bootstrap` and `bootstrap/list` per
+/// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-bootstrap-list.
+pub async fn list(State(ipfs): State<Ipfs>) -> ApiResult<Json<PeersResponse>> {
+    Ok(PeersResponse::new(&ipfs.get_bootstraps().await?))
+}
+
+/// `bootstrap/add` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-bootstrap-add, adding
+/// the default peers with `default`.
+pub async fn add(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<PeersResponse>> {
+    if params.flag("default", false)? {
+        return Ok(PeersResponse::new(&ipfs.default_bootstrap().await?));
+    }
+
+    let addrs = addresses(&params)?;
+    if addrs.is_empty() {
+        return Err(ApiError::bad_request("argument \"peer\" is required"));
+    }
+
+    let mut added = Vec::with_capacity(addrs.len());
+    for addr in addrs {
+        added.push(ipfs.add_bootstrap(addr).await?);
+    }
+
+    Ok(PeersResponse::new(&added))
+}
+
+/// `bootstrap/add/default` per
+/// https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-bootstrap-add-default.
+pub async fn add_default(State(ipfs): State<Ipfs>) -> ApiResult<Json<PeersResponse>> {
+    Ok(PeersResponse::new(&ipfs.default_bootstrap().await?))
+}
+
+/// `bootstrap/rm` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-bootstrap-rm, removing
+/// all of the peers with `all`.
+pub async fn rm(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<PeersResponse>> {
+    if params.flag("all", false)? {
+        return Ok(PeersResponse::new(&ipfs.clear_bootstrap().await?));
+    }
+
+    let addrs = addresses(&params)?;
+    if addrs.is_empty() {
+        return Err(ApiError::bad_request("argument \"peer\" is required"));
+    }
+
+    let mut removed = Vec::with_capacity(addrs.len());
+    for addr in addrs {
+        removed.push(ipfs.remove_bootstrap(addr).await?);
+    }
+
+    Ok(PeersResponse::new(&removed))
+}
+
+/// `bootstrap/rm/all` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-bootstrap-rm-all.
+pub async fn rm_all(State(ipfs): State<Ipfs>) -> ApiResult<Json<PeersResponse>> {
+    Ok(PeersResponse::new(&ipfs.clear_bootstrap().await?))
+}
@@ -0,0 +1,95 @@
+use super::support::{
+    ndjson, next_file, parse_codec, parse_hash, parse_path, resolve_path, streamed, ApiError,
+    ApiResult, Params,
+};
+use axum::body::Body;
+use axum::extract::{Multipart, State};
+use axum::Json;
+use futures::stream;
+use libipld::codec::Codec;
+use libipld::{Ipld, IpldCodec};
+use rust_ipfs::Ipfs;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PutResponse {
+    // the cid as a dag-json link, {"/": cid}
+    cid: BTreeMap<&'static str, String>,
+}
+
+/// `dag/put` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-dag-put, decoding every part of
+/// the multipart body with the `input-codec` and storing it with the `store-codec`.
+pub async fn put(State(ipfs): State<Ipfs>, params: Params, mut multipart: Multipart) -> ApiResult {
+    let store_codec = parse_codec(params.get("store-codec").unwrap_or("dag-cbor"))?;
+    let input_codec = parse_codec(params.get("input-codec").unwrap_or("dag-json"))?;
+    let hash = parse_hash(params.get("hash").unwrap_or("sha2-256"))?;
+    let pin = params.flag("pin", false)?;
+
+    let mut responses = Vec::new();
+    while let Some(data) = next_file(&mut multipart).await? {
+        let ipld: Ipld = input_codec.decode(&data).map_err(|e| {
+            ApiError::bad_request(format!("failed to decode the {input_codec:?} input: {e}"))
+        })?;
+
+        let mut put = ipfs.put_dag(ipld).codec(store_codec).hash(hash);
+        if pin {
+            put = put.pin(true);
+        }
+        let cid = put.await?;
+
+        responses.push(Ok(PutResponse {
+            cid: BTreeMap::from([("/", cid.to_string())]),
+        }));
+    }
+
+    if responses.is_empty() {
+        return Err(ApiError::bad_request(
+            "file argument \"object data\" is required",
+        ));
+    }
+
+    Ok(ndjson(stream::iter(responses)))
+}
+
+/// `dag/get` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-dag-get, responding with the
+/// document or the part of it at the end of the path encoded with the `output-codec`.
+pub async fn get(State(ipfs): State<Ipfs>, params: Params) -> ApiResult {
+    let path = resolve_path(&ipfs, parse_path(params.arg("ref")?)?).await?;
+    let output_codec = parse_codec(params.get("output-codec").unwrap_or("dag-json"))?;
+
+    let ipld = ipfs.get_dag(path).await?;
+    let data = output_codec.encode(&ipld).map_err(|e| {
+        ApiError::bad_request(format!(
+            "failed to encode the document as {output_codec:?}: {e}"
+        ))
+    })?;
+
+    let content_type = match output_codec {
+        IpldCodec::DagJson => "application/json",
+        _ => "application/octet-stream",
+    };
+
+    Ok(streamed(Body::from(data), content_type))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ResolveResponse {
+    cid: BTreeMap<&'static str, String>,
+    rem_path: String,
+}
+
+/// `dag/resolve` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-dag-resolve, resolving the
+/// path to the last block and the path remaining inside of it.
+pub async fn resolve(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<ResolveResponse>> {
+    let path = resolve_path(&ipfs, parse_path(params.arg("ref")?)?).await?;
+
+    let (resolved, remaining) = ipfs.dag().resolve(path, true, &[], false).await?;
+
+    Ok(Json(ResolveResponse {
+        cid: BTreeMap::from([("/", resolved.source().to_string())]),
+        rem_path: remaining.to_string(),
+    }))
+}
@@ -0,0 +1,75 @@
+use super::support::{ndjson, ApiResult};
+use axum::extract::State;
+use futures::stream::StreamExt;
+use rust_ipfs::{FileRef, FilestoreStatus, Ipfs};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// The statuses of kubo for the filestore entries.
+const STATUS_OK: u32 = 0;
+const STATUS_FILE_ERROR: u32 = 10;
+const STATUS_FILE_NOT_FOUND: u32 = 11;
+const STATUS_FILE_CHANGED: u32 = 12;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Entry {
+    status: u32,
+    error_msg: String,
+    // the cid as a dag-json link, {"/": cid}
+    key: BTreeMap<&'static str, String>,
+    file_path: String,
+    offset: u64,
+    size: u64,
+}
+
+impl Entry {
+    fn new(cid: String, reference: FileRef, status: u32, error_msg: String) -> Self {
+        Entry {
+            status,
+            error_msg,
+            key: BTreeMap::from([("/", cid)]),
+            file_path: reference.path.to_string_lossy().into_owned(),
+            offset: reference.offset,
+            size: reference.length,
+        }
+    }
+}
+
+/// `filestore/ls` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-filestore-ls, listing the
+/// blocks of the files added without copying.
+pub async fn list(State(ipfs): State<Ipfs>) -> ApiResult {
+    let entries = ipfs.filestore_list().await.map(|entry| {
+        let (cid, reference) = entry?;
+        Ok(Entry::new(
+            cid.to_string(),
+            reference,
+            STATUS_OK,
+            String::new(),
+        ))
+    });
+
+    Ok(ndjson(entries))
+}
+
+/// `filestore/verify` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-filestore-verify,
+/// reading the blocks of the files added without copying from the files.
+pub async fn verify(State(ipfs): State<Ipfs>) -> ApiResult {
+    let entries = ipfs.filestore_verify().await.map(|entry| {
+        let entry = entry?;
+        let (status, error_msg) = match entry.status {
+            FilestoreStatus::Ok => (STATUS_OK, String::new()),
+            FilestoreStatus::Changed => (STATUS_FILE_CHANGED, String::new()),
+            FilestoreStatus::NoFile => (STATUS_FILE_NOT_FOUND, String::new()),
+            FilestoreStatus::FileError(e) => (STATUS_FILE_ERROR, e),
+        };
+        Ok(Entry::new(
+            entry.cid.to_string(),
+            entry.reference,
+            status,
+            error_msg,
+        ))
+    });
+
+    Ok(ndjson(entries))
+}
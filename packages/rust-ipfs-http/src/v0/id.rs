@@ -0,0 +1,53 @@
+use super::support::{ApiError, ApiResult, Params};
+use axum::extract::State;
+use axum::Json;
+use base64::Engine;
+use rust_ipfs::{Ipfs, PeerId};
+use serde::Serialize;
+
+/// `id` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-id, describing the local node or
+/// the peer given as `arg`.
+pub async fn identity(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<Response>> {
+    let peer = params
+        .get("arg")
+        .map(|arg| arg.parse::<PeerId>())
+        .transpose()
+        .map_err(|_| ApiError::bad_request("invalid peer id"))?;
+
+    let info = ipfs.identity(peer).await?;
+
+    Ok(Json(Response {
+        id: info.peer_id.to_string(),
+        public_key: base64::engine::general_purpose::STANDARD
+            .encode(info.public_key.encode_protobuf()),
+        addresses: info
+            .listen_addrs
+            .into_iter()
+            .map(|addr| addr.to_string())
+            .collect(),
+        agent_version: info.agent_version,
+        protocol_version: info.protocol_version,
+        protocols: info
+            .protocols
+            .into_iter()
+            .map(|protocol| protocol.to_string())
+            .collect(),
+    }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Response {
+    // PeerId
+    #[serde(rename = "ID")]
+    id: String,
+    // base64 of the protobuf encoding
+    public_key: String,
+    // Multiaddrs
+    addresses: Vec<String>,
+    // <agent_name>/<version>, like rust-ipfs/0.11.0
+    agent_version: String,
+    // like ipfs/0.1.0
+    protocol_version: String,
+    protocols: Vec<String>,
+}
@@ -0,0 +1,51 @@
+use super::support::{parse_path, resolve_cid, ApiResult, Params};
+use axum::extract::State;
+use axum::Json;
+use rust_ipfs::path::PathRoot;
+use rust_ipfs::Ipfs;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PathResponse {
+    path: String,
+}
+
+/// `resolve` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-resolve. Without `recursive`,
+/// `/ipns` names are resolved a single step, otherwise the path is resolved to the Cid it ends
+/// in.
+pub async fn resolve(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<PathResponse>> {
+    let path = parse_path(params.arg("name")?)?;
+    let recursive = params.flag("recursive", true)?;
+
+    let path = match path.root() {
+        PathRoot::Ipld(_) => path,
+        _ if !recursive => {
+            let resolved = ipfs.resolve_ipns(&path, false).await?;
+            return Ok(Json(PathResponse {
+                path: resolved.to_string(),
+            }));
+        }
+        _ => ipfs.resolve_ipns(&path, true).await?,
+    };
+
+    let cid = resolve_cid(&ipfs, path).await?;
+
+    Ok(Json(PathResponse {
+        path: format!("/ipfs/{cid}"),
+    }))
+}
+
+/// `dns` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-dns, resolving the DNSLink of the
+/// domain name.
+pub async fn dns(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<PathResponse>> {
+    let domain = params.arg("domain-name")?;
+    let recursive = params.flag("recursive", true)?;
+
+    let path = parse_path(&format!("/ipns/{domain}"))?;
+    let resolved = ipfs.resolve_ipns(&path, recursive).await?;
+
+    Ok(Json(PathResponse {
+        path: resolved.to_string(),
+    }))
+}
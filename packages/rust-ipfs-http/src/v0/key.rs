@@ -0,0 +1,207 @@
+use super::support::{first_file, key_id, ApiError, ApiResult, Params};
+use axum::extract::{Multipart, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use rust_ipfs::keystore::{KeyFormat, KeyType};
+use rust_ipfs::{Ipfs, PublicKey};
+use serde::Serialize;
+
+/// The name kubo uses for the key of the node itself, which is not stored in the keystore.
+const SELF_KEY: &str = "self";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct KeyResponse {
+    name: String,
+    id: String,
+}
+
+impl KeyResponse {
+    fn new(name: &str, public_key: &PublicKey) -> Self {
+        KeyResponse {
+            name: name.to_string(),
+            id: key_id(&public_key.to_peer_id()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct KeysResponse {
+    keys: Vec<KeyResponse>,
+}
+
+/// Fails if the name is reserved or already taken.
+async fn ensure_available(ipfs: &Ipfs, name: &str) -> ApiResult<()> {
+    if name == SELF_KEY {
+        return Err(ApiError::bad_request("cannot create key with name 'self'"));
+    }
+    if ipfs.keystore().contains(name).await? {
+        return Err(ApiError::internal(format!(
+            "key with name '{name}' already exists"
+        )));
+    }
+    Ok(())
+}
+
+/// `key/gen` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-key-gen. RSA keys are not
+/// supported, but ECDSA and secp256k1 keys are.
+pub async fn generate(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<KeyResponse>> {
+    let name = params.arg("name")?;
+    let key_type = match params.get("type").unwrap_or("ed25519") {
+        "ed25519" => KeyType::Ed25519,
+        "ecdsa" => KeyType::Ecdsa,
+        "secp256k1" => KeyType::Secp256k1,
+        other => {
+            return Err(ApiError::bad_request(format!(
+                "unrecognized key type: {other}"
+            )))
+        }
+    };
+
+    ensure_available(&ipfs, name).await?;
+    let public_key = ipfs.keystore().generate_key(Some(name), key_type).await?;
+
+    Ok(Json(KeyResponse::new(name, &public_key)))
+}
+
+/// `key/list` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-key-list, starting with the
+/// key of the node itself.
+pub async fn list(State(ipfs): State<Ipfs>) -> ApiResult<Json<KeysResponse>> {
+    let mut keys = vec![KeyResponse::new(SELF_KEY, &ipfs.keypair().public())];
+    for (name, public_key) in ipfs.keystore().list().await? {
+        keys.push(KeyResponse::new(&name, &public_key));
+    }
+
+    Ok(Json(KeysResponse { keys }))
+}
+
+/// `key/rm` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-key-rm.
+pub async fn rm(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<KeysResponse>> {
+    let keystore = ipfs.keystore();
+
+    let mut keys = Vec::new();
+    for name in params.args() {
+        if name == SELF_KEY {
+            return Err(ApiError::bad_request("cannot remove key with name 'self'"));
+        }
+        let keypair = keystore
+            .get_keypair(name)
+            .await
+            .map_err(|_| ApiError::internal(format!("no key named {name} was found")))?;
+        keystore.remove(name).await?;
+        keys.push(KeyResponse::new(name, &keypair.public()));
+    }
+
+    if keys.is_empty() {
+        return Err(ApiError::bad_request("argument \"name\" is required"));
+    }
+
+    Ok(Json(KeysResponse { keys }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RenameResponse {
+    was: String,
+    now: String,
+    id: String,
+    overwrite: bool,
+}
+
+/// `key/rename` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-key-rename, replacing an
+/// existing key of the new name only with `force`.
+pub async fn rename(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<RenameResponse>> {
+    let mut args = params.args();
+    let (was, now) = match (args.next(), args.next()) {
+        (Some(was), Some(now)) => (was, now),
+        _ => {
+            return Err(ApiError::bad_request(
+                "argument \"old-name\" and \"new-name\" are required",
+            ))
+        }
+    };
+    let force = params.flag("force", false)?;
+
+    if was == SELF_KEY || now == SELF_KEY {
+        return Err(ApiError::bad_request("cannot rename key with name 'self'"));
+    }
+
+    let keystore = ipfs.keystore();
+    let keypair = keystore.get_keypair(was).await?;
+
+    let overwrite = keystore.contains(now).await?;
+    if overwrite {
+        if !force {
+            return Err(ApiError::internal(
+                "key by that name already exists, refusing to overwrite",
+            ));
+        }
+        keystore.remove(now).await?;
+    }
+    keystore.rename(was, now).await?;
+
+    Ok(Json(RenameResponse {
+        was: was.to_string(),
+        now: now.to_string(),
+        id: key_id(&keypair.public().to_peer_id()),
+        overwrite,
+    }))
+}
+
+/// Parses the `format` option of the export and the import, which defaults to the protobuf
+/// encoding like in kubo.
+fn key_format(params: &Params) -> ApiResult<KeyFormat<'_>> {
+    match params.get("format").unwrap_or("libp2p-protobuf-cleartext") {
+        "libp2p-protobuf-cleartext" => Ok(KeyFormat::Protobuf),
+        "pem-pkcs8-cleartext" => Ok(KeyFormat::Pkcs8Pem),
+        other => Err(ApiError::bad_request(format!(
+            "unrecognized format: {other}"
+        ))),
+    }
+}
+
+/// `key/export` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-key-export, responding with
+/// the encoded key.
+pub async fn export(State(ipfs): State<Ipfs>, params: Params) -> ApiResult {
+    let name = params.arg("name")?;
+    let format = key_format(&params)?;
+
+    if name == SELF_KEY {
+        // same as kubo, which only exports the key of the node while it is not running
+        return Err(ApiError::internal(
+            "cannot export key with name 'self' while the daemon is running",
+        ));
+    }
+
+    let key = ipfs.keystore().export_key(name, format).await?;
+
+    let response: Response = (
+        [(header::CONTENT_TYPE, "application/octet-stream")],
+        key.as_ref().to_vec(),
+    )
+        .into_response();
+    Ok(response)
+}
+
+/// `key/import` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-key-import, reading the
+/// encoded key from the first part of the multipart body.
+pub async fn import(
+    State(ipfs): State<Ipfs>,
+    params: Params,
+    mut multipart: Multipart,
+) -> ApiResult<Json<KeyResponse>> {
+    let name = params.arg("name")?;
+    let format = key_format(&params)?;
+
+    let data = first_file(&mut multipart, "key").await?;
+
+    ensure_available(&ipfs, name).await?;
+    let public_key = ipfs
+        .keystore()
+        .import_encoded_key(&data, format, Some(name))
+        .await?;
+
+    Ok(Json(KeyResponse::new(name, &public_key)))
+}
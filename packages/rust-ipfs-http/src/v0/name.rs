@@ -0,0 +1,67 @@
+use super::support::{parse_path, ApiResult, Params};
+use axum::extract::State;
+use axum::Json;
+use rust_ipfs::ipns::IpnsOption;
+use rust_ipfs::Ipfs;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PublishResponse {
+    name: String,
+    value: String,
+}
+
+/// `name/publish` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-name-publish. The record is
+/// only stored locally with `allow-offline`, otherwise it is also put to the DHT.
+pub async fn publish(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<PublishResponse>> {
+    let path = parse_path(params.arg("ipfs-path")?)?;
+    let key = params.get("key").filter(|key| *key != "self");
+    let option = match params.flag("allow-offline", false)? {
+        true => IpnsOption::Local,
+        false => IpnsOption::DHT,
+    };
+
+    let mut publish = ipfs.ipns().publish(key, &path, Some(option));
+    if let Some(lifetime) = params.duration("lifetime")? {
+        publish = publish.lifetime(lifetime);
+    }
+    if let Some(ttl) = params.duration("ttl")? {
+        publish = publish.ttl(ttl);
+    }
+    let published = publish.await?;
+
+    let published = published.to_string();
+    let name = published
+        .strip_prefix("/ipns/")
+        .unwrap_or(&published)
+        .to_string();
+
+    Ok(Json(PublishResponse {
+        name,
+        value: path.to_string(),
+    }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ResolveResponse {
+    path: String,
+}
+
+/// `name/resolve` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-name-resolve, resolving the
+/// name of the node itself when no `arg` is given.
+pub async fn resolve(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<ResolveResponse>> {
+    let name = match params.get("arg") {
+        Some(arg) if arg.starts_with("/ipns/") => arg.to_string(),
+        Some(arg) => format!("/ipns/{arg}"),
+        None => format!("/ipns/{}", ipfs.keypair().public().to_peer_id()),
+    };
+    let recursive = params.flag("recursive", true)?;
+
+    let resolved = ipfs.resolve_ipns(&parse_path(&name)?, recursive).await?;
+
+    Ok(Json(ResolveResponse {
+        path: resolved.to_string(),
+    }))
+}
@@ -0,0 +1,167 @@
+use super::support::{parse_path, resolve_cid, ApiError, ApiResult, Params};
+use axum::extract::State;
+use axum::Json;
+use futures::stream::TryStreamExt;
+use libipld::Cid;
+use rust_ipfs::{Ipfs, PinKind, PinMode};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PinsResponse {
+    pins: Vec<String>,
+}
+
+/// Resolves all of the `arg` parameters into Cids.
+async fn args_to_cids(ipfs: &Ipfs, params: &Params) -> ApiResult<Vec<Cid>> {
+    let mut cids = Vec::new();
+    for arg in params.args() {
+        cids.push(resolve_cid(ipfs, parse_path(arg)?).await?);
+    }
+    if cids.is_empty() {
+        return Err(ApiError::bad_request("argument \"ipfs-path\" is required"));
+    }
+    Ok(cids)
+}
+
+/// `pin/add` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-pin-add, with the `name` of
+/// the pin as an option.
+pub async fn add(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<PinsResponse>> {
+    let recursive = params.flag("recursive", true)?;
+    let name = params.get("name").filter(|name| !name.is_empty());
+    let cids = args_to_cids(&ipfs, &params).await?;
+
+    for cid in &cids {
+        let mut pin = ipfs.insert_pin(cid);
+        if recursive {
+            pin = pin.recursive();
+        }
+        if let Some(name) = name {
+            pin = pin.name(name);
+        }
+        pin.await?;
+    }
+
+    Ok(Json(PinsResponse {
+        pins: cids.iter().map(Cid::to_string).collect(),
+    }))
+}
+
+/// `pin/rm` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-pin-rm.
+pub async fn rm(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<PinsResponse>> {
+    let recursive = params.flag("recursive", true)?;
+    let cids = args_to_cids(&ipfs, &params).await?;
+
+    for cid in &cids {
+        let mut unpin = ipfs.remove_pin(cid);
+        if recursive {
+            unpin = unpin.recursive();
+        }
+        unpin.await?;
+    }
+
+    Ok(Json(PinsResponse {
+        pins: cids.iter().map(Cid::to_string).collect(),
+    }))
+}
+
+/// `pin/update` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-pin-update, replacing the
+/// recursive pin of the first argument with one of the second.
+pub async fn update(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<PinsResponse>> {
+    let unpin = params.flag("unpin", true)?;
+    let cids = args_to_cids(&ipfs, &params).await?;
+
+    let (old, new) = match cids.as_slice() {
+        [old, new] => (*old, *new),
+        _ => {
+            return Err(ApiError::bad_request(
+                "expected the paths of the old and the new pin",
+            ))
+        }
+    };
+
+    if unpin {
+        ipfs.update_pin(&old, &new).await?;
+    } else {
+        ipfs.insert_pin(&new).recursive().await?;
+    }
+
+    Ok(Json(PinsResponse {
+        pins: vec![old.to_string(), new.to_string()],
+    }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListResponse {
+    keys: BTreeMap<String, PinInfo>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PinInfo {
+    r#type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+}
+
+/// `pin/ls` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-pin-ls, listing either all of
+/// the pins or the pins of the paths given as `arg`.
+pub async fn list(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<ListResponse>> {
+    let filter = match params.get("type").unwrap_or("all") {
+        "all" => None,
+        "direct" => Some(PinMode::Direct),
+        "indirect" => Some(PinMode::Indirect),
+        "recursive" => Some(PinMode::Recursive),
+        other => {
+            return Err(ApiError::bad_request(format!(
+                "invalid type {other:?}, must be one of {{direct, indirect, recursive, all}}"
+            )))
+        }
+    };
+    let names = params.flag("names", false)?;
+
+    let pins: Vec<(Cid, String)> = if params.args().next().is_some() {
+        let cids = args_to_cids(&ipfs, &params).await?;
+        ipfs.query_pins(cids, filter)
+            .await?
+            .into_iter()
+            .map(|(cid, kind)| {
+                let kind = match kind {
+                    PinKind::IndirectFrom(root) => format!("indirect through {root}"),
+                    PinKind::Direct => "direct".into(),
+                    PinKind::Recursive(_) | PinKind::RecursiveIntention => "recursive".into(),
+                };
+                (cid, kind)
+            })
+            .collect()
+    } else {
+        ipfs.list_pins(filter)
+            .await
+            .map_ok(|(cid, mode)| (cid, mode_name(mode).to_string()))
+            .try_collect()
+            .await?
+    };
+
+    let mut keys = BTreeMap::new();
+    for (cid, kind) in pins {
+        let name = if names {
+            let metadata = ipfs.pin_metadata(&cid).await?;
+            Some(metadata.and_then(|m| m.name).unwrap_or_default())
+        } else {
+            None
+        };
+        keys.insert(cid.to_string(), PinInfo { r#type: kind, name });
+    }
+
+    Ok(Json(ListResponse { keys }))
+}
+
+fn mode_name(mode: PinMode) -> &'static str {
+    match mode {
+        PinMode::Direct => "direct",
+        PinMode::Indirect => "indirect",
+        PinMode::Recursive => "recursive",
+    }
+}
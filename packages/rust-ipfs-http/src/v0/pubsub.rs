@@ -0,0 +1,95 @@
+use super::support::{first_file, ndjson, ApiError, ApiResult, Params};
+use axum::extract::{Multipart, State};
+use axum::Json;
+use futures::stream::StreamExt;
+use libipld::multibase::{self, Base};
+use rust_ipfs::Ipfs;
+use serde::Serialize;
+
+/// Decodes a topic, which kubo expects to be multibase encoded in the URL arguments.
+fn topic(encoded: &str) -> ApiResult<String> {
+    let (_, topic) = multibase::decode(encoded)
+        .map_err(|_| ApiError::bad_request("URL arg must be multibase encoded"))?;
+    String::from_utf8(topic).map_err(|_| ApiError::bad_request("topic must be valid utf-8"))
+}
+
+fn encode(data: impl AsRef<[u8]>) -> String {
+    multibase::encode(Base::Base64Url, data)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StringsResponse {
+    strings: Vec<String>,
+}
+
+/// `pubsub/ls` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-pubsub-ls, with the topics
+/// multibase encoded.
+pub async fn ls(State(ipfs): State<Ipfs>) -> ApiResult<Json<StringsResponse>> {
+    let strings = ipfs.pubsub_subscribed().await?.iter().map(encode).collect();
+
+    Ok(Json(StringsResponse { strings }))
+}
+
+/// `pubsub/peers` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-pubsub-peers, listing the
+/// peers of the topic given as `arg` or of all of the topics.
+pub async fn peers(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<StringsResponse>> {
+    let topic = params.args().next().map(topic).transpose()?;
+
+    let strings = ipfs
+        .pubsub_peers(topic)
+        .await?
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+    Ok(Json(StringsResponse { strings }))
+}
+
+/// `pubsub/pub` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-pubsub-pub, publishing the
+/// first part of the multipart body.
+pub async fn publish(
+    State(ipfs): State<Ipfs>,
+    params: Params,
+    mut multipart: Multipart,
+) -> ApiResult<()> {
+    let topic = topic(params.arg("topic")?)?;
+    let data = first_file(&mut multipart, "data").await?;
+
+    ipfs.pubsub_publish(topic, data).await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct Message {
+    from: String,
+    data: String,
+    seqno: String,
+    #[serde(rename = "topicIDs")]
+    topic_ids: Vec<String>,
+}
+
+/// `pubsub/sub` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-pubsub-sub, streaming the
+/// messages with their data, sequence number and topic multibase encoded. The topic is
+/// unsubscribed from once the response is dropped. Unlike kubo, a topic can only have a single
+/// subscriber at a time.
+pub async fn subscribe(State(ipfs): State<Ipfs>, params: Params) -> ApiResult {
+    let topic = topic(params.arg("topic")?)?;
+
+    let subscription = ipfs.pubsub_subscribe(topic).await?;
+
+    let messages = subscription.map(|message| {
+        Ok(Message {
+            from: message
+                .source
+                .map(|peer_id| peer_id.to_string())
+                .unwrap_or_default(),
+            data: encode(&message.data),
+            seqno: encode(message.sequence_number.unwrap_or_default().to_be_bytes()),
+            topic_ids: vec![encode(message.topic.as_str())],
+        })
+    });
+
+    Ok(ndjson(messages))
+}
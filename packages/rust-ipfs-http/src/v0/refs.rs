@@ -0,0 +1,100 @@
+use super::support::{ndjson, parse_path, resolve_path, ApiError, ApiResult, Params};
+use axum::extract::State;
+use futures::stream::{self, StreamExt};
+use libipld::{Cid, Ipld, IpldCodec};
+use rust_ipfs::dag::ResolvedNode;
+use rust_ipfs::refs::iplds_refs;
+use rust_ipfs::Ipfs;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct Edge {
+    #[serde(rename = "Ref")]
+    ok: String,
+    #[serde(rename = "Err")]
+    err: String,
+}
+
+impl Edge {
+    fn ok(reference: String) -> Self {
+        Edge {
+            ok: reference,
+            err: String::new(),
+        }
+    }
+}
+
+/// `refs` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-refs, streaming the links of the
+/// documents at the end of the paths formatted with `format`, in which `<src>`, `<dst>` and
+/// `<linkname>` are replaced like in kubo. Failures to load the linked blocks are reported in the
+/// `Err` of their lines.
+pub async fn refs(State(ipfs): State<Ipfs>, params: Params) -> ApiResult {
+    let edges = params.flag("edges", false)?;
+    let format = match (params.get("format"), edges) {
+        (Some(_), true) => {
+            return Err(ApiError::bad_request(
+                "using format argument with edges is not allowed",
+            ))
+        }
+        (Some(format), false) => format.to_string(),
+        (None, true) => "<src> -> <dst>".to_string(),
+        (None, false) => "<dst>".to_string(),
+    };
+    let unique = params.flag("unique", false)?;
+    let max_depth = match params.flag("recursive", false)? {
+        true => match params.parse::<i64>("max-depth")? {
+            Some(depth) if depth >= 0 => Some(depth as u64),
+            _ => None,
+        },
+        false => Some(1),
+    };
+
+    if params.args().next().is_none() {
+        return Err(ApiError::bad_request("argument \"ipfs-path\" is required"));
+    }
+
+    let mut iplds: Vec<(Cid, Ipld)> = Vec::new();
+    for arg in params.args() {
+        let path = resolve_path(&ipfs, parse_path(arg)?).await?;
+        match ipfs.dag().resolve(path, true, &[], false).await? {
+            (ResolvedNode::Block(block), _) => {
+                let ipld = block
+                    .decode::<IpldCodec, Ipld>()
+                    .map_err(ApiError::internal)?;
+                iplds.push((*block.cid(), ipld));
+            }
+            (ResolvedNode::Projection(cid, ipld), _) => iplds.push((cid, ipld)),
+            // the data of dag-pb documents does not contain links
+            (ResolvedNode::DagPbData(..) | ResolvedNode::Link(..), _) => {}
+        }
+    }
+
+    let edges = iplds_refs(ipfs.repo().clone(), iplds, max_depth, unique).map(move |edge| {
+        Ok(match edge {
+            Ok(edge) => {
+                let reference = format
+                    .replace("<src>", &edge.source.to_string())
+                    .replace("<dst>", &edge.destination.to_string())
+                    .replace("<linkname>", edge.name.as_deref().unwrap_or_default());
+                Edge::ok(reference)
+            }
+            Err(e) => Edge {
+                ok: String::new(),
+                err: e.to_string(),
+            },
+        })
+    });
+
+    Ok(ndjson(edges))
+}
+
+/// `refs/local` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-refs-local.
+pub async fn local(State(ipfs): State<Ipfs>) -> ApiResult {
+    let refs = ipfs
+        .refs_local()
+        .await
+        .into_iter()
+        .map(|cid| Ok(Edge::ok(cid.to_string())));
+
+    Ok(ndjson(stream::iter(refs)))
+}
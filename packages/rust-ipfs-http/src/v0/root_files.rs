@@ -1,159 +1,94 @@
-use crate::v0::support::{
-    with_ipfs, MaybeTimeoutExt, StreamResponse, StringError, StringSerialized,
+use super::support::{
+    parse_path, resolve_cid, resolve_path, streamed, ApiError, ApiResult, Params,
 };
 use async_stream::try_stream;
+use axum::body::Body;
+use axum::extract::State;
+use axum::Json;
 use bytes::Bytes;
-use futures::stream::TryStream;
+use futures::stream::{self, Stream, StreamExt};
+use libipld::Cid;
 use rust_ipfs::unixfs::ll::walk::{self, ContinuedWalk, Walker};
-use rust_ipfs::unixfs::{ll::file::FileReadFailed, TraversalFailed};
-use rust_ipfs::{dag::ResolveError, Block, Ipfs, IpfsPath, IpfsTypes};
-use serde::Deserialize;
+use rust_ipfs::unixfs::{Entry, UnixfsLs};
+use rust_ipfs::{Block, Ipfs, IpfsPath};
+use serde::Serialize;
 use std::fmt;
 use std::path::Path;
-use warp::{query, Filter, Rejection, Reply};
 
 mod tar_helper;
 use tar_helper::TarHelper;
 
 mod add;
-
-#[derive(Debug, Deserialize)]
-pub struct AddArgs {
-    // unknown meaning; ignoring it doesn't fail any tests
-    #[serde(default, rename = "stream-channels")]
-    #[allow(unused)] // sent by client
-    stream_channels: bool,
-    // progress reports totaling to the input file size
-    #[serde(default)]
-    progress: bool,
-    /// When true, a new directory is created to hold more than 1 root level directories.
-    #[serde(default, rename = "wrap-with-directory")]
-    wrap_with_directory: bool,
-}
-
-pub fn add<T: IpfsTypes>(
-    ipfs: &Ipfs<T>,
-) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
-    with_ipfs(ipfs)
-        .and(query::<AddArgs>())
-        .and(warp::header::<mime::Mime>("content-type")) // TODO: rejects if missing
-        .and(warp::body::stream())
-        .and_then(add::add_inner)
-}
-
-#[derive(Debug, Deserialize)]
-pub struct CatArgs {
-    arg: StringSerialized<IpfsPath>,
-    offset: Option<u64>,
-    length: Option<u64>,
-    timeout: Option<StringSerialized<humantime::Duration>>,
-}
-
-pub fn cat<T: IpfsTypes>(
-    ipfs: &Ipfs<T>,
-) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
-    with_ipfs(ipfs).and(query::<CatArgs>()).and_then(cat_inner)
-}
-
-async fn cat_inner<T: IpfsTypes>(ipfs: Ipfs<T>, args: CatArgs) -> Result<impl Reply, Rejection> {
-    let path = args.arg.into_inner();
-
-    let range = match (args.offset, args.length) {
-        (Some(start), Some(len)) => Some(start..(start + len)),
-        (Some(_start), None) => return Err(crate::v0::support::NotImplemented.into()),
-        (None, Some(len)) => Some(0..len),
-        (None, None) => None,
-    };
-
-    // TODO: timeout for the whole stream!
-    let ret = ipfs::unixfs::cat(ipfs, path, range)
-        .maybe_timeout(args.timeout.map(StringSerialized::into_inner))
-        .await
-        .map_err(StringError::from)?;
-
-    let stream = match ret {
-        Ok(stream) => stream,
-        Err(TraversalFailed::Resolving(err @ ResolveError::NotFound(..))) => {
-            // this is checked in the tests
-            return Err(StringError::from(err).into());
+pub use add::add;
+
+/// `cat` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-cat, with the `offset` and the
+/// `length` of the range to read.
+pub async fn cat(State(ipfs): State<Ipfs>, params: Params) -> ApiResult {
+    let path = resolve_path(&ipfs, parse_path(params.arg("ipfs-path")?)?).await?;
+    let offset = params.parse::<u64>("offset")?;
+    let length = params.parse::<u64>("length")?;
+
+    let mut cat = ipfs.cat_unixfs(path);
+    match (offset, length) {
+        (None, None) => {}
+        (offset, length) => {
+            let start = offset.unwrap_or(0);
+            let end = length.map_or(u64::MAX, |length| start.saturating_add(length));
+            cat = cat.range(start..end);
         }
-        Err(TraversalFailed::Walking(_, FileReadFailed::UnexpectedType(ut)))
-            if ut.is_directory() =>
-        {
-            return Err(StringError::from("this dag node is a directory").into())
-        }
-        Err(e) => return Err(StringError::from(e).into()),
-    };
-
-    Ok(StreamResponse(stream))
-}
+    }
 
-#[derive(Deserialize)]
-struct GetArgs {
-    arg: StringSerialized<IpfsPath>,
-    timeout: Option<StringSerialized<humantime::Duration>>,
-}
+    // the first part is waited for so that failing to resolve the path can still be responded
+    // to with an error status
+    let first = match cat.next().await {
+        Some(Ok(bytes)) => Some(bytes),
+        Some(Err(e)) => return Err(e.into()),
+        None => None,
+    };
+    let body = stream::iter(first.map(Ok)).chain(cat);
 
-pub fn get<T: IpfsTypes>(
-    ipfs: &Ipfs<T>,
-) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
-    with_ipfs(ipfs).and(query::<GetArgs>()).and_then(get_inner)
+    Ok(streamed(Body::from_stream(body), "text/plain"))
 }
 
-async fn get_inner<T: IpfsTypes>(ipfs: Ipfs<T>, args: GetArgs) -> Result<impl Reply, Rejection> {
-    use futures::stream::TryStreamExt;
-
-    let path = args.arg.into_inner();
-
-    // FIXME: this timeout is only for the first step, should be for the whole walk!
-    let block = resolve_dagpb(&ipfs, path)
-        .maybe_timeout(args.timeout.map(StringSerialized::into_inner))
-        .await
-        .map_err(StringError::from)?
-        .map_err(StringError::from)?;
-
-    Ok(StreamResponse(walk(ipfs, block).into_stream()))
-}
+/// `get` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-get, responding with a tar archive
+/// named after the Cid of the path.
+pub async fn get(State(ipfs): State<Ipfs>, params: Params) -> ApiResult {
+    if params.flag("compress", false)? {
+        return Err(ApiError::bad_request("compression is not supported"));
+    }
+    let path = resolve_path(&ipfs, parse_path(params.arg("ipfs-path")?)?).await?;
 
-async fn resolve_dagpb<T: IpfsTypes>(ipfs: &Ipfs<T>, path: IpfsPath) -> Result<Block, StringError> {
-    let (resolved, _) = ipfs
-        .dag()
-        .resolve(path, true)
-        .await
-        .map_err(StringError::from)?;
+    let (resolved, _) = ipfs.dag().resolve(path, true, &[], false).await?;
+    let block = resolved.into_unixfs_block()?;
 
-    resolved.into_unixfs_block().map_err(StringError::from)
+    Ok(streamed(
+        Body::from_stream(walk(ipfs, block)),
+        "application/x-tar",
+    ))
 }
 
-fn walk<Types: IpfsTypes>(
-    ipfs: Ipfs<Types>,
-    Block {
-        cid: root,
-        data: first_block_data,
-    }: Block,
-) -> impl TryStream<Ok = Bytes, Error = GetError> + 'static {
+fn walk(ipfs: Ipfs, root: Block) -> impl Stream<Item = Result<Bytes, GetError>> + Send + 'static {
     let mut cache = None;
     let mut tar_helper = TarHelper::with_capacity(16 * 1024);
 
     // the HTTP api uses the final Cid name as the root name in the generated tar
     // archive.
-    let name = root.to_string();
-    let mut walker = Walker::new(root, name);
+    let name = root.cid().to_string();
+    let mut walker = Walker::new(*root.cid(), name);
 
-    let mut buffer = Some(first_block_data);
+    let mut buffer = Some(root);
 
     try_stream! {
         while walker.should_continue() {
-            let data = match buffer.take() {
+            let block = match buffer.take() {
                 Some(first) => first,
                 None => {
                     let (next, _) = walker.pending_links();
-                    let Block { data, .. } = ipfs.get_block(next).await?;
-                    data
+                    ipfs.get_block(next).await?
                 }
             };
 
-            match walker.next(&data, &mut cache)? {
+            match walker.next(block.data(), &mut cache)? {
                 ContinuedWalk::Bucket(..) => {}
                 ContinuedWalk::File(segment, _, path, metadata, size) => {
                     if segment.is_first() {
@@ -213,11 +148,11 @@ enum GetError {
     InvalidFileName(Vec<u8>),
     InvalidLinkName(Vec<u8>),
     Walk(walk::Error),
-    Loading(ipfs::Error),
+    Loading(rust_ipfs::Error),
 }
 
-impl From<ipfs::Error> for GetError {
-    fn from(e: ipfs::Error) -> Self {
+impl From<rust_ipfs::Error> for GetError {
+    fn from(e: rust_ipfs::Error) -> Self {
         GetError::Loading(e)
     }
 }
@@ -233,10 +168,10 @@ impl fmt::Display for GetError {
         use GetError::*;
         match self {
             NonUtf8Symlink => write!(fmt, "symlink target could not be converted to utf-8"),
-            Walk(e) => write!(fmt, "{}", e),
-            Loading(e) => write!(fmt, "loading failed: {}", e),
-            InvalidFileName(x) => write!(fmt, "filename cannot be put inside tar: {:?}", x),
-            InvalidLinkName(x) => write!(fmt, "symlink name cannot be put inside tar: {:?}", x),
+            Walk(e) => write!(fmt, "{e}"),
+            Loading(e) => write!(fmt, "loading failed: {e}"),
+            InvalidFileName(x) => write!(fmt, "filename cannot be put inside tar: {x:?}"),
+            InvalidLinkName(x) => write!(fmt, "symlink name cannot be put inside tar: {x:?}"),
         }
     }
 }
@@ -250,16 +185,99 @@ impl std::error::Error for GetError {
     }
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LsResponse {
+    objects: Vec<LsObject>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LsObject {
+    hash: String,
+    links: Vec<LsLink>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LsLink {
+    name: String,
+    hash: String,
+    size: usize,
+    // the unixfs data type, 1 for directories and 2 for files
+    r#type: u8,
+    target: String,
+}
+
+/// `ls` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-ls, listing the direct entries of
+/// the directories given as `arg`. Files are listed without any links.
+pub async fn ls(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<LsResponse>> {
+    let mut objects = Vec::new();
+
+    for arg in params.args() {
+        let cid = resolve_cid(&ipfs, parse_path(arg)?).await?;
+        objects.push(LsObject {
+            hash: arg.to_string(),
+            links: list_directory(&ipfs, cid).await?,
+        });
+    }
+
+    if objects.is_empty() {
+        return Err(ApiError::bad_request("argument \"ipfs-path\" is required"));
+    }
+
+    Ok(Json(LsResponse { objects }))
+}
+
+async fn list_directory(ipfs: &Ipfs, cid: Cid) -> ApiResult<Vec<LsLink>> {
+    let mut ls = UnixfsLs::with_ipfs(ipfs, IpfsPath::from(cid));
+
+    // the listing walks the whole directory, of which only the direct entries are shown
+    let mut directory = false;
+    let mut links = vec![];
+    while let Some(entry) = ls.next().await {
+        let (name, cid, size, r#type) = match entry {
+            Entry::Error { error } => return Err(error.into()),
+            Entry::RootDirectory { .. } => {
+                directory = true;
+                continue;
+            }
+            Entry::File {
+                cid, file, size, ..
+            } if !file.contains('/') => (file, cid, size, 2),
+            Entry::Directory { cid, path, .. } if !path.contains('/') => (path, cid, 0, 1),
+            _ => continue,
+        };
+        links.push(LsLink {
+            name,
+            hash: cid.to_string(),
+            size,
+            r#type,
+            target: String::new(),
+        });
+    }
+
+    if !directory {
+        // a file is listed as itself
+        return Ok(vec![]);
+    }
+
+    links.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(links)
+}
+
 #[cfg(test)]
 mod tests {
-    use cid::Cid;
-    use futures::stream::{FuturesOrdered, TryStreamExt};
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
     use hex_literal::hex;
-    use multihash::Sha2_256;
-    use rust_ipfs::{Block, Ipfs, IpfsTypes, Node};
+    use libipld::multihash::{Code, MultihashDigest};
+    use libipld::Cid;
+    use rust_ipfs::{Block, Node};
     use std::convert::TryFrom;
     use std::io::Read;
     use std::path::PathBuf;
+    use tower::ServiceExt;
 
     // Entry we'll use in expectations
     #[derive(Debug, PartialEq, Eq)]
@@ -321,19 +339,10 @@ mod tests {
             &hex!("0a260802122077656c6c2068656c6c6f207468657265206c6f6e672066696c656e616d65730a1820"),
         ];
 
-        drop(put_all_blocks(&ipfs, blocks).await.unwrap());
-
-        let filter = super::get(&ipfs);
-
-        let response = warp::test::request()
-            .method("POST")
-            .path("/get?arg=QmdKuCuXDuVTsnGpzPgZEuJmiCEn6LZhGHHHwWPQH28DeD")
-            .reply(&filter)
-            .await;
-
-        assert_eq!(response.status(), 200);
+        put_all_blocks(&ipfs, blocks).await;
 
-        let found = get_archive_entries(response.body());
+        let body = get(&ipfs, "QmdKuCuXDuVTsnGpzPgZEuJmiCEn6LZhGHHHwWPQH28DeD").await;
+        let found = get_archive_entries(body);
 
         let long_filename = "42a353817a6be8b1dea010fa856c2fddb6e116c84ea7a297f4f751ca4b3da367dd5c685ef1917b26c439af7e302be89ac2d2f96112f3c8f102bf7f751a862781f09e60e6181dc355e44f76d5ab6fb4dc62e4d87523e85fca7e29e8dc8c3dd8dc67a36b9b7a3a47016a827adc65898f5f90faab6218ad199b481a601b4392a5_";
 
@@ -349,9 +358,9 @@ mod tests {
             Entry::Dir("QmdKuCuXDuVTsnGpzPgZEuJmiCEn6LZhGHHHwWPQH28DeD/some/nested/dir/hierarchy/longer/and/longer/and".into()),
             Entry::Dir("QmdKuCuXDuVTsnGpzPgZEuJmiCEn6LZhGHHHwWPQH28DeD/some/nested/dir/hierarchy/longer/and/longer/and/longer".into()),
             Entry::Symlink(
-                format!("QmdKuCuXDuVTsnGpzPgZEuJmiCEn6LZhGHHHwWPQH28DeD/some/nested/dir/hierarchy/longer/and/longer/and/longer/{}", long_filename).into(),
-                format!("../../../../../../../../../{}", long_filename).into()),
-            Entry::File(format!("QmdKuCuXDuVTsnGpzPgZEuJmiCEn6LZhGHHHwWPQH28DeD/{}", long_filename).into(), 32, b"well hello there long filenames\n".to_vec()),
+                format!("QmdKuCuXDuVTsnGpzPgZEuJmiCEn6LZhGHHHwWPQH28DeD/some/nested/dir/hierarchy/longer/and/longer/and/longer/{long_filename}").into(),
+                format!("../../../../../../../../../{long_filename}").into()),
+            Entry::File(format!("QmdKuCuXDuVTsnGpzPgZEuJmiCEn6LZhGHHHwWPQH28DeD/{long_filename}").into(), 32, b"well hello there long filenames\n".to_vec()),
         ];
 
         assert_eq!(found, expected);
@@ -374,19 +383,10 @@ mod tests {
             &hex!("0a07080212010a1801"),
         ];
 
-        drop(put_all_blocks(&ipfs, blocks).await.unwrap());
+        put_all_blocks(&ipfs, blocks).await;
 
-        let filter = super::get(&ipfs);
-
-        let response = warp::test::request()
-            .method("POST")
-            .path("/get?arg=QmRJHYTNvC3hmd9gJQARxLR1QMEincccBV53bBw524yyq6")
-            .reply(&filter)
-            .await;
-
-        assert_eq!(response.status(), 200);
-
-        let found = get_archive_entries(response.body());
+        let body = get(&ipfs, "QmRJHYTNvC3hmd9gJQARxLR1QMEincccBV53bBw524yyq6").await;
+        let found = get_archive_entries(body);
 
         let expected = vec![Entry::File(
             "QmRJHYTNvC3hmd9gJQARxLR1QMEincccBV53bBw524yyq6".into(),
@@ -397,6 +397,19 @@ mod tests {
         assert_eq!(found, expected);
     }
 
+    async fn get(ipfs: &Node, arg: &str) -> bytes::Bytes {
+        let (shutdown_tx, _) = tokio::sync::mpsc::channel(1);
+        let routes = crate::v0::routes(ipfs, shutdown_tx);
+
+        let request = Request::post(format!("/api/v0/get?arg={arg}"))
+            .body(Body::empty())
+            .unwrap();
+        let response = routes.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), 200);
+
+        to_bytes(response.into_body(), usize::MAX).await.unwrap()
+    }
+
     fn get_archive_entries(bytes: impl AsRef<[u8]>) -> Vec<Entry> {
         let mut cursor = std::io::Cursor::new(bytes.as_ref());
 
@@ -411,29 +424,12 @@ mod tests {
             .unwrap()
     }
 
-    fn put_all_blocks<'a, T: IpfsTypes>(
-        ipfs: &'a Ipfs<T>,
-        blocks: &'a [&'a [u8]],
-    ) -> impl std::future::Future<Output = Result<Vec<Cid>, ipfs::Error>> + 'a {
-        let mut inorder = FuturesOrdered::new();
+    async fn put_all_blocks(ipfs: &Node, blocks: &[&[u8]]) {
         for block in blocks {
-            inorder.push(put_block(ipfs, block));
+            let cid = Cid::new_v0(Code::Sha2_256.digest(block)).unwrap();
+            ipfs.put_block(Block::new_unchecked(cid, block.to_vec()))
+                .await
+                .unwrap();
         }
-
-        inorder.try_collect::<Vec<_>>()
-    }
-
-    fn put_block<'a, T: IpfsTypes>(
-        ipfs: &'a Ipfs<T>,
-        block: &'a [u8],
-    ) -> impl std::future::Future<Output = Result<Cid, ipfs::Error>> + 'a {
-        let cid = Cid::new_v0(Sha2_256::digest(block)).unwrap();
-
-        let block = Block {
-            cid,
-            data: block.into(),
-        };
-
-        ipfs.put_block(block)
     }
 }
@@ -0,0 +1,349 @@
+use crate::v0::support::{ndjson, parse_hash, ApiError, ApiResult, Params, Quoted};
+use axum::extract::{Multipart, State};
+use axum::response::Response as HttpResponse;
+use libipld::multihash::Code;
+use libipld::Cid;
+use rust_ipfs::unixfs::ll::{
+    dir::builder::{BufferingTreeBuilder, TreeNode, TreeOptions},
+    file::adder::{BalancedCollector, Chunker, Collector, FileAdder, TrickleCollector},
+    CidOptions, Metadata,
+};
+use rust_ipfs::{Block, Ipfs};
+use serde::Serialize;
+
+/// Options of `add` which are parsed before reading the body.
+#[derive(Debug)]
+struct AddOptions {
+    // progress reports totaling to the input file size
+    progress: bool,
+    /// When true, a new directory is created to hold more than 1 root level directories.
+    wrap_with_directory: bool,
+    pin: bool,
+    chunker: Chunker,
+    collector: Collector,
+    cid_options: CidOptions,
+    raw_leaves: bool,
+}
+
+impl AddOptions {
+    fn parse(params: &Params) -> ApiResult<Self> {
+        let hash = parse_hash(params.get("hash").unwrap_or("sha2-256"))?;
+
+        // resolved the same way as go-ipfs, version 0 unless a different hash was asked for
+        let version = match (params.parse::<u64>("cid-version")?, hash) {
+            (Some(0), Code::Sha2_256) | (None, Code::Sha2_256) => libipld::cid::Version::V0,
+            (Some(0), _) => {
+                return Err(ApiError::bad_request(
+                    "cid version 0 only supports sha2-256",
+                ))
+            }
+            (Some(1), _) | (None, _) => libipld::cid::Version::V1,
+            (Some(other), _) => {
+                return Err(ApiError::bad_request(format!(
+                    "unknown cid version: {other}"
+                )))
+            }
+        };
+        let raw_leaves = params.flag("raw-leaves", version == libipld::cid::Version::V1)?;
+
+        let chunker = match params.get("chunker") {
+            Some(chunker) => parse_chunker(chunker)?,
+            None => Chunker::default(),
+        };
+        let collector = match params.flag("trickle", false)? {
+            true => TrickleCollector::default().into(),
+            false => BalancedCollector::default().into(),
+        };
+
+        Ok(AddOptions {
+            progress: params.flag("progress", false)?,
+            wrap_with_directory: params.flag("wrap-with-directory", false)?,
+            pin: params.flag("pin", true)?,
+            chunker,
+            collector,
+            cid_options: CidOptions::default().with_version(version).with_hash(hash),
+            raw_leaves,
+        })
+    }
+}
+
+/// Parses the chunkers of go-ipfs: `size-{size}`, `rabin-{min}-{avg}-{max}` and `rabin-{avg}`.
+fn parse_chunker(chunker: &str) -> ApiResult<Chunker> {
+    let invalid = || ApiError::bad_request(format!("unrecognized chunker option: {chunker:?}"));
+    let numbers = |s: &str| {
+        s.split('-')
+            .map(|n| n.parse::<usize>().map_err(|_| invalid()))
+            .collect::<ApiResult<Vec<_>>>()
+    };
+
+    if let Some(size) = chunker.strip_prefix("size-") {
+        return match numbers(size)?.as_slice() {
+            [size] if *size > 0 => Ok(Chunker::Size(*size)),
+            _ => Err(invalid()),
+        };
+    }
+
+    // the cut points of buzhash depend on the byte hash table of go-ipfs-chunker
+    if chunker == "buzhash" {
+        return Err(ApiError::bad_request(
+            "buzhash chunker is not supported, use size-<bytes> or rabin",
+        ));
+    }
+
+    if chunker == "rabin" {
+        return Ok(Chunker::Rabin {
+            min: 256 * 1024 / 3,
+            avg: 256 * 1024,
+            max: 256 * 1024 + 256 * 1024 / 2,
+        });
+    }
+
+    if let Some(sizes) = chunker.strip_prefix("rabin-") {
+        return match numbers(sizes)?.as_slice() {
+            [avg] => Ok(Chunker::Rabin {
+                min: avg / 3,
+                avg: *avg,
+                max: avg + avg / 2,
+            }),
+            [min, avg, max] if min <= avg && avg <= max && *min >= 16 => Ok(Chunker::Rabin {
+                min: *min,
+                avg: *avg,
+                max: *max,
+            }),
+            _ => Err(invalid()),
+        };
+    }
+
+    Err(invalid())
+}
+
+/// The possible response messages from /add.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum Response {
+    /// When progress=true query parameter has been given, this will be output for every chunk.
+    #[serde(rename_all = "PascalCase")]
+    Progress {
+        /// Name of the file being added or empty if none was provided.
+        name: String,
+        /// Bytes processed so far; unlike `Added::size` this is not stringified.
+        bytes: u64,
+    },
+    /// Output for every input item.
+    #[serde(rename_all = "PascalCase")]
+    Added {
+        /// Name of the file added from filename or the resulting Cid.
+        name: String,
+        /// The resulting Cid as a string.
+        hash: Quoted<Cid>,
+        /// Stringified version of the total cumulative size in bytes.
+        size: Quoted<u64>,
+    },
+}
+
+/// `add` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-add. Every part of the multipart
+/// body is a file, or a directory when the content type is `application/x-directory`, named by
+/// the percent-encoded filename. The roots are pinned recursively unless `pin=false`.
+pub async fn add(
+    State(ipfs): State<Ipfs>,
+    params: Params,
+    mut multipart: Multipart,
+) -> ApiResult<HttpResponse> {
+    let opts = AddOptions::parse(&params)?;
+
+    let stream = async_stream::try_stream! {
+        let mut tree_opts = TreeOptions::default();
+        tree_opts.cid_options(opts.cid_options);
+        if opts.wrap_with_directory {
+            tree_opts.wrap_with_directory();
+        }
+
+        let mut tree = BufferingTreeBuilder::new(tree_opts);
+        let mut roots = Vec::new();
+
+        while let Some(mut field) = multipart.next_field().await.map_err(ApiError::bad_request)? {
+            let filename = field.file_name().unwrap_or_default();
+            let filename = percent_encoding::percent_decode_str(filename)
+                .decode_utf8()
+                .map_err(|e| ApiError::bad_request(format!("invalid multipart filename: {e}")))?;
+
+            // normalize single first slash; clients post both '/some-directory/...' and
+            // 'some-directory/...'
+            let filename = filename
+                .strip_prefix('/')
+                .unwrap_or(&filename)
+                .to_string();
+
+            if field.content_type() == Some("application/x-directory") {
+                // the mode and the modification time are not read from the headers, but this
+                // will still add an empty directory which is a good thing.
+                tree.set_metadata(&filename, Metadata::default())
+                    .map_err(ApiError::bad_request)?;
+                continue;
+            }
+
+            let mut adder = FileAdder::builder()
+                .with_chunker(opts.chunker)
+                .with_collector(opts.collector.clone())
+                .with_cid_options(opts.cid_options)
+                .with_raw_leaves(opts.raw_leaves)
+                .build();
+
+            // how many bytes we have stored as blocks
+            let mut total_written = 0u64;
+            // how many bytes of input we have read
+            let mut total_read = 0u64;
+            let mut root = None;
+
+            while let Some(chunk) = field.chunk().await.map_err(ApiError::bad_request)? {
+                let mut read = 0usize;
+                while read < chunk.len() {
+                    let (blocks, used) = adder.push(&chunk[read..]);
+                    read += used;
+                    for (cid, data) in blocks {
+                        total_written += data.len() as u64;
+                        root = Some(ipfs.put_block(Block::new_unchecked(cid, data)).await?);
+                    }
+                }
+                total_read += chunk.len() as u64;
+
+                if opts.progress {
+                    yield Response::Progress {
+                        name: filename.clone(),
+                        bytes: total_read,
+                    };
+                }
+            }
+
+            for (cid, data) in adder.finish() {
+                total_written += data.len() as u64;
+                root = Some(ipfs.put_block(Block::new_unchecked(cid, data)).await?);
+            }
+
+            let root = root.ok_or_else(|| ApiError::internal("adding the file produced no blocks"))?;
+
+            trace!("completed processing file of {} bytes: {:?}", total_read, filename);
+
+            // using the filename as the path since we can tolerate a single empty named file
+            // however the second one will cause issues
+            tree.put_link(&filename, root, total_written)
+                .map_err(ApiError::bad_request)?;
+
+            if !opts.wrap_with_directory && !filename.contains('/') {
+                roots.push(root);
+            }
+
+            // cid needs to be repeated if no filename was given; in which case there should not
+            // be anything to build as tree either.
+            let name = match filename.is_empty() {
+                true => root.to_string(),
+                false => filename,
+            };
+
+            yield Response::Added {
+                name,
+                hash: Quoted(root),
+                size: Quoted(total_written),
+            };
+        }
+
+        let mut iter = tree.build();
+
+        while let Some(res) = iter.next_borrowed() {
+            let TreeNode { path, cid, total_size, block } = res.map_err(ApiError::internal)?;
+
+            let cid = ipfs.put_block(Block::new_unchecked(*cid, block.to_vec())).await?;
+
+            if !path.contains('/') && (path.is_empty() || !opts.wrap_with_directory) {
+                roots.push(cid);
+            }
+
+            yield Response::Added {
+                name: path.to_string(),
+                hash: Quoted(cid),
+                size: Quoted(total_size),
+            };
+        }
+
+        if opts.pin {
+            for root in roots {
+                ipfs.insert_pin(&root).recursive().await?;
+            }
+        }
+    };
+
+    Ok(ndjson(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_chunker;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use rust_ipfs::unixfs::ll::file::adder::Chunker;
+    use rust_ipfs::Node;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn add_single_block_file() {
+        let ipfs = Node::new("test_node").await;
+        let (shutdown_tx, _) = tokio::sync::mpsc::channel(1);
+        let routes = crate::v0::routes(&ipfs, shutdown_tx);
+
+        // this is from interface-ipfs-core, pretty much simplest add a buffer test case
+        let request = Request::post("/api/v0/add")
+            .header(
+                "content-type",
+                "multipart/form-data; boundary=-----------------------------Z0oYi6XyTm7_x2L4ty8JL",
+            )
+            .body(Body::from(
+                &b"-------------------------------Z0oYi6XyTm7_x2L4ty8JL\r\n\
+                    Content-Disposition: form-data; name=\"file\"; filename=\"testfile.txt\"\r\n\
+                    Content-Type: application/octet-stream\r\n\
+                    \r\n\
+                    Plz add me!\n\
+                    \r\n-------------------------------Z0oYi6XyTm7_x2L4ty8JL--\r\n"[..],
+            ))
+            .unwrap();
+
+        let response = routes.oneshot(request).await.unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        assert_eq!(
+            std::str::from_utf8(&body).unwrap(),
+            "{\"Name\":\"testfile.txt\",\"Hash\":\"Qma4hjFTnCasJ8PVp3mZbZK5g2vGDT4LByLJ7m8ciyRFZP\",\"Size\":\"20\"}\n"
+        );
+
+        let cid = "Qma4hjFTnCasJ8PVp3mZbZK5g2vGDT4LByLJ7m8ciyRFZP"
+            .parse()
+            .unwrap();
+        assert!(ipfs.is_pinned(&cid).await.unwrap());
+    }
+
+    #[test]
+    fn go_ipfs_chunkers() {
+        assert!(matches!(
+            parse_chunker("size-262144"),
+            Ok(Chunker::Size(262144))
+        ));
+        assert!(matches!(
+            parse_chunker("rabin-16-32-64"),
+            Ok(Chunker::Rabin {
+                min: 16,
+                avg: 32,
+                max: 64
+            })
+        ));
+        assert!(matches!(
+            parse_chunker("rabin-3000"),
+            Ok(Chunker::Rabin {
+                min: 1000,
+                avg: 3000,
+                max: 4500
+            })
+        ));
+        assert!(parse_chunker("size-0").is_err());
+        assert!(parse_chunker("rabin-64-32-16").is_err());
+        assert!(parse_chunker("buzhash").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use super::support::{first_file, ndjson, parse_path, resolve_cid, ApiError, ApiResult, Params};
+use axum::extract::{Multipart, State};
+use axum::Json;
+use base64::Engine;
+use futures::stream::StreamExt;
+use rust_ipfs::{Ipfs, PeerId, Quorum};
+use serde::Serialize;
+
+/// The kinds of the routing query events of kubo, of which only the final results are sent.
+const FINAL_PEER: usize = 2;
+const PROVIDER: usize = 4;
+const VALUE: usize = 5;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Response {
+    // the value of `get`, blank otherwise
+    extra: String,
+    #[serde(rename = "ID")]
+    id: String,
+    // the peers found
+    responses: Vec<ResponsesMember>,
+    r#type: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ResponsesMember {
+    // Multiaddrs
+    addrs: Vec<String>,
+    // PeerId
+    #[serde(rename = "ID")]
+    id: String,
+}
+
+/// `routing/findpeer` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-routing-findpeer.
+pub async fn find_peer(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<Response>> {
+    let peer_id = params
+        .arg("peerID")?
+        .parse::<PeerId>()
+        .map_err(|_| ApiError::bad_request("invalid peer id"))?;
+
+    let addrs = ipfs
+        .find_peer(peer_id)
+        .await?
+        .into_iter()
+        .map(|addr| addr.to_string())
+        .collect();
+
+    Ok(Json(Response {
+        extra: String::new(),
+        id: String::new(),
+        responses: vec![ResponsesMember {
+            addrs,
+            id: peer_id.to_string(),
+        }],
+        r#type: FINAL_PEER,
+    }))
+}
+
+/// `dht/query` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-dht-query, streaming the
+/// closest peers to the peer id found in the DHT.
+pub async fn query(State(ipfs): State<Ipfs>, params: Params) -> ApiResult {
+    let peer_id = params
+        .arg("peerID")?
+        .parse::<PeerId>()
+        .map_err(|_| ApiError::bad_request("invalid peer id"))?;
+
+    let events = ipfs
+        .get_closest_peers(peer_id)
+        .await?
+        .into_iter()
+        .map(|peer_id| {
+            Ok(Response {
+                extra: String::new(),
+                id: String::new(),
+                responses: vec![ResponsesMember {
+                    addrs: Vec::new(),
+                    id: peer_id.to_string(),
+                }],
+                r#type: FINAL_PEER,
+            })
+        });
+
+    Ok(ndjson(futures::stream::iter(events)))
+}
+
+/// `routing/findprovs` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-routing-findprovs,
+/// streaming the providers as they are found.
+pub async fn find_providers(State(ipfs): State<Ipfs>, params: Params) -> ApiResult {
+    let cid = resolve_cid(&ipfs, parse_path(params.arg("key")?)?).await?;
+    let num_providers = params.parse::<usize>("num-providers")?.unwrap_or(20);
+    if num_providers == 0 {
+        return Err(ApiError::bad_request(
+            "number of providers must be greater than 0",
+        ));
+    }
+
+    let providers = ipfs.get_providers(cid).await?;
+
+    let events = providers.take(num_providers).map(|peer_id| {
+        Ok(Response {
+            extra: String::new(),
+            id: String::new(),
+            responses: vec![ResponsesMember {
+                addrs: Vec::new(),
+                id: peer_id.to_string(),
+            }],
+            r#type: PROVIDER,
+        })
+    });
+
+    Ok(ndjson(events))
+}
+
+/// `routing/provide` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-routing-provide.
+pub async fn provide(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<()> {
+    let mut provided = false;
+    for arg in params.args() {
+        let cid = resolve_cid(&ipfs, parse_path(arg)?).await?;
+        ipfs.provide(cid).await?;
+        provided = true;
+    }
+
+    if !provided {
+        return Err(ApiError::bad_request("argument \"key\" is required"));
+    }
+
+    Ok(())
+}
+
+/// `routing/get` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-routing-get, responding
+/// with the first record found as base64 in `Extra`.
+pub async fn get(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<Response>> {
+    let key = params.arg("key")?;
+
+    let record = ipfs
+        .dht_get(key)
+        .await?
+        .next()
+        .await
+        .ok_or_else(|| ApiError::internal("routing: not found"))?;
+
+    Ok(Json(Response {
+        extra: base64::engine::general_purpose::STANDARD.encode(record.value),
+        id: record.publisher.map(|p| p.to_string()).unwrap_or_default(),
+        responses: Vec::new(),
+        r#type: VALUE,
+    }))
+}
+
+/// `routing/put` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-routing-put, reading the
+/// value from the first part of the multipart body.
+pub async fn put(
+    State(ipfs): State<Ipfs>,
+    params: Params,
+    mut multipart: Multipart,
+) -> ApiResult<Json<Response>> {
+    let key = params.arg("key")?;
+
+    let value = first_file(&mut multipart, "value-file").await?;
+
+    ipfs.dht_put(key, value.to_vec(), Quorum::One).await?;
+
+    Ok(Json(Response {
+        extra: String::new(),
+        id: ipfs.keypair().public().to_peer_id().to_string(),
+        responses: Vec::new(),
+        r#type: VALUE,
+    }))
+}
@@ -0,0 +1,78 @@
+use super::support::ApiResult;
+use axum::extract::State;
+use axum::Json;
+use rust_ipfs::Ipfs;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RepoResponse {
+    // the size on disk, which is less than the size of the blocks when they are compressed
+    repo_size: usize,
+    num_objects: usize,
+    // zero when the size of the repository is not limited
+    storage_max: usize,
+}
+
+/// `stats/repo` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-stats-repo.
+pub async fn repo(State(ipfs): State<Ipfs>) -> ApiResult<Json<RepoResponse>> {
+    let repo = ipfs.repo();
+
+    Ok(Json(RepoResponse {
+        repo_size: repo.get_total_physical_size().await?,
+        num_objects: ipfs.refs_local().await.len(),
+        storage_max: repo.max_storage_size(),
+    }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BitswapResponse {
+    // the cids as dag-json links, {"/": cid}
+    wantlist: Vec<BTreeMap<&'static str, String>>,
+    peers: Vec<String>,
+}
+
+/// `stats/bitswap` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-stats-bitswap. Only the
+/// wantlist and the peers are known, the counters of kubo are left out.
+pub async fn bitswap(State(ipfs): State<Ipfs>) -> ApiResult<Json<BitswapResponse>> {
+    let wantlist = ipfs
+        .bitswap_wantlist(None)
+        .await?
+        .into_iter()
+        .map(|cid| BTreeMap::from([("/", cid.to_string())]))
+        .collect();
+    let peers = ipfs
+        .get_bitswap_peers()
+        .await?
+        .into_iter()
+        .map(|peer_id| peer_id.to_string())
+        .collect();
+
+    Ok(Json(BitswapResponse { wantlist, peers }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProvideResponse {
+    total_provides: u64,
+    last_reprovide_batch_size: usize,
+    // nanoseconds, like the durations of kubo
+    last_reprovide_duration: u128,
+}
+
+/// `stats/provide` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-stats-provide, based on
+/// the runs of the reprovider.
+pub async fn provide(State(ipfs): State<Ipfs>) -> ApiResult<Json<ProvideResponse>> {
+    let stats = ipfs.reprovider_stats().await?;
+
+    Ok(Json(ProvideResponse {
+        total_provides: stats.provided as u64,
+        last_reprovide_batch_size: stats.total,
+        last_reprovide_duration: stats
+            .last_run_duration
+            .map(|duration| duration.as_nanos())
+            .unwrap_or_default(),
+    }))
+}
@@ -0,0 +1,386 @@
+//! Parts shared by the endpoints: the error responses, the query string arguments and the streamed
+//! responses.
+
+use axum::async_trait;
+use axum::body::Body;
+use axum::extract::{FromRequestParts, Multipart};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::stream::{Stream, StreamExt};
+use libipld::multibase::Base;
+use libipld::multihash::{Code, Multihash};
+use libipld::{Cid, IpldCodec};
+use rust_ipfs::path::PathRoot;
+use rust_ipfs::{Ipfs, IpfsPath, PeerId};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The error responses returned by the kubo RPC API.
+/// See also: https://github.com/ferristseng/rust-ipfs-api/blob/master/ipfs-api-prelude/src/response/error.rs
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MessageResponse {
+    message: String,
+    code: usize,
+    r#type: MessageKind,
+}
+
+/// The `MessageResponse` has this field, unsure if it can be anything other than "error".
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageKind {
+    Error,
+}
+
+/// Failure of an endpoint, responded to as a [`MessageResponse`].
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: usize,
+    message: String,
+}
+
+pub type ApiResult<T = Response> = Result<T, ApiError>;
+
+impl ApiError {
+    /// Failure caused by the request, such as a missing or an invalid argument. Kubo uses the
+    /// code 1 for these.
+    pub fn bad_request(message: impl fmt::Display) -> Self {
+        ApiError {
+            status: StatusCode::BAD_REQUEST,
+            code: 1,
+            message: message.to_string(),
+        }
+    }
+
+    /// Failure of the command itself.
+    pub fn internal(message: impl fmt::Display) -> Self {
+        ApiError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            code: 0,
+            message: message.to_string(),
+        }
+    }
+
+    fn to_message(&self) -> MessageResponse {
+        MessageResponse {
+            message: self.message.clone(),
+            code: self.code,
+            r#type: MessageKind::Error,
+        }
+    }
+}
+
+impl<E: Into<anyhow::Error>> From<E> for ApiError {
+    fn from(e: E) -> Self {
+        // the alternate format includes the causes, which usually are the interesting part
+        ApiError::internal(format!("{:#}", e.into()))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, axum::Json(self.to_message())).into_response()
+    }
+}
+
+/// The query string of a request. Kubo clients repeat `arg` for every positional argument, which
+/// is why the parameters are kept in order instead of being deserialized into a struct.
+#[derive(Debug, Default)]
+pub struct Params(Vec<(String, String)>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Params {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let params = parts
+            .uri
+            .query()
+            .map(|query| {
+                form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Params(params))
+    }
+}
+
+impl Params {
+    /// Returns the values of all `arg` parameters in order.
+    pub fn args(&self) -> impl Iterator<Item = &str> {
+        self.all("arg")
+    }
+
+    /// Returns the first `arg` parameter, which is documented as `name` in the error message.
+    pub fn arg(&self, name: &str) -> ApiResult<&str> {
+        self.args()
+            .next()
+            .ok_or_else(|| ApiError::bad_request(format!("argument {name:?} is required")))
+    }
+
+    /// Returns the values of the parameter in order.
+    pub fn all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+        self.0
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the first value of the parameter.
+    pub fn get<'a>(&'a self, key: &'a str) -> Option<&'a str> {
+        self.all(key).next()
+    }
+
+    /// Parses the first value of the parameter.
+    pub fn parse<T>(&self, key: &str) -> ApiResult<Option<T>>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.get(key)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|e| ApiError::bad_request(format!("invalid {key:?}: {e}")))
+            })
+            .transpose()
+    }
+
+    /// Parses a boolean option the same way as kubo, where a parameter without a value is true.
+    pub fn flag(&self, key: &str, default: bool) -> ApiResult<bool> {
+        match self.get(key) {
+            None => Ok(default),
+            Some("" | "1" | "t" | "T" | "true" | "TRUE" | "True") => Ok(true),
+            Some("0" | "f" | "F" | "false" | "FALSE" | "False") => Ok(false),
+            Some(other) => Err(ApiError::bad_request(format!(
+                "invalid {key:?}: {other:?} is not a boolean"
+            ))),
+        }
+    }
+
+    /// Parses a duration option given in the format of Go, such as `1m30s`.
+    pub fn duration(&self, key: &str) -> ApiResult<Option<Duration>> {
+        self.get(key)
+            .map(|value| {
+                parse_duration(value)
+                    .ok_or_else(|| ApiError::bad_request(format!("invalid {key:?}: {value:?}")))
+            })
+            .transpose()
+    }
+}
+
+/// Parses a duration in the format of Go's `time.ParseDuration`, a sequence of decimal numbers
+/// followed by one of the units `ns`, `us`, `µs`, `ms`, `s`, `m` or `h`.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    if value == "0" {
+        return Some(Duration::ZERO);
+    }
+
+    let mut rest = value;
+    let mut total = 0f64;
+
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(number_len);
+        let number = number.parse::<f64>().ok()?;
+
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        let seconds = match unit {
+            "ns" => 1e-9,
+            "us" | "µs" => 1e-6,
+            "ms" => 1e-3,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => return None,
+        };
+
+        total += number * seconds;
+        rest = tail;
+    }
+
+    Some(Duration::from_secs_f64(total))
+}
+
+/// Parses the name of a hash function as used by the `hash` and `mhtype` options.
+pub fn parse_hash(value: &str) -> ApiResult<Code> {
+    Ok(match value {
+        "sha2-256" => Code::Sha2_256,
+        "sha2-512" => Code::Sha2_512,
+        "sha3-256" => Code::Sha3_256,
+        "sha3-512" => Code::Sha3_512,
+        "blake2b-256" => Code::Blake2b256,
+        "blake2b-512" => Code::Blake2b512,
+        "blake3" => Code::Blake3_256,
+        other => {
+            return Err(ApiError::bad_request(format!(
+                "unrecognized hash function: {other:?}"
+            )))
+        }
+    })
+}
+
+/// Parses the name of an IPLD codec as used by the `cid-codec` and `store-codec` options.
+pub fn parse_codec(value: &str) -> ApiResult<IpldCodec> {
+    Ok(match value {
+        "raw" => IpldCodec::Raw,
+        "dag-pb" => IpldCodec::DagPb,
+        "dag-cbor" => IpldCodec::DagCbor,
+        "dag-json" => IpldCodec::DagJson,
+        other => {
+            return Err(ApiError::bad_request(format!(
+                "unsupported codec: {other:?}"
+            )))
+        }
+    })
+}
+
+/// Reads the next part of the multipart body, returning `None` once all of the parts have been
+/// read.
+pub async fn next_file(multipart: &mut Multipart) -> ApiResult<Option<Bytes>> {
+    match multipart
+        .next_field()
+        .await
+        .map_err(ApiError::bad_request)?
+    {
+        Some(field) => Ok(Some(field.bytes().await.map_err(ApiError::bad_request)?)),
+        None => Ok(None),
+    }
+}
+
+/// Reads the first part of the multipart body, which kubo documents as the file argument `name`.
+pub async fn first_file(multipart: &mut Multipart, name: &str) -> ApiResult<Bytes> {
+    next_file(multipart)
+        .await?
+        .ok_or_else(|| ApiError::bad_request(format!("file argument {name:?} is required")))
+}
+
+/// Parses a path argument, which can also be a plain Cid.
+pub fn parse_path(value: &str) -> ApiResult<IpfsPath> {
+    IpfsPath::from_str(value)
+        .map_err(|e| ApiError::bad_request(format!("invalid path {value:?}: {e}")))
+}
+
+/// Resolves `/ipns` paths to the `/ipfs` path they point to.
+pub async fn resolve_path(ipfs: &Ipfs, path: IpfsPath) -> ApiResult<IpfsPath> {
+    match path.root() {
+        PathRoot::Ipld(_) => Ok(path),
+        _ => Ok(ipfs.resolve_ipns(&path, true).await?),
+    }
+}
+
+/// Resolves the path to the Cid of the block it points to.
+pub async fn resolve_cid(ipfs: &Ipfs, path: IpfsPath) -> ApiResult<Cid> {
+    let path = resolve_path(ipfs, path).await?;
+
+    let plain_cid = match path.iter().next() {
+        None => path.root().cid().copied(),
+        Some(_) => None,
+    };
+
+    match plain_cid {
+        Some(cid) => Ok(cid),
+        None => {
+            let (resolved, _) = ipfs.dag().resolve(path, true, &[], false).await?;
+            Ok(*resolved.source())
+        }
+    }
+}
+
+/// Returns the identifier kubo shows for keys and names, the peer id as a base36 encoded
+/// `libp2p-key` Cid.
+pub fn key_id(peer_id: &PeerId) -> String {
+    Multihash::from_bytes(&peer_id.to_bytes())
+        .ok()
+        .and_then(|hash| {
+            Cid::new_v1(0x72, hash)
+                .to_string_of_base(Base::Base36Lower)
+                .ok()
+        })
+        .unwrap_or_else(|| peer_id.to_string())
+}
+
+/// Serializes the value as a string, as kubo does for some of the numbers.
+#[derive(Debug)]
+pub struct Quoted<D>(pub D);
+
+impl<D: fmt::Display> Serialize for Quoted<D> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(&self.0)
+    }
+}
+
+/// Appends the value as a line of newline delimited json.
+pub fn json_line<T: Serialize>(buffer: &mut BytesMut, value: &T) -> Bytes {
+    serde_json::to_writer(buffer.writer(), value).expect("serializing to memory cannot fail");
+    buffer.put_u8(b'\n');
+    buffer.split().freeze()
+}
+
+/// Streams the items as newline delimited json. As the status has already been sent by the time
+/// an item fails, the error is sent as the last line.
+pub fn ndjson<S, T>(stream: S) -> Response
+where
+    S: Stream<Item = ApiResult<T>> + Send + 'static,
+    T: Serialize,
+{
+    let mut buffer = BytesMut::new();
+
+    let body = stream.scan(false, move |failed, item| {
+        if *failed {
+            return futures::future::ready(None);
+        }
+        let line = match item {
+            Ok(item) => json_line(&mut buffer, &item),
+            Err(e) => {
+                *failed = true;
+                json_line(&mut buffer, &e.to_message())
+            }
+        };
+        futures::future::ready(Some(Ok::<_, Infallible>(line)))
+    });
+
+    streamed(Body::from_stream(body), "application/json")
+}
+
+/// Responds with the streamed body, marked as chunked output the same way as kubo.
+pub fn streamed(body: Body, content_type: &'static str) -> Response {
+    let mut response = Response::new(body);
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    headers.insert("x-chunked-output", HeaderValue::from_static("1"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_duration;
+    use std::time::Duration;
+
+    #[test]
+    fn go_durations() {
+        assert_eq!(parse_duration("0"), Some(Duration::ZERO));
+        assert_eq!(parse_duration("24h"), Some(Duration::from_secs(86400)));
+        assert_eq!(parse_duration("1m30s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1.5h"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("300ms"), Some(Duration::from_millis(300)));
+        assert_eq!(parse_duration("10"), None);
+        assert_eq!(parse_duration("1d"), None);
+        assert_eq!(parse_duration(""), Some(Duration::ZERO));
+    }
+}
@@ -0,0 +1,155 @@
+use super::support::{ApiError, ApiResult, Params};
+use axum::extract::State;
+use axum::Json;
+use rust_ipfs::p2p::MultiaddrExt;
+use rust_ipfs::{Ipfs, Multiaddr, PeerId};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PeersResponse {
+    peers: Vec<Peer>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Peer {
+    addr: String,
+    peer: String,
+}
+
+/// `swarm/peers` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-swarm-peers.
+pub async fn peers(State(ipfs): State<Ipfs>) -> ApiResult<Json<PeersResponse>> {
+    let mut addrs: HashMap<PeerId, Vec<Multiaddr>> = ipfs.addrs().await?.into_iter().collect();
+
+    let peers = ipfs
+        .connected()
+        .await?
+        .into_iter()
+        .map(|peer_id| Peer {
+            addr: addrs
+                .remove(&peer_id)
+                .and_then(|addrs| addrs.into_iter().next())
+                .map(|addr| addr.to_string())
+                .unwrap_or_default(),
+            peer: peer_id.to_string(),
+        })
+        .collect();
+
+    Ok(Json(PeersResponse { peers }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AddrsResponse {
+    addrs: BTreeMap<String, Vec<String>>,
+}
+
+/// `swarm/addrs` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-swarm-addrs, listing the
+/// known addresses of the peers.
+pub async fn addrs(State(ipfs): State<Ipfs>) -> ApiResult<Json<AddrsResponse>> {
+    let addrs = ipfs
+        .addrs()
+        .await?
+        .into_iter()
+        .map(|(peer_id, addrs)| {
+            let addrs = addrs.iter().map(Multiaddr::to_string).collect();
+            (peer_id.to_string(), addrs)
+        })
+        .collect();
+
+    Ok(Json(AddrsResponse { addrs }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StringsResponse {
+    strings: Vec<String>,
+}
+
+/// `swarm/addrs/local` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-swarm-addrs-local,
+/// with the addresses suffixed by the peer id when `id` is given.
+pub async fn addrs_local(
+    State(ipfs): State<Ipfs>,
+    params: Params,
+) -> ApiResult<Json<StringsResponse>> {
+    let with_id = params.flag("id", false)?;
+    let peer_id = ipfs.keypair().public().to_peer_id();
+
+    let mut addrs = ipfs.external_addresses().await?;
+    for addr in ipfs.listening_addresses().await? {
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+
+    let strings = addrs
+        .into_iter()
+        .map(|addr| match with_id {
+            true => format!("{addr}/p2p/{peer_id}"),
+            false => addr.to_string(),
+        })
+        .collect();
+
+    Ok(Json(StringsResponse { strings }))
+}
+
+/// `swarm/addrs/listen` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-swarm-addrs-listen.
+pub async fn addrs_listen(State(ipfs): State<Ipfs>) -> ApiResult<Json<StringsResponse>> {
+    let strings = ipfs
+        .listening_addresses()
+        .await?
+        .iter()
+        .map(Multiaddr::to_string)
+        .collect();
+
+    Ok(Json(StringsResponse { strings }))
+}
+
+/// Parses the `arg` parameters as addresses which end in the peer id.
+fn peer_addresses(params: &Params) -> ApiResult<Vec<(PeerId, Multiaddr)>> {
+    let addrs = params
+        .args()
+        .map(|arg| {
+            let addr = arg
+                .parse::<Multiaddr>()
+                .map_err(|e| ApiError::bad_request(format!("invalid address {arg:?}: {e}")))?;
+            let peer_id = addr.peer_id().ok_or_else(|| {
+                ApiError::bad_request(format!("address {arg:?} does not contain a peer id"))
+            })?;
+            Ok((peer_id, addr))
+        })
+        .collect::<ApiResult<Vec<_>>>()?;
+
+    if addrs.is_empty() {
+        return Err(ApiError::bad_request("argument \"address\" is required"));
+    }
+
+    Ok(addrs)
+}
+
+/// `swarm/connect` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-swarm-connect.
+pub async fn connect(State(ipfs): State<Ipfs>, params: Params) -> ApiResult<Json<StringsResponse>> {
+    let mut strings = Vec::new();
+    for (peer_id, addr) in peer_addresses(&params)? {
+        ipfs.connect(addr).await?;
+        strings.push(format!("connect {peer_id} success"));
+    }
+
+    Ok(Json(StringsResponse { strings }))
+}
+
+/// `swarm/disconnect` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-swarm-disconnect.
+pub async fn disconnect(
+    State(ipfs): State<Ipfs>,
+    params: Params,
+) -> ApiResult<Json<StringsResponse>> {
+    let mut strings = Vec::new();
+    for (peer_id, _) in peer_addresses(&params)? {
+        ipfs.disconnect(peer_id).await?;
+        strings.push(format!("disconnect {peer_id} success"));
+    }
+
+    Ok(Json(StringsResponse { strings }))
+}
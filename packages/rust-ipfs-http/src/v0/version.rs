@@ -0,0 +1,26 @@
+use axum::Json;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Response {
+    version: &'static str,
+    commit: &'static str,
+    repo: &'static str,
+    system: String,
+    golang: &'static str,
+}
+
+/// `version` per https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-version. The options only change
+/// the output of the cli, so they are not looked at.
+pub async fn version() -> Json<Response> {
+    Json(Response {
+        version: env!("CARGO_PKG_VERSION"),
+        commit: "",
+        // the version of the repository layout, which is not versioned in rust-ipfs
+        repo: "",
+        system: format!("{}/{}", std::env::consts::ARCH, std::env::consts::OS),
+        // kubo clients expect this to be present
+        golang: "",
+    })
+}
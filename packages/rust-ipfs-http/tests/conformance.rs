@@ -0,0 +1,607 @@
+//! Checks the responses of the API over HTTP against the shapes returned by kubo.
+
+use libipld::multibase::{self, Base};
+use libipld::multihash::{Code, MultihashDigest};
+use libipld::{Cid, IpldCodec};
+use reqwest::{Client, StatusCode};
+use rust_ipfs::Node;
+use rust_ipfs_http::v0;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const BOUNDARY: &str = "conformance-boundary";
+
+struct Api {
+    node: Node,
+    client: Client,
+    base: String,
+}
+
+impl Api {
+    async fn start() -> Api {
+        let node = Node::new("api_node").await;
+        let (shutdown_tx, _shutdown_rx) = tokio::sync::mpsc::channel(1);
+        let routes = v0::routes(&node, shutdown_tx);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}/api/v0", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, routes).await });
+
+        Api {
+            node,
+            client: Client::new(),
+            base,
+        }
+    }
+
+    async fn post(&self, path: &str) -> reqwest::Response {
+        self.client
+            .post(format!("{}/{path}", self.base))
+            .send()
+            .await
+            .unwrap()
+    }
+
+    async fn json(&self, path: &str) -> Value {
+        let response = self.post(path).await;
+        let status = response.status();
+        let body = response.text().await.unwrap();
+        assert_eq!(status, StatusCode::OK, "{path}: {body}");
+        serde_json::from_str(&body).unwrap()
+    }
+
+    /// Returns the parsed lines of a newline delimited json response.
+    async fn lines(&self, path: &str) -> Vec<Value> {
+        let response = self.post(path).await;
+        assert_eq!(response.status(), StatusCode::OK, "{path}");
+        parse_lines(response).await
+    }
+
+    /// Posts the files as the parts of a `multipart/form-data` body.
+    async fn upload(&self, path: &str, files: &[(&str, &[u8])]) -> reqwest::Response {
+        let mut body = Vec::new();
+        for (name, content) in files {
+            body.extend_from_slice(
+                format!(
+                    "--{BOUNDARY}\r\n\
+                     Content-Disposition: form-data; name=\"file\"; filename=\"{name}\"\r\n\
+                     Content-Type: application/octet-stream\r\n\r\n"
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(content);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{BOUNDARY}--\r\n").as_bytes());
+
+        self.client
+            .post(format!("{}/{path}", self.base))
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .body(body)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    /// Adds the files, returning the parsed lines of the response.
+    async fn add(&self, query: &str, files: &[(&str, &[u8])]) -> Vec<Value> {
+        let response = self.upload(&format!("add{query}"), files).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        parse_lines(response).await
+    }
+}
+
+async fn parse_lines(response: reqwest::Response) -> Vec<Value> {
+    response
+        .text()
+        .await
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn errors() {
+    let api = Api::start().await;
+
+    let response = api.post("no/such/command").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.text().await.unwrap(), "404 page not found");
+
+    let response = api
+        .client
+        .get(format!("{}/version", api.base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+    let response = api.post("cat").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["Type"], "error");
+    assert_eq!(body["Code"], 1);
+    assert!(body["Message"].as_str().unwrap().contains("required"));
+}
+
+#[tokio::test]
+async fn id_and_version() {
+    let api = Api::start().await;
+    let peer_id = api.node.keypair().public().to_peer_id().to_string();
+
+    let id = api.json("id").await;
+    assert_eq!(id["ID"], peer_id.as_str());
+    assert!(id["PublicKey"].is_string());
+    assert!(id["Addresses"].is_array());
+    assert!(id["AgentVersion"].is_string());
+    assert!(id["Protocols"].is_array());
+
+    let version = api.json("version").await;
+    assert!(version["Version"].is_string());
+    assert!(version["System"].is_string());
+}
+
+#[tokio::test]
+async fn add_cat_ls_get() {
+    let api = Api::start().await;
+
+    let lines = api
+        .add(
+            "?wrap-with-directory=true",
+            &[("a.txt", b"first file\n"), ("b.txt", b"second file\n")],
+        )
+        .await;
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["Name"], "a.txt");
+    assert_eq!(lines[1]["Name"], "b.txt");
+    assert_eq!(lines[2]["Name"], "");
+    assert!(lines[0]["Size"].is_string());
+    let root = lines[2]["Hash"].as_str().unwrap().to_string();
+
+    let response = api.post(&format!("cat?arg={root}/b.txt")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), "second file\n");
+
+    let response = api
+        .post(&format!("cat?arg=/ipfs/{root}/a.txt&offset=6&length=4"))
+        .await;
+    assert_eq!(response.text().await.unwrap(), "file");
+
+    let ls = api.json(&format!("ls?arg={root}")).await;
+    assert_eq!(ls["Objects"][0]["Hash"], root.as_str());
+    let links = ls["Objects"][0]["Links"].as_array().unwrap();
+    assert_eq!(links.len(), 2);
+    assert_eq!(links[0]["Name"], "a.txt");
+    assert_eq!(links[0]["Hash"], lines[0]["Hash"]);
+    assert_eq!(links[0]["Type"], 2);
+
+    let response = api.post(&format!("get?arg={root}")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/x-tar");
+    let tar = response.bytes().await.unwrap();
+    let mut archive = tar::Archive::new(&tar[..]);
+    let mut entries = archive
+        .entries()
+        .unwrap()
+        .map(|entry| {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().into_owned();
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut entry, &mut content).unwrap();
+            (path, content)
+        })
+        .collect::<Vec<_>>();
+    entries.sort();
+    assert_eq!(
+        entries,
+        [
+            (root.clone(), String::new()),
+            (format!("{root}/a.txt"), "first file\n".to_string()),
+            (format!("{root}/b.txt"), "second file\n".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn pins() {
+    let api = Api::start().await;
+
+    let lines = api.add("?pin=false", &[("file", b"pin me")]).await;
+    let cid = lines[0]["Hash"].as_str().unwrap().to_string();
+
+    let pins = api.json("pin/ls").await;
+    assert_eq!(pins, json!({ "Keys": {} }));
+
+    let added = api.json(&format!("pin/add?arg={cid}&name=mine")).await;
+    assert_eq!(added, json!({ "Pins": [cid] }));
+
+    let pins = api.json("pin/ls?type=recursive&names=true").await;
+    assert_eq!(
+        pins,
+        json!({ "Keys": { cid.as_str(): { "Type": "recursive", "Name": "mine" } } })
+    );
+
+    let pins = api.json(&format!("pin/ls?arg={cid}")).await;
+    assert_eq!(
+        pins,
+        json!({ "Keys": { cid.as_str(): { "Type": "recursive" } } })
+    );
+
+    let lines = api.add("?pin=false", &[("file", b"pin me instead")]).await;
+    let other = lines[0]["Hash"].as_str().unwrap().to_string();
+
+    let updated = api.json(&format!("pin/update?arg={cid}&arg={other}")).await;
+    assert_eq!(updated, json!({ "Pins": [cid, other] }));
+
+    let removed = api.json(&format!("pin/rm?arg={other}")).await;
+    assert_eq!(removed, json!({ "Pins": [other] }));
+
+    let pins = api.json("pin/ls").await;
+    assert_eq!(pins, json!({ "Keys": {} }));
+}
+
+#[tokio::test]
+async fn names() {
+    let api = Api::start().await;
+
+    let lines = api.add("", &[("file", b"published")]).await;
+    let cid = lines[0]["Hash"].as_str().unwrap().to_string();
+
+    let published = api
+        .json(&format!(
+            "name/publish?arg=/ipfs/{cid}&allow-offline=true&lifetime=1h&ttl=1m"
+        ))
+        .await;
+    assert_eq!(published["Value"], format!("/ipfs/{cid}"));
+    let name = published["Name"].as_str().unwrap().to_string();
+
+    let resolved = api.json(&format!("name/resolve?arg={name}")).await;
+    assert_eq!(resolved, json!({ "Path": format!("/ipfs/{cid}") }));
+
+    let resolved = api.json("name/resolve").await;
+    assert_eq!(resolved, json!({ "Path": format!("/ipfs/{cid}") }));
+
+    let response = api.post(&format!("cat?arg=/ipns/{name}")).await;
+    assert_eq!(response.text().await.unwrap(), "published");
+}
+
+#[tokio::test]
+async fn keys() {
+    let api = Api::start().await;
+
+    let generated = api.json("key/gen?arg=first").await;
+    assert_eq!(generated["Name"], "first");
+    let id = generated["Id"].as_str().unwrap().to_string();
+    assert!(id.starts_with('k'));
+
+    let response = api.post("key/gen?arg=first").await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let list = api.json("key/list").await;
+    let names = list["Keys"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|key| key["Name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["self", "first"]);
+
+    let renamed = api.json("key/rename?arg=first&arg=second").await;
+    assert_eq!(
+        renamed,
+        json!({ "Was": "first", "Now": "second", "Id": id, "Overwrite": false })
+    );
+
+    let response = api.post("key/export?arg=second").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let exported = response.bytes().await.unwrap();
+
+    let removed = api.json("key/rm?arg=second").await;
+    assert_eq!(removed, json!({ "Keys": [{ "Name": "second", "Id": id }] }));
+
+    let response = api
+        .upload("key/import?arg=third", &[("key", &exported)])
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let imported: Value = response.json().await.unwrap();
+    assert_eq!(imported, json!({ "Name": "third", "Id": id }));
+}
+
+#[tokio::test]
+async fn stats() {
+    let api = Api::start().await;
+    api.add("", &[("file", b"counted")]).await;
+
+    let repo = api.json("stats/repo").await;
+    assert!(repo["RepoSize"].as_u64().unwrap() > 0);
+    assert!(repo["NumObjects"].as_u64().unwrap() > 0);
+    assert!(repo["StorageMax"].is_u64());
+
+    let bitswap = api.json("stats/bitswap").await;
+    assert_eq!(bitswap["Wantlist"], json!([]));
+    assert_eq!(bitswap["Peers"], json!([]));
+
+    let provide = api.json("stats/provide").await;
+    assert!(provide["TotalProvides"].is_u64());
+}
+
+#[tokio::test]
+async fn swarm_and_routing() {
+    let api = Api::start().await;
+    let other = Node::new("other_node").await;
+    let other_peer = other.keypair().public().to_peer_id();
+    let other_addr = other.addrs[0].clone();
+
+    let connected = api.json(&format!("swarm/connect?arg={other_addr}")).await;
+    assert_eq!(
+        connected,
+        json!({ "Strings": [format!("connect {other_peer} success")] })
+    );
+
+    let peers = api.json("swarm/peers").await;
+    assert_eq!(peers["Peers"][0]["Peer"], other_peer.to_string());
+
+    let listen = api.json("swarm/addrs/listen").await;
+    assert!(!listen["Strings"].as_array().unwrap().is_empty());
+
+    let addrs = api.json("swarm/addrs").await;
+    assert!(addrs["Addrs"].is_object());
+
+    let found = api
+        .json(&format!("routing/findpeer?arg={other_peer}"))
+        .await;
+    assert_eq!(found["Type"], 2);
+    assert_eq!(found["Responses"][0]["ID"], other_peer.to_string());
+    let found_with_dht = api.json(&format!("dht/findpeer?arg={other_peer}")).await;
+    assert_eq!(found_with_dht, found);
+
+    let disconnected = api
+        .json(&format!("swarm/disconnect?arg={other_addr}"))
+        .await;
+    assert_eq!(
+        disconnected,
+        json!({ "Strings": [format!("disconnect {other_peer} success")] })
+    );
+
+    let response = api.post("swarm/connect?arg=/ip4/127.0.0.1/tcp/1").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn filestore() {
+    let api = Api::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("referenced");
+    std::fs::write(&file, b"referenced in place").unwrap();
+
+    let path = api
+        .node
+        .add_unixfs(file.clone())
+        .nocopy(true)
+        .await
+        .unwrap();
+    let cid = path.root().cid().unwrap().to_string();
+    let file_path = file.canonicalize().unwrap().to_string_lossy().into_owned();
+
+    let response = api.post("filestore/ls").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let entry: Value = serde_json::from_str(response.text().await.unwrap().trim()).unwrap();
+    assert_eq!(
+        entry,
+        json!({
+            "Status": 0,
+            "ErrorMsg": "",
+            "Key": { "/": cid },
+            "FilePath": file_path,
+            "Offset": 0,
+            "Size": 19,
+        })
+    );
+
+    std::fs::remove_file(&file).unwrap();
+
+    let response = api.post("filestore/verify").await;
+    let entry: Value = serde_json::from_str(response.text().await.unwrap().trim()).unwrap();
+    assert_eq!(entry["Status"], 11);
+    assert_eq!(entry["Key"]["/"], cid.as_str());
+}
+
+#[tokio::test]
+async fn blocks() {
+    let api = Api::start().await;
+    let cid = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(b"raw block")).to_string();
+
+    let response = api.upload("block/put", &[("block", b"raw block")]).await;
+    assert_eq!(
+        parse_lines(response).await,
+        [json!({ "Key": cid, "Size": 9 })]
+    );
+
+    let stat = api.json(&format!("block/stat?arg={cid}")).await;
+    assert_eq!(stat, json!({ "Key": cid, "Size": 9 }));
+
+    let response = api.post(&format!("block/get?arg={cid}")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(&response.bytes().await.unwrap()[..], b"raw block");
+
+    // the empty dag-pb node, as created by kubo
+    let response = api.upload("block/put?format=v0", &[("block", b"")]).await;
+    assert_eq!(
+        parse_lines(response).await,
+        [json!({ "Key": "QmdfTbBqBPQ7VNxZEYEj14VmRuZBkqFbiwReogJgS1zR1n", "Size": 0 })]
+    );
+
+    let response = api
+        .upload("block/put?format=v0&mhtype=sha2-512", &[("block", b"")])
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let removed = api.lines(&format!("block/rm?arg={cid}")).await;
+    assert_eq!(removed, [json!({ "Hash": cid })]);
+
+    let removed = api.lines(&format!("block/rm?arg={cid}")).await;
+    assert_eq!(
+        removed,
+        [json!({ "Hash": cid, "Error": "blockstore: block not found" })]
+    );
+
+    let removed = api.lines(&format!("block/rm?arg={cid}&force=true")).await;
+    assert_eq!(removed, [json!({ "Hash": cid })]);
+    let removed = api
+        .lines(&format!("block/rm?arg={cid}&force=true&quiet=true"))
+        .await;
+    assert!(removed.is_empty());
+}
+
+#[tokio::test]
+async fn dags_and_refs() {
+    let api = Api::start().await;
+    let lines = api.add("?cid-version=1", &[("file", b"linked")]).await;
+    let file = lines[0]["Hash"].as_str().unwrap().to_string();
+
+    let document = format!(r#"{{"a":{{"b":[1,2]}},"file":{{"/":"{file}"}}}}"#);
+    let response = api
+        .upload("dag/put", &[("document", document.as_bytes())])
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let lines = parse_lines(response).await;
+    let cid = lines[0]["Cid"]["/"].as_str().unwrap().to_string();
+    assert_eq!(
+        Cid::try_from(cid.as_str()).unwrap().codec(),
+        u64::from(IpldCodec::DagCbor)
+    );
+
+    let response = api.post(&format!("dag/get?arg={cid}/a")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let value: Value = response.json().await.unwrap();
+    assert_eq!(value, json!({ "b": [1, 2] }));
+
+    let resolved = api.json(&format!("dag/resolve?arg={cid}/a/b")).await;
+    assert_eq!(resolved, json!({ "Cid": { "/": cid }, "RemPath": "a/b" }));
+
+    let refs = api.lines(&format!("refs?arg={cid}")).await;
+    assert_eq!(refs, [json!({ "Ref": file, "Err": "" })]);
+
+    // only dag-pb links have names
+    let lines = api
+        .add("?wrap-with-directory=true", &[("a.txt", b"named")])
+        .await;
+    let root = lines[1]["Hash"].as_str().unwrap();
+    let refs = api
+        .lines(&format!("refs?arg={root}&format=<src> <linkname>"))
+        .await;
+    assert_eq!(refs, [json!({ "Ref": format!("{root} a.txt"), "Err": "" })]);
+
+    let response = api
+        .post(&format!("refs?arg={cid}&format=<dst>&edges=true"))
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let local = api.lines("refs/local").await;
+    for cid in [&cid, &file] {
+        assert!(local.contains(&json!({ "Ref": cid, "Err": "" })));
+    }
+}
+
+#[tokio::test]
+async fn pubsub() {
+    let api = Api::start().await;
+    let other = Node::new("other_node").await;
+    api.node.connect(other.addrs[0].clone()).await.unwrap();
+
+    let topic = multibase::encode(Base::Base64Url, "news");
+    let mut subscription = api.post(&format!("pubsub/sub?arg={topic}")).await;
+    assert_eq!(subscription.status(), StatusCode::OK);
+    let mut other_messages = other.pubsub_subscribe("news").await.unwrap();
+
+    let subscribed = api.json("pubsub/ls").await;
+    assert_eq!(subscribed, json!({ "Strings": [topic] }));
+
+    let other_peer = other.keypair().public().to_peer_id().to_string();
+    let mut joined = false;
+    for _ in 0..100 {
+        let peers = api.json(&format!("pubsub/peers?arg={topic}")).await;
+        if peers == json!({ "Strings": [other_peer] }) {
+            joined = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(joined);
+
+    let response = api
+        .upload(&format!("pubsub/pub?arg={topic}"), &[("data", b"from api")])
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let message = tokio::time::timeout(
+        Duration::from_secs(10),
+        futures::StreamExt::next(&mut other_messages),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(message.data, b"from api");
+
+    other
+        .pubsub_publish("news", &b"from other"[..])
+        .await
+        .unwrap();
+    let chunk = tokio::time::timeout(Duration::from_secs(10), subscription.chunk())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let message: Value = serde_json::from_slice(&chunk).unwrap();
+    assert_eq!(message["from"], other_peer.as_str());
+    assert_eq!(
+        message["data"],
+        multibase::encode(Base::Base64Url, "from other")
+    );
+    assert_eq!(message["topicIDs"], json!([topic]));
+
+    let response = api.post("pubsub/pub?arg=news").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn resolve_bootstrap_and_bitswap() {
+    let api = Api::start().await;
+    let lines = api
+        .add("?wrap-with-directory=true", &[("a.txt", b"resolved")])
+        .await;
+    let (file, root) = (lines[0]["Hash"].clone(), lines[1]["Hash"].clone());
+    let root = root.as_str().unwrap();
+
+    let resolved = api.json(&format!("resolve?arg=/ipfs/{root}/a.txt")).await;
+    assert_eq!(
+        resolved,
+        json!({ "Path": format!("/ipfs/{}", file.as_str().unwrap()) })
+    );
+
+    let published = api
+        .json(&format!("name/publish?arg=/ipfs/{root}&allow-offline=true"))
+        .await;
+    let name = published["Name"].as_str().unwrap();
+    let resolved = api.json(&format!("resolve?arg=/ipns/{name}")).await;
+    assert_eq!(resolved, json!({ "Path": format!("/ipfs/{root}") }));
+
+    let peer = "/ip4/127.0.0.1/tcp/4001/p2p/12D3KooWQ5m5vVKW7ECBv4NNhY9tWQmvXy5MwUdnqSWK5vqG1Ptj";
+    api.json("bootstrap/rm/all").await;
+    let added = api.json(&format!("bootstrap/add?arg={peer}")).await;
+    assert_eq!(added, json!({ "Peers": [peer] }));
+    let listed = api.json("bootstrap/list").await;
+    assert_eq!(listed, json!({ "Peers": [peer] }));
+    let removed = api.json(&format!("bootstrap/rm?arg={peer}")).await;
+    assert_eq!(removed, json!({ "Peers": [peer] }));
+    let listed = api.json("bootstrap").await;
+    assert_eq!(listed, json!({ "Peers": [] }));
+
+    let wantlist = api.json("bitswap/wantlist").await;
+    assert_eq!(wantlist, json!({ "Keys": [] }));
+    let stat = api.json("bitswap/stat").await;
+    assert_eq!(stat["Wantlist"], json!([]));
+}
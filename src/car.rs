@@ -0,0 +1,700 @@
+//! Import and export of DAGs in the [CAR] (Content Addressable aRchive) format.
+//!
+//! Both CARv1 and CARv2 are supported for export. CARv2 archives are written with an
+//! `IndexSorted` index appended after the data payload. On import, CARv1 and CARv2 archives are
+//! accepted, with the CARv2 index being skipped as every block is verified against its multihash
+//! before being written to the repo.
+//!
+//! [CAR]: https://ipld.io/specs/transport/car/
+
+use std::collections::BTreeMap;
+use std::task::Poll;
+use std::time::Duration;
+
+use anyhow::Context;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::future::BoxFuture;
+use futures::stream::{BoxStream, FusedStream};
+use futures::{FutureExt, Stream, StreamExt, TryStreamExt};
+use libipld::cbor::DagCborCodec;
+use libipld::codec::Codec;
+use libipld::{Cid, Ipld, IpldCodec};
+use libp2p::PeerId;
+use tracing::Span;
+use tracing_futures::Instrument;
+
+use crate::error::Error;
+use crate::refs::{Edge, IpldRefs};
+use crate::repo::Repo;
+use crate::Block;
+
+/// The fixed CARv2 pragma, which is a valid CARv1 header declaring `version: 2`.
+const CARV2_PRAGMA: [u8; 11] = [
+    0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02,
+];
+
+/// Size of the CARv2 header following the pragma.
+const CARV2_HEADER_SIZE: usize = 40;
+
+/// Multicodec code of the `IndexSorted` CARv2 index.
+const CARV2_INDEX_SORTED: u64 = 0x0400;
+
+/// Version of the CAR format to produce on export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CarVersion {
+    /// Plain CARv1 with the header and the block sections.
+    #[default]
+    V1,
+    /// CARv2 wrapping a CARv1 payload, followed by an `IndexSorted` index.
+    V2,
+}
+
+/// Describes which part of the DAG is exported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Selector {
+    /// Only the root block.
+    Root,
+    /// The root block and the blocks up to the given depth below the root.
+    Depth(u64),
+    /// The whole DAG.
+    #[default]
+    All,
+}
+
+/// Exports a DAG as a stream of CAR bytes.
+///
+/// Blocks are written in the order they are found when walking the DAG breadth-first from the
+/// root, with each block being written only once.
+#[must_use = "do nothing unless you poll the stream"]
+pub struct CarExport {
+    repo: Option<Repo>,
+    root: Cid,
+    selector: Selector,
+    version: CarVersion,
+    span: Span,
+    providers: Vec<PeerId>,
+    local_only: bool,
+    timeout: Option<Duration>,
+    stream: Option<BoxStream<'static, Result<Bytes, Error>>>,
+}
+
+impl CarExport {
+    pub fn new(repo: &Repo, root: Cid, selector: Selector) -> Self {
+        Self {
+            repo: Some(repo.clone()),
+            root,
+            selector,
+            version: CarVersion::V1,
+            span: Span::current(),
+            providers: Vec::new(),
+            local_only: false,
+            timeout: None,
+            stream: None,
+        }
+    }
+
+    /// Set the version of the archive
+    pub fn version(mut self, version: CarVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Peer that may contain the blocks
+    pub fn provider(mut self, peer_id: PeerId) -> Self {
+        if !self.providers.contains(&peer_id) {
+            self.providers.push(peer_id);
+        }
+        self
+    }
+
+    /// List of peers that may contain the blocks
+    pub fn providers(mut self, list: &[PeerId]) -> Self {
+        self.providers = list.to_vec();
+        self
+    }
+
+    /// Duration to fetch a block from the network before timing out
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Export blocks that are available locally only
+    pub fn local(mut self) -> Self {
+        self.local_only = true;
+        self
+    }
+
+    /// Set a flag to export blocks that are available locally only
+    pub fn set_local(mut self, local: bool) -> Self {
+        self.local_only = local;
+        self
+    }
+
+    /// Set tracing span
+    pub fn span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+}
+
+impl Stream for CarExport {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        loop {
+            match &mut self.stream {
+                Some(stream) => match futures::ready!(stream.poll_next_unpin(cx)) {
+                    None => {
+                        self.stream.take();
+                        return Poll::Ready(None);
+                    }
+                    item => return Poll::Ready(item),
+                },
+                None => {
+                    let repo = match self.repo.take() {
+                        Some(repo) => repo,
+                        None => return Poll::Ready(None),
+                    };
+
+                    let root = self.root;
+                    let selector = self.selector;
+                    let version = self.version;
+                    let providers = std::mem::take(&mut self.providers);
+                    let local_only = self.local_only;
+                    let timeout = self.timeout;
+
+                    let stream = async_stream::try_stream! {
+                        let _g = repo.gc_guard().await;
+
+                        match version {
+                            CarVersion::V1 => {
+                                yield encode_v1_header(&[root])?;
+
+                                let mut blocks = dag_blocks(&repo, root, selector, &providers, local_only, timeout);
+                                while let Some(block) = blocks.try_next().await? {
+                                    yield encode_section(&block);
+                                }
+                            }
+                            CarVersion::V2 => {
+                                // The CARv2 header requires the size of the payload up front, so
+                                // the dag is walked once to collect the blocks, in order, and
+                                // their positions, after which the blocks are read back locally.
+                                let header = encode_v1_header(&[root])?;
+
+                                let mut order = Vec::new();
+                                let mut offset = header.len() as u64;
+
+                                let mut blocks = dag_blocks(&repo, root, selector, &providers, local_only, timeout);
+                                while let Some(block) = blocks.try_next().await? {
+                                    let length = section_len(&block);
+                                    order.push((*block.cid(), offset));
+                                    offset += length;
+                                }
+                                drop(blocks);
+
+                                let data_size = offset;
+                                let data_offset = (CARV2_PRAGMA.len() + CARV2_HEADER_SIZE) as u64;
+
+                                yield encode_v2_header(data_offset, data_size);
+                                yield header;
+
+                                for (cid, _) in &order {
+                                    let block = repo
+                                        .get_block_now(cid)
+                                        .await?
+                                        .ok_or_else(|| anyhow::anyhow!("block {cid} is no longer available"))?;
+                                    yield encode_section(&block);
+                                }
+
+                                yield encode_index_sorted(&order);
+                            }
+                        }
+                    };
+
+                    self.stream = Some(stream.instrument(self.span.clone()).boxed());
+                }
+            }
+        }
+    }
+}
+
+impl FusedStream for CarExport {
+    fn is_terminated(&self) -> bool {
+        self.repo.is_none() && self.stream.is_none()
+    }
+}
+
+/// Imports the blocks of a CAR into the repo.
+///
+/// Each block is verified against the multihash of its `Cid` before being written. Resolves to
+/// the roots declared in the header of the archive.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct CarImport {
+    repo: Repo,
+    stream: BoxStream<'static, std::io::Result<Bytes>>,
+    pin: Option<bool>,
+    span: Span,
+}
+
+impl CarImport {
+    pub fn new(
+        repo: &Repo,
+        stream: impl Stream<Item = std::io::Result<Bytes>> + Send + 'static,
+    ) -> Self {
+        Self {
+            repo: repo.clone(),
+            stream: stream.boxed(),
+            pin: None,
+            span: Span::current(),
+        }
+    }
+
+    /// Pin the roots of the archive once all blocks have been imported
+    pub fn pin(mut self, recursive: bool) -> Self {
+        self.pin = Some(recursive);
+        self
+    }
+
+    /// Set tracing span
+    pub fn span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+}
+
+impl std::future::IntoFuture for CarImport {
+    type Output = Result<Vec<Cid>, Error>;
+
+    type IntoFuture = BoxFuture<'static, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        let span = debug_span!(parent: &self.span, "import_car");
+        let repo = self.repo;
+        let pin = self.pin;
+        let mut reader = CarReader::new(self.stream);
+
+        async move {
+            let _g = repo.gc_guard().await;
+
+            let (mut roots, version) = reader.read_header().await?;
+
+            let mut limit = None;
+
+            if version == 2 {
+                let header = reader.read_exact(CARV2_HEADER_SIZE).await?;
+                let (data_offset, data_size) = decode_v2_header(&header)?;
+                let position = (CARV2_PRAGMA.len() + CARV2_HEADER_SIZE) as u64;
+                let padding = data_offset
+                    .checked_sub(position)
+                    .ok_or_else(|| anyhow::anyhow!("invalid carv2 data offset {data_offset}"))?;
+                reader.skip(padding).await?;
+
+                let start = reader.position;
+                let (inner_roots, inner_version) = reader.read_header().await?;
+                anyhow::ensure!(
+                    inner_version == 1,
+                    "unexpected version {inner_version} of the carv2 payload"
+                );
+                roots = inner_roots;
+                limit = Some(start + data_size);
+            } else {
+                anyhow::ensure!(version == 1, "unsupported car version {version}");
+            }
+
+            while limit.map(|limit| reader.position < limit).unwrap_or(true) {
+                let Some(length) = reader.read_varint().await? else {
+                    break;
+                };
+
+                let section = reader.read_exact(length as usize).await?;
+                let mut cursor = std::io::Cursor::new(section.as_ref());
+                let cid = Cid::read_bytes(&mut cursor)?;
+                let data = section.slice(cursor.position() as usize..);
+
+                // `Block::new` verifies the data against the multihash of the cid
+                let block = Block::new(cid, data.to_vec())
+                    .with_context(|| format!("block {cid} failed verification"))?;
+
+                repo.put_block(block).await?;
+            }
+
+            if let Some(recursive) = pin {
+                for root in &roots {
+                    if repo.is_pinned(root).await? {
+                        continue;
+                    }
+                    let mut pin = repo.pin(root).local();
+                    if recursive {
+                        pin = pin.recursive();
+                    }
+                    pin.await?;
+                }
+            }
+
+            Ok(roots)
+        }
+        .instrument(span)
+        .boxed()
+    }
+}
+
+/// Walks the dag from `root` following `selector`, yielding each unique block once.
+fn dag_blocks<'a>(
+    repo: &'a Repo,
+    root: Cid,
+    selector: Selector,
+    providers: &'a [PeerId],
+    local_only: bool,
+    timeout: Option<Duration>,
+) -> BoxStream<'a, Result<Block, Error>> {
+    async_stream::try_stream! {
+        let block = repo
+            .get_block_with_session(None, &root, providers, local_only, timeout)
+            .await?;
+
+        let depth = match selector {
+            Selector::Root => Some(0),
+            Selector::Depth(depth) => Some(depth),
+            Selector::All => None,
+        };
+
+        let ipld = match depth {
+            Some(0) => None,
+            _ if root.codec() == u64::from(IpldCodec::Raw) => None,
+            _ => Some(block.decode::<IpldCodec, Ipld>()?),
+        };
+
+        yield block;
+
+        let Some(ipld) = ipld else {
+            return;
+        };
+
+        let mut refs = IpldRefs::default().with_only_unique().providers(providers);
+
+        if let Some(depth) = depth {
+            refs = refs.with_max_depth(depth);
+        }
+
+        if let Some(timeout) = timeout {
+            refs = refs.with_timeout(timeout);
+        }
+
+        if local_only {
+            refs = refs.with_existing_blocks();
+        }
+
+        let mut edges = refs
+            .with_exit_on_error()
+            .refs_of_resolved(repo, vec![(root, ipld)])
+            .boxed();
+
+        while let Some(Edge { destination, .. }) = edges.try_next().await? {
+            if destination == root {
+                continue;
+            }
+
+            let block = repo
+                .get_block_now(&destination)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("block {destination} is not available"))?;
+
+            yield block;
+        }
+    }
+    .boxed()
+}
+
+fn encode_v1_header(roots: &[Cid]) -> Result<Bytes, Error> {
+    let header = Ipld::Map(BTreeMap::from([
+        (
+            "roots".to_string(),
+            Ipld::List(roots.iter().copied().map(Ipld::Link).collect()),
+        ),
+        ("version".to_string(), Ipld::Integer(1)),
+    ]));
+
+    let encoded = DagCborCodec.encode(&header)?;
+
+    let mut buffer = BytesMut::with_capacity(encoded.len() + 10);
+    put_varint(&mut buffer, encoded.len() as u64);
+    buffer.extend_from_slice(&encoded);
+    Ok(buffer.freeze())
+}
+
+fn encode_v2_header(data_offset: u64, data_size: u64) -> Bytes {
+    let mut buffer = BytesMut::with_capacity(CARV2_PRAGMA.len() + CARV2_HEADER_SIZE);
+    buffer.extend_from_slice(&CARV2_PRAGMA);
+    // characteristics; the index is not marked as fully indexed as identity cids are not included
+    buffer.put_u128_le(0);
+    buffer.put_u64_le(data_offset);
+    buffer.put_u64_le(data_size);
+    buffer.put_u64_le(data_offset + data_size);
+    buffer.freeze()
+}
+
+fn decode_v2_header(mut header: &[u8]) -> Result<(u64, u64), Error> {
+    anyhow::ensure!(header.len() == CARV2_HEADER_SIZE, "truncated carv2 header");
+    let _characteristics = header.get_u128_le();
+    let data_offset = header.get_u64_le();
+    let data_size = header.get_u64_le();
+    let _index_offset = header.get_u64_le();
+    Ok((data_offset, data_size))
+}
+
+fn section_len(block: &Block) -> u64 {
+    let length = (block.cid().encoded_len() + block.data().len()) as u64;
+    let mut buf = unsigned_varint::encode::u64_buffer();
+    unsigned_varint::encode::u64(length, &mut buf).len() as u64 + length
+}
+
+fn encode_section(block: &Block) -> Bytes {
+    let cid = block.cid().to_bytes();
+    let data = block.data();
+    let length = cid.len() + data.len();
+
+    let mut buffer = BytesMut::with_capacity(length + 10);
+    put_varint(&mut buffer, length as u64);
+    buffer.extend_from_slice(&cid);
+    buffer.extend_from_slice(data);
+    buffer.freeze()
+}
+
+/// Encodes the `IndexSorted` index where the digests are grouped into buckets by their width and
+/// sorted within each bucket, each entry pointing to the offset of the section in the payload.
+fn encode_index_sorted(order: &[(Cid, u64)]) -> Bytes {
+    let mut buckets: BTreeMap<u32, Vec<(&[u8], u64)>> = BTreeMap::new();
+
+    for (cid, offset) in order {
+        let digest = cid.hash().digest();
+        buckets
+            .entry(digest.len() as u32 + 8)
+            .or_default()
+            .push((digest, *offset));
+    }
+
+    let mut buffer = BytesMut::new();
+    put_varint(&mut buffer, CARV2_INDEX_SORTED);
+    buffer.put_i32_le(buckets.len() as i32);
+
+    for (width, mut entries) in buckets {
+        entries.sort_unstable();
+        entries.dedup_by(|a, b| a.0 == b.0);
+        buffer.put_u32_le(width);
+        buffer.put_u64_le(entries.len() as u64 * width as u64);
+        for (digest, offset) in entries {
+            buffer.extend_from_slice(digest);
+            buffer.put_u64_le(offset);
+        }
+    }
+
+    buffer.freeze()
+}
+
+fn put_varint(buffer: &mut BytesMut, value: u64) {
+    let mut buf = unsigned_varint::encode::u64_buffer();
+    buffer.extend_from_slice(unsigned_varint::encode::u64(value, &mut buf));
+}
+
+/// Incremental reader over a stream of bytes, tracking the amount of bytes consumed.
+struct CarReader {
+    stream: BoxStream<'static, std::io::Result<Bytes>>,
+    buffer: BytesMut,
+    position: u64,
+    eof: bool,
+}
+
+impl CarReader {
+    fn new(stream: BoxStream<'static, std::io::Result<Bytes>>) -> Self {
+        Self {
+            stream,
+            buffer: BytesMut::new(),
+            position: 0,
+            eof: false,
+        }
+    }
+
+    /// Pulls from the stream until the buffer has at least `size` bytes or the stream ended.
+    async fn fill(&mut self, size: usize) -> Result<(), Error> {
+        while self.buffer.len() < size && !self.eof {
+            match self.stream.next().await {
+                Some(bytes) => self.buffer.extend_from_slice(&bytes?),
+                None => self.eof = true,
+            }
+        }
+        Ok(())
+    }
+
+    async fn read_exact(&mut self, size: usize) -> Result<Bytes, Error> {
+        self.fill(size).await?;
+        anyhow::ensure!(self.buffer.len() >= size, "unexpected end of car");
+        self.position += size as u64;
+        Ok(self.buffer.split_to(size).freeze())
+    }
+
+    async fn skip(&mut self, size: u64) -> Result<(), Error> {
+        let mut remaining = size;
+        while remaining > 0 {
+            let chunk = remaining.min(64 * 1024) as usize;
+            self.read_exact(chunk).await?;
+            remaining -= chunk as u64;
+        }
+        Ok(())
+    }
+
+    /// Reads the next varint, returning `None` if the stream ended cleanly.
+    async fn read_varint(&mut self) -> Result<Option<u64>, Error> {
+        let mut needed = 1;
+        loop {
+            self.fill(needed).await?;
+
+            if self.buffer.is_empty() {
+                return Ok(None);
+            }
+
+            match unsigned_varint::decode::u64(&self.buffer) {
+                Ok((value, rest)) => {
+                    let consumed = self.buffer.len() - rest.len();
+                    self.buffer.advance(consumed);
+                    self.position += consumed as u64;
+                    return Ok(Some(value));
+                }
+                Err(unsigned_varint::decode::Error::Insufficient) if !self.eof => {
+                    needed = self.buffer.len() + 1;
+                }
+                Err(e) => return Err(anyhow::anyhow!("invalid varint in car: {e}")),
+            }
+        }
+    }
+
+    /// Reads a CARv1 header or the CARv2 pragma, returning the roots and the version.
+    async fn read_header(&mut self) -> Result<(Vec<Cid>, u64), Error> {
+        let length = self
+            .read_varint()
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("car is empty"))?;
+
+        let bytes = self.read_exact(length as usize).await?;
+        let header: Ipld = DagCborCodec.decode(&bytes)?;
+
+        let version = match header.get("version") {
+            Ok(Ipld::Integer(version)) => *version as u64,
+            _ => anyhow::bail!("car header is missing the version"),
+        };
+
+        let roots = match header.get("roots") {
+            Ok(Ipld::List(roots)) => roots
+                .iter()
+                .map(|root| match root {
+                    Ipld::Link(cid) => Ok(*cid),
+                    _ => Err(anyhow::anyhow!("car header root is not a link")),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ if version == 2 => Vec::new(),
+            _ => anyhow::bail!("car header is missing the roots"),
+        };
+
+        Ok((roots, version))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CarVersion, Selector, CARV2_PRAGMA};
+    use crate::Node;
+    use bytes::Bytes;
+    use futures::{StreamExt, TryStreamExt};
+    use libipld::ipld;
+
+    async fn export(node: &Node, version: CarVersion, selector: Selector) -> (libipld::Cid, Bytes) {
+        let leaf = node.put_dag(ipld!({ "leaf": true })).await.unwrap();
+        let mid = node.put_dag(ipld!({ "leaf": leaf })).await.unwrap();
+        let root = node
+            .put_dag(ipld!({ "mid": mid, "leaf": leaf }))
+            .await
+            .unwrap();
+
+        let bytes = node
+            .export_car(root, selector)
+            .version(version)
+            .try_fold(Vec::new(), |mut acc, bytes| async move {
+                acc.extend_from_slice(&bytes);
+                Ok(acc)
+            })
+            .await
+            .unwrap();
+
+        (root, bytes.into())
+    }
+
+    #[tokio::test]
+    async fn export_and_import_v1() {
+        let node_a = Node::new("a").await;
+        let node_b = Node::new("b").await;
+
+        let (root, car) = export(&node_a, CarVersion::V1, Selector::All).await;
+
+        let stream = futures::stream::once(async move { Ok(car) }).boxed();
+        let roots = node_b.import_car(stream).pin(true).await.unwrap();
+
+        assert_eq!(roots, vec![root]);
+        assert!(node_b.is_pinned(&root).await.unwrap());
+        assert_eq!(node_b.refs_local().await.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn export_and_import_v2() {
+        let node_a = Node::new("a").await;
+        let node_b = Node::new("b").await;
+
+        let (root, car) = export(&node_a, CarVersion::V2, Selector::All).await;
+        assert_eq!(&car[..CARV2_PRAGMA.len()], &CARV2_PRAGMA);
+
+        // feed the archive in small pieces to exercise the buffering
+        let chunks = car
+            .chunks(7)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect::<Vec<_>>();
+
+        let roots = node_b
+            .import_car(futures::stream::iter(chunks))
+            .await
+            .unwrap();
+
+        assert_eq!(roots, vec![root]);
+        assert_eq!(node_b.refs_local().await.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn export_root_only() {
+        let node_a = Node::new("a").await;
+        let node_b = Node::new("b").await;
+
+        let (root, car) = export(&node_a, CarVersion::V1, Selector::Root).await;
+
+        let stream = futures::stream::once(async move { Ok(car) });
+        node_b.import_car(stream).await.unwrap();
+
+        assert_eq!(node_b.refs_local().await, vec![root]);
+    }
+
+    #[tokio::test]
+    async fn import_rejects_corrupted_block() {
+        let node_a = Node::new("a").await;
+        let node_b = Node::new("b").await;
+
+        let (_, car) = export(&node_a, CarVersion::V1, Selector::Root).await;
+
+        let mut car = car.to_vec();
+        let last = car.len() - 1;
+        car[last] ^= 0xff;
+
+        let stream = futures::stream::once(async move { Ok(Bytes::from(car)) });
+        assert!(node_b.import_car(stream).await.is_err());
+        assert!(node_b.refs_local().await.is_empty());
+    }
+}
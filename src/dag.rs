@@ -395,7 +395,13 @@ impl IpldDag {
                     .resolve_hamt(lookup, &mut cache, providers, local_only)
                     .await
                 {
-                    Ok(dest) => (src, dest),
+                    Ok(Some(dest)) => (src, dest),
+                    Ok(None) => {
+                        return Err(RawResolveLocalError::NotFound {
+                            document: src,
+                            segment_index: start,
+                        })
+                    }
                     Err(e) => return Err(RawResolveLocalError::UnsupportedDocument(src, e.into())),
                 },
                 Complete(other) => {
@@ -415,14 +421,15 @@ impl IpldDag {
     }
 
     /// To resolve a segment through a HAMT-sharded directory we need to load more blocks, which is
-    /// why this is a method and not a free `fn` like the other resolving activities.
+    /// why this is a method and not a free `fn` like the other resolving activities. Returns `None`
+    /// when the directory has no such entry.
     async fn resolve_hamt(
         &self,
         mut lookup: ShardedLookup<'_>,
         cache: &mut Option<Cache>,
         providers: &[PeerId],
         local_only: bool,
-    ) -> Result<Cid, Error> {
+    ) -> Result<Option<Cid>, Error> {
         use MaybeResolved::*;
 
         loop {
@@ -432,8 +439,8 @@ impl IpldDag {
 
             match lookup.continue_walk(block.data(), cache)? {
                 NeedToLoadMore(next) => lookup = next,
-                Found(cid) => return Ok(cid),
-                NotFound => return Err(anyhow::anyhow!("key not found: ???")),
+                Found(cid) => return Ok(Some(cid)),
+                NotFound => return Ok(None),
             }
         }
     }
@@ -719,10 +726,13 @@ impl ResolvedNode {
         }
     }
 
-    /// Unwraps the dagpb block variant and turns others into UnexpectedResolved.
+    /// Unwraps the dagpb or raw block variant and turns others into UnexpectedResolved.
     /// This is useful wherever unixfs operations are continued after resolving an IpfsPath.
     pub fn into_unixfs_block(self) -> Result<Block, UnexpectedResolved> {
-        if self.source().codec() != <IpldCodec as Into<u64>>::into(IpldCodec::DagPb) {
+        let codec = self.source().codec();
+        if codec != <IpldCodec as Into<u64>>::into(IpldCodec::DagPb)
+            && codec != <IpldCodec as Into<u64>>::into(IpldCodec::Raw)
+        {
             Err(UnexpectedResolved::UnexpectedCodec(
                 IpldCodec::DagPb.into(),
                 self,
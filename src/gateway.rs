@@ -0,0 +1,888 @@
+//! HTTP gateway serving the content reachable from the node, in the manner of the
+//! [path and subdomain gateways](https://specs.ipfs.tech/http-gateways/) of kubo.
+//!
+//! Unixfs files are served with their content type guessed from the name, supporting single
+//! range requests, and directories are served through their `index.html`, or listed when there is
+//! none. Any block or DAG can also be fetched as a trustless response with `?format=raw` or
+//! `?format=car`, or the equivalent `Accept` header.
+
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Error;
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::header::{
+    ACCEPT, ACCEPT_RANGES, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE,
+    CONTENT_TYPE, ETAG, HOST, IF_NONE_MATCH, LOCATION, RANGE,
+};
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use bytes::Bytes;
+use futures::{StreamExt, TryStreamExt};
+use libipld::cid::Version;
+use libipld::multibase::Base;
+use libipld::multihash::{Code, Multihash};
+use libipld::{Cid, IpldCodec};
+use libp2p::PeerId;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use rust_unixfs::dir::{self, DirectoryLink};
+use rust_unixfs::walk::{ContinuedWalk, Walker};
+use tokio_util::sync::{CancellationToken, DropGuard};
+use tracing::Span;
+use tracing_futures::Instrument;
+
+use crate::car::{CarExport, Selector};
+use crate::dag::{ResolveError, ResolvedNode};
+use crate::path::{IpfsPath, PathRoot};
+use crate::unixfs::UnixfsCat;
+use crate::{Block, Ipfs};
+
+/// Cache-Control of the immutable content of `/ipfs` paths
+const IMMUTABLE: &str = "public, max-age=29030400, immutable";
+
+/// Cache-Control of the content of `/ipns` paths, which can change once the name is republished
+const MUTABLE: &str = "public, max-age=60";
+
+/// Number of the root blocks of directory entries loaded at once while listing a directory
+const LISTING_CONCURRENCY: usize = 16;
+
+/// Characters escaped in the links of directory listings
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// HTTP gateway of a node. Created with [`Ipfs::gateway`].
+///
+/// Serves `/ipfs/<cid>/<path>` and `/ipns/<name>/<path>` requests, and when subdomain hosts are
+/// set, `<cid>.ipfs.<host>/<path>` and `<name>.ipns.<host>/<path>` requests, with path requests
+/// made to the host redirected to the subdomain.
+#[derive(Clone)]
+pub struct Gateway {
+    ipfs: Ipfs,
+    subdomain_hosts: Vec<String>,
+    local_only: bool,
+    timeout: Option<Duration>,
+    span: Span,
+}
+
+impl Gateway {
+    pub fn new(ipfs: &Ipfs) -> Self {
+        Self {
+            ipfs: ipfs.clone(),
+            subdomain_hosts: vec![],
+            local_only: false,
+            timeout: None,
+            span: Span::current(),
+        }
+    }
+
+    /// Host, e.g. `localhost` or `dweb.link`, under which the content is served from subdomains
+    pub fn subdomain_host(mut self, host: impl Into<String>) -> Self {
+        self.subdomain_hosts.push(host.into().to_lowercase());
+        self
+    }
+
+    /// Only serve the content available locally
+    pub fn local(mut self) -> Self {
+        self.local_only = true;
+        self
+    }
+
+    /// Duration to fetch a block from the network before timing out
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set tracing span
+    pub fn span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+
+    /// Returns the router handling the requests, to be served along with other routes.
+    pub fn router(self) -> Router {
+        Router::new().fallback(handle).with_state(Arc::new(self))
+    }
+
+    /// Serves the gateway on the address until the returned [`GatewayServer`] is dropped.
+    pub async fn listen(self, addr: SocketAddr) -> Result<GatewayServer, Error> {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+
+        let token = CancellationToken::new();
+        let span = self.span.clone();
+        let router = self.router();
+        let shutdown = token.clone();
+
+        crate::rt::spawn(
+            async move {
+                let server = axum::serve(listener, router)
+                    .with_graceful_shutdown(async move { shutdown.cancelled().await });
+                if let Err(e) = server.await {
+                    error!("gateway stopped: {e}");
+                }
+            }
+            .instrument(span),
+        );
+
+        Ok(GatewayServer {
+            local_addr,
+            _guard: token.drop_guard(),
+        })
+    }
+}
+
+/// Gateway being served, stopped when dropped.
+pub struct GatewayServer {
+    local_addr: SocketAddr,
+    _guard: DropGuard,
+}
+
+impl GatewayServer {
+    /// Address the gateway listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+/// Error returned to the client as a plain text response.
+struct GatewayError(StatusCode, String);
+
+impl GatewayError {
+    fn new(status: StatusCode, message: impl ToString) -> Self {
+        Self(status, message.to_string())
+    }
+}
+
+impl IntoResponse for GatewayError {
+    fn into_response(self) -> Response {
+        (
+            self.0,
+            [(CONTENT_TYPE, "text/plain; charset=utf-8")],
+            self.1,
+        )
+            .into_response()
+    }
+}
+
+impl From<ResolveError> for GatewayError {
+    fn from(e: ResolveError) -> Self {
+        let status = match e {
+            ResolveError::NotFound(..)
+            | ResolveError::NoLinks(..)
+            | ResolveError::ListIndexOutOfRange { .. } => StatusCode::NOT_FOUND,
+            ResolveError::IpnsResolutionFailed(_) | ResolveError::Loading(..) => {
+                StatusCode::BAD_GATEWAY
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self::new(status, e)
+    }
+}
+
+/// Representation of the content requested with `?format=` or the `Accept` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Unixfs,
+    Raw,
+    Car,
+}
+
+impl Format {
+    fn from_request(uri: &Uri, headers: &HeaderMap) -> Result<Self, GatewayError> {
+        let query = uri.query().unwrap_or_default();
+        if let Some(format) = query
+            .split('&')
+            .find_map(|param| param.strip_prefix("format="))
+        {
+            return match format {
+                "raw" => Ok(Format::Raw),
+                "car" => Ok(Format::Car),
+                format => Err(GatewayError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("unsupported format {format}"),
+                )),
+            };
+        }
+
+        let accept = headers
+            .get(ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+
+        if accept.contains("application/vnd.ipld.raw") {
+            Ok(Format::Raw)
+        } else if accept.contains("application/vnd.ipld.car") {
+            Ok(Format::Car)
+        } else {
+            Ok(Format::Unixfs)
+        }
+    }
+}
+
+/// Unixfs node found at the end of the path.
+enum UnixfsNode {
+    Directory,
+    /// File with its size and the bytes of its first block
+    File(u64, Bytes),
+    Symlink(Bytes),
+}
+
+async fn handle(
+    State(gateway): State<Arc<Gateway>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Response {
+    let span = debug_span!(parent: &gateway.span, "gateway", %method, %uri);
+
+    async move {
+        if method != Method::GET && method != Method::HEAD {
+            return GatewayError::new(StatusCode::METHOD_NOT_ALLOWED, "method not allowed")
+                .into_response();
+        }
+
+        // the body of the responses to HEAD requests is dropped by the server without being read
+        match gateway.content_path(&uri, &headers) {
+            Ok(Ok(path)) => gateway
+                .serve(&path, &uri, &headers)
+                .await
+                .unwrap_or_else(IntoResponse::into_response),
+            Ok(Err(location)) => redirect(&location),
+            Err(e) => e.into_response(),
+        }
+    }
+    .instrument(span)
+    .await
+}
+
+impl Gateway {
+    /// Returns the content path of the request, or the location the request is redirected to.
+    fn content_path(
+        &self,
+        uri: &Uri,
+        headers: &HeaderMap,
+    ) -> Result<Result<String, String>, GatewayError> {
+        let path = percent_decode_str(uri.path())
+            .decode_utf8()
+            .map_err(|e| GatewayError::new(StatusCode::BAD_REQUEST, e))?;
+
+        let host = headers
+            .get(HOST)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_lowercase();
+        let hostname = host.split(':').next().unwrap_or_default();
+
+        for gateway_host in &self.subdomain_hosts {
+            if hostname == gateway_host {
+                return match subdomain_redirect(uri, headers, &host) {
+                    Some(location) => Ok(Err(location)),
+                    None => Err(GatewayError::new(StatusCode::NOT_FOUND, "not found")),
+                };
+            }
+
+            let Some(subdomain) = hostname
+                .strip_suffix(gateway_host.as_str())
+                .and_then(|subdomain| subdomain.strip_suffix('.'))
+            else {
+                continue;
+            };
+
+            return match subdomain.split_once('.') {
+                Some((key, "ipfs")) => Ok(Ok(format!("/ipfs/{key}{path}"))),
+                Some((key, "ipns")) => Ok(Ok(format!("/ipns/{}{path}", decode_dnslink_label(key)))),
+                _ => Err(GatewayError::new(StatusCode::NOT_FOUND, "not found")),
+            };
+        }
+
+        if path.starts_with("/ipfs/") || path.starts_with("/ipns/") {
+            Ok(Ok(path.into_owned()))
+        } else {
+            Err(GatewayError::new(StatusCode::NOT_FOUND, "not found"))
+        }
+    }
+
+    async fn serve(
+        &self,
+        content_path: &str,
+        uri: &Uri,
+        headers: &HeaderMap,
+    ) -> Result<Response, GatewayError> {
+        let path = IpfsPath::from_str(content_path)
+            .map_err(|e| GatewayError::new(StatusCode::BAD_REQUEST, e))?;
+        let path = self.stored_version(path).await;
+        let cache_control = match path.root() {
+            PathRoot::Ipld(_) => IMMUTABLE,
+            _ => MUTABLE,
+        };
+        let format = Format::from_request(uri, headers)?;
+
+        let (resolved, _) = self
+            .ipfs
+            .dag()
+            .resolve_with_session(None, path.clone(), true, &[], self.local_only, self.timeout)
+            .await?;
+        let cid = *resolved.source();
+
+        let etag = match format {
+            Format::Unixfs => format!("\"{cid}\""),
+            Format::Raw => format!("\"{cid}.raw\""),
+            Format::Car => format!("\"{cid}.car\""),
+        };
+
+        if headers
+            .get(IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value == "*" || value.split(',').any(|tag| tag.trim() == etag))
+        {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_MODIFIED)
+                .header(ETAG, etag)
+                .header(CACHE_CONTROL, cache_control)
+                .body(Body::empty())
+                .expect("valid response"));
+        }
+
+        let mut response = match format {
+            Format::Raw => self.serve_raw(&cid).await?,
+            Format::Car => self.serve_car(&cid),
+            Format::Unixfs => {
+                let block = resolved.into_unixfs_block().map_err(|_| {
+                    GatewayError::new(
+                        StatusCode::NOT_IMPLEMENTED,
+                        "only unixfs content can be served, use ?format=raw or ?format=car",
+                    )
+                })?;
+                self.serve_unixfs(block, &path, uri, headers).await?
+            }
+        };
+
+        let redirected = response.status().is_redirection();
+        let response_headers = response.headers_mut();
+        if !redirected {
+            response_headers.insert(ETAG, header_value(&etag));
+            response_headers.insert(CACHE_CONTROL, HeaderValue::from_static(cache_control));
+        }
+        response_headers.insert("x-ipfs-path", header_value(content_path));
+
+        Ok(response)
+    }
+
+    /// Subdomains always contain a CIDv1 while the blocks are stored under the cid they were
+    /// added with, so the equivalent CIDv0 is used when only that one is available locally.
+    async fn stored_version(&self, path: IpfsPath) -> IpfsPath {
+        let Some(cid) = path.root().cid().copied() else {
+            return path;
+        };
+
+        if cid.version() != Version::V1
+            || cid.codec() != u64::from(IpldCodec::DagPb)
+            || cid.hash().code() != u64::from(Code::Sha2_256)
+        {
+            return path;
+        }
+
+        let repo = self.ipfs.repo();
+        let Ok(v0) = Cid::new_v0(*cid.hash()) else {
+            return path;
+        };
+        if repo.contains(&cid).await.unwrap_or_default()
+            || !repo.contains(&v0).await.unwrap_or_default()
+        {
+            return path;
+        }
+
+        let segments = path.iter().collect::<Vec<_>>().join("/");
+        IpfsPath::new(PathRoot::Ipld(v0))
+            .sub_path(&segments)
+            .unwrap_or(path)
+    }
+
+    async fn get_block(&self, cid: &Cid) -> Result<Block, GatewayError> {
+        self.ipfs
+            .repo()
+            .get_block_with_session(None, cid, &[], self.local_only, self.timeout)
+            .await
+            .map_err(|e| GatewayError::new(StatusCode::BAD_GATEWAY, e))
+    }
+
+    async fn serve_raw(&self, cid: &Cid) -> Result<Response, GatewayError> {
+        let block = self.get_block(cid).await?;
+
+        Ok(Response::builder()
+            .header(CONTENT_TYPE, "application/vnd.ipld.raw")
+            .header(CONTENT_LENGTH, block.data().len())
+            .header(
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{cid}.bin\""),
+            )
+            .header("x-content-type-options", "nosniff")
+            .body(Body::from(block.data().to_vec()))
+            .expect("valid response"))
+    }
+
+    fn serve_car(&self, cid: &Cid) -> Response {
+        let mut export = CarExport::new(self.ipfs.repo(), *cid, Selector::All)
+            .set_local(self.local_only)
+            .span(self.span.clone());
+        if let Some(timeout) = self.timeout {
+            export = export.timeout(timeout);
+        }
+
+        Response::builder()
+            .header(CONTENT_TYPE, "application/vnd.ipld.car; version=1")
+            .header(
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{cid}.car\""),
+            )
+            .header("x-content-type-options", "nosniff")
+            .body(Body::from_stream(export.map_err(into_io_error)))
+            .expect("valid response")
+    }
+
+    async fn serve_unixfs(
+        &self,
+        block: Block,
+        path: &IpfsPath,
+        uri: &Uri,
+        headers: &HeaderMap,
+    ) -> Result<Response, GatewayError> {
+        let name = path.iter().last().map(ToString::to_string);
+
+        match unixfs_node(&block)? {
+            UnixfsNode::File(size, first) => {
+                Ok(self.serve_file(block, name.as_deref(), size, &first, headers))
+            }
+            UnixfsNode::Symlink(target) => Ok(Response::builder()
+                .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+                .header(CONTENT_LENGTH, target.len())
+                .body(Body::from(target))
+                .expect("valid response")),
+            UnixfsNode::Directory => {
+                // links of the listing and of the index are relative to the directory
+                if !uri.path().ends_with('/') {
+                    let location = match uri.query() {
+                        Some(query) => format!("{}/?{query}", uri.path()),
+                        None => format!("{}/", uri.path()),
+                    };
+                    return Ok(redirect(&location));
+                }
+
+                let index_path = path
+                    .sub_path("index.html")
+                    .map_err(|e| GatewayError::new(StatusCode::BAD_REQUEST, e))?;
+
+                match self
+                    .ipfs
+                    .dag()
+                    .resolve_with_session(
+                        None,
+                        index_path,
+                        true,
+                        &[],
+                        self.local_only,
+                        self.timeout,
+                    )
+                    .await
+                {
+                    Ok((ResolvedNode::Block(index), _)) => {
+                        if let UnixfsNode::File(size, first) = unixfs_node(&index)? {
+                            return Ok(self.serve_file(
+                                index,
+                                Some("index.html"),
+                                size,
+                                &first,
+                                headers,
+                            ));
+                        }
+                    }
+                    Ok(_) | Err(ResolveError::NotFound(..)) => {}
+                    Err(e) => return Err(e.into()),
+                }
+
+                self.serve_listing(&block, path).await
+            }
+        }
+    }
+
+    fn serve_file(
+        &self,
+        block: Block,
+        name: Option<&str>,
+        size: u64,
+        first: &[u8],
+        headers: &HeaderMap,
+    ) -> Response {
+        let content_type = name
+            .and_then(content_type_of)
+            .unwrap_or_else(|| sniff_content_type(first));
+
+        let range = headers
+            .get(RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| parse_range(value, size));
+
+        let mut cat = UnixfsCat::with_ipfs(&self.ipfs, block)
+            .set_local(self.local_only)
+            .span(self.span.clone());
+        if let Some(timeout) = self.timeout {
+            cat = cat.timeout(timeout);
+        }
+
+        let mut builder = Response::builder()
+            .header(CONTENT_TYPE, content_type)
+            .header(ACCEPT_RANGES, "bytes");
+
+        match range {
+            Some(Ok(range)) => {
+                builder = builder
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header(CONTENT_LENGTH, range.end - range.start)
+                    .header(
+                        CONTENT_RANGE,
+                        format!("bytes {}-{}/{size}", range.start, range.end - 1),
+                    );
+                cat = cat.range(range);
+            }
+            Some(Err(())) => {
+                return Response::builder()
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(CONTENT_RANGE, format!("bytes */{size}"))
+                    .body(Body::empty())
+                    .expect("valid response");
+            }
+            None => builder = builder.header(CONTENT_LENGTH, size),
+        }
+
+        builder
+            .body(Body::from_stream(cat.map_err(into_io_error)))
+            .expect("valid response")
+    }
+
+    /// Lists the entries of the directory, loading the buckets of HAMT shards and the root block
+    /// of every entry but nothing below them.
+    async fn serve_listing(
+        &self,
+        block: &Block,
+        path: &IpfsPath,
+    ) -> Result<Response, GatewayError> {
+        let mut links = vec![];
+        let mut pending = vec![];
+        let mut next = Some(block.clone());
+        while let Some(block) = next {
+            let block_links = dir::links(block.data())
+                .map_err(|e| GatewayError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            for link in block_links {
+                match link {
+                    DirectoryLink::Entry { name, cid, .. } => links.push((name, cid)),
+                    DirectoryLink::Bucket(cid) => pending.push(cid),
+                }
+            }
+            next = match pending.pop() {
+                Some(cid) => Some(self.get_block(&cid).await?),
+                None => None,
+            };
+        }
+
+        let mut entries = futures::stream::iter(links)
+            .map(|(name, cid)| async move {
+                let node = unixfs_node(&self.get_block(&cid).await?)?;
+                Ok::<_, GatewayError>((name, cid, node))
+            })
+            .buffered(LISTING_CONCURRENCY)
+            .try_collect::<Vec<_>>()
+            .await?;
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let title = html_escape(&path.to_string());
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<h1>Index of {title}</h1>\n<table>\n"
+        );
+        if path.iter().next().is_some() {
+            html.push_str("<tr><td><a href=\"../\">..</a></td><td></td><td></td></tr>\n");
+        }
+        for (name, cid, node) in entries {
+            let href = utf8_percent_encode(&name, PATH_SEGMENT);
+            let name = html_escape(&name);
+            let (href, name, size) = match node {
+                UnixfsNode::File(size, _) => (href.to_string(), name, size.to_string()),
+                UnixfsNode::Symlink(_) => (href.to_string(), name, String::new()),
+                UnixfsNode::Directory => (format!("{href}/"), format!("{name}/"), String::new()),
+            };
+            let _ = writeln!(
+                html,
+                "<tr><td><a href=\"{href}\">{name}</a></td><td>{cid}</td><td>{size}</td></tr>"
+            );
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+
+        Ok(Response::builder()
+            .header(CONTENT_TYPE, "text/html; charset=utf-8")
+            .header(CONTENT_LENGTH, html.len())
+            .body(Body::from(html))
+            .expect("valid response"))
+    }
+}
+
+/// Reads the type of the unixfs node from its root block.
+fn unixfs_node(block: &Block) -> Result<UnixfsNode, GatewayError> {
+    let mut walker = Walker::new(*block.cid(), String::new());
+    match walker.next(block.data(), &mut None) {
+        Ok(ContinuedWalk::RootDirectory(..) | ContinuedWalk::Directory(..))
+        | Ok(ContinuedWalk::Bucket(..)) => Ok(UnixfsNode::Directory),
+        Ok(ContinuedWalk::File(segment, _, _, _, size)) => Ok(UnixfsNode::File(
+            size,
+            Bytes::copy_from_slice(segment.as_bytes()),
+        )),
+        Ok(ContinuedWalk::Symlink(target, ..)) => {
+            Ok(UnixfsNode::Symlink(Bytes::copy_from_slice(target)))
+        }
+        Err(e) => Err(GatewayError::new(StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+/// Returns the location of the subdomain serving a path request made to a subdomain host, with
+/// the cid encoded in base32 and the peer id in base36 as the subdomains are case-insensitive.
+fn subdomain_redirect(uri: &Uri, headers: &HeaderMap, host: &str) -> Option<String> {
+    let path = uri.path();
+    let (namespace, rest) = path.strip_prefix('/')?.split_once('/')?;
+    let (key, rest) = match rest.find('/') {
+        Some(position) => rest.split_at(position),
+        None => (rest, ""),
+    };
+
+    let label = match namespace {
+        "ipfs" => {
+            let cid = Cid::try_from(key).ok()?;
+            Cid::new_v1(cid.codec(), *cid.hash()).to_string()
+        }
+        "ipns" => match PeerId::from_str(key) {
+            Ok(peer_id) => {
+                let hash = Multihash::from_bytes(&peer_id.to_bytes()).ok()?;
+                Cid::new_v1(0x72, hash)
+                    .to_string_of_base(Base::Base36Lower)
+                    .ok()?
+            }
+            Err(_) => encode_dnslink_label(key),
+        },
+        _ => return None,
+    };
+
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("http");
+    let query = uri
+        .query()
+        .map(|query| format!("?{query}"))
+        .unwrap_or_default();
+
+    Some(format!(
+        "{scheme}://{label}.{namespace}.{host}{rest}{query}"
+    ))
+}
+
+/// Encodes a DNSLink name into a single label, e.g. `en.wikipedia-on-ipfs.org` into
+/// `en-wikipedia--on--ipfs-org`.
+fn encode_dnslink_label(name: &str) -> String {
+    name.replace('-', "--").replace('.', "-")
+}
+
+/// Decodes the label of a DNSLink name, keeping peer ids and cids as is.
+fn decode_dnslink_label(label: &str) -> String {
+    if !label.contains('-') || PeerId::from_str(label).is_ok() || Cid::try_from(label).is_ok() {
+        return label.to_string();
+    }
+
+    label
+        .split("--")
+        .map(|part| part.replace('-', "."))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Parses a single `bytes` range. Returns `None` for ranges which are ignored, serving the whole
+/// file, and `Some(Err(()))` for unsatisfiable ranges.
+fn parse_range(value: &str, size: u64) -> Option<Result<Range<u64>, ()>> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+
+    let (start, end) = spec.split_once('-')?;
+    let range = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let length: u64 = suffix.parse().ok()?;
+            if length == 0 {
+                return Some(Err(()));
+            }
+            size.saturating_sub(length)..size
+        }
+        (start, "") => start.parse().ok()?..size,
+        (start, end) => {
+            let start: u64 = start.parse().ok()?;
+            let end: u64 = end.parse().ok()?;
+            if end < start {
+                return None;
+            }
+            start..size.min(end.saturating_add(1))
+        }
+    };
+
+    if range.start >= size {
+        return Some(Err(()));
+    }
+
+    Some(Ok(range))
+}
+
+fn content_type_of(name: &str) -> Option<&'static str> {
+    let (_, extension) = name.rsplit_once('.')?;
+    let content_type = match extension.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/vnd.microsoft.icon",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        _ => return None,
+    };
+    Some(content_type)
+}
+
+/// Guesses the content type of a file without a known extension from its first bytes.
+fn sniff_content_type(data: &[u8]) -> &'static str {
+    let start = &data[..data.len().min(512)];
+    let text = match std::str::from_utf8(start) {
+        Ok(text) => text,
+        // the prefix can end in the middle of a character
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&start[..e.valid_up_to()]).expect("valid up to the error")
+        }
+        Err(_) => return "application/octet-stream",
+    };
+
+    if text.contains('\0') {
+        return "application/octet-stream";
+    }
+
+    let trimmed = text.trim_start().to_ascii_lowercase();
+    if trimmed.starts_with("<!doctype html") || trimmed.starts_with("<html") {
+        "text/html; charset=utf-8"
+    } else {
+        "text/plain; charset=utf-8"
+    }
+}
+
+fn html_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn header_value(value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).unwrap_or_else(|_| {
+        HeaderValue::from_str(&utf8_percent_encode(value, CONTROLS).to_string())
+            .expect("percent encoded value is valid")
+    })
+}
+
+fn redirect(location: &str) -> Response {
+    Response::builder()
+        .status(StatusCode::MOVED_PERMANENTLY)
+        .header(LOCATION, header_value(location))
+        .body(Body::empty())
+        .expect("valid response")
+}
+
+fn into_io_error(e: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_dnslink_label, encode_dnslink_label, parse_range, sniff_content_type};
+
+    #[test]
+    fn ranges() {
+        assert_eq!(parse_range("bytes=0-9", 100), Some(Ok(0..10)));
+        assert_eq!(parse_range("bytes=90-", 100), Some(Ok(90..100)));
+        assert_eq!(parse_range("bytes=-10", 100), Some(Ok(90..100)));
+        assert_eq!(parse_range("bytes=-200", 100), Some(Ok(0..100)));
+        assert_eq!(parse_range("bytes=50-200", 100), Some(Ok(50..100)));
+        assert_eq!(parse_range("bytes=100-", 100), Some(Err(())));
+        assert_eq!(parse_range("bytes=-0", 100), Some(Err(())));
+        assert_eq!(parse_range("bytes=0-1,5-6", 100), None);
+        assert_eq!(parse_range("bytes=9-0", 100), None);
+        assert_eq!(parse_range("items=0-9", 100), None);
+    }
+
+    #[test]
+    fn dnslink_labels() {
+        let name = "en.wikipedia-on-ipfs.org";
+        let label = encode_dnslink_label(name);
+        assert_eq!(label, "en-wikipedia--on--ipfs-org");
+        assert_eq!(decode_dnslink_label(&label), name);
+        assert_eq!(
+            decode_dnslink_label("k51qzi5uqu5dlvj2baxnqndepeb86cbk3ng7n3i46uzyxzyqj2xjonzllnv0v8"),
+            "k51qzi5uqu5dlvj2baxnqndepeb86cbk3ng7n3i46uzyxzyqj2xjonzllnv0v8"
+        );
+    }
+
+    #[test]
+    fn sniffing() {
+        assert_eq!(sniff_content_type(b"hello"), "text/plain; charset=utf-8");
+        assert_eq!(
+            sniff_content_type(b"  <!DOCTYPE html><html></html>"),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(
+            sniff_content_type(&[0, 159, 146, 150]),
+            "application/octet-stream"
+        );
+        // truncated in the middle of a multibyte character
+        assert_eq!(
+            sniff_content_type(&"é".as_bytes()[..1]),
+            "text/plain; charset=utf-8"
+        );
+    }
+}
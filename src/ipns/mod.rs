@@ -1,13 +1,25 @@
 //! IPNS functionality around [`Ipfs`].
 
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
 use futures_timeout::TimeoutExt;
+use libipld::Cid;
+use libp2p::identity::Keypair;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use tracing::Span;
+use tracing_futures::Instrument;
 
 use crate::error::Error;
 use crate::p2p::DnsResolver;
 use crate::path::{IpfsPath, PathRoot};
+use crate::repo::Repo;
 use crate::Ipfs;
 
 mod dnslink;
+pub(crate) mod pubsub;
 
 /// IPNS facade around [`Ipns`].
 #[derive(Clone, Debug)]
@@ -16,11 +28,21 @@ pub struct Ipns {
     resolver: Option<DnsResolver>,
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+/// Default time for which a published record is valid.
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(48 * 60 * 60);
+
+/// Default time for which resolvers may cache a published record.
+const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum IpnsOption {
     Local,
     #[default]
     DHT,
+    /// Publishes and resolves over pubsub, compatible with kubo's `--enable-namesys-pubsub`.
+    /// Resolving subscribes to the name, keeping the latest record received for as long as the
+    /// node runs.
+    PubSub,
 }
 
 impl Ipns {
@@ -37,9 +59,18 @@ impl Ipns {
     }
 
     /// Resolves a ipns path to an ipld path.
-    // TODO: Implement ipns pubsub
-    // TODO: Maybe implement a check to the dht store itself too?
     pub async fn resolve(&self, path: &IpfsPath) -> Result<IpfsPath, Error> {
+        self.resolve_with_option(path, IpnsOption::default()).await
+    }
+
+    /// Resolves a ipns path to an ipld path, looking up the records not found locally as given by
+    /// the option.
+    // TODO: Maybe implement a check to the dht store itself too?
+    pub async fn resolve_with_option(
+        &self,
+        path: &IpfsPath,
+        option: IpnsOption,
+    ) -> Result<IpfsPath, Error> {
         let path = path.to_owned();
         match path.root() {
             PathRoot::Ipld(_) => Ok(path),
@@ -69,6 +100,11 @@ impl Ipns {
                 let repo = self.ipfs.repo();
                 let datastore = repo.data_store();
 
+                if let IpnsOption::PubSub = option {
+                    let cached = datastore.contains(mb.as_bytes()).await.unwrap_or_default();
+                    pubsub::subscribe(&self.ipfs, *peer, &mb, !cached).await?;
+                }
+
                 if let Ok(Some(data)) = datastore.get(mb.as_bytes()).await {
                     if let Ok(path) = rust_ipns::Record::decode(data).and_then(|record| {
                         //Although stored locally, we should verify the record anyway
@@ -88,6 +124,10 @@ impl Ipns {
                     }
                 }
 
+                if !matches!(option, IpnsOption::DHT) {
+                    anyhow::bail!("No records found")
+                }
+
                 let stream = self.ipfs.dht_get(mb).await?;
 
                 //TODO: Implement configurable timeout
@@ -133,77 +173,407 @@ impl Ipns {
         }
     }
 
-    pub async fn publish(
+    /// Publishes the path under the name of the key, or of the node if `key` is `None`.
+    pub fn publish(
         &self,
         key: Option<&str>,
         path: &IpfsPath,
         option: Option<IpnsOption>,
-    ) -> Result<IpfsPath, Error> {
-        use libipld::Cid;
-        use libp2p::kad::Quorum;
-        use std::str::FromStr;
+    ) -> IpnsPublish {
+        IpnsPublish {
+            ipfs: self.ipfs.clone(),
+            key: key.map(ToString::to_string),
+            path: path.clone(),
+            option,
+            lifetime: None,
+            ttl: None,
+            sequence: None,
+            span: self.ipfs.span.clone(),
+        }
+    }
+}
 
-        let keypair = match key {
-            Some(key) => self.ipfs.keystore().get_keypair(key).await?,
-            None => self.ipfs.keypair().clone(),
-        };
+/// Publishing options of a name, kept to republish the record with the same options.
+#[derive(Serialize, Deserialize)]
+struct Published {
+    /// Lifetime of the record in milliseconds
+    lifetime: u64,
+    option: IpnsOption,
+}
 
-        let peer_id = keypair.public().to_peer_id();
+/// Publishes an ipns record, created with [`Ipns::publish`].
+#[must_use]
+pub struct IpnsPublish {
+    ipfs: Ipfs,
+    key: Option<String>,
+    path: IpfsPath,
+    option: Option<IpnsOption>,
+    lifetime: Option<Duration>,
+    ttl: Option<Duration>,
+    sequence: Option<u64>,
+    span: Span,
+}
 
-        let hash: libipld::multihash::Multihash =
-            libipld::multihash::Multihash::from_bytes(&peer_id.to_bytes())?;
+impl IpnsPublish {
+    /// Time for which the record is valid. Defaults to 48 hours.
+    pub fn lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = Some(lifetime);
+        self
+    }
 
-        let cid = Cid::new_v1(0x72, hash);
+    /// Time for which resolvers may cache the record. Defaults to 1 minute.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
 
-        let mb = format!(
-            "/ipns/{}",
-            cid.to_string_of_base(libipld::multibase::Base::Base36Lower)?
-        );
+    /// Sequence of the record, which has to be greater than the sequence of the record published
+    /// previously. Defaults to the next sequence.
+    pub fn sequence(mut self, sequence: u64) -> Self {
+        self.sequence = Some(sequence);
+        self
+    }
+
+    /// Set tracing span
+    pub fn span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+}
+
+impl std::future::IntoFuture for IpnsPublish {
+    type Output = Result<IpfsPath, Error>;
+
+    type IntoFuture = BoxFuture<'static, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        let span = self.span;
+        async move {
+            use libp2p::kad::Quorum;
+            use std::str::FromStr;
+
+            let ipfs = self.ipfs;
+            let path = self.path;
+
+            let keypair = match self.key.as_deref() {
+                Some(key) => ipfs.keystore().get_keypair(key).await?,
+                None => ipfs.keypair().clone(),
+            };
+
+            let peer_id = keypair.public().to_peer_id();
 
-        let repo = self.ipfs.repo();
+            let mb = name_key(&peer_id)?;
 
-        let datastore = repo.data_store();
+            let repo = ipfs.repo();
 
-        let record_data = datastore.get(mb.as_bytes()).await.unwrap_or_default();
+            let datastore = repo.data_store();
 
-        let mut seq = 0;
+            // released once the record is stored, before publishing it
+            let guard = repo.inner.ipns_lock.lock().await;
 
-        if let Some(record) = record_data.as_ref() {
-            let record = rust_ipns::Record::decode(record)?;
-            //Although stored locally, we should verify the record anyway
-            record.verify(peer_id)?;
+            let record_data = datastore.get(mb.as_bytes()).await.unwrap_or_default();
 
-            let data = record.data()?;
+            let mut seq = 0;
 
-            let ipfs_path = IpfsPath::from_str(&String::from_utf8_lossy(data.value()))?;
+            if let Some(record) = record_data.as_ref() {
+                let record = rust_ipns::Record::decode(record)?;
+                //Although stored locally, we should verify the record anyway
+                record.verify(peer_id)?;
 
-            if ipfs_path.eq(path) {
-                return IpfsPath::from_str(&mb);
+                let data = record.data()?;
+
+                let ipfs_path = IpfsPath::from_str(&String::from_utf8_lossy(data.value()))?;
+
+                let options_set =
+                    self.lifetime.is_some() || self.ttl.is_some() || self.sequence.is_some();
+
+                if ipfs_path.eq(&path) && !options_set {
+                    return IpfsPath::from_str(&mb);
+                }
+
+                // inc req of the record
+                seq = record.sequence() + 1;
+            }
+
+            if let Some(sequence) = self.sequence {
+                anyhow::ensure!(
+                    sequence >= seq,
+                    "sequence has to be greater than {}",
+                    seq.saturating_sub(1)
+                );
+                seq = sequence;
             }
 
-            // inc req of the record
-            seq = record.sequence() + 1;
+            let lifetime = self.lifetime.unwrap_or(DEFAULT_LIFETIME);
+            let ttl = self.ttl.unwrap_or(DEFAULT_TTL);
+            let option = self.option.unwrap_or_default();
+
+            let path_bytes = path.to_string();
+
+            let record = rust_ipns::Record::new(
+                &keypair,
+                path_bytes.as_bytes(),
+                chrono::Duration::from_std(lifetime)?,
+                seq,
+                ttl.as_nanos().try_into()?,
+            )?;
+
+            let bytes = record.encode()?;
+
+            datastore.put(mb.as_bytes(), &bytes).await?;
+
+            let published = Published {
+                lifetime: lifetime.as_millis().try_into()?,
+                option,
+            };
+
+            datastore
+                .put(
+                    published_key(&mb).as_bytes(),
+                    &serde_json::to_vec(&published)?,
+                )
+                .await?;
+
+            drop(guard);
+
+            match option {
+                IpnsOption::DHT => ipfs.dht_put(&mb, bytes, Quorum::One).await?,
+                IpnsOption::PubSub => {
+                    pubsub::subscribe(&ipfs, peer_id, &mb, false).await?;
+                    // without subscribed peers the record is sent once they subscribe
+                    if let Err(e) = ipfs.pubsub_publish(pubsub::topic(&peer_id), bytes).await {
+                        debug!("ipns: record not published over pubsub: {e}");
+                    }
+                }
+                IpnsOption::Local => {}
+            };
+
+            IpfsPath::from_str(&mb)
+        }
+        .instrument(span)
+        .boxed()
+    }
+}
+
+/// Record due to be republished by the node task.
+pub(crate) struct Republish {
+    pub(crate) peer_id: PeerId,
+    pub(crate) option: IpnsOption,
+    pub(crate) record: Vec<u8>,
+}
+
+/// Re-signs the records published with the given keys once half of the lifetime they were
+/// published with has passed, extending their validity by the lifetime. Records which were not
+/// published locally are skipped, as are the records which cannot be read, after logging.
+///
+/// Returns the republished records along with the time until the next record is due, if any.
+pub(crate) async fn republish(
+    repo: &Repo,
+    keypairs: Vec<Keypair>,
+) -> (Vec<Republish>, Option<Duration>) {
+    let mut republished = Vec::new();
+    let mut next = None::<Duration>;
+
+    for keypair in keypairs {
+        let peer_id = keypair.public().to_peer_id();
+        match republish_record(repo, &keypair).await {
+            Ok(Some((record, due))) => {
+                republished.extend(record);
+                next = Some(next.map_or(due, |next| next.min(due)));
+            }
+            Ok(None) => {}
+            Err(e) => warn!("ipns: failed to republish the record of {peer_id}: {e}"),
         }
+    }
+
+    (republished, next)
+}
+
+/// Republishes the record of the key if it is due, returning the time until it is due next or
+/// `None` if there is no record published with the key.
+async fn republish_record(
+    repo: &Repo,
+    keypair: &Keypair,
+) -> Result<Option<(Option<Republish>, Duration)>, Error> {
+    let datastore = repo.data_store();
+    let peer_id = keypair.public().to_peer_id();
+    let mb = name_key(&peer_id)?;
+
+    // a record published meanwhile would be overwritten with the republished one
+    let _guard = repo.inner.ipns_lock.lock().await;
+
+    let Some(published) = datastore.get(published_key(&mb).as_bytes()).await? else {
+        return Ok(None);
+    };
+
+    let Some(data) = datastore.get(mb.as_bytes()).await? else {
+        return Ok(None);
+    };
+
+    let published: Published = serde_json::from_slice(&published)?;
+    let record = rust_ipns::Record::decode(data)?;
+    record.verify(peer_id)?;
+
+    let lifetime = Duration::from_millis(published.lifetime);
+    let remaining = record
+        .validity()?
+        .signed_duration_since(chrono::Utc::now())
+        .to_std()
+        .unwrap_or_default();
+
+    if remaining > lifetime / 2 {
+        return Ok(Some((None, remaining - lifetime / 2)));
+    }
+
+    // the value is unchanged, so is the sequence
+    let record = rust_ipns::Record::new(
+        keypair,
+        record.data()?.value(),
+        chrono::Duration::milliseconds(published.lifetime.try_into()?),
+        record.sequence(),
+        record.ttl(),
+    )?;
+
+    let bytes = record.encode()?;
+    datastore.put(mb.as_bytes(), &bytes).await?;
+
+    let republished = Republish {
+        peer_id,
+        option: published.option,
+        record: bytes,
+    };
+
+    Ok(Some((Some(republished), lifetime / 2)))
+}
 
-        let path_bytes = path.to_string();
+fn name_key(peer_id: &PeerId) -> Result<String, Error> {
+    let hash = libipld::multihash::Multihash::from_bytes(&peer_id.to_bytes())?;
+    let cid = Cid::new_v1(0x72, hash);
+    Ok(format!(
+        "/ipns/{}",
+        cid.to_string_of_base(libipld::multibase::Base::Base36Lower)?
+    ))
+}
 
-        let record = rust_ipns::Record::new(
-            &keypair,
-            path_bytes.as_bytes(),
-            chrono::Duration::try_hours(48).expect("shouldnt panic"),
-            seq,
-            60000,
-        )?;
+fn published_key(name_key: &str) -> String {
+    format!("/local{name_key}")
+}
 
-        let bytes = record.encode()?;
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use libp2p::identity::Keypair;
+
+    use super::{name_key, republish, IpnsOption};
+    use crate::{IpfsPath, Node};
+
+    #[tokio::test]
+    async fn publish_options() {
+        let node = Node::new("test_node").await;
+        let cid = node.put_dag(libipld::ipld!("name")).await.unwrap();
+
+        node.ipns()
+            .publish(None, &IpfsPath::from(cid), Some(IpnsOption::Local))
+            .lifetime(Duration::from_secs(60 * 60))
+            .ttl(Duration::from_secs(30))
+            .sequence(5)
+            .await
+            .unwrap();
+
+        let key = name_key(&node.id).unwrap();
+        let data = node.repo().data_store().get(key.as_bytes()).await.unwrap();
+        let record = rust_ipns::Record::decode(data.unwrap()).unwrap();
+
+        assert_eq!(record.sequence(), 5);
+        assert_eq!(record.ttl(), 30_000_000_000);
+        let lifetime = record
+            .validity()
+            .unwrap()
+            .signed_duration_since(chrono::Utc::now());
+        assert!(lifetime <= chrono::Duration::try_hours(1).unwrap());
+        assert!(lifetime > chrono::Duration::try_minutes(59).unwrap());
+
+        // the sequence cannot go backwards
+        assert!(node
+            .ipns()
+            .publish(None, &IpfsPath::from(cid), Some(IpnsOption::Local))
+            .sequence(5)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn republish_extends_validity() {
+        let node = Node::new("test_node").await;
+        let cid = node.put_dag(libipld::ipld!("name")).await.unwrap();
+
+        node.ipns()
+            .publish(None, &IpfsPath::from(cid), Some(IpnsOption::Local))
+            .lifetime(Duration::from_millis(200))
+            .await
+            .unwrap();
+
+        let key = name_key(&node.id).unwrap();
+        let datastore = node.repo().data_store();
+        let data = datastore.get(key.as_bytes()).await.unwrap().unwrap();
+        let published = rust_ipns::Record::decode(data).unwrap();
+
+        // not due before half of the lifetime has passed
+        let (republished, next) = republish(node.repo(), vec![node.keypair().clone()]).await;
+        assert!(republished.is_empty());
+        assert!(next.unwrap() <= Duration::from_millis(100));
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+
+        // keys without published records are skipped
+        let keypairs = vec![node.keypair().clone(), Keypair::generate_ed25519()];
+        let (republished, next) = republish(node.repo(), keypairs).await;
+
+        assert_eq!(republished.len(), 1);
+        assert_eq!(next, Some(Duration::from_millis(100)));
+        assert_eq!(republished[0].peer_id, node.id);
+        assert_eq!(republished[0].option, IpnsOption::Local);
+
+        let record = rust_ipns::Record::decode(&republished[0].record).unwrap();
+        record.verify(node.id).unwrap();
+        assert_eq!(record.sequence(), published.sequence());
+        assert_eq!(
+            record.data().unwrap().value(),
+            published.data().unwrap().value()
+        );
+        assert!(record.validity().unwrap() > published.validity().unwrap());
+
+        let stored = datastore.get(key.as_bytes()).await.unwrap().unwrap();
+        assert_eq!(stored, republished[0].record);
+    }
+
+    #[tokio::test]
+    async fn republish_skips_unreadable_records() {
+        let node = Node::new("test_node").await;
+        let cid = node.put_dag(libipld::ipld!("name")).await.unwrap();
+        let other = Keypair::generate_ed25519();
+        node.keystore()
+            .import_key(&other, Some("other"))
+            .await
+            .unwrap();
+
+        for key in [None, Some("other")] {
+            node.ipns()
+                .publish(key, &IpfsPath::from(cid), Some(IpnsOption::Local))
+                .lifetime(Duration::from_millis(1))
+                .await
+                .unwrap();
+        }
 
-        datastore.put(mb.as_bytes(), &bytes).await?;
+        let datastore = node.repo().data_store();
+        let key = name_key(&other.public().to_peer_id()).unwrap();
+        datastore.put(key.as_bytes(), b"garbage").await.unwrap();
 
-        match option.unwrap_or_default() {
-            IpnsOption::DHT => self.ipfs.dht_put(&mb, bytes, Quorum::One).await?,
-            IpnsOption::Local => {}
-        };
+        let keypairs = vec![other, node.keypair().clone()];
+        let (republished, _) = republish(node.repo(), keypairs).await;
 
-        IpfsPath::from_str(&mb)
+        assert_eq!(republished.len(), 1);
+        assert_eq!(republished[0].peer_id, node.id);
     }
 }
@@ -0,0 +1,227 @@
+//! IPNS over PubSub, compatible with the pubsub value store used by kubo with
+//! `--enable-namesys-pubsub`.
+//!
+//! Records are published as is on the `/record/<base64url of the routing key>` topic. The
+//! subscribers keep the highest-sequence valid record, which is stored under the same datastore
+//! key as the records published locally, and publish it again periodically and whenever a peer
+//! subscribes to the topic so that peers subscribing later learn about the name.
+
+use std::time::Duration;
+
+use base64::Engine;
+use futures::channel::mpsc::Sender;
+use futures::channel::oneshot;
+use futures::{SinkExt, StreamExt};
+use futures_timeout::TimeoutExt;
+use libp2p::PeerId;
+use rust_ipns::Record;
+
+use crate::error::Error;
+use crate::repo::Repo;
+use crate::{Ipfs, IpfsEvent, PubsubEvent};
+
+/// Interval at which the known record is published again.
+const REBROADCAST_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Time to wait for a record after subscribing when nothing has been cached yet.
+const FIRST_RECORD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Returns the topic of the name, named after the routing key `/ipns/<peer id bytes>`.
+pub(crate) fn topic(peer_id: &PeerId) -> String {
+    let key = [b"/ipns/".as_slice(), &peer_id.to_bytes()].concat();
+    format!(
+        "/record/{}",
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(key)
+    )
+}
+
+/// Subscribes to the topic of the name unless already subscribed, caching the records received
+/// under `key` in the datastore for as long as the node runs. If `wait` is set, waits for the first
+/// record after subscribing.
+pub(super) async fn subscribe(
+    ipfs: &Ipfs,
+    peer_id: PeerId,
+    key: &str,
+    wait: bool,
+) -> Result<(), Error> {
+    let topic = topic(&peer_id);
+
+    if ipfs.pubsub_subscribed().await?.contains(&topic) {
+        return Ok(());
+    }
+
+    let events = ipfs.pubsub_events(topic.clone()).await?;
+    let mut stream = ipfs.pubsub_subscribe(topic.clone()).await?;
+
+    let repo = ipfs.repo().clone();
+
+    if wait {
+        let first = async {
+            while let Some(message) = stream.next().await {
+                if store(&repo, peer_id, key, &message.data).await? {
+                    break;
+                }
+            }
+            Ok::<_, Error>(())
+        }
+        .timeout(FIRST_RECORD_TIMEOUT)
+        .await;
+
+        if let Ok(Err(e)) = first {
+            return Err(e);
+        }
+    }
+
+    // only the channel to the task is kept so that the node stops once the facade is dropped
+    let to_task = ipfs.to_task.clone();
+    let key = key.to_string();
+
+    crate::rt::spawn(async move {
+        let mut events = events.fuse();
+        let mut rebroadcast = futures_timer::Delay::new(REBROADCAST_INTERVAL);
+
+        loop {
+            tokio::select! {
+                message = stream.next() => {
+                    let Some(message) = message else {
+                        break;
+                    };
+
+                    if let Err(e) = store(&repo, peer_id, &key, &message.data).await {
+                        warn!("ipns: failed to store record from pubsub: {e}");
+                    }
+                }
+                Some(event) = events.next() => {
+                    if matches!(event, PubsubEvent::Subscribe { .. }) {
+                        rebroadcast_record(&repo, &to_task, peer_id, &key, &topic).await;
+                    }
+                }
+                _ = &mut rebroadcast => {
+                    rebroadcast_record(&repo, &to_task, peer_id, &key, &topic).await;
+                    rebroadcast.reset(REBROADCAST_INTERVAL);
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Decodes and validates the record, storing it if it is newer than the one stored under `key`.
+/// Returns whether the record was stored.
+async fn store(repo: &Repo, peer_id: PeerId, key: &str, data: &[u8]) -> Result<bool, Error> {
+    let Some(record) = valid_record(peer_id, data) else {
+        return Ok(false);
+    };
+
+    let datastore = repo.data_store();
+
+    let current = datastore
+        .get(key.as_bytes())
+        .await?
+        .and_then(|data| valid_record(peer_id, &data));
+
+    if let Some(current) = current {
+        let newer = record.sequence() > current.sequence()
+            || (record.sequence() == current.sequence()
+                && record.validity()? > current.validity()?);
+
+        if !newer {
+            return Ok(false);
+        }
+    }
+
+    datastore.put(key.as_bytes(), data).await?;
+    Ok(true)
+}
+
+fn valid_record(peer_id: PeerId, data: &[u8]) -> Option<Record> {
+    let record = Record::decode(data).ok()?;
+    record.verify(peer_id).ok()?;
+    (record.validity().ok()? > chrono::Utc::now()).then_some(record)
+}
+
+async fn rebroadcast_record(
+    repo: &Repo,
+    to_task: &Sender<IpfsEvent>,
+    peer_id: PeerId,
+    key: &str,
+    topic: &str,
+) {
+    let Ok(Some(data)) = repo.data_store().get(key.as_bytes()).await else {
+        return;
+    };
+
+    if valid_record(peer_id, &data).is_none() {
+        return;
+    }
+
+    let (tx, rx) = oneshot::channel();
+
+    if to_task
+        .clone()
+        .send(IpfsEvent::PubsubPublish(topic.to_string(), data.into(), tx))
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    if let Ok(Ok(Err(e))) = rx.await {
+        debug!("ipns: failed to rebroadcast record: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::topic;
+    use crate::ipns::IpnsOption;
+    use crate::{Node, PeerId};
+
+    #[test]
+    fn topic_is_named_after_routing_key() {
+        let peer_id: PeerId = "12D3KooWLRPJAA5o6Z6umFNN5BUoHSnFG4ZUKVwjrmJ8oTXnpgxy"
+            .parse()
+            .unwrap();
+
+        // base64url of "/ipns/" followed by the peer id bytes
+        assert_eq!(
+            topic(&peer_id),
+            "/record/L2lwbnMvACQIARIgnY15Cwdbb5vbye0L7Zd0ZoFdxClMF18pUv6vIc0T7ZI"
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_over_pubsub() {
+        let publisher = Node::new("publisher").await;
+        let resolver = Node::new("resolver").await;
+
+        resolver.connect(publisher.addrs[0].clone()).await.unwrap();
+
+        let cid = publisher
+            .put_dag(libipld::ipld!("ipns over pubsub"))
+            .await
+            .unwrap();
+        let path = crate::IpfsPath::from(cid);
+
+        let name = publisher
+            .ipns()
+            .publish(None, &path, Some(IpnsOption::PubSub))
+            .await
+            .unwrap();
+
+        // the record is sent by the publisher once the resolver subscribes
+        let resolved = resolver
+            .ipns()
+            .resolve_with_option(&name, IpnsOption::PubSub)
+            .await
+            .unwrap();
+
+        assert_eq!(resolved, path);
+        assert!(resolver
+            .pubsub_subscribed()
+            .await
+            .unwrap()
+            .contains(&topic(&publisher.id)));
+    }
+}
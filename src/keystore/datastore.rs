@@ -0,0 +1,180 @@
+//! Key storage backed by the datastore of a repo. See [`DataStoreKeyStorage`] for more information.
+
+use anyhow::Error;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use zeroize::Zeroizing;
+
+use super::{decode_name, encode_name, seal, unseal, Key, KeyStorage};
+use crate::repo::Repo;
+
+const PREFIX: &str = "/keystore/";
+
+/// Key storage keeping the keys in the [`DataStore`](crate::repo::DataStore) of the repo, under
+/// `/keystore/` followed by the name encoded the same way as the file names of [`FsKeyStorage`].
+/// The repo has to be initialized before the keys can be accessed, which is done when starting the
+/// node it is given to.
+///
+/// If a passphrase is set, the keys are encrypted before being written.
+///
+/// [`FsKeyStorage`]: super::FsKeyStorage
+pub struct DataStoreKeyStorage {
+    repo: Repo,
+    passphrase: Option<Zeroizing<String>>,
+}
+
+impl DataStoreKeyStorage {
+    pub fn new(repo: &Repo) -> Self {
+        DataStoreKeyStorage {
+            repo: repo.clone(),
+            passphrase: None,
+        }
+    }
+
+    /// Encrypts the keys written from now on with the passphrase.
+    pub fn with_passphrase(mut self, passphrase: impl Into<String>) -> Self {
+        self.passphrase = Some(Zeroizing::new(passphrase.into()));
+        self
+    }
+}
+
+fn key(name: &str) -> Result<String, Error> {
+    Ok(format!("{PREFIX}{}", encode_name(name)?))
+}
+
+#[async_trait::async_trait]
+impl KeyStorage for DataStoreKeyStorage {
+    async fn set(&self, name: &str, key: &[u8]) -> Result<(), Error> {
+        let data = Zeroizing::new(seal(self.passphrase.as_ref(), key)?);
+        self.repo
+            .data_store()
+            .put(self::key(name)?.as_bytes(), &data)
+            .await
+    }
+
+    async fn get(&self, name: &str) -> Result<Key, Error> {
+        let data = self
+            .repo
+            .data_store()
+            .get(key(name)?.as_bytes())
+            .await?
+            .ok_or(anyhow::anyhow!("Key doesnt exist"))?;
+
+        unseal(self.passphrase.as_ref(), data)
+    }
+
+    async fn contains(&self, name: &str) -> Result<bool, Error> {
+        self.repo.data_store().contains(key(name)?.as_bytes()).await
+    }
+
+    async fn remove(&self, name: &str) -> Result<(), Error> {
+        let key = key(name)?;
+        let data_store = self.repo.data_store();
+
+        if !data_store.contains(key.as_bytes()).await? {
+            anyhow::bail!("Key doesnt exist");
+        }
+
+        data_store.remove(key.as_bytes()).await
+    }
+
+    async fn rename(&self, name: &str, new_name: &str) -> Result<(), Error> {
+        let key = key(name)?;
+        let new_key = self::key(new_name)?;
+        let data_store = self.repo.data_store();
+
+        if data_store.contains(new_key.as_bytes()).await? {
+            anyhow::bail!("{new_name} exist");
+        }
+
+        // the stored data is moved as is, without decrypting it
+        let data = data_store
+            .get(key.as_bytes())
+            .await?
+            .map(Zeroizing::new)
+            .ok_or(anyhow::anyhow!("Key doesnt exist"))?;
+
+        data_store.put(new_key.as_bytes(), &data).await?;
+        data_store.remove(key.as_bytes()).await
+    }
+
+    async fn list(&self) -> Result<BoxStream<'static, Key>, Error> {
+        let mut entries = self.repo.data_store().iter().await;
+        let mut keys = vec![];
+
+        while let Some((key, data)) = entries.next().await {
+            let Some(name) = std::str::from_utf8(&key)
+                .ok()
+                .and_then(|key| key.strip_prefix(PREFIX))
+                .and_then(decode_name)
+            else {
+                continue;
+            };
+
+            match unseal(self.passphrase.as_ref(), data) {
+                Ok(key) => keys.push(key),
+                Err(e) => warn!("failed to read key {name}: {e}"),
+            }
+        }
+
+        Ok(futures::stream::iter(keys).boxed())
+    }
+
+    async fn names(&self) -> Result<BoxStream<'static, String>, Error> {
+        let mut entries = self.repo.data_store().iter().await;
+        let mut names = vec![];
+
+        while let Some((key, _)) = entries.next().await {
+            let name = std::str::from_utf8(&key)
+                .ok()
+                .and_then(|key| key.strip_prefix(PREFIX))
+                .and_then(decode_name);
+            names.extend(name);
+        }
+
+        Ok(futures::stream::iter(names).boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::keystore::{DataStoreKeyStorage, KeyStorage, Keystore};
+    use crate::repo::Repo;
+
+    #[tokio::test]
+    async fn keys_are_stored_in_datastore() -> anyhow::Result<()> {
+        let repo = Repo::new_memory();
+        repo.init().await?;
+
+        let storage = DataStoreKeyStorage::new(&repo).with_passphrase("passphrase");
+        let keystore = Keystore::new(Arc::new(storage));
+
+        let pkey = keystore.generate_ed25519(Some("primary")).await?;
+        keystore.generate_ecdsa(Some("secondary")).await?;
+        keystore.rename("secondary", "other").await?;
+
+        assert!(
+            repo.data_store()
+                .contains(b"/keystore/key_obzgs3lboj4q")
+                .await?
+        );
+
+        let keystore = Keystore::new(Arc::new(
+            DataStoreKeyStorage::new(&repo).with_passphrase("passphrase"),
+        ));
+        assert_eq!(keystore.get_keypair("primary").await?.public(), pkey);
+        assert!(keystore.contains("other").await?);
+        assert!(!keystore.contains("secondary").await?);
+        assert_eq!(keystore.keypairs().await?.len(), 2);
+        assert_eq!(keystore.names().await?, vec!["other", "primary"]);
+
+        let storage = DataStoreKeyStorage::new(&repo);
+        assert!(storage.get("primary").await.is_err());
+        storage.remove("primary").await?;
+        assert!(storage.remove("primary").await.is_err());
+
+        Ok(())
+    }
+}
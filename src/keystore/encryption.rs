@@ -0,0 +1,167 @@
+//! Passphrase based encryption of the keys at rest.
+//!
+//! The encryption key is derived from the passphrase with argon2id and a random salt, and the key
+//! is sealed with XChaCha20-Poly1305. The envelope starts with a header holding the magic bytes,
+//! the version, the argon2 parameters and the salt, which is authenticated along with the
+//! ciphertext:
+//!
+//! ```text
+//! magic (8) | version (1) | m_cost (4) | t_cost (4) | p_cost (4) | salt (16) | nonce (24) | ciphertext
+//! ```
+//!
+//! Envelopes asking for higher argon2 costs than the defaults used when encrypting are refused.
+
+use anyhow::Error;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use super::Key;
+
+const MAGIC: &[u8; 8] = b"\0ipfskey";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + 1 + 3 * 4 + SALT_LEN;
+
+/// Returns true if the data is an envelope created by [`encrypt`].
+pub(crate) fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Encrypts the key with the passphrase.
+pub(crate) fn encrypt(passphrase: &[u8], key: &[u8]) -> Result<Vec<u8>, Error> {
+    let params = Params::default();
+
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let mut envelope = Vec::with_capacity(HEADER_LEN + NONCE_LEN + key.len() + 16);
+    envelope.extend_from_slice(MAGIC);
+    envelope.push(VERSION);
+    envelope.extend_from_slice(&params.m_cost().to_le_bytes());
+    envelope.extend_from_slice(&params.t_cost().to_le_bytes());
+    envelope.extend_from_slice(&params.p_cost().to_le_bytes());
+    envelope.extend_from_slice(&salt);
+
+    let cipher = cipher(passphrase, &salt, params)?;
+    let ciphertext = cipher
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: key,
+                aad: &envelope,
+            },
+        )
+        .map_err(|_| anyhow::anyhow!("failed to encrypt key"))?;
+
+    envelope.extend_from_slice(&nonce);
+    envelope.extend_from_slice(&ciphertext);
+
+    Ok(envelope)
+}
+
+/// Decrypts an envelope created by [`encrypt`] with the passphrase.
+pub(crate) fn decrypt(passphrase: &[u8], envelope: &[u8]) -> Result<Key, Error> {
+    if !is_encrypted(envelope) || envelope.len() < HEADER_LEN + NONCE_LEN {
+        anyhow::bail!("invalid encrypted key");
+    }
+
+    let (header, rest) = envelope.split_at(HEADER_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let version = header[MAGIC.len()];
+    if version != VERSION {
+        anyhow::bail!("unsupported encrypted key version {version}");
+    }
+
+    let read_u32 = |at: usize| {
+        let start = MAGIC.len() + 1 + at * 4;
+        u32::from_le_bytes(header[start..start + 4].try_into().expect("4 bytes"))
+    };
+
+    let (m_cost, t_cost, p_cost) = (read_u32(0), read_u32(1), read_u32(2));
+
+    // the parameters are only authenticated after deriving the key with them, so an envelope
+    // asking for more than the writer uses could exhaust the memory or the cpu
+    if m_cost > Params::DEFAULT_M_COST
+        || t_cost > Params::DEFAULT_T_COST
+        || p_cost > Params::DEFAULT_P_COST
+    {
+        anyhow::bail!("encrypted key parameters exceed the supported costs");
+    }
+
+    let params = Params::new(m_cost, t_cost, p_cost, None)
+        .map_err(|e| anyhow::anyhow!("invalid encrypted key parameters: {e}"))?;
+    let salt = &header[HEADER_LEN - SALT_LEN..];
+
+    let cipher = cipher(passphrase, salt, params)?;
+    let key = cipher
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| anyhow::anyhow!("failed to decrypt key: invalid passphrase"))?;
+
+    Ok(key.into())
+}
+
+fn cipher(passphrase: &[u8], salt: &[u8], params: Params) -> Result<XChaCha20Poly1305, Error> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase, salt, key.as_mut())
+        .map_err(|e| anyhow::anyhow!("failed to derive key: {e}"))?;
+    Ok(XChaCha20Poly1305::new(key.as_ref().into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decrypt, encrypt, is_encrypted};
+
+    #[test]
+    fn round_trip() {
+        let envelope = encrypt(b"passphrase", b"secret key").unwrap();
+
+        assert!(is_encrypted(&envelope));
+        assert!(!envelope.windows(10).any(|w| w == b"secret key"));
+
+        let key = decrypt(b"passphrase", &envelope).unwrap();
+        assert_eq!(key.as_ref(), b"secret key");
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let envelope = encrypt(b"passphrase", b"secret key").unwrap();
+        assert!(decrypt(b"other", &envelope).is_err());
+    }
+
+    #[test]
+    fn costs_are_capped() {
+        let envelope = encrypt(b"passphrase", b"secret key").unwrap();
+
+        // m_cost, t_cost and p_cost in turn
+        for at in [9, 13, 17] {
+            let mut envelope = envelope.clone();
+            envelope[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+            let Err(err) = decrypt(b"passphrase", &envelope) else {
+                panic!("decrypted with the costs raised");
+            };
+            assert!(err.to_string().contains("exceed"), "{err}");
+        }
+    }
+
+    #[test]
+    fn tampered_header() {
+        let mut envelope = encrypt(b"passphrase", b"secret key").unwrap();
+        // part of the salt
+        envelope[30] ^= 1;
+        assert!(decrypt(b"passphrase", &envelope).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+//! Filesystem backed key storage. See [`FsKeyStorage`] for more information.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Error;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use zeroize::Zeroizing;
+
+use super::{decode_name, encode_name, seal, unseal, Key, KeyStorage};
+
+/// Key storage keeping one file per key in a directory, named the same way as in the keystore of
+/// kubo: `key_` followed by the unpadded lowercase base32 of the name. The files contain the
+/// protobuf encoding of the keypair, so a kubo keystore can be used as is.
+///
+/// If a passphrase is set, the keys are encrypted before being written, in which case the
+/// directory can no longer be used by kubo.
+pub struct FsKeyStorage {
+    path: PathBuf,
+    passphrase: Option<Zeroizing<String>>,
+    /// Serializes the modifications so that renaming cannot replace a key being written.
+    lock: Mutex<()>,
+}
+
+impl FsKeyStorage {
+    /// Opens the key storage in the directory, creating it if needed.
+    pub async fn new(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+
+        if !path.is_dir() {
+            fs::create_dir_all(&path).await?;
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&path, std::fs::Permissions::from_mode(0o700)).await?;
+            }
+        }
+
+        Ok(FsKeyStorage {
+            path,
+            passphrase: None,
+            lock: Mutex::default(),
+        })
+    }
+
+    /// Encrypts the keys written from now on with the passphrase.
+    pub fn with_passphrase(mut self, passphrase: impl Into<String>) -> Self {
+        self.passphrase = Some(Zeroizing::new(passphrase.into()));
+        self
+    }
+
+    fn key_path(&self, name: &str) -> Result<PathBuf, Error> {
+        Ok(self.path.join(encode_name(name)?))
+    }
+}
+
+#[async_trait::async_trait]
+impl KeyStorage for FsKeyStorage {
+    async fn set(&self, name: &str, key: &[u8]) -> Result<(), Error> {
+        let path = self.key_path(name)?;
+        let data = Zeroizing::new(seal(self.passphrase.as_ref(), key)?);
+
+        let _guard = self.lock.lock().await;
+        write_through_tempfile(&path, &data).await?;
+        Ok(())
+    }
+
+    async fn get(&self, name: &str) -> Result<Key, Error> {
+        let path = self.key_path(name)?;
+
+        let data = match fs::read(path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                anyhow::bail!("Key doesnt exist")
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        unseal(self.passphrase.as_ref(), data)
+    }
+
+    async fn contains(&self, name: &str) -> Result<bool, Error> {
+        let path = self.key_path(name)?;
+        Ok(fs::try_exists(path).await?)
+    }
+
+    async fn remove(&self, name: &str) -> Result<(), Error> {
+        let path = self.key_path(name)?;
+
+        let _guard = self.lock.lock().await;
+        match fs::remove_file(path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                anyhow::bail!("Key doesnt exist")
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn rename(&self, name: &str, new_name: &str) -> Result<(), Error> {
+        let path = self.key_path(name)?;
+        let new_path = self.key_path(new_name)?;
+
+        let _guard = self.lock.lock().await;
+
+        if fs::try_exists(&new_path).await? {
+            anyhow::bail!("{new_name} exist");
+        }
+
+        match fs::rename(path, new_path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                anyhow::bail!("Key doesnt exist")
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list(&self) -> Result<BoxStream<'static, Key>, Error> {
+        let mut entries = fs::read_dir(&self.path).await?;
+        let mut keys = vec![];
+
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name();
+            let Some(name) = file_name.to_str().and_then(decode_name) else {
+                continue;
+            };
+
+            let data = match fs::read(entry.path()).await {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("failed to read key {name}: {e}");
+                    continue;
+                }
+            };
+
+            match unseal(self.passphrase.as_ref(), data) {
+                Ok(key) => keys.push(key),
+                Err(e) => warn!("failed to read key {name}: {e}"),
+            }
+        }
+
+        Ok(futures::stream::iter(keys).boxed())
+    }
+
+    async fn names(&self) -> Result<BoxStream<'static, String>, Error> {
+        let mut entries = fs::read_dir(&self.path).await?;
+        let mut names = vec![];
+
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(name) = entry.file_name().to_str().and_then(decode_name) {
+                names.push(name);
+            }
+        }
+
+        Ok(futures::stream::iter(names).boxed())
+    }
+}
+
+/// Writes the data to a temporary file readable only by the owner, which is then moved in place so
+/// that a key is never left partially written.
+async fn write_through_tempfile(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let temp_path = path.with_extension("tmp");
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let result = async {
+        let mut file = options.open(&temp_path).await?;
+        file.write_all(data).await?;
+        file.sync_all().await?;
+        fs::rename(&temp_path, path).await
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path).await;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::keystore::{FsKeyStorage, KeyStorage, Keystore};
+
+    #[tokio::test]
+    async fn keys_persist_across_reopen() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let keystore = Keystore::new(Arc::new(FsKeyStorage::new(dir.path()).await?));
+        let pkey = keystore.generate_ed25519(Some("primary")).await?;
+        keystore.generate_secp256k1(Some("secondary")).await?;
+        keystore.rename("secondary", "other").await?;
+
+        // named the same way as kubo
+        assert!(dir.path().join("key_obzgs3lboj4q").is_file());
+
+        let keystore = Keystore::new(Arc::new(FsKeyStorage::new(dir.path()).await?));
+        assert_eq!(keystore.get_keypair("primary").await?.public(), pkey);
+        assert!(keystore.contains("other").await?);
+        assert!(!keystore.contains("secondary").await?);
+        assert_eq!(keystore.keypairs().await?.len(), 2);
+        assert_eq!(keystore.names().await?, vec!["other", "primary"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn encrypted_keys() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let storage = FsKeyStorage::new(dir.path())
+            .await?
+            .with_passphrase("passphrase");
+        let keystore = Keystore::new(Arc::new(storage));
+        let pkey = keystore.generate_ed25519(Some("primary")).await?;
+
+        let keypair = keystore.get_keypair("primary").await?.try_into_ed25519()?;
+        let secret = keypair.secret();
+        let data = std::fs::read(dir.path().join("key_obzgs3lboj4q"))?;
+        assert!(!data.windows(32).any(|w| w == secret.as_ref()));
+
+        let storage = FsKeyStorage::new(dir.path())
+            .await?
+            .with_passphrase("other");
+        assert!(storage.get("primary").await.is_err());
+        assert_eq!(storage.len().await?, 0);
+
+        let storage = FsKeyStorage::new(dir.path()).await?;
+        assert!(storage.get("primary").await.is_err());
+
+        let storage = FsKeyStorage::new(dir.path())
+            .await?
+            .with_passphrase("passphrase");
+        let keystore = Keystore::new(Arc::new(storage));
+        assert_eq!(keystore.get_keypair("primary").await?.public(), pkey);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn missing_keys() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let storage = FsKeyStorage::new(dir.path()).await?;
+
+        assert!(storage.get("missing").await.is_err());
+        assert!(storage.remove("missing").await.is_err());
+        assert!(storage.rename("missing", "other").await.is_err());
+        assert!(storage.set("a/b", b"key").await.is_err());
+
+        Ok(())
+    }
+}
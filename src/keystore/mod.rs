@@ -1,3 +1,9 @@
+//! Storage of the named keys used to publish IPNS records, see [`Keystore`].
+//!
+//! The keys are kept in memory by default, or stored on disk with [`FsKeyStorage`] or in the
+//! [`DataStore`](crate::repo::DataStore) of a repo with [`DataStoreKeyStorage`], optionally
+//! encrypted with a passphrase.
+
 use std::{
     collections::{btree_map::Entry, BTreeMap},
     sync::Arc,
@@ -5,9 +11,24 @@ use std::{
 
 use anyhow::Error;
 use futures::{stream::BoxStream, StreamExt};
+use libipld::multibase::Base;
 use libp2p::identity::{Keypair, PublicKey};
 use tokio::sync::Mutex;
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
+
+mod datastore;
+mod encryption;
+mod format;
+#[cfg(not(target_arch = "wasm32"))]
+mod fs;
+
+pub use datastore::DataStoreKeyStorage;
+pub use format::KeyFormat;
+#[cfg(not(target_arch = "wasm32"))]
+pub use fs::FsKeyStorage;
+
+/// Prefix of the encoded key names, which is also the prefix of the file names used by kubo.
+const NAME_PREFIX: &str = "key_";
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum KeyType {
@@ -51,6 +72,18 @@ impl From<&[u8]> for Key {
     }
 }
 
+/// Where the node keeps the keys of its [`Keystore`], selected with
+/// [`IpfsOptions::keystore_type`](crate::IpfsOptions::keystore_type).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeystoreType {
+    /// In a [`FsKeyStorage`] in the `keystore` directory of the repo for
+    /// [`StorageType::Disk`](crate::StorageType::Disk), and in memory otherwise
+    #[default]
+    Repo,
+    /// In the keystore given with [`IpfsOptions::keystore`](crate::IpfsOptions::keystore)
+    Custom,
+}
+
 #[derive(Clone)]
 pub struct Keystore {
     storage: Arc<dyn KeyStorage>,
@@ -95,6 +128,33 @@ impl Keystore {
         Ok(public_key)
     }
 
+    /// Import a key encoded in the given [`KeyFormat`], such as one exported with
+    /// [`Keystore::export_key`].
+    /// If `name` is not supplied, the [`crate::PeerId`] will be the used as the name by default
+    pub async fn import_encoded_key(
+        &self,
+        data: &[u8],
+        format: KeyFormat<'_>,
+        name: Option<&str>,
+    ) -> Result<PublicKey, Error> {
+        let (key, keypair) = format::decode(data, format)?;
+        let public_key = keypair.public();
+
+        let peer_id = public_key.to_peer_id().to_string();
+
+        let name = name.unwrap_or(peer_id.as_str());
+
+        self.storage.set(name, key.as_ref()).await?;
+
+        Ok(public_key)
+    }
+
+    /// Export a key stored in the [`Keystore`] in the given [`KeyFormat`]
+    pub async fn export_key(&self, name: &str, format: KeyFormat<'_>) -> Result<Key, Error> {
+        let key = self.storage.get(name).await?;
+        format::encode(key.as_ref(), format)
+    }
+
     /// Generate a Ed25519 Keypair
     /// If `name` is not supplied, the [`crate::PeerId`] will be the used as the name by default
     pub async fn generate_ed25519(&self, name: Option<&str>) -> Result<PublicKey, Error> {
@@ -153,6 +213,41 @@ impl Keystore {
         self.storage.rename(name, new_name).await
     }
 
+    /// Remove a key stored in [`Keystore`]
+    pub async fn remove(&self, name: &str) -> Result<(), Error> {
+        self.storage.remove(name).await
+    }
+
+    /// Returns the names and public keys of the keys stored in the [`Keystore`], ordered by name.
+    /// The keys that cannot be read are skipped.
+    pub async fn list(&self) -> Result<Vec<(String, PublicKey)>, Error> {
+        let mut keys = vec![];
+        for name in self.names().await? {
+            match self.get_keypair(&name).await {
+                Ok(keypair) => keys.push((name, keypair.public())),
+                Err(e) => warn!("failed to read key {name}: {e}"),
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Returns the names of the keys stored in the [`Keystore`], in order
+    pub async fn names(&self) -> Result<Vec<String>, Error> {
+        let mut names = self.storage.names().await?.collect::<Vec<_>>().await;
+        names.sort();
+        Ok(names)
+    }
+
+    /// Returns the keypairs stored in the [`Keystore`]
+    pub(crate) async fn keypairs(&self) -> Result<Vec<Keypair>, Error> {
+        let keys = self.storage.list().await?;
+        let keypairs = keys
+            .filter_map(|key| async move { Keypair::from_protobuf_encoding(key.as_ref()).ok() })
+            .collect()
+            .await;
+        Ok(keypairs)
+    }
+
     /// Check to determine if a the [`Keystore`] contains a key
     pub async fn contains(&self, name: &str) -> Result<bool, Error> {
         self.storage.contains(name).await
@@ -167,10 +262,18 @@ pub trait KeyStorage: Sync + Send + 'static {
     async fn remove(&self, name: &str) -> Result<(), Error>;
     async fn rename(&self, name: &str, new_name: &str) -> Result<(), Error>;
     async fn list(&self) -> Result<BoxStream<'static, Key>, Error>;
+    /// Returns the names of the stored keys. Storages which cannot list the names of their keys
+    /// return an error, which is the default.
+    async fn names(&self) -> Result<BoxStream<'static, String>, Error> {
+        anyhow::bail!("listing the names of the keys is not supported")
+    }
     async fn len(&self) -> Result<usize, Error> {
         let amount = self.list().await?.count().await;
         Ok(amount)
     }
+    async fn is_empty(&self) -> Result<bool, Error> {
+        Ok(self.len().await? == 0)
+    }
 }
 
 #[derive(Default)]
@@ -237,11 +340,72 @@ impl KeyStorage for MemoryKeyStorage {
 
         Ok(stream.boxed())
     }
+
+    async fn names(&self) -> Result<BoxStream<'static, String>, Error> {
+        let names = self.inner.lock().await.keys().cloned().collect::<Vec<_>>();
+        Ok(futures::stream::iter(names).boxed())
+    }
+}
+
+/// Checks the name of a key the same way as kubo does.
+fn validate_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() {
+        anyhow::bail!("key names must be at least one character");
+    }
+
+    if name.contains('/') {
+        anyhow::bail!("key names may not contain slashes");
+    }
+
+    if name.starts_with('.') {
+        anyhow::bail!("key names may not begin with a period");
+    }
+
+    Ok(())
+}
+
+/// Encodes the name as `key_` followed by the unpadded lowercase base32 of the name.
+fn encode_name(name: &str) -> Result<String, Error> {
+    validate_name(name)?;
+    Ok(format!(
+        "{NAME_PREFIX}{}",
+        Base::Base32Lower.encode(name.as_bytes())
+    ))
+}
+
+/// Decodes a name encoded with [`encode_name`].
+fn decode_name(encoded: &str) -> Option<String> {
+    let encoded = encoded.strip_prefix(NAME_PREFIX)?;
+    let name = Base::Base32Lower.decode(encoded).ok()?;
+    String::from_utf8(name).ok()
+}
+
+/// Encrypts the key if a passphrase is set.
+fn seal(passphrase: Option<&Zeroizing<String>>, key: &[u8]) -> Result<Vec<u8>, Error> {
+    match passphrase {
+        Some(passphrase) => encryption::encrypt(passphrase.as_bytes(), key),
+        None => Ok(key.to_vec()),
+    }
+}
+
+/// Decrypts the stored key if it was encrypted. Keys stored without encryption are returned as is
+/// so that the keys stored before setting a passphrase remain readable.
+fn unseal(passphrase: Option<&Zeroizing<String>>, data: Vec<u8>) -> Result<Key, Error> {
+    let data = Key::from(data);
+
+    if !encryption::is_encrypted(data.as_ref()) {
+        return Ok(data);
+    }
+
+    match passphrase {
+        Some(passphrase) => encryption::decrypt(passphrase.as_bytes(), data.as_ref()),
+        None => anyhow::bail!("key is encrypted but no passphrase was set"),
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::keystore::Keystore;
+    use crate::keystore::{decode_name, encode_name, KeyFormat, Keystore};
 
     #[tokio::test]
     async fn keystore_with_peerid() -> anyhow::Result<()> {
@@ -310,4 +474,72 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn kubo_compatible_names() {
+        // file name used by kubo for a key named "test"
+        assert_eq!(encode_name("test").unwrap(), "key_orsxg5a");
+        assert_eq!(decode_name("key_orsxg5a").as_deref(), Some("test"));
+
+        assert!(encode_name("").is_err());
+        assert!(encode_name("a/b").is_err());
+        assert!(encode_name(".hidden").is_err());
+    }
+
+    #[tokio::test]
+    async fn keystore_remove_and_list() -> anyhow::Result<()> {
+        let keystore = Keystore::in_memory();
+        let pkey1 = keystore.generate_ed25519(Some("primary")).await?;
+        let pkey2 = keystore.generate_ecdsa(Some("secondary")).await?;
+
+        assert_eq!(
+            keystore.list().await?,
+            vec![
+                ("primary".to_string(), pkey1),
+                ("secondary".to_string(), pkey2.clone())
+            ]
+        );
+
+        assert_eq!(keystore.names().await?, vec!["primary", "secondary"]);
+
+        keystore.remove("primary").await?;
+        assert!(keystore.remove("primary").await.is_err());
+        assert_eq!(
+            keystore.list().await?,
+            vec![("secondary".to_string(), pkey2)]
+        );
+        assert_eq!(keystore.names().await?, vec!["secondary"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keystore_export_import() -> anyhow::Result<()> {
+        let keystore = Keystore::in_memory();
+        let other = Keystore::in_memory();
+        let pkey = keystore.generate_ed25519(Some("primary")).await?;
+
+        for format in [
+            KeyFormat::Protobuf,
+            KeyFormat::Pkcs8Pem,
+            KeyFormat::Encrypted("passphrase"),
+        ] {
+            let exported = keystore.export_key("primary", format).await?;
+            let imported = other
+                .import_encoded_key(exported.as_ref(), format, Some("imported"))
+                .await?;
+            assert_eq!(imported, pkey);
+            assert_eq!(other.get_keypair("imported").await?.public(), pkey);
+        }
+
+        let exported = keystore
+            .export_key("primary", KeyFormat::Encrypted("passphrase"))
+            .await?;
+        assert!(other
+            .import_encoded_key(exported.as_ref(), KeyFormat::Encrypted("other"), None)
+            .await
+            .is_err());
+
+        Ok(())
+    }
 }
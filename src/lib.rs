@@ -22,14 +22,20 @@
 // the docs better.
 //#![allow(private_intra_doc_links)]
 
+pub mod car;
 pub mod config;
 pub mod dag;
 pub mod error;
+#[cfg(all(feature = "gateway", not(target_arch = "wasm32")))]
+pub mod gateway;
 pub mod ipns;
-mod keystore;
+pub mod keystore;
+pub mod mfs;
 pub mod p2p;
 pub mod path;
 pub mod refs;
+#[cfg(all(feature = "remote_pinning", not(target_arch = "wasm32")))]
+pub mod remote_pin;
 pub mod repo;
 pub(crate) mod rt;
 mod task;
@@ -63,12 +69,14 @@ use p2p::{
     RelayConfig, SwarmConfig, TransportConfig,
 };
 use repo::{
-    BlockStore, DataStore, GCConfig, GCTrigger, Lock, RepoFetch, RepoInsertPin, RepoRemovePin,
+    BlockStore, DataStore, GCConfig, GCTrigger, Lock, RepoFetch, RepoGarbageCollect, RepoInsertPin,
+    RepoRemovePin, RepoVerify,
 };
 
 use tokio_util::sync::{CancellationToken, DropGuard};
 use tracing::Span;
 use tracing_futures::Instrument;
+use web_time::SystemTime;
 
 use unixfs::UnixfsGet;
 use unixfs::{AddOpt, IpfsUnixfs, UnixfsAdd, UnixfsCat, UnixfsLs};
@@ -85,6 +93,7 @@ use std::{
 use self::{
     dag::IpldDag,
     ipns::Ipns,
+    mfs::Mfs,
     p2p::{create_swarm, TSwarm},
     repo::Repo,
 };
@@ -96,9 +105,13 @@ pub use self::{
     p2p::BehaviourEvent,
     p2p::KadResult,
     path::IpfsPath,
-    repo::{PinKind, PinMode},
+    repo::{FileRef, FilestoreEntry, FilestoreStatus, PinKind, PinMetadata, PinMode},
 };
 
+pub use self::repo::blockstore::compression::Compression;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::repo::{DiskBlockStore, DiskOptions};
+
 pub type Block = libipld::Block<libipld::DefaultParams>;
 
 use libipld::{Cid, Ipld};
@@ -186,6 +199,11 @@ pub struct IpfsOptions {
     /// existing repository.
     pub ipfs_path: StorageType,
 
+    /// Options of the disk storage, such as the backend of the block store, used with
+    /// [`StorageType::Disk`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub disk_options: repo::DiskOptions,
+
     /// Nodes used as bootstrap peers.
     pub bootstrap: Vec<Multiaddr>,
 
@@ -215,7 +233,8 @@ pub struct IpfsOptions {
     pub kad_configuration: Either<KadConfig, libp2p::kad::Config>,
 
     /// Kad Store Config
-    /// Note: Only supports MemoryStoreConfig at this time
+    /// Note: Limits of the records kept in memory, which also apply when the records are
+    /// persisted with [`KadStoreType::DataStore`](crate::p2p::KadStoreType)
     pub kad_store_config: KadStoreConfig,
 
     /// Ping Configuration
@@ -224,14 +243,31 @@ pub struct IpfsOptions {
     /// Address book configuration
     pub addr_config: AddressBookConfig,
 
+    /// Keystore holding the keys used to publish IPNS records, used with
+    /// [`KeystoreType::Custom`](keystore::KeystoreType::Custom).
     pub keystore: Keystore,
 
+    /// Where the keys of the keystore are kept
+    pub keystore_type: keystore::KeystoreType,
+
+    /// Remote pinning services available through [`Ipfs::remote_pins`], by name
+    #[cfg(all(feature = "remote_pinning", not(target_arch = "wasm32")))]
+    pub remote_pinning_services: HashMap<String, remote_pin::RemotePinningService>,
+
     /// Connection idle
     pub connection_idle: Duration,
 
     /// Repo Provider option
     pub provider: RepoProvider,
 
+    /// Reprovider configuration, used unless `provider` is [`RepoProvider::None`]
+    pub reprovider: ReproviderConfig,
+
+    /// Longest interval at which the ipns records published by the node and with the keys of the
+    /// [`Keystore`] are signed and published again, which is otherwise done once half of the
+    /// lifetime of a record has passed. `None` disables republishing.
+    pub ipns_republish_interval: Option<Duration>,
+
     /// The span for tracing purposes, `None` value is converted to `tracing::trace_span!("ipfs")`.
     ///
     /// All futures returned by `Ipfs`, background task actions and swarm actions are instrumented
@@ -275,14 +311,61 @@ pub enum RepoProvider {
     /// Provide pinned blocks
     Pinned,
 
-    /// Provide root blocks only
+    /// Provide root blocks of recursive pins only
     Roots,
 }
 
+/// Configuration of the reprovider, which announces the blocks selected by [`RepoProvider`] on
+/// start and then periodically, so that the provider records do not expire.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ReproviderConfig {
+    /// Time between the end of a run and the start of the next one
+    pub interval: Duration,
+
+    /// Amount of blocks announced concurrently
+    pub batch_size: usize,
+}
+
+impl Default for ReproviderConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(22 * 60 * 60),
+            batch_size: 64,
+        }
+    }
+}
+
+/// Progress of the reprovider
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReproviderStats {
+    /// Whether a run is in progress
+    pub running: bool,
+
+    /// Amount of blocks selected for the current or the last run
+    pub total: usize,
+
+    /// Amount of blocks announced during the current or the last run
+    pub provided: usize,
+
+    /// Amount of blocks which failed to be announced during the current or the last run
+    pub failed: usize,
+
+    /// Amount of completed runs
+    pub runs: u64,
+
+    /// Time at which the last completed run started
+    pub last_run: Option<SystemTime>,
+
+    /// Duration of the last completed run
+    pub last_run_duration: Option<Duration>,
+}
+
 impl Default for IpfsOptions {
     fn default() -> Self {
         Self {
             ipfs_path: StorageType::Memory,
+            #[cfg(not(target_arch = "wasm32"))]
+            disk_options: Default::default(),
             bootstrap: Default::default(),
             #[cfg(feature = "beetle_bitswap")]
             bitswap_config: Default::default(),
@@ -293,7 +376,12 @@ impl Default for IpfsOptions {
             identify_configuration: Default::default(),
             addr_config: Default::default(),
             provider: Default::default(),
+            reprovider: Default::default(),
+            ipns_republish_interval: Some(Duration::from_secs(4 * 60 * 60)),
             keystore: Keystore::in_memory(),
+            keystore_type: Default::default(),
+            #[cfg(all(feature = "remote_pinning", not(target_arch = "wasm32")))]
+            remote_pinning_services: Default::default(),
             connection_idle: Duration::from_secs(30),
             listening_addrs: vec![],
             transport_configuration: TransportConfig::default(),
@@ -332,6 +420,9 @@ pub struct Ipfs {
     repo: Repo,
     key: Keypair,
     keystore: Keystore,
+    #[cfg(all(feature = "remote_pinning", not(target_arch = "wasm32")))]
+    remote_pinning_services:
+        Arc<parking_lot::RwLock<HashMap<String, remote_pin::RemotePinningService>>>,
     identify_conf: IdentifyConfiguration,
     to_task: Sender<IpfsEvent>,
     record_key_validator: HashMap<String, Arc<dyn Fn(&str) -> anyhow::Result<Key> + Sync + Send>>,
@@ -394,6 +485,8 @@ enum IpfsEvent {
     ),
     GetProviders(Cid, Channel<Option<BoxStream<'static, PeerId>>>),
     Provide(Cid, Channel<ReceiverChannel<KadResult>>),
+    Reprovide(Channel<ReproviderStats>),
+    ReproviderStats(OneshotSender<ReproviderStats>),
     DhtMode(DhtMode, Channel<()>),
     DhtGet(Key, Channel<BoxStream<'static, Record>>),
     DhtPut(Key, Vec<u8>, Quorum, Channel<ReceiverChannel<KadResult>>),
@@ -767,6 +860,14 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void> + Send> UninitializedIpfs<C> {
         self
     }
 
+    /// Sets a path along with the options of the disk storage, such as the backend of the block
+    /// store
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_disk_storage<P: AsRef<Path>>(mut self, path: P, options: repo::DiskOptions) -> Self {
+        self.options.disk_options = options;
+        self.set_path(path)
+    }
+
     /// Set transport configuration
     pub fn set_transport_configuration(mut self, config: crate::p2p::TransportConfig) -> Self {
         self.options.transport_configuration = config;
@@ -817,6 +918,18 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void> + Send> UninitializedIpfs<C> {
         self
     }
 
+    /// Set reprovider configuration
+    pub fn set_reprovider_configuration(mut self, config: ReproviderConfig) -> Self {
+        self.options.reprovider = config;
+        self
+    }
+
+    /// Set longest interval at which the ipns records are republished, `None` disabling republishing
+    pub fn set_ipns_republish_interval(mut self, interval: impl Into<Option<Duration>>) -> Self {
+        self.options.ipns_republish_interval = interval.into();
+        self
+    }
+
     /// Set keypair
     pub fn set_keypair(mut self, keypair: &Keypair) -> Self {
         self.keys = Some(keypair.clone());
@@ -829,9 +942,23 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void> + Send> UninitializedIpfs<C> {
         self
     }
 
-    /// Set a keystore
+    /// Set a keystore, replacing the default one (see [`IpfsOptions::keystore_type`])
     pub fn set_keystore(mut self, keystore: &Keystore) -> Self {
         self.options.keystore = keystore.clone();
+        self.options.keystore_type = keystore::KeystoreType::Custom;
+        self
+    }
+
+    /// Register a remote pinning service under `name` (see [`Ipfs::remote_pins`])
+    #[cfg(all(feature = "remote_pinning", not(target_arch = "wasm32")))]
+    pub fn add_remote_pinning_service(
+        mut self,
+        name: impl Into<String>,
+        service: remote_pin::RemotePinningService,
+    ) -> Self {
+        self.options
+            .remote_pinning_services
+            .insert(name.into(), service);
         self
     }
 
@@ -910,15 +1037,16 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void> + Send> UninitializedIpfs<C> {
                 }
                 repo
             }
-            None => {
+            None => match &options.ipfs_path {
                 #[cfg(not(target_arch = "wasm32"))]
-                if let StorageType::Disk(path) = &options.ipfs_path {
+                StorageType::Disk(path) => {
                     if !path.is_dir() {
                         tokio::fs::create_dir_all(path).await?;
                     }
+                    Repo::new_fs_with(path, options.disk_options)
                 }
-                Repo::new(&mut options.ipfs_path)
-            }
+                _ => Repo::new(&mut options.ipfs_path),
+            },
         };
 
         repo.init().instrument(init_span.clone()).await?;
@@ -953,7 +1081,15 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void> + Send> UninitializedIpfs<C> {
         let (to_task, receiver) = channel::<IpfsEvent>(1);
         let id_conf = options.identify_configuration.clone();
 
-        let keystore = options.keystore.clone();
+        let keystore = match (options.keystore_type, &options.ipfs_path) {
+            (keystore::KeystoreType::Custom, _) => options.keystore.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            (keystore::KeystoreType::Repo, StorageType::Disk(path)) => {
+                let storage = keystore::FsKeyStorage::new(path.join("keystore")).await?;
+                Keystore::new(Arc::new(storage))
+            }
+            (keystore::KeystoreType::Repo, _) => Keystore::in_memory(),
+        };
 
         let ipfs = Ipfs {
             span: facade_span,
@@ -961,6 +1097,10 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void> + Send> UninitializedIpfs<C> {
             identify_conf: id_conf,
             key: keys.clone(),
             keystore,
+            #[cfg(all(feature = "remote_pinning", not(target_arch = "wasm32")))]
+            remote_pinning_services: Arc::new(parking_lot::RwLock::new(
+                options.remote_pinning_services.clone(),
+            )),
             to_task,
             record_key_validator,
             _guard,
@@ -968,25 +1108,18 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void> + Send> UninitializedIpfs<C> {
 
         //Note: If `All` or `Pinned` are used, we would have to auto adjust the amount of
         //      provider records by adding the amount of blocks to the config.
-        //TODO: Add persistent layer for kad store
-        let blocks = match options.provider {
-            RepoProvider::None => vec![],
-            RepoProvider::All => ipfs.repo.list_blocks().await.collect::<Vec<_>>().await,
-            RepoProvider::Pinned => {
-                ipfs.repo
-                    .list_pins(None)
-                    .await
-                    .filter_map(|result| async move { result.map(|(cid, _)| cid).ok() })
-                    .collect()
-                    .await
-            }
-            RepoProvider::Roots => {
-                //TODO: Scan blockstore for root unixfs blocks
-                warn!("RepoProvider::Roots is not implemented... ignoring...");
-                vec![]
-            }
+        let provider = match options.protocols.kad {
+            true => options.provider,
+            false => RepoProvider::None,
         };
 
+        let blocks = task::reprovider_keys(&ipfs.repo, provider)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("failed to list the blocks to provide: {e}");
+                vec![]
+            });
+
         let count = blocks.len();
 
         let store_config = &mut options.kad_store_config;
@@ -1044,7 +1177,6 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void> + Send> UninitializedIpfs<C> {
                                 break
                             },
                             _ = &mut interval => {
-                                let _g = repo.inner.gclock.write().await;
                                 tracing::debug!("preparing gc operation");
                                 let pinned = repo
                                     .list_pins(None)
@@ -1081,9 +1213,13 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void> + Send> UninitializedIpfs<C> {
 
                                 if cleanup {
                                     tracing::debug!("running cleanup of unpinned blocks");
-                                    let blocks = repo.cleanup().await.unwrap();
-                                    tracing::debug!(removed_blocks = blocks.len(), "blocks removed");
-                                    tracing::debug!("cleanup finished");
+                                    match repo.gc().await {
+                                        Ok(report) => {
+                                            tracing::debug!(removed_blocks = report.removed.len(), bytes_freed = report.bytes_freed, "blocks removed");
+                                            tracing::debug!("cleanup finished");
+                                        }
+                                        Err(e) => tracing::warn!("gc failed: {e}"),
+                                    }
                                 }
 
                                 interval.reset(time);
@@ -1108,21 +1244,14 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void> + Send> UninitializedIpfs<C> {
             };
         }
 
-        for block in blocks {
-            if let Some(kad) = fut.swarm.behaviour_mut().kademlia.as_mut() {
-                let key = Key::from(block.hash().to_bytes());
-                match kad.start_providing(key) {
-                    Ok(id) => {
-                        let (tx, _rx) = oneshot_channel();
-                        fut.kad_subscriptions.insert(id, tx);
-                    }
-                    Err(e) => match e {
-                        libp2p::kad::store::Error::MaxProvidedKeys => break,
-                        _ => unreachable!(),
-                    },
-                };
-            }
-        }
+        // the blocks listed above are announced in the first run of the reprovider
+        fut.reprovider = task::Reprovider::new(&ipfs.repo, provider, options.reprovider, blocks);
+        fut.republisher = task::Republisher::new(
+            &ipfs.repo,
+            &keys,
+            &ipfs.keystore,
+            options.ipns_republish_interval,
+        );
 
         rt::spawn({
             async move {
@@ -1152,6 +1281,12 @@ impl Ipfs {
         IpldDag::new(self.clone())
     }
 
+    /// Returns a [`Gateway`](gateway::Gateway) to serve the content over HTTP
+    #[cfg(all(feature = "gateway", not(target_arch = "wasm32")))]
+    pub fn gateway(&self) -> gateway::Gateway {
+        gateway::Gateway::new(self).span(self.span.clone())
+    }
+
     /// Return an [`Repo`] to access the internal repo of the node
     pub fn repo(&self) -> &Repo {
         &self.repo
@@ -1162,6 +1297,11 @@ impl Ipfs {
         IpfsUnixfs::new(self.clone())
     }
 
+    /// Returns an [`Mfs`] for mutable file system operations, similar to `ipfs files`
+    pub fn files(&self) -> Mfs {
+        Mfs::new(self.clone())
+    }
+
     /// Returns a [`Ipns`] for ipns operations
     pub fn ipns(&self) -> Ipns {
         Ipns::new(self.clone())
@@ -1189,12 +1329,18 @@ impl Ipfs {
             .await
     }
 
-    /// Cleans up of all unpinned blocks
-    /// Note: This will prevent writing operations in [`Repo`] until it finish clearing unpinned
-    ///       blocks.
-    pub async fn gc(&self) -> Result<Vec<Cid>, Error> {
-        let _g = self.repo.inner.gclock.write().await;
-        self.repo.cleanup().instrument(self.span.clone()).await
+    /// Cleans up the blocks which are not reachable from the pins, the mutable file system or the
+    /// root sets registered with [`Repo::register_root_set`]
+    /// Note: Writing operations in [`Repo`] are only blocked while removing each batch of blocks.
+    pub fn gc(&self) -> RepoGarbageCollect {
+        self.repo.gc().span(self.span.clone())
+    }
+
+    /// Verifies the stored blocks against their multihash and checks that the recursive pins have
+    /// every block reachable from them stored, optionally removing the corrupt blocks and fetching
+    /// them again from the network
+    pub fn verify(&self) -> RepoVerify {
+        self.repo.verify().span(self.span.clone())
     }
 
     /// Pins a given Cid recursively or directly (non-recursively).
@@ -1229,6 +1375,31 @@ impl Ipfs {
         self.repo().remove_pin(cid).span(self.span.clone())
     }
 
+    /// Pins `new` recursively in place of the recursive pin of `old`, carrying over its name and
+    /// metadata. Only the blocks of `new` missing locally are fetched, and the blocks shared by
+    /// both remain pinned throughout.
+    ///
+    /// The pins are replaced at once by the in-memory, sled, redb and flatfs pin stores. Pin stores
+    /// relying on the default [`repo::PinStore::update_recursive_pin`], such as the IndexedDB one,
+    /// insert the new pin before removing the old one instead, so both pins can be observed in
+    /// between, and remain if the update is interrupted.
+    pub async fn update_pin(&self, old: &Cid, new: &Cid) -> Result<(), Error> {
+        let span = debug_span!(parent: &self.span, "update_pin", old = %old, new = %new);
+        self.repo.update_pin(old, new).instrument(span).await
+    }
+
+    /// Returns the name, metadata and creation time of a direct or recursive pin.
+    pub async fn pin_metadata(&self, cid: &Cid) -> Result<Option<PinMetadata>, Error> {
+        let span = debug_span!(parent: &self.span, "pin_metadata", cid = %cid);
+        self.repo.pin_metadata(cid).instrument(span).await
+    }
+
+    /// Returns the pins with a name containing `name`.
+    pub async fn pins_by_name(&self, name: &str) -> Result<Vec<(Cid, PinMetadata)>, Error> {
+        let span = debug_span!(parent: &self.span, "pins_by_name", name);
+        self.repo.pins_by_name(name).instrument(span).await
+    }
+
     /// Checks whether a given block is pinned.
     ///
     /// Returns true if the block is pinned, false if not. See Crash unsafety notes for the false
@@ -1292,6 +1463,23 @@ impl Ipfs {
         self.dag().get_dag(path).span(self.span.clone())
     }
 
+    /// Exports the dag starting at `root` as a CAR stream, following the given [`car::Selector`].
+    ///
+    /// See [`car::CarExport`] for more information.
+    pub fn export_car(&self, root: Cid, selector: car::Selector) -> car::CarExport {
+        car::CarExport::new(&self.repo, root, selector).span(self.span.clone())
+    }
+
+    /// Imports the blocks of a CARv1 or CARv2 stream into the repo, verifying each block.
+    ///
+    /// Resolves to the roots of the archive. See [`car::CarImport`] for more information.
+    pub fn import_car(
+        &self,
+        stream: impl Stream<Item = std::io::Result<Bytes>> + Send + 'static,
+    ) -> car::CarImport {
+        car::CarImport::new(&self.repo, stream).span(self.span.clone())
+    }
+
     /// Creates a stream which will yield the bytes of an UnixFS file from the root Cid, with the
     /// optional file byte range. If the range is specified and is outside of the file, the stream
     /// will end without producing any bytes.
@@ -1724,6 +1912,24 @@ impl Ipfs {
             .await
     }
 
+    /// Lists the blocks of the files added with [`UnixfsAdd::nocopy`], which are stored as
+    /// references to the files, like `ipfs filestore ls` of kubo.
+    pub async fn filestore_list(&self) -> BoxStream<'static, Result<(Cid, FileRef), Error>> {
+        self.repo
+            .filestore_list()
+            .instrument(self.span.clone())
+            .await
+    }
+
+    /// Verifies the blocks of the files added with [`UnixfsAdd::nocopy`] against the files,
+    /// like `ipfs filestore verify` of kubo.
+    pub async fn filestore_verify(&self) -> BoxStream<'static, Result<FilestoreEntry, Error>> {
+        self.repo
+            .filestore_verify()
+            .instrument(self.span.clone())
+            .await
+    }
+
     /// Returns local listening addresses
     pub async fn listening_addresses(&self) -> Result<Vec<Multiaddr>, Error> {
         async move {
@@ -1910,6 +2116,37 @@ impl Ipfs {
         }
     }
 
+    /// Announces the blocks selected by [`RepoProvider`] without waiting for the next run of the
+    /// reprovider, returning once the run has finished. If a run is already in progress, waits for
+    /// it to finish instead.
+    pub async fn reprovide_now(&self) -> Result<ReproviderStats, Error> {
+        async move {
+            let (tx, rx) = oneshot_channel();
+
+            self.to_task.clone().send(IpfsEvent::Reprovide(tx)).await?;
+
+            rx.await?
+        }
+        .instrument(self.span.clone())
+        .await
+    }
+
+    /// Returns the progress of the reprovider
+    pub async fn reprovider_stats(&self) -> Result<ReproviderStats, Error> {
+        async move {
+            let (tx, rx) = oneshot_channel();
+
+            self.to_task
+                .clone()
+                .send(IpfsEvent::ReproviderStats(tx))
+                .await?;
+
+            Ok(rx.await?)
+        }
+        .instrument(self.span.clone())
+        .await
+    }
+
     /// Fetches the block, and, if set, recursively walk the graph loading all the blocks to the blockstore.
     pub fn fetch(&self, cid: &Cid) -> RepoFetch {
         self.repo.fetch(cid).span(self.span.clone())
@@ -2394,6 +2631,49 @@ impl Ipfs {
         &self.keystore
     }
 
+    /// Register a remote pinning service under `name`, replacing any service registered under
+    /// the same name
+    #[cfg(all(feature = "remote_pinning", not(target_arch = "wasm32")))]
+    pub fn add_remote_pinning_service(
+        &self,
+        name: impl Into<String>,
+        service: remote_pin::RemotePinningService,
+    ) {
+        self.remote_pinning_services
+            .write()
+            .insert(name.into(), service);
+    }
+
+    /// Unregister the remote pinning service, returning true if it was registered
+    #[cfg(all(feature = "remote_pinning", not(target_arch = "wasm32")))]
+    pub fn remove_remote_pinning_service(&self, name: &str) -> bool {
+        self.remote_pinning_services.write().remove(name).is_some()
+    }
+
+    /// Names and endpoints of the registered remote pinning services
+    #[cfg(all(feature = "remote_pinning", not(target_arch = "wasm32")))]
+    pub fn remote_pinning_services(&self) -> Vec<(String, String)> {
+        self.remote_pinning_services
+            .read()
+            .iter()
+            .map(|(name, service)| (name.clone(), service.endpoint().to_string()))
+            .collect()
+    }
+
+    /// Pins of the remote pinning service registered under `name`
+    #[cfg(all(feature = "remote_pinning", not(target_arch = "wasm32")))]
+    pub fn remote_pins(&self, name: &str) -> Result<remote_pin::RemotePins, Error> {
+        let service = self
+            .remote_pinning_services
+            .read()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("remote pinning service {name} is not registered"))?;
+
+        let span = debug_span!(parent: &self.span, "remote_pins", name);
+        Ok(remote_pin::RemotePins::new(self.clone(), service, span))
+    }
+
     /// Exit daemon.
     pub async fn exit_daemon(mut self) {
         // FIXME: this is a stopgap measure needed while repo is part of the struct Ipfs instead of
@@ -2800,4 +3080,111 @@ mod tests {
         ipfs.remove_pin(&cid).await.unwrap();
         assert!(!ipfs.is_pinned(&cid).await.unwrap());
     }
+
+    #[tokio::test]
+    async fn custom_storage_with_composed_block_stores() {
+        use crate::repo::blockstore::{
+            bloom::BloomBlockStore, cache::LruBlockStore, memory::MemBlockStore,
+            tiered::TieredBlockStore,
+        };
+
+        let secondary = MemBlockStore::new(Default::default());
+        let shared = Block::new(
+            Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(b"shared")),
+            b"shared".to_vec(),
+        )
+        .unwrap();
+        secondary.put(shared.clone()).await.unwrap();
+
+        let primary = BloomBlockStore::new(Box::new(MemBlockStore::new(Default::default())), 64);
+        let tiered = TieredBlockStore::new(Box::new(primary), Box::new(secondary));
+        let blockstore = LruBlockStore::new(Box::new(tiered), 1024 * 1024);
+
+        let ipfs = UninitializedIpfsNoop::new()
+            .set_storage_type(StorageType::Custom {
+                blockstore: Some(Box::new(blockstore)),
+                datastore: Some(Box::new(repo::datastore::memory::MemDataStore::new(
+                    Default::default(),
+                ))),
+                lock: Some(Box::new(repo::lock::MemLock)),
+            })
+            .start()
+            .await
+            .unwrap();
+
+        let cid = ipfs.put_dag(ipld!([-1, -2, -3])).await.unwrap();
+        assert_eq!(ipfs.get_dag(cid).await.unwrap(), ipld!([-1, -2, -3]));
+        assert_eq!(ipfs.get_block(shared.cid()).await.unwrap(), shared);
+    }
+
+    #[cfg(feature = "redb_block_store")]
+    #[tokio::test]
+    async fn disk_storage_with_redb_block_store() {
+        let tmp = tempfile::tempdir().unwrap();
+        let options = DiskOptions {
+            block_store: DiskBlockStore::Redb,
+            ..Default::default()
+        };
+        let ipfs = UninitializedIpfsNoop::new()
+            .set_disk_storage(tmp.path(), options)
+            .start()
+            .await
+            .unwrap();
+
+        let cid = ipfs.put_dag(ipld!([-1, -2, -3])).await.unwrap();
+        assert_eq!(ipfs.get_dag(cid).await.unwrap(), ipld!([-1, -2, -3]));
+        assert!(tmp.path().join("blockstore/ipfs_blockstore.db").is_file());
+        assert_eq!(
+            ipfs.repo().list_blocks().await.collect::<Vec<_>>().await,
+            vec![cid]
+        );
+    }
+
+    #[tokio::test]
+    async fn reprovide_strategies() {
+        for (strategy, expected) in [
+            (RepoProvider::Roots, 1),
+            (RepoProvider::Pinned, 2),
+            (RepoProvider::All, 3),
+        ] {
+            let ipfs = UninitializedIpfsNoop::new()
+                .with_default()
+                .set_provider(strategy)
+                .set_reprovider_configuration(ReproviderConfig {
+                    batch_size: 1,
+                    ..Default::default()
+                })
+                .start()
+                .await
+                .unwrap();
+
+            // the empty repo is announced on start
+            while ipfs.reprovider_stats().await.unwrap().runs == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+
+            let leaf = ipfs.put_dag(ipld!("leaf")).await.unwrap();
+            ipfs.put_dag(ipld!({ "leaf": leaf }))
+                .pin(true)
+                .await
+                .unwrap();
+            ipfs.put_dag(ipld!("unpinned")).await.unwrap();
+
+            let stats = ipfs.reprovide_now().await.unwrap();
+            assert!(!stats.running);
+            assert_eq!(stats.total, expected, "{strategy:?}");
+            assert_eq!(stats.provided + stats.failed, expected);
+            assert_eq!(stats.runs, 2);
+            assert_eq!(ipfs.reprovider_stats().await.unwrap(), stats);
+
+            ipfs.exit_daemon().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn reprovider_disabled() {
+        let ipfs = Node::new("test_node").await;
+        assert!(ipfs.reprovide_now().await.is_err());
+        assert_eq!(ipfs.reprovider_stats().await.unwrap().runs, 0);
+    }
 }
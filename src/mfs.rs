@@ -0,0 +1,1231 @@
+//! Mutable file system (MFS) on top of UnixFS, similar to `ipfs files` of go-ipfs.
+//!
+//! The file system is a UnixFS directory tree, the root of which is kept in the [`DataStore`] so
+//! that it survives restarts. Every modification renders the changed directories up to the root
+//! again and stores the new root right away, unless a write is done without flushing, in which
+//! case the root is kept in memory until [`Mfs::flush`]. Writing to a file only renders the leaves
+//! covering the written range and the link blocks above them again. The blocks reachable from the
+//! stored and the unflushed root are not removed by the garbage collection.
+//!
+//! [`DataStore`]: crate::repo::DataStore
+
+use std::collections::BTreeMap;
+use std::future::IntoFuture;
+
+use anyhow::{anyhow, bail};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::{FutureExt, StreamExt};
+use libipld::multihash::Code;
+use libipld::pb::PbNode;
+use libipld::{Cid, IpldCodec};
+use rust_unixfs::dir::builder::{BufferingTreeBuilder, TreeOptions};
+use rust_unixfs::dir::{links, DirectoryLink};
+use rust_unixfs::file::adder::FileAdder;
+use rust_unixfs::file::modify::{FileLink, FileNode};
+use rust_unixfs::walk::{ContinuedWalk, Walker};
+use rust_unixfs::CidOptions;
+use tracing::{Instrument, Span};
+
+use crate::error::Error;
+use crate::repo::Repo;
+use crate::unixfs::UnixfsCat;
+use crate::{Block, Ipfs, IpfsPath};
+
+/// The key under which the root of the file system is stored, same as in go-ipfs.
+pub(crate) const ROOT_KEY: &[u8] = b"/local/filesroot";
+
+/// Chunk size of the files written through MFS, the default of [`FileAdder`].
+const CHUNK_SIZE: u64 = 256 * 1024;
+
+/// Maximum number of links in the link blocks of the files written through MFS, the default of
+/// [`FileAdder`].
+const BRANCHING_FACTOR: u64 = 174;
+
+/// MFS facade around [`Ipfs`].
+#[derive(Clone, Debug)]
+pub struct Mfs {
+    ipfs: Ipfs,
+}
+
+/// Type of the node an MFS path points to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeType {
+    File,
+    Directory,
+    Symlink,
+}
+
+/// Information about the node an MFS path points to, as returned by [`Mfs::stat`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stat {
+    pub cid: Cid,
+    pub node_type: NodeType,
+    /// Size of the file contents, zero for directories.
+    pub size: u64,
+    /// Size of the whole tree, including the UnixFS and dag-pb encoding overhead.
+    pub cumulative_size: u64,
+    /// Number of links in the root block of the node.
+    pub blocks: usize,
+}
+
+/// An entry of an MFS directory, as returned by [`Mfs::ls`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub cid: Cid,
+    pub node_type: NodeType,
+    /// Size of the file contents, zero for directories.
+    pub size: u64,
+}
+
+impl Mfs {
+    pub fn new(ipfs: Ipfs) -> Self {
+        Mfs { ipfs }
+    }
+
+    /// Creates a directory at the given path. With `parents` the missing parent directories are
+    /// created as well, and an already existing directory is not an error.
+    pub async fn mkdir(&self, path: &str, parents: bool) -> Result<(), Error> {
+        let repo = self.ipfs.repo();
+        let _g = repo.inner.mfs_lock.lock().await;
+
+        let components = components(path)?;
+        let Some((name, dir)) = components.split_last() else {
+            return match parents {
+                true => Ok(()),
+                false => Err(anyhow!("cannot create directory '/': already exists")),
+            };
+        };
+
+        let root = load_root(repo).await?;
+
+        let existing = lookup(repo, root, &components).await?;
+        match existing {
+            Some(cid) if parents && node_type(repo, &cid).await? == NodeType::Directory => {
+                return Ok(())
+            }
+            Some(_) => bail!("cannot create directory '{path}': already exists"),
+            None => {}
+        }
+
+        let created = Directory::default().store(repo).await?;
+
+        let root = update(repo, root, dir, parents, |dir| {
+            dir.entries.insert(name.to_string(), created);
+            Ok(())
+        })
+        .await?;
+
+        store_root(repo, &root).await
+    }
+
+    /// Writes `data` to the file at the given path, see [`MfsWrite`] for the options.
+    pub fn write(&self, path: impl Into<String>, data: impl Into<Bytes>) -> MfsWrite {
+        MfsWrite {
+            ipfs: self.ipfs.clone(),
+            path: path.into(),
+            data: data.into(),
+            offset: 0,
+            truncate: false,
+            create: false,
+            parents: false,
+            flush: true,
+            span: Span::current(),
+        }
+    }
+
+    /// Copies the UnixFS file or directory at `src` to the MFS path `dest`. The blocks of `src`
+    /// are not fetched, only the root of it is linked to.
+    pub async fn cp(&self, src: IpfsPath, dest: &str) -> Result<(), Error> {
+        let repo = self.ipfs.repo();
+        let _g = repo.inner.mfs_lock.lock().await;
+
+        let components = components(dest)?;
+        let Some((name, dir)) = components.split_last() else {
+            bail!("cannot copy to '/': already exists");
+        };
+
+        let (resolved, _) = self.ipfs.dag().resolve(src, true, &[], false).await?;
+        let block = resolved.into_unixfs_block()?;
+        let entry = (*block.cid(), cumulative_size(&block)?);
+
+        let root = load_root(repo).await?;
+        let root = update(repo, root, dir, false, |dir| {
+            if dir.entries.contains_key(*name) {
+                bail!("cannot copy to '{dest}': already exists");
+            }
+            dir.entries.insert(name.to_string(), entry);
+            Ok(())
+        })
+        .await?;
+
+        store_root(repo, &root).await
+    }
+
+    /// Moves the node at `src` to `dest`. If `dest` is an existing directory, the node is moved
+    /// into it.
+    pub async fn mv(&self, src: &str, dest: &str) -> Result<(), Error> {
+        let repo = self.ipfs.repo();
+        let _g = repo.inner.mfs_lock.lock().await;
+
+        let src_components = components(src)?;
+        let Some((src_name, src_dir)) = src_components.split_last() else {
+            bail!("cannot move '/'");
+        };
+
+        let mut dest_components = components(dest)?;
+
+        let root = load_root(repo).await?;
+
+        if let Some(cid) = lookup(repo, root, &dest_components).await? {
+            if node_type(repo, &cid).await? != NodeType::Directory {
+                bail!("cannot move to '{dest}': already exists");
+            }
+            dest_components.push(src_name);
+        }
+
+        if dest_components.starts_with(&src_components) {
+            if dest_components == src_components {
+                return Ok(());
+            }
+            bail!("cannot move '{src}' into itself");
+        }
+
+        let Some((dest_name, dest_dir)) = dest_components.split_last() else {
+            unreachable!("the root is always an existing directory");
+        };
+
+        let mut entry = None;
+
+        let root = update(repo, root, src_dir, false, |dir| {
+            entry = dir.entries.remove(*src_name);
+            entry
+                .map(|_| ())
+                .ok_or_else(|| anyhow!("'{src}' does not exist"))
+        })
+        .await?;
+
+        let entry = entry.expect("removed from the source directory");
+
+        let root = update(repo, root, dest_dir, false, |dir| {
+            if dir.entries.contains_key(*dest_name) {
+                bail!("cannot move to '{dest}': already exists");
+            }
+            dir.entries.insert(dest_name.to_string(), entry);
+            Ok(())
+        })
+        .await?;
+
+        store_root(repo, &root).await
+    }
+
+    /// Removes the node at the given path. Directories are only removed with `recursive`.
+    pub async fn rm(&self, path: &str, recursive: bool) -> Result<(), Error> {
+        let repo = self.ipfs.repo();
+        let _g = repo.inner.mfs_lock.lock().await;
+
+        let components = components(path)?;
+        let Some((name, dir)) = components.split_last() else {
+            bail!("cannot remove '/'");
+        };
+
+        let root = load_root(repo).await?;
+
+        let Some(cid) = lookup(repo, root, &components).await? else {
+            bail!("'{path}' does not exist");
+        };
+
+        if !recursive && node_type(repo, &cid).await? == NodeType::Directory {
+            bail!("'{path}' is a directory, it can only be removed recursively");
+        }
+
+        let root = update(repo, root, dir, false, |dir| {
+            dir.entries.remove(*name);
+            Ok(())
+        })
+        .await?;
+
+        store_root(repo, &root).await
+    }
+
+    /// Returns information about the node at the given path.
+    pub async fn stat(&self, path: &str) -> Result<Stat, Error> {
+        let repo = self.ipfs.repo();
+        let _g = repo.inner.mfs_lock.lock().await;
+
+        let cid = resolve(repo, path).await?;
+        let block = repo.get_block(&cid, &[], false).await?;
+
+        let (node_type, size) = inspect(&block)?;
+        let blocks = match block.cid().codec() == u64::from(IpldCodec::DagPb) {
+            true => PbNode::from_bytes(Bytes::copy_from_slice(block.data()))?
+                .links
+                .len(),
+            false => 0,
+        };
+
+        Ok(Stat {
+            cid,
+            node_type,
+            size,
+            cumulative_size: cumulative_size(&block)?,
+            blocks,
+        })
+    }
+
+    /// Lists the entries of the directory at the given path, or the file itself when the path
+    /// points to a file.
+    pub async fn ls(&self, path: &str) -> Result<Vec<Entry>, Error> {
+        let repo = self.ipfs.repo();
+        let _g = repo.inner.mfs_lock.lock().await;
+
+        let cid = resolve(repo, path).await?;
+        let block = repo.get_block(&cid, &[], false).await?;
+
+        let (node_type, size) = inspect(&block)?;
+        if node_type != NodeType::Directory {
+            let name = components(path)?.last().copied().unwrap_or_default();
+            return Ok(vec![Entry {
+                name: name.to_string(),
+                cid,
+                node_type,
+                size,
+            }]);
+        }
+
+        let directory = Directory::load(repo, cid).await?;
+
+        let mut entries = Vec::with_capacity(directory.entries.len());
+        for (name, (cid, _)) in directory.entries {
+            let block = repo.get_block(&cid, &[], false).await?;
+            let (node_type, size) = inspect(&block)?;
+            entries.push(Entry {
+                name,
+                cid,
+                node_type,
+                size,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Reads the file at the given path, starting from `offset` and reading at most `count` bytes
+    /// when given.
+    pub async fn read(&self, path: &str, offset: u64, count: Option<u64>) -> Result<Bytes, Error> {
+        let repo = self.ipfs.repo();
+        let _g = repo.inner.mfs_lock.lock().await;
+
+        let cid = resolve(repo, path).await?;
+        let end = count.map_or(u64::MAX, |count| offset.saturating_add(count));
+
+        let bytes = UnixfsCat::with_repo(repo, cid).range(offset..end).await?;
+        Ok(bytes)
+    }
+
+    /// Stores the root left unflushed by the writes done without flushing, returning the Cid of
+    /// the node at the given path.
+    pub async fn flush(&self, path: &str) -> Result<Cid, Error> {
+        let repo = self.ipfs.repo();
+        let _g = repo.inner.mfs_lock.lock().await;
+
+        let root = load_root(repo).await?;
+        store_root(repo, &root).await?;
+
+        resolve(repo, path).await
+    }
+}
+
+/// Writes data to an MFS file. By default the file has to exist, and the data overwrites the
+/// beginning of the file.
+#[must_use = "does nothing unless you `.await` it"]
+pub struct MfsWrite {
+    ipfs: Ipfs,
+    path: String,
+    data: Bytes,
+    offset: u64,
+    truncate: bool,
+    create: bool,
+    parents: bool,
+    flush: bool,
+    span: Span,
+}
+
+impl MfsWrite {
+    /// Byte offset to start writing at. Writing past the end of the file fills the gap with
+    /// zeroes.
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Truncates the file before writing.
+    pub fn truncate(mut self) -> Self {
+        self.truncate = true;
+        self
+    }
+
+    /// Creates the file if it does not exist.
+    pub fn create(mut self) -> Self {
+        self.create = true;
+        self
+    }
+
+    /// Creates the missing parent directories.
+    pub fn parents(mut self) -> Self {
+        self.parents = true;
+        self
+    }
+
+    /// Stores the new root of the file system after writing, which is the default. Without
+    /// flushing the new root is only kept in memory until [`Mfs::flush`] or the next operation
+    /// which flushes, saving the update of the datastore on each of a series of writes.
+    pub fn flush(mut self, flush: bool) -> Self {
+        self.flush = flush;
+        self
+    }
+
+    pub fn span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+}
+
+impl IntoFuture for MfsWrite {
+    type Output = Result<(), Error>;
+
+    type IntoFuture = BoxFuture<'static, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        let span = self.span.clone();
+        async move {
+            let repo = self.ipfs.repo();
+            let _g = repo.inner.mfs_lock.lock().await;
+
+            let path = &self.path;
+            let components = components(path)?;
+            let Some((name, dir)) = components.split_last() else {
+                bail!("cannot write to '/': is a directory");
+            };
+
+            let root = load_root(repo).await?;
+
+            let existing = match lookup(repo, root, &components).await? {
+                Some(cid) if node_type(repo, &cid).await? != NodeType::File => {
+                    bail!("cannot write to '{path}': not a file")
+                }
+                Some(_) if self.truncate => None,
+                Some(cid) => Some(cid),
+                None if self.create => None,
+                None => bail!("'{path}' does not exist"),
+            };
+
+            let file = match existing {
+                Some(cid) => write_file(repo, cid, self.offset, &self.data).await?,
+                None => {
+                    let mut adder = FileAdder::default();
+                    push_zeroes(repo, &mut adder, self.offset).await?;
+                    push(repo, &mut adder, &self.data).await?;
+                    finish(repo, adder).await?
+                }
+            };
+
+            let root = update(repo, root, dir, self.parents, |dir| {
+                dir.entries.insert(name.to_string(), file);
+                Ok(())
+            })
+            .await?;
+
+            match self.flush {
+                true => store_root(repo, &root).await,
+                false => {
+                    *repo.inner.mfs_unflushed.lock() = Some(root);
+                    Ok(())
+                }
+            }
+        }
+        .instrument(span)
+        .boxed()
+    }
+}
+
+/// Entries of a directory, with the cumulative sizes of the linked trees.
+#[derive(Default)]
+struct Directory {
+    entries: BTreeMap<String, (Cid, u64)>,
+}
+
+impl Directory {
+    /// Loads the entries of a plain or HAMT sharded directory.
+    async fn load(repo: &Repo, cid: Cid) -> Result<Self, Error> {
+        let mut entries = BTreeMap::new();
+        let mut pending = vec![cid];
+
+        while let Some(cid) = pending.pop() {
+            if cid.codec() != u64::from(IpldCodec::DagPb) {
+                bail!("{cid} is not a directory");
+            }
+
+            let block = repo.get_block(&cid, &[], false).await?;
+
+            let links =
+                links(block.data()).map_err(|e| anyhow!("{cid} is not a directory: {e}"))?;
+            for link in links {
+                match link {
+                    DirectoryLink::Entry {
+                        name,
+                        cid,
+                        total_size,
+                    } => {
+                        entries.insert(name, (cid, total_size));
+                    }
+                    DirectoryLink::Bucket(cid) => pending.push(cid),
+                }
+            }
+        }
+
+        Ok(Directory { entries })
+    }
+
+    /// Stores the directory, returning the Cid and the cumulative size of it.
+    async fn store(self, repo: &Repo) -> Result<(Cid, u64), Error> {
+        let mut opts = TreeOptions::default();
+        opts.wrap_with_directory();
+
+        let mut builder = BufferingTreeBuilder::new(opts);
+        for (name, (cid, total_size)) in self.entries {
+            builder.put_link(&name, cid, total_size)?;
+        }
+
+        let mut root = None;
+        for node in builder.build() {
+            let node = node?;
+            root = Some((node.cid, node.total_size));
+            repo.put_block(Block::new_unchecked(node.cid, node.block.into_vec()))
+                .await?;
+        }
+
+        root.ok_or_else(|| anyhow!("directory was not rendered"))
+    }
+}
+
+/// Returns the normalized components of an absolute MFS path.
+fn components(path: &str) -> Result<Vec<&str>, Error> {
+    if !path.starts_with('/') {
+        bail!("MFS paths must be absolute: '{path}'");
+    }
+
+    let mut components = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+
+    Ok(components)
+}
+
+/// Loads the root of the file system, creating an empty directory for it on first use.
+async fn load_root(repo: &Repo) -> Result<Cid, Error> {
+    if let Some(root) = *repo.inner.mfs_unflushed.lock() {
+        return Ok(root);
+    }
+
+    match repo.data_store().get(ROOT_KEY).await? {
+        Some(bytes) => Ok(Cid::try_from(bytes)?),
+        None => Directory::default().store(repo).await.map(|(cid, _)| cid),
+    }
+}
+
+/// Stores the root of the file system, replacing the unflushed root.
+async fn store_root(repo: &Repo, root: &Cid) -> Result<(), Error> {
+    repo.data_store().put(ROOT_KEY, &root.to_bytes()).await?;
+    repo.inner.mfs_unflushed.lock().take();
+    Ok(())
+}
+
+/// Resolves an MFS path to a Cid, failing if the path does not exist.
+async fn resolve(repo: &Repo, path: &str) -> Result<Cid, Error> {
+    let components = components(path)?;
+    let root = load_root(repo).await?;
+
+    lookup(repo, root, &components)
+        .await?
+        .ok_or_else(|| anyhow!("'{path}' does not exist"))
+}
+
+async fn lookup(repo: &Repo, root: Cid, components: &[&str]) -> Result<Option<Cid>, Error> {
+    let mut current = root;
+
+    for component in components {
+        let directory = Directory::load(repo, current).await?;
+        match directory.entries.get(*component) {
+            Some((cid, _)) => current = *cid,
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(current))
+}
+
+/// Applies `f` on the directory at the path given as `components`, and renders all of the
+/// directories up to the root again, returning the new root. With `parents` the missing
+/// directories are created on the way.
+async fn update<F>(
+    repo: &Repo,
+    root: Cid,
+    components: &[&str],
+    parents: bool,
+    f: F,
+) -> Result<Cid, Error>
+where
+    F: FnOnce(&mut Directory) -> Result<(), Error>,
+{
+    let mut directories = vec![(String::new(), Directory::load(repo, root).await?)];
+
+    for (depth, component) in components.iter().enumerate() {
+        let (_, parent) = directories.last().expect("root is always present");
+        let directory = match parent.entries.get(*component) {
+            Some((cid, _)) => Directory::load(repo, *cid).await?,
+            None if parents => Directory::default(),
+            None => bail!("'/{}' does not exist", components[..=depth].join("/")),
+        };
+        directories.push((component.to_string(), directory));
+    }
+
+    let (mut name, mut directory) = directories.pop().expect("root is always present");
+    f(&mut directory)?;
+
+    let mut stored = directory.store(repo).await?;
+
+    while let Some((parent_name, mut parent)) = directories.pop() {
+        parent.entries.insert(name, stored);
+        stored = parent.store(repo).await?;
+        name = parent_name;
+    }
+
+    Ok(stored.0)
+}
+
+/// Pushes the content to the adder, storing the blocks completed by it.
+async fn push(repo: &Repo, adder: &mut FileAdder, mut content: &[u8]) -> Result<(), Error> {
+    while !content.is_empty() {
+        let (produced, consumed) = adder.push(content);
+        let blocks = produced.collect::<Vec<_>>();
+        content = &content[consumed..];
+
+        for (cid, block) in blocks {
+            repo.put_block(Block::new_unchecked(cid, block)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Pushes `len` zeroes to the adder a chunk at a time, so that a gap in the file is never
+/// allocated in whole.
+async fn push_zeroes(repo: &Repo, adder: &mut FileAdder, mut len: u64) -> Result<(), Error> {
+    let zeroes = vec![0; len.min(CHUNK_SIZE) as usize];
+
+    while len > 0 {
+        let chunk = len.min(CHUNK_SIZE) as usize;
+        push(repo, adder, &zeroes[..chunk]).await?;
+        len -= chunk as u64;
+    }
+
+    Ok(())
+}
+
+/// Stores the remaining blocks of the file, returning the Cid and the cumulative size of it.
+async fn finish(repo: &Repo, adder: FileAdder) -> Result<(Cid, u64), Error> {
+    let mut root = None;
+    for (cid, block) in adder.finish() {
+        let block = Block::new_unchecked(cid, block);
+        repo.put_block(block.clone()).await?;
+        root = Some(block);
+    }
+
+    let root = root.ok_or_else(|| anyhow!("file was not rendered"))?;
+    Ok((*root.cid(), cumulative_size(&root)?))
+}
+
+/// Writes `data` at `offset` of the file, returning the new Cid and the cumulative size of it. The
+/// range already in the file is overwritten in place, and the rest is appended.
+async fn write_file(repo: &Repo, root: Cid, offset: u64, data: &[u8]) -> Result<(Cid, u64), Error> {
+    let block = repo.get_block(&root, &[], false).await?;
+    let size = FileNode::parse(&root, block.data())?.file_size();
+    let end = offset + data.len() as u64;
+
+    let mut file = (root, cumulative_size(&block)?);
+
+    if offset < size && !data.is_empty() {
+        let overwritten = (size.min(end) - offset) as usize;
+        let link = overwrite(repo, root, offset, &data[..overwritten]).await?;
+        file = (link.cid, link.total_size);
+    }
+
+    if end > size {
+        // writing past the end of the file fills the gap with zeroes
+        let gap = offset.saturating_sub(size);
+        let tail = &data[size.saturating_sub(offset) as usize..];
+        file = append(repo, file.0, gap, tail).await?;
+    }
+
+    Ok(file)
+}
+
+/// Overwrites the content of the tree starting at `offset`, rendering only the blocks covering the
+/// written range again. The range must be within the file.
+fn overwrite<'a>(
+    repo: &'a Repo,
+    cid: Cid,
+    offset: u64,
+    data: &'a [u8],
+) -> BoxFuture<'a, Result<FileLink, Error>> {
+    async move {
+        let block = repo.get_block(&cid, &[], false).await?;
+        let mut node = FileNode::parse(&cid, block.data())?;
+        let end = offset + data.len() as u64;
+
+        let inline = node.data().len() as u64;
+        if offset < inline {
+            let len = (inline.min(end) - offset) as usize;
+            node.overwrite_data(offset as usize, &data[..len]);
+        }
+
+        let mut base = inline;
+        for index in 0..node.links().len() {
+            let link = node.links()[index];
+            let range = base..base + link.file_size;
+            base = range.end;
+
+            let (start, stop) = (offset.max(range.start), end.min(range.end));
+            if start >= stop {
+                continue;
+            }
+
+            let written = &data[(start - offset) as usize..(stop - offset) as usize];
+            let modified = overwrite(repo, link.cid, start - range.start, written).await?;
+            node.set_link(index, modified);
+        }
+
+        let (link, block) = node.render(&cid_options(&cid));
+        repo.put_block(Block::new_unchecked(link.cid, block))
+            .await?;
+        Ok(link)
+    }
+    .boxed()
+}
+
+/// Appends `gap` zeroes and the tail to the file, returning the new Cid and the cumulative size of
+/// it. Only the blocks on the right spine of files of the default balanced layout are rendered
+/// again, while files of other layouts are added again in whole.
+async fn append(repo: &Repo, root: Cid, gap: u64, tail: &[u8]) -> Result<(Cid, u64), Error> {
+    let mut spine = Vec::new();
+    let mut cid = root;
+
+    let leaf = loop {
+        let block = repo.get_block(&cid, &[], false).await?;
+        let node = FileNode::parse(&cid, block.data())?;
+        match node.links().last() {
+            Some(last) => {
+                cid = last.cid;
+                spine.push(node);
+            }
+            None => break node,
+        }
+    };
+
+    let metadata = spine.first().unwrap_or(&leaf).metadata();
+
+    let builder = FileAdder::builder()
+        .with_cid_options(cid_options(&root))
+        .with_raw_leaves(leaf.is_raw_block())
+        .with_metadata(metadata);
+
+    let mut adder = match balanced_subtrees(&spine, &leaf) {
+        Some(subtrees) => {
+            let mut adder = builder.build_with_subtrees(subtrees);
+            push(repo, &mut adder, leaf.data()).await?;
+            adder
+        }
+        None => {
+            let mut adder = builder.build();
+            let mut content = UnixfsCat::with_repo(repo, root);
+            while let Some(bytes) = content.next().await {
+                push(repo, &mut adder, &bytes?).await?;
+            }
+            adder
+        }
+    };
+
+    push_zeroes(repo, &mut adder, gap).await?;
+    push(repo, &mut adder, tail).await?;
+    finish(repo, adder).await
+}
+
+/// Returns the complete subtrees to the left of the right spine of a file of the balanced layout
+/// created with the defaults of [`FileAdder`], along with their depth, or `None` for a file of any
+/// other layout or chunking.
+fn balanced_subtrees(spine: &[FileNode], leaf: &FileNode) -> Option<Vec<(usize, FileLink)>> {
+    let leaf_metadata = spine.is_empty() || leaf.metadata().is_empty();
+    if !(leaf.is_file() || leaf.is_raw_block())
+        || leaf.data().len() as u64 > CHUNK_SIZE
+        || !leaf_metadata
+    {
+        return None;
+    }
+
+    let height = spine.len();
+    let mut subtrees = Vec::new();
+
+    for (level, node) in spine.iter().enumerate() {
+        let depth = height - level - 1;
+        let complete = BRANCHING_FACTOR
+            .checked_pow(depth as u32)
+            .and_then(|leaves| leaves.checked_mul(CHUNK_SIZE))?;
+        let (_, rest) = node.links().split_last()?;
+
+        let balanced = node.is_file()
+            && node.data().is_empty()
+            && node.links().len() as u64 <= BRANCHING_FACTOR
+            && (level > 0 || node.links().len() > 1)
+            && (level == 0 || node.metadata().is_empty())
+            && rest.iter().all(|link| link.file_size == complete);
+
+        if !balanced {
+            return None;
+        }
+
+        subtrees.extend(rest.iter().map(|link| (depth, *link)));
+    }
+
+    Some(subtrees)
+}
+
+/// Returns the options which render a modified block the same way as the original one.
+fn cid_options(cid: &Cid) -> CidOptions {
+    let options = CidOptions::default().with_version(cid.version());
+    match Code::try_from(cid.hash().code()) {
+        // inlined blocks are hashed with the default once they grow
+        Ok(code) if cid.hash().code() != 0x00 => options.with_hash(code),
+        _ => options,
+    }
+}
+
+async fn node_type(repo: &Repo, cid: &Cid) -> Result<NodeType, Error> {
+    let block = repo.get_block(cid, &[], false).await?;
+    inspect(&block).map(|(node_type, _)| node_type)
+}
+
+/// Returns the type and the file size of the UnixFS node.
+fn inspect(block: &Block) -> Result<(NodeType, u64), Error> {
+    if block.cid().codec() == u64::from(IpldCodec::Raw) {
+        return Ok((NodeType::File, block.data().len() as u64));
+    }
+
+    let mut walker = Walker::new(*block.cid(), String::new());
+    Ok(match walker.next(block.data(), &mut None)? {
+        ContinuedWalk::File(.., size) => (NodeType::File, size),
+        ContinuedWalk::Symlink(target, ..) => (NodeType::Symlink, target.len() as u64),
+        ContinuedWalk::RootDirectory(..)
+        | ContinuedWalk::Directory(..)
+        | ContinuedWalk::Bucket(..) => (NodeType::Directory, 0),
+    })
+}
+
+/// Returns the size of the block and the sizes of the trees linked from it.
+fn cumulative_size(block: &Block) -> Result<u64, Error> {
+    let size = block.data().len() as u64;
+
+    if block.cid().codec() != u64::from(IpldCodec::DagPb) {
+        return Ok(size);
+    }
+
+    let node = PbNode::from_bytes(Bytes::copy_from_slice(block.data()))?;
+    Ok(size + node.links.iter().filter_map(|link| link.size).sum::<u64>())
+}
+
+/// Returns the stored and the unflushed root of the file system. The blocks reachable from them
+/// are kept by the garbage collector.
+pub(crate) async fn local_roots(repo: &Repo) -> Result<Vec<Cid>, Error> {
+    let mut roots = repo
+        .data_store()
+        .get(ROOT_KEY)
+        .await?
+        .map(Cid::try_from)
+        .transpose()?
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    roots.extend(*repo.inner.mfs_unflushed.lock());
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::IntoFuture;
+    use std::time::Duration;
+
+    use super::NodeType;
+    use crate::repo::Repo;
+    use crate::{Node, PinMode, UninitializedIpfsNoop};
+    use bytes::Bytes;
+    use futures::StreamExt;
+    use libipld::multihash::{Code, MultihashDigest};
+    use libipld::{Cid, IpldCodec};
+    use rust_unixfs::file::adder::FileAdder;
+
+    /// Adds the file contents, returning the Cid and the cumulative size of the file.
+    async fn add_file(
+        repo: &Repo,
+        mut adder: FileAdder,
+        content: &[u8],
+    ) -> Result<(Cid, u64), crate::error::Error> {
+        super::push(repo, &mut adder, content).await?;
+        super::finish(repo, adder).await
+    }
+
+    async fn load_stored(repo: &Repo) -> Option<Cid> {
+        let bytes = repo.data_store().get(super::ROOT_KEY).await.unwrap()?;
+        Some(Cid::try_from(bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn write_and_read() {
+        let ipfs = Node::new("test_node").await;
+        let files = ipfs.files();
+
+        assert!(files.write("/a/file", "foobar").create().await.is_err());
+
+        files
+            .write("/a/file", "foobar")
+            .create()
+            .parents()
+            .await
+            .unwrap();
+        assert_eq!(files.read("/a/file", 0, None).await.unwrap(), "foobar");
+
+        files.write("/a/file", "xx").offset(3).await.unwrap();
+        assert_eq!(files.read("/a/file", 0, None).await.unwrap(), "fooxxr");
+
+        files.write("/a/file", "yy").offset(8).await.unwrap();
+        assert_eq!(
+            files.read("/a/file", 0, None).await.unwrap(),
+            Bytes::from_static(b"fooxxr\0\0yy")
+        );
+        assert_eq!(files.read("/a/file", 3, Some(2)).await.unwrap(), "xx");
+
+        files.write("/a/file", "baz").truncate().await.unwrap();
+        assert_eq!(files.read("/a/file", 0, None).await.unwrap(), "baz");
+
+        let stat = files.stat("/a/file").await.unwrap();
+        assert_eq!(stat.node_type, NodeType::File);
+        assert_eq!(stat.size, 3);
+        assert_eq!(
+            ipfs.cat_unixfs(stat.cid).await.unwrap(),
+            Bytes::from_static(b"baz")
+        );
+    }
+
+    #[tokio::test]
+    async fn directories() {
+        let ipfs = Node::new("test_node").await;
+        let files = ipfs.files();
+
+        // the empty directory from go-ipfs
+        assert_eq!(
+            files.flush("/").await.unwrap().to_string(),
+            "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn"
+        );
+
+        assert!(files.mkdir("/a/b", false).await.is_err());
+        files.mkdir("/a/b", true).await.unwrap();
+        files.mkdir("/a/b", true).await.unwrap();
+        assert!(files.mkdir("/a/b", false).await.is_err());
+
+        files.write("/a/b/c", "foobar").create().await.unwrap();
+        files.mv("/a/b/c", "/a").await.unwrap();
+        files.mv("/a/c", "/a/d").await.unwrap();
+        assert!(files.mv("/a", "/a/b").await.is_err());
+
+        let names = |entries: Vec<super::Entry>| {
+            entries
+                .into_iter()
+                .map(|entry| (entry.name, entry.node_type))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(files.ls("/a").await.unwrap()),
+            [
+                (String::from("b"), NodeType::Directory),
+                (String::from("d"), NodeType::File)
+            ]
+        );
+
+        assert!(files.rm("/a/b", false).await.is_err());
+        files.rm("/a/b", true).await.unwrap();
+        files.rm("/a/d", false).await.unwrap();
+        assert!(files.ls("/a").await.unwrap().is_empty());
+        assert!(files.stat("/a/d").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn writes_match_adding_the_whole_file() {
+        let ipfs = Node::new("test_node").await;
+        let files = ipfs.files();
+        let chunk = super::CHUNK_SIZE as usize;
+
+        let mut content = (0..=255u8)
+            .cycle()
+            .take(chunk * 3 + 100)
+            .collect::<Vec<_>>();
+        files
+            .write("/file", content[..chunk + 10].to_vec())
+            .create()
+            .await
+            .unwrap();
+        let ipfs = &ipfs;
+        let links = |cid: Cid| async move {
+            let block = ipfs.get_block(&cid).await.unwrap();
+            let node = super::FileNode::parse(&cid, block.data()).unwrap();
+            node.links().to_vec()
+        };
+        let leaves = links(files.stat("/file").await.unwrap().cid).await;
+
+        // appending only renders the last leaf and the root again
+        files
+            .write("/file", content[chunk + 10..].to_vec())
+            .offset(chunk as u64 + 10)
+            .await
+            .unwrap();
+
+        // overwriting across two leaves
+        content[chunk * 2 - 2..chunk * 2 + 2].copy_from_slice(b"abcd");
+        files
+            .write("/file", "abcd")
+            .offset(chunk as u64 * 2 - 2)
+            .await
+            .unwrap();
+
+        let stat = files.stat("/file").await.unwrap();
+        let (expected, size) = add_file(ipfs.repo(), Default::default(), &content)
+            .await
+            .unwrap();
+        assert_eq!(stat.cid, expected);
+        assert_eq!(stat.cumulative_size, size);
+        assert_eq!(files.read("/file", 0, None).await.unwrap(), content);
+
+        let appended = links(stat.cid).await;
+        assert_eq!(appended.len(), 4);
+        assert_eq!(appended[0], leaves[0]);
+    }
+
+    #[tokio::test]
+    async fn appends_keep_the_options_of_other_chunkings() {
+        use rust_unixfs::file::adder::Chunker;
+        use rust_unixfs::CidOptions;
+
+        let ipfs = Node::new("test_node").await;
+        let files = ipfs.files();
+        let builder = || {
+            FileAdder::builder()
+                .with_cid_options(CidOptions::default().with_version(libipld::cid::Version::V1))
+                .with_raw_leaves(true)
+        };
+
+        let content = (0..=255u8).cycle().take(3000).collect::<Vec<_>>();
+        let adder = builder().with_chunker(Chunker::Size(1000)).build();
+        let (file, _) = add_file(ipfs.repo(), adder, &content).await.unwrap();
+        files.cp(file.into(), "/file").await.unwrap();
+
+        // the gap spans several chunks
+        let offset = content.len() as u64 + super::CHUNK_SIZE * 2 + 5;
+        files.write("/file", "tail").offset(offset).await.unwrap();
+
+        let mut expected = content;
+        expected.resize(offset as usize, 0);
+        expected.extend_from_slice(b"tail");
+
+        let stat = files.stat("/file").await.unwrap();
+        let (cid, size) = add_file(ipfs.repo(), builder().build(), &expected)
+            .await
+            .unwrap();
+        assert_eq!(stat.cid, cid);
+        assert_eq!(stat.cumulative_size, size);
+        assert_eq!(files.read("/file", 0, None).await.unwrap(), expected);
+
+        // as do the gaps of new files
+        files
+            .write("/new", "tail")
+            .offset(offset)
+            .create()
+            .await
+            .unwrap();
+        let mut expected = vec![0; offset as usize];
+        expected.extend_from_slice(b"tail");
+        let (cid, _) = add_file(ipfs.repo(), Default::default(), &expected)
+            .await
+            .unwrap();
+        assert_eq!(files.stat("/new").await.unwrap().cid, cid);
+    }
+
+    #[tokio::test]
+    async fn unflushed_writes() {
+        let ipfs = Node::new("test_node").await;
+        let files = ipfs.files();
+
+        let stored = files.flush("/").await.unwrap();
+        files
+            .write("/file", "foobar")
+            .create()
+            .flush(false)
+            .await
+            .unwrap();
+
+        assert_eq!(files.read("/file", 0, None).await.unwrap(), "foobar");
+        assert_eq!(load_stored(ipfs.repo()).await, Some(stored));
+
+        let stat = files.stat("/file").await.unwrap();
+        let removed = ipfs.gc().await.unwrap().removed;
+        assert!(!removed.contains(&stat.cid));
+
+        let root = files.flush("/").await.unwrap();
+        assert_ne!(root, stored);
+        assert_eq!(load_stored(ipfs.repo()).await, Some(root));
+        assert!(ipfs.repo().inner.mfs_unflushed.lock().is_none());
+    }
+
+    #[tokio::test]
+    async fn copy_from_ipfs_path() {
+        let ipfs = Node::new("test_node").await;
+        let files = ipfs.files();
+
+        let path = ipfs
+            .add_unixfs((String::from("foo.txt"), b"foobar".to_vec()))
+            .wrap()
+            .await
+            .unwrap();
+
+        let root = *path.root().cid().unwrap();
+
+        files.cp(root.into(), "/dir").await.unwrap();
+        assert!(files.cp(root.into(), "/dir").await.is_err());
+        files.cp(path, "/file").await.unwrap();
+
+        assert_eq!(files.flush("/dir").await.unwrap(), root);
+        assert_eq!(files.read("/file", 0, None).await.unwrap(), "foobar");
+        assert_eq!(files.read("/dir/foo.txt", 0, None).await.unwrap(), "foobar");
+
+        let stat = files.stat("/dir").await.unwrap();
+        assert_eq!(stat.node_type, NodeType::Directory);
+        assert_eq!(stat.blocks, 1);
+    }
+
+    #[tokio::test]
+    async fn protected_from_gc() {
+        let ipfs = Node::new("test_node").await;
+        let files = ipfs.files();
+
+        files
+            .write("/file", vec![1u8; 1024 * 1024])
+            .create()
+            .await
+            .unwrap();
+        let removed = ipfs.gc().await.unwrap().removed;
+
+        let root = files.flush("/").await.unwrap();
+        let stat = files.stat("/file").await.unwrap();
+        assert!(!removed.contains(&root));
+        assert!(!removed.contains(&stat.cid));
+        assert_eq!(
+            files.read("/file", 0, None).await.unwrap().len(),
+            1024 * 1024
+        );
+
+        // the replaced blocks are no longer protected
+        files.rm("/file", false).await.unwrap();
+        let removed = ipfs.gc().await.unwrap().removed;
+        assert!(removed.contains(&stat.cid));
+        assert!(!removed.contains(&files.flush("/").await.unwrap()));
+
+        let pins = ipfs
+            .list_pins(None::<PinMode>)
+            .await
+            .collect::<Vec<_>>()
+            .await;
+        assert!(pins.is_empty());
+    }
+
+    #[tokio::test]
+    async fn root_survives_restart() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let repo = Repo::new_fs(tmp.path());
+
+        let ipfs = UninitializedIpfsNoop::new()
+            .set_repo(&repo)
+            .start()
+            .await
+            .unwrap();
+        ipfs.files()
+            .write("/file", "foobar")
+            .create()
+            .await
+            .unwrap();
+        let root = ipfs.files().flush("/").await.unwrap();
+        ipfs.exit_daemon().await;
+
+        let ipfs = UninitializedIpfsNoop::new()
+            .set_repo(&repo)
+            .start()
+            .await
+            .unwrap();
+        assert_eq!(ipfs.files().flush("/").await.unwrap(), root);
+        assert_eq!(ipfs.files().read("/file", 0, None).await.unwrap(), "foobar");
+    }
+
+    #[tokio::test]
+    async fn writes_while_collecting() {
+        struct Writer(super::Mfs);
+
+        impl std::fmt::Debug for Writer {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("Writer").finish()
+            }
+        }
+
+        // writes while the gc marks the reachable blocks
+        #[async_trait::async_trait]
+        impl crate::repo::RootSet for Writer {
+            async fn roots(&self) -> Result<Vec<Cid>, crate::error::Error> {
+                self.0.write("/file", "foobar").create().await?;
+                Ok(vec![])
+            }
+        }
+
+        let ipfs = Node::new("test_node").await;
+        let data = b"garbage".to_vec();
+        let cid = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(&data));
+        ipfs.repo()
+            .put_block(crate::Block::new_unchecked(cid, data))
+            .await
+            .unwrap();
+        ipfs.repo()
+            .register_root_set("writer", Writer(ipfs.files()));
+
+        let report = tokio::time::timeout(Duration::from_secs(10), ipfs.gc().into_future())
+            .await
+            .expect("mfs writes are not blocked by the gc")
+            .unwrap();
+        ipfs.repo().unregister_root_set("writer");
+
+        assert!(report.removed.contains(&cid));
+        assert_eq!(ipfs.files().read("/file", 0, None).await.unwrap(), "foobar");
+    }
+}
@@ -1,4 +1,5 @@
 use super::gossipsub::GossipsubStream;
+use super::kad_store::KadStore;
 use super::{addressbook, protocol};
 #[cfg(feature = "beetle_bitswap")]
 use bytes::Bytes;
@@ -27,7 +28,7 @@ use libp2p::core::Multiaddr;
 use libp2p::dcutr::Behaviour as Dcutr;
 use libp2p::identify::{Behaviour as Identify, Config as IdentifyConfig};
 use libp2p::identity::{Keypair, PeerId};
-use libp2p::kad::store::{MemoryStore, MemoryStoreConfig};
+use libp2p::kad::store::MemoryStoreConfig;
 use libp2p::kad::{
     Behaviour as Kademlia, BucketInserts as KademliaBucketInserts, Config as KademliaConfig,
     Record, StoreInserts as KademliaStoreInserts,
@@ -61,7 +62,7 @@ where
     pub bitswap: Toggle<Bitswap<Repo>>,
     #[cfg(not(any(feature = "libp2p_bitswap", feature = "beetle_bitswap")))]
     pub bitswap: Toggle<super::bitswap::Behaviour>,
-    pub kademlia: Toggle<Kademlia<MemoryStore>>,
+    pub kademlia: Toggle<Kademlia<KadStore>>,
     pub ping: Toggle<Ping>,
     pub identify: Toggle<Identify>,
     pub pubsub: Toggle<GossipsubStream>,
@@ -280,6 +281,7 @@ pub struct KadConfig {
     pub provider_record_ttl: Option<Duration>,
     pub insert_method: KadInserts,
     pub store_filter: KadStoreInserts,
+    pub store_type: KadStoreType,
 }
 
 #[derive(Clone, Debug, Default, Copy)]
@@ -289,6 +291,17 @@ pub enum KadInserts {
     Manual,
 }
 
+/// Where kademlia keeps the records and provider records.
+#[derive(Clone, Debug, Default, Copy, PartialEq, Eq)]
+pub enum KadStoreType {
+    /// Records are kept in memory and lost on restart
+    #[default]
+    Memory,
+    /// Records are kept in memory and persisted to the repo [`DataStore`](crate::repo::DataStore),
+    /// being loaded again on start unless they have expired
+    DataStore,
+}
+
 #[derive(Clone, Debug, Default, Copy)]
 pub enum KadStoreInserts {
     #[default]
@@ -348,6 +361,7 @@ impl Default for KadConfig {
             publication_interval: None,
             insert_method: Default::default(),
             store_filter: Default::default(),
+            store_type: Default::default(),
         }
     }
 }
@@ -438,12 +452,9 @@ where
         }
         .into();
 
-        let store = {
-            //TODO: Make customizable
-            //TODO: Use persistent store for kad
-            let config = options.kad_store_config.memory.clone().unwrap_or_default();
-
-            MemoryStore::with_config(peer_id, config)
+        let store_type = match &options.kad_configuration {
+            Either::Left(kad) => kad.store_type,
+            Either::Right(_) => KadStoreType::Memory,
         };
 
         let kad_config = match options.kad_configuration.clone() {
@@ -451,9 +462,19 @@ where
             Either::Right(kad) => kad,
         };
 
-        let mut kademlia: Toggle<Kademlia<MemoryStore>> = Toggle::from(
-            (protocols.kad).then(|| Kademlia::with_config(peer_id, store, kad_config)),
-        );
+        let store = match protocols.kad {
+            true => {
+                let config = options.kad_store_config.memory.clone().unwrap_or_default();
+                Some(match store_type {
+                    KadStoreType::Memory => KadStore::memory(peer_id, config),
+                    KadStoreType::DataStore => KadStore::persistent(peer_id, config, repo).await?,
+                })
+            }
+            false => None,
+        };
+
+        let mut kademlia: Toggle<Kademlia<KadStore>> =
+            Toggle::from(store.map(|store| Kademlia::with_config(peer_id, store, kad_config)));
 
         if let Some(kad) = kademlia.as_mut() {
             for mut addr in options.bootstrap.clone() {
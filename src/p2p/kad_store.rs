@@ -0,0 +1,499 @@
+//! Kademlia record store which keeps the records in memory, optionally persisting them to the
+//! [`DataStore`] of the repo so that the stored records and provider records survive restarts.
+//!
+//! The [`RecordStore`] trait is synchronous while the [`DataStore`] is not, so the changes are
+//! written in the background in the order they were made. The expiration times are stored as
+//! wall-clock times, and the records which have expired while the node was not running are
+//! removed when loading.
+//!
+//! [`DataStore`]: crate::repo::DataStore
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::StreamExt;
+use libipld::multibase::{self, Base};
+use libp2p::kad::store::{self, MemoryStore, MemoryStoreConfig, RecordStore};
+use libp2p::kad::{ProviderRecord, Record, RecordKey as Key};
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use web_time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+use crate::repo::Repo;
+
+const RECORDS_PREFIX: &str = "/kad/records/";
+const PROVIDERS_PREFIX: &str = "/kad/providers/";
+
+/// Record store used by kademlia, selected with [`KadStoreType`](super::KadStoreType).
+pub struct KadStore {
+    memory: MemoryStore,
+    persist: Option<UnboundedSender<Change>>,
+}
+
+enum Change {
+    PutRecord(Record),
+    RemoveRecord(Key),
+    AddProvider(ProviderRecord),
+    RemoveProvider(Key, PeerId),
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredRecord {
+    value: Vec<u8>,
+    publisher: Option<PeerId>,
+    /// Expiration time in milliseconds since the unix epoch.
+    expires: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredProvider {
+    addresses: Vec<Multiaddr>,
+    /// Expiration time in milliseconds since the unix epoch.
+    expires: Option<u64>,
+}
+
+impl KadStore {
+    /// Creates a store keeping the records only in memory.
+    pub fn memory(local_id: PeerId, config: MemoryStoreConfig) -> Self {
+        KadStore {
+            memory: MemoryStore::with_config(local_id, config),
+            persist: None,
+        }
+    }
+
+    /// Creates a store persisting the records to the [`DataStore`](crate::repo::DataStore) of
+    /// the repo, loading the previously stored records which have not yet expired.
+    pub async fn persistent(
+        local_id: PeerId,
+        config: MemoryStoreConfig,
+        repo: &Repo,
+    ) -> Result<Self, Error> {
+        let mut memory = MemoryStore::with_config(local_id, config);
+        let mut expired = Vec::new();
+
+        let mut records = repo
+            .data_store()
+            .iter_prefix(RECORDS_PREFIX.as_bytes())
+            .await;
+
+        while let Some((key, value)) = records.next().await {
+            let Ok(key) = String::from_utf8(key) else {
+                continue;
+            };
+            let Some(encoded) = key.strip_prefix(RECORDS_PREFIX) else {
+                continue;
+            };
+            let Ok((_, record_key)) = multibase::decode(encoded) else {
+                continue;
+            };
+            let Ok(stored) = serde_json::from_slice::<StoredRecord>(&value) else {
+                continue;
+            };
+
+            let Some(expires) = to_instant(stored.expires) else {
+                expired.push(key);
+                continue;
+            };
+
+            let record = Record {
+                key: Key::from(record_key),
+                value: stored.value,
+                publisher: stored.publisher,
+                expires,
+            };
+
+            if let Err(e) = memory.put(record) {
+                warn!("failed to load kad record: {e}");
+            }
+        }
+
+        let mut providers = repo
+            .data_store()
+            .iter_prefix(PROVIDERS_PREFIX.as_bytes())
+            .await;
+
+        while let Some((key, value)) = providers.next().await {
+            let Ok(key) = String::from_utf8(key) else {
+                continue;
+            };
+            let Some((encoded, provider)) = key
+                .strip_prefix(PROVIDERS_PREFIX)
+                .and_then(|rest| rest.split_once('/'))
+            else {
+                continue;
+            };
+            let Ok((_, record_key)) = multibase::decode(encoded) else {
+                continue;
+            };
+            let Ok(provider) = provider.parse::<PeerId>() else {
+                continue;
+            };
+            let Ok(stored) = serde_json::from_slice::<StoredProvider>(&value) else {
+                continue;
+            };
+
+            let Some(expires) = to_instant(stored.expires) else {
+                expired.push(key);
+                continue;
+            };
+
+            let record = ProviderRecord {
+                key: Key::from(record_key),
+                provider,
+                expires,
+                addresses: stored.addresses,
+            };
+
+            if let Err(e) = memory.add_provider(record) {
+                warn!("failed to load kad provider record: {e}");
+            }
+        }
+
+        for key in expired {
+            repo.data_store().remove(key.as_bytes()).await?;
+        }
+
+        let (tx, mut rx) = unbounded();
+        let repo = repo.clone();
+
+        crate::rt::spawn(async move {
+            while let Some(change) = rx.next().await {
+                if let Err(e) = persist(&repo, change).await {
+                    warn!("failed to persist kad store change: {e}");
+                }
+            }
+        });
+
+        Ok(KadStore {
+            memory,
+            persist: Some(tx),
+        })
+    }
+
+    fn persist(&self, change: impl FnOnce() -> Change) {
+        if let Some(tx) = &self.persist {
+            let _ = tx.unbounded_send(change());
+        }
+    }
+}
+
+impl RecordStore for KadStore {
+    type RecordsIter<'a> = <MemoryStore as RecordStore>::RecordsIter<'a>;
+    type ProvidedIter<'a> = <MemoryStore as RecordStore>::ProvidedIter<'a>;
+
+    fn get(&self, k: &Key) -> Option<Cow<'_, Record>> {
+        self.memory.get(k)
+    }
+
+    fn put(&mut self, r: Record) -> store::Result<()> {
+        match self.persist.is_some() {
+            true => {
+                self.memory.put(r.clone())?;
+                self.persist(|| Change::PutRecord(r));
+                Ok(())
+            }
+            false => self.memory.put(r),
+        }
+    }
+
+    fn remove(&mut self, k: &Key) {
+        self.memory.remove(k);
+        self.persist(|| Change::RemoveRecord(k.clone()));
+    }
+
+    fn records(&self) -> Self::RecordsIter<'_> {
+        self.memory.records()
+    }
+
+    fn add_provider(&mut self, record: ProviderRecord) -> store::Result<()> {
+        if self.persist.is_none() {
+            return self.memory.add_provider(record);
+        }
+
+        let providers = |memory: &MemoryStore| {
+            memory
+                .providers(&record.key)
+                .into_iter()
+                .map(|p| p.provider)
+                .collect::<HashSet<_>>()
+        };
+
+        let before = providers(&self.memory);
+        self.memory.add_provider(record.clone())?;
+        let after = providers(&self.memory);
+
+        // the memory store only keeps the providers closest to the key, evicting the farthest one
+        for evicted in before.difference(&after) {
+            self.persist(|| Change::RemoveProvider(record.key.clone(), *evicted));
+        }
+
+        if after.contains(&record.provider) {
+            self.persist(|| Change::AddProvider(record));
+        }
+
+        Ok(())
+    }
+
+    fn providers(&self, key: &Key) -> Vec<ProviderRecord> {
+        self.memory.providers(key)
+    }
+
+    fn provided(&self) -> Self::ProvidedIter<'_> {
+        self.memory.provided()
+    }
+
+    fn remove_provider(&mut self, k: &Key, p: &PeerId) {
+        self.memory.remove_provider(k, p);
+        self.persist(|| Change::RemoveProvider(k.clone(), *p));
+    }
+}
+
+async fn persist(repo: &Repo, change: Change) -> Result<(), Error> {
+    let data_store = repo.data_store();
+
+    match change {
+        Change::PutRecord(record) => {
+            let stored = StoredRecord {
+                value: record.value,
+                publisher: record.publisher,
+                expires: from_instant(record.expires),
+            };
+            let value = serde_json::to_vec(&stored)?;
+            data_store
+                .put(record_key(&record.key).as_bytes(), &value)
+                .await
+        }
+        Change::RemoveRecord(key) => remove(repo, &record_key(&key)).await,
+        Change::AddProvider(record) => {
+            let stored = StoredProvider {
+                addresses: record.addresses,
+                expires: from_instant(record.expires),
+            };
+            let value = serde_json::to_vec(&stored)?;
+            data_store
+                .put(
+                    provider_key(&record.key, &record.provider).as_bytes(),
+                    &value,
+                )
+                .await
+        }
+        Change::RemoveProvider(key, provider) => remove(repo, &provider_key(&key, &provider)).await,
+    }
+}
+
+async fn remove(repo: &Repo, key: &str) -> Result<(), Error> {
+    // removing a key which was never stored is not an error for the kademlia store
+    match repo.data_store().contains(key.as_bytes()).await? {
+        true => repo.data_store().remove(key.as_bytes()).await,
+        false => Ok(()),
+    }
+}
+
+fn record_key(key: &Key) -> String {
+    format!(
+        "{RECORDS_PREFIX}{}",
+        multibase::encode(Base::Base32Lower, key.as_ref())
+    )
+}
+
+fn provider_key(key: &Key, provider: &PeerId) -> String {
+    format!(
+        "{PROVIDERS_PREFIX}{}/{provider}",
+        multibase::encode(Base::Base32Lower, key.as_ref())
+    )
+}
+
+/// Converts the expiration time to milliseconds since the unix epoch.
+fn from_instant(expires: Option<Instant>) -> Option<u64> {
+    let remaining = expires?.saturating_duration_since(Instant::now());
+    let at = SystemTime::now() + remaining;
+    Some(
+        at.duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64,
+    )
+}
+
+/// Converts the stored expiration time back to an `Instant`, returning `None` if it has already
+/// expired.
+fn to_instant(expires: Option<u64>) -> Option<Option<Instant>> {
+    let Some(millis) = expires else {
+        return Some(None);
+    };
+
+    let at = UNIX_EPOCH + Duration::from_millis(millis);
+    let remaining = at.duration_since(SystemTime::now()).ok()?;
+    Some(Some(Instant::now() + remaining))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use libp2p::kad::store::RecordStore;
+    use libp2p::kad::{ProviderRecord, Record, RecordKey as Key};
+    use libp2p::PeerId;
+
+    use super::{provider_key, record_key, KadStore};
+    use crate::repo::Repo;
+    use web_time::Instant;
+
+    #[tokio::test]
+    async fn records_survive_reload() {
+        let repo = Repo::new_memory();
+        let local_id = PeerId::random();
+        let provider = PeerId::random();
+
+        let mut store = KadStore::persistent(local_id, Default::default(), &repo)
+            .await
+            .unwrap();
+
+        let mut record = Record::new(Key::new(b"kept"), b"value".to_vec());
+        record.expires = Some(Instant::now() + Duration::from_secs(3600));
+        store.put(record.clone()).unwrap();
+        store
+            .put(Record::new(Key::new(b"removed"), b"value".to_vec()))
+            .unwrap();
+        store.remove(&Key::new(b"removed"));
+
+        let mut expiring = Record::new(Key::new(b"expiring"), b"value".to_vec());
+        expiring.expires = Some(Instant::now() + Duration::from_millis(100));
+        store.put(expiring).unwrap();
+
+        store
+            .add_provider(ProviderRecord::new(
+                Key::new(b"provided"),
+                local_id,
+                Vec::new(),
+            ))
+            .unwrap();
+        store
+            .add_provider(ProviderRecord::new(
+                Key::new(b"provided"),
+                provider,
+                vec!["/ip4/127.0.0.1/tcp/4001".parse().unwrap()],
+            ))
+            .unwrap();
+
+        let last = provider_key(&Key::new(b"provided"), &provider);
+        wait_for(&repo, &last).await;
+        drop(store);
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let store = KadStore::persistent(local_id, Default::default(), &repo)
+            .await
+            .unwrap();
+
+        let loaded = store.get(&Key::new(b"kept")).unwrap();
+        assert_eq!(loaded.value, record.value);
+        assert!(loaded.expires.is_some());
+        assert!(store.get(&Key::new(b"removed")).is_none());
+        assert!(store.get(&Key::new(b"expiring")).is_none());
+
+        let providers = store.providers(&Key::new(b"provided"));
+        assert_eq!(providers.len(), 2);
+        assert_eq!(store.provided().count(), 1);
+
+        // the expired record is removed from the data store when loading
+        let expired = record_key(&Key::new(b"expiring"));
+        assert!(!repo
+            .data_store()
+            .contains(expired.as_bytes())
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn removed_provider_is_not_loaded() {
+        let repo = Repo::new_memory();
+        let local_id = PeerId::random();
+        let provider = PeerId::random();
+        let key = Key::new(b"provided");
+
+        let mut store = KadStore::persistent(local_id, Default::default(), &repo)
+            .await
+            .unwrap();
+
+        store
+            .add_provider(ProviderRecord::new(key.clone(), provider, Vec::new()))
+            .unwrap();
+        store.remove_provider(&key, &provider);
+        store
+            .put(Record::new(Key::new(b"last"), b"value".to_vec()))
+            .unwrap();
+
+        wait_for(&repo, &record_key(&Key::new(b"last"))).await;
+        drop(store);
+
+        let store = KadStore::persistent(local_id, Default::default(), &repo)
+            .await
+            .unwrap();
+
+        assert!(store.providers(&key).is_empty());
+        assert!(store.get(&Key::new(b"last")).is_some());
+    }
+
+    #[tokio::test]
+    async fn evicted_providers_are_not_loaded() {
+        use libp2p::kad::store::MemoryStoreConfig;
+        use libp2p::kad::KBucketKey;
+
+        let repo = Repo::new_memory();
+        let local_id = PeerId::random();
+        let key = Key::new(b"provided");
+        let config = || MemoryStoreConfig {
+            max_providers_per_key: 1,
+            ..Default::default()
+        };
+
+        // added from the farthest to the closest, each one evicts the previous one
+        let target = KBucketKey::new(key.clone());
+        let mut providers = (0..4).map(|_| PeerId::random()).collect::<Vec<_>>();
+        providers.sort_by_key(|p| std::cmp::Reverse(KBucketKey::from(*p).distance(&target)));
+
+        let mut store = KadStore::persistent(local_id, config(), &repo)
+            .await
+            .unwrap();
+        for provider in &providers {
+            store
+                .add_provider(ProviderRecord::new(key.clone(), *provider, Vec::new()))
+                .unwrap();
+        }
+        store
+            .put(Record::new(Key::new(b"last"), b"value".to_vec()))
+            .unwrap();
+
+        wait_for(&repo, &record_key(&Key::new(b"last"))).await;
+        drop(store);
+
+        for (i, provider) in providers.iter().enumerate() {
+            let stored = repo
+                .data_store()
+                .contains(provider_key(&key, provider).as_bytes())
+                .await
+                .unwrap();
+            assert_eq!(stored, i == providers.len() - 1);
+        }
+
+        let store = KadStore::persistent(local_id, config(), &repo)
+            .await
+            .unwrap();
+        let loaded = store.providers(&key);
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(Some(&loaded[0].provider), providers.last());
+    }
+
+    async fn wait_for(repo: &Repo, key: &str) {
+        for _ in 0..100 {
+            if repo.data_store().contains(key.as_bytes()).await.unwrap() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("{key} was not persisted");
+    }
+}
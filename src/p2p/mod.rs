@@ -29,12 +29,14 @@ pub use self::behaviour::IdentifyConfiguration;
 #[cfg(feature = "beetle_bitswap")]
 pub use self::behaviour::{BitswapConfig, BitswapProtocol};
 
-pub use self::behaviour::{KadConfig, KadInserts, KadStoreConfig};
+pub use self::behaviour::{KadConfig, KadInserts, KadStoreConfig, KadStoreType};
 pub use self::behaviour::{RateLimit, RelayConfig};
+pub use self::kad_store::KadStore;
 #[cfg(not(target_arch = "wasm32"))]
 pub use self::transport::generate_cert;
 pub use self::transport::{DnsResolver, TransportConfig, UpgradeVersion};
 pub(crate) mod gossipsub;
+mod kad_store;
 mod transport;
 
 pub use addr::MultiaddrExt;
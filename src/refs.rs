@@ -45,6 +45,7 @@ pub(crate) struct IpldRefs {
     unique: bool,
     download_blocks: bool,
     exit_on_error: bool,
+    report_missing: bool,
     providers: Vec<PeerId>,
     timeout: Option<Duration>,
 }
@@ -56,6 +57,7 @@ impl Default for IpldRefs {
             unique: false,
             download_blocks: true,
             exit_on_error: false,
+            report_missing: false,
             providers: vec![],
             timeout: None,
         }
@@ -106,6 +108,13 @@ impl IpldRefs {
         self
     }
 
+    /// Yield [`IpldRefsError::BlockNotFound`] for the blocks which are not found locally or cannot
+    /// be read, and continue with the rest. Only applies along with [`IpldRefs::with_existing_blocks`].
+    pub fn with_missing_reported(mut self) -> IpldRefs {
+        self.report_missing = true;
+        self
+    }
+
     pub fn refs_of_resolved<'a, MaybeOwned, Iter>(
         self,
         repo: MaybeOwned,
@@ -154,6 +163,7 @@ where
         timeout: None,
         providers: vec![],
         exit_on_error: true,
+        report_missing: false,
     };
     iplds_refs_inner(repo, iplds, opts).map_err(|e| match e {
         IpldRefsError::Loading(e) => e,
@@ -182,6 +192,7 @@ where
         download_blocks,
         timeout,
         exit_on_error,
+        report_missing,
         providers,
     } = opts;
 
@@ -243,6 +254,9 @@ where
                             yield Err(IpldRefsError::BlockNotFound(cid.to_owned()));
                             return;
                         }
+                        if report_missing {
+                            yield Err(IpldRefsError::BlockNotFound(cid.to_owned()));
+                        }
                         continue;
                     }
                     Err(e) => {
@@ -250,6 +264,11 @@ where
                             yield Err(IpldRefsError::from(e));
                             return;
                         }
+                        if report_missing {
+                            // a block which cannot be read is as good as missing
+                            warn!(cid = %cid, source = %source, "failed to read: {}", e);
+                            yield Err(IpldRefsError::BlockNotFound(cid.to_owned()));
+                        }
                         continue;
                     }
                 }
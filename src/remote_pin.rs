@@ -0,0 +1,566 @@
+//! Client of the [IPFS Pinning Services API](https://ipfs.github.io/pinning-services-api-spec/),
+//! used to ask a remote pinning service to pin content of the node.
+//!
+//! Services are registered on the node under a name with [`Ipfs::add_remote_pinning_service`]
+//! and used through [`Ipfs::remote_pins`].
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::future::IntoFuture;
+use std::pin::pin;
+use std::time::Duration;
+
+use anyhow::Error;
+use futures::future::{self, BoxFuture, Either};
+use futures::FutureExt;
+use libipld::Cid;
+use libp2p::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use reqwest::{Client, Method, RequestBuilder, Response, Url};
+use serde::{Deserialize, Serialize};
+use tracing::Span;
+use tracing_futures::Instrument;
+use zeroize::Zeroizing;
+
+use crate::p2p::MultiaddrExt;
+use crate::Ipfs;
+
+/// Interval between the status checks while waiting for a pin to complete.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Endpoint and access token of a remote pinning service.
+#[derive(Clone)]
+pub struct RemotePinningService {
+    endpoint: Url,
+    key: Zeroizing<String>,
+    client: Client,
+}
+
+impl fmt::Debug for RemotePinningService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemotePinningService")
+            .field("endpoint", &self.endpoint.as_str())
+            .finish()
+    }
+}
+
+impl RemotePinningService {
+    /// Creates the service from its API endpoint, e.g. `https://api.pinata.cloud/psa`, and the
+    /// access token sent as bearer token.
+    pub fn new(endpoint: &str, key: impl Into<String>) -> Result<Self, Error> {
+        let mut endpoint = Url::parse(endpoint)?;
+
+        if endpoint.cannot_be_a_base() {
+            anyhow::bail!("{endpoint} is not a valid endpoint");
+        }
+
+        // the `/pins` path is appended to the endpoint, which therefore has to end with a slash
+        if !endpoint.path().ends_with('/') {
+            let path = format!("{}/", endpoint.path());
+            endpoint.set_path(&path);
+        }
+
+        Ok(Self {
+            endpoint,
+            key: Zeroizing::new(key.into()),
+            client: Client::new(),
+        })
+    }
+
+    /// Endpoint of the service
+    pub fn endpoint(&self) -> &str {
+        self.endpoint.as_str()
+    }
+
+    fn request(&self, method: Method, path: &str) -> Result<RequestBuilder, Error> {
+        let url = self.endpoint.join(path)?;
+        Ok(self
+            .client
+            .request(method, url)
+            .bearer_auth(self.key.as_str()))
+    }
+}
+
+/// Pin object of the pinning services API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemotePin {
+    /// Root of the content to pin
+    #[serde(with = "cid_string")]
+    pub cid: Cid,
+    /// Name of the pin
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Addresses of the peers providing the content
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub origins: Vec<Multiaddr>,
+    /// Arbitrary key/value metadata
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub meta: BTreeMap<String, String>,
+}
+
+/// Status of a pin request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemotePinStatus {
+    Queued,
+    Pinning,
+    Pinned,
+    Failed,
+}
+
+impl RemotePinStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RemotePinStatus::Queued => "queued",
+            RemotePinStatus::Pinning => "pinning",
+            RemotePinStatus::Pinned => "pinned",
+            RemotePinStatus::Failed => "failed",
+        }
+    }
+
+    /// Returns true if the service is done with the request, successfully or not.
+    pub fn is_final(&self) -> bool {
+        matches!(self, RemotePinStatus::Pinned | RemotePinStatus::Failed)
+    }
+}
+
+impl fmt::Display for RemotePinStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Pin request tracked by the service, the `PinStatus` object of the pinning services API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemotePinRequest {
+    /// Identifier of the request, used to query, replace or remove the pin
+    #[serde(rename = "requestid")]
+    pub request_id: String,
+    pub status: RemotePinStatus,
+    /// Time at which the request was received, as a RFC 3339 timestamp
+    pub created: String,
+    pub pin: RemotePin,
+    /// Addresses of the peers of the service which will fetch the content
+    #[serde(default)]
+    pub delegates: Vec<Multiaddr>,
+    /// Additional information provided by the service
+    #[serde(default)]
+    pub info: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct RemotePinResults {
+    count: usize,
+    results: Vec<RemotePinRequest>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetails,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetails {
+    reason: String,
+    details: Option<String>,
+}
+
+/// Returns the response if successful, or the error reported by the service.
+async fn check_response(response: Response) -> Result<Response, Error> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    match response.json::<ErrorResponse>().await {
+        Ok(ErrorResponse {
+            error:
+                ErrorDetails {
+                    reason,
+                    details: Some(details),
+                },
+        }) => anyhow::bail!("remote pinning service error ({status}): {reason}: {details}"),
+        Ok(ErrorResponse {
+            error: ErrorDetails { reason, .. },
+        }) => anyhow::bail!("remote pinning service error ({status}): {reason}"),
+        Err(_) => anyhow::bail!("remote pinning service error ({status})"),
+    }
+}
+
+/// Remote pins of a service registered on the node, returned by [`Ipfs::remote_pins`].
+#[derive(Debug, Clone)]
+pub struct RemotePins {
+    ipfs: Ipfs,
+    service: RemotePinningService,
+    span: Span,
+}
+
+impl RemotePins {
+    pub(crate) fn new(ipfs: Ipfs, service: RemotePinningService, span: Span) -> Self {
+        Self {
+            ipfs,
+            service,
+            span,
+        }
+    }
+
+    /// Asks the service to pin `cid`. The addresses of the node are sent as origins, so the
+    /// service can fetch the content directly from the node.
+    pub fn add(&self, cid: &Cid) -> RemotePinAdd {
+        RemotePinAdd::new(self.clone(), *cid, None)
+    }
+
+    /// Replaces the pin of an existing request with `cid`, e.g. a new version of the content.
+    /// The service returns a new request, which supersedes the old one.
+    pub fn replace(&self, request_id: &str, cid: &Cid) -> RemotePinAdd {
+        RemotePinAdd::new(self.clone(), *cid, Some(request_id.to_string()))
+    }
+
+    /// Lists the pin requests, the pinned ones by default.
+    pub fn list(&self) -> RemotePinList {
+        RemotePinList::new(self.clone())
+    }
+
+    /// Returns the pin request.
+    pub async fn get(&self, request_id: &str) -> Result<RemotePinRequest, Error> {
+        let span = debug_span!(parent: &self.span, "remote_pin_get", request_id);
+        async move {
+            let request = self
+                .service
+                .request(Method::GET, &format!("pins/{request_id}"))?;
+            let response = check_response(request.send().await?).await?;
+            Ok(response.json().await?)
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Removes the pin request, after which the service can delete the content.
+    pub async fn remove(&self, request_id: &str) -> Result<(), Error> {
+        let span = debug_span!(parent: &self.span, "remote_pin_remove", request_id);
+        async move {
+            let request = self
+                .service
+                .request(Method::DELETE, &format!("pins/{request_id}"))?;
+            check_response(request.send().await?).await?;
+            Ok(())
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Polls the status of the request every `interval` until the content is pinned. Returns an
+    /// error if the service failed to pin it or if it is not pinned within `timeout`.
+    pub async fn wait(
+        &self,
+        request_id: &str,
+        interval: Duration,
+        timeout: Option<Duration>,
+    ) -> Result<RemotePinRequest, Error> {
+        let poll = async {
+            loop {
+                let request = self.get(request_id).await?;
+                match request.status {
+                    RemotePinStatus::Pinned => return Ok(request),
+                    RemotePinStatus::Failed => {
+                        anyhow::bail!("remote pinning service failed to pin {}", request.pin.cid)
+                    }
+                    RemotePinStatus::Queued | RemotePinStatus::Pinning => {
+                        futures_timer::Delay::new(interval).await
+                    }
+                }
+            }
+        };
+
+        let Some(timeout) = timeout else {
+            return poll.await;
+        };
+
+        match future::select(pin!(poll), futures_timer::Delay::new(timeout)).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => {
+                anyhow::bail!("timed out waiting for the remote pin request {request_id}")
+            }
+        }
+    }
+
+    /// Addresses the service can fetch the content from.
+    async fn origins(&self) -> Result<Vec<Multiaddr>, Error> {
+        let peer_id = self.ipfs.keypair().public().to_peer_id();
+        let mut addrs = self.ipfs.listening_addresses().await?;
+        addrs.extend(self.ipfs.external_addresses().await?);
+
+        let mut origins = Vec::with_capacity(addrs.len());
+        for mut addr in addrs {
+            if addr.peer_id().is_none() {
+                addr.push(Protocol::P2p(peer_id));
+            }
+            if !origins.contains(&addr) {
+                origins.push(addr);
+            }
+        }
+
+        Ok(origins)
+    }
+}
+
+/// Adds or replaces a remote pin. Created with [`RemotePins::add`] or [`RemotePins::replace`].
+///
+/// Unless [`RemotePinAdd::background`] is set, the future completes once the content is pinned
+/// by the service.
+pub struct RemotePinAdd {
+    remote: RemotePins,
+    cid: Cid,
+    replace: Option<String>,
+    name: Option<String>,
+    meta: BTreeMap<String, String>,
+    origins: Vec<Multiaddr>,
+    background: bool,
+    poll_interval: Duration,
+    timeout: Option<Duration>,
+}
+
+impl RemotePinAdd {
+    fn new(remote: RemotePins, cid: Cid, replace: Option<String>) -> Self {
+        Self {
+            remote,
+            cid,
+            replace,
+            name: None,
+            meta: BTreeMap::new(),
+            origins: vec![],
+            background: false,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            timeout: None,
+        }
+    }
+
+    /// Name of the pin
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Key/value metadata of the pin
+    pub fn meta(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.meta.insert(key.into(), value.into());
+        self
+    }
+
+    /// Additional address the content can be fetched from, besides the addresses of the node
+    pub fn origin(mut self, addr: Multiaddr) -> Self {
+        self.origins.push(addr);
+        self
+    }
+
+    /// Returns once the request is accepted, without waiting for the content to be pinned
+    pub fn background(mut self) -> Self {
+        self.background = true;
+        self
+    }
+
+    /// Interval between status checks while waiting for the content to be pinned
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Duration to wait for the content to be pinned before returning an error. The request is
+    /// left in place on the service.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl IntoFuture for RemotePinAdd {
+    type Output = Result<RemotePinRequest, Error>;
+
+    type IntoFuture = BoxFuture<'static, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        let remote = self.remote;
+        let span = debug_span!(parent: &remote.span, "remote_pin_add", cid = %self.cid, replace = ?self.replace);
+
+        async move {
+            let mut origins = remote.origins().await?;
+            origins.extend(self.origins);
+
+            let pin = RemotePin {
+                cid: self.cid,
+                name: self.name,
+                origins,
+                meta: self.meta,
+            };
+
+            let path = match &self.replace {
+                Some(request_id) => format!("pins/{request_id}"),
+                None => "pins".to_string(),
+            };
+
+            let request = remote.service.request(Method::POST, &path)?.json(&pin);
+            let response = check_response(request.send().await?).await?;
+            let request: RemotePinRequest = response.json().await?;
+
+            // connecting to the delegates lets them fetch the content right away
+            for addr in request.delegates.clone() {
+                let ipfs = remote.ipfs.clone();
+                crate::rt::spawn(async move {
+                    if let Err(e) = ipfs.connect(addr.clone()).await {
+                        debug!("failed to connect to delegate {addr}: {e}");
+                    }
+                });
+            }
+
+            if self.background || request.status == RemotePinStatus::Pinned {
+                return Ok(request);
+            }
+
+            remote
+                .wait(&request.request_id, self.poll_interval, self.timeout)
+                .await
+        }
+        .instrument(span)
+        .boxed()
+    }
+}
+
+/// Lists remote pins. Created with [`RemotePins::list`].
+pub struct RemotePinList {
+    remote: RemotePins,
+    cids: Vec<Cid>,
+    name: Option<String>,
+    status: Vec<RemotePinStatus>,
+    limit: Option<usize>,
+}
+
+impl RemotePinList {
+    fn new(remote: RemotePins) -> Self {
+        Self {
+            remote,
+            cids: vec![],
+            name: None,
+            status: vec![],
+            limit: None,
+        }
+    }
+
+    /// Only lists the pins of the cid, can be used multiple times
+    pub fn cid(mut self, cid: &Cid) -> Self {
+        self.cids.push(*cid);
+        self
+    }
+
+    /// Only lists the pins with the exact name
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Lists the pins with the status instead of the pinned ones, can be used multiple times
+    pub fn status(mut self, status: RemotePinStatus) -> Self {
+        if !self.status.contains(&status) {
+            self.status.push(status);
+        }
+        self
+    }
+
+    /// Maximum number of pins to list
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+impl IntoFuture for RemotePinList {
+    type Output = Result<Vec<RemotePinRequest>, Error>;
+
+    type IntoFuture = BoxFuture<'static, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        let remote = self.remote;
+        let span = debug_span!(parent: &remote.span, "remote_pin_list");
+
+        async move {
+            let mut query = vec![];
+            if !self.cids.is_empty() {
+                let cids = self
+                    .cids
+                    .iter()
+                    .map(Cid::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                query.push(("cid", cids));
+            }
+            if let Some(name) = self.name {
+                query.push(("name", name));
+            }
+            if !self.status.is_empty() {
+                let status = self
+                    .status
+                    .iter()
+                    .map(RemotePinStatus::as_str)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                query.push(("status", status));
+            }
+
+            let mut pins: Vec<RemotePinRequest> = vec![];
+            let mut seen = HashSet::new();
+
+            // the results are sorted from the most recent and paginated with `before`. services
+            // differ on whether the pins created at that time are included again, so the pages are
+            // deduplicated by request id.
+            loop {
+                let remaining = self.limit.map(|limit| limit - pins.len());
+                let mut request = remote.service.request(Method::GET, "pins")?.query(&query);
+                if let Some(remaining) = remaining {
+                    request = request.query(&[("limit", remaining)]);
+                }
+                if let Some(last) = pins.last() {
+                    request = request.query(&[("before", &last.created)]);
+                }
+
+                let response = check_response(request.send().await?).await?;
+                let RemotePinResults { count, results } = response.json().await?;
+
+                let done = results.len() >= count;
+                let listed = pins.len();
+                pins.extend(
+                    results
+                        .into_iter()
+                        .filter(|request| seen.insert(request.request_id.clone())),
+                );
+
+                // a page without new pins would be requested again with the same cursor
+                let done = done || pins.len() == listed;
+
+                if done || self.limit.is_some_and(|limit| pins.len() >= limit) {
+                    break;
+                }
+            }
+
+            if let Some(limit) = self.limit {
+                pins.truncate(limit);
+            }
+
+            Ok(pins)
+        }
+        .instrument(span)
+        .boxed()
+    }
+}
+
+mod cid_string {
+    use libipld::Cid;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(cid: &Cid, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(cid)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Cid, D::Error> {
+        let cid = String::deserialize(deserializer)?;
+        Cid::try_from(cid.as_str()).map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,157 @@
+//! Group commit of the blocks put into the embedded database block stores.
+//!
+//! Committing a transaction per block is what makes the embedded databases slow for many small
+//! blocks, as each commit is synced to the disk. The puts are instead queued to a writer thread,
+//! which commits all of the blocks queued since the previous commit, up to the batch size, in a
+//! single transaction and only then answers the puts.
+//!
+//! The stores also keep the running totals of the sizes of the stored blocks in [`StoredSizes`],
+//! as summing them over the whole database is too slow to do on every gc tick.
+use super::compression;
+use crate::error::Error;
+use crate::repo::BlockPut;
+use crate::Block;
+use libipld::Cid;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{mpsc, oneshot};
+
+type Reply = oneshot::Sender<Result<BlockPut, String>>;
+
+pub(crate) struct BatchWriter {
+    sender: Option<mpsc::UnboundedSender<(Block, Reply)>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl std::fmt::Debug for BatchWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BatchWriter").finish()
+    }
+}
+
+impl BatchWriter {
+    /// Starts the writer thread, which calls `write` with at most `batch_size` blocks at a time.
+    /// `write` returns whether each of the blocks was new, and is expected to write either all of
+    /// the blocks or none of them.
+    pub fn spawn<F>(name: &str, batch_size: usize, mut write: F) -> Result<Self, Error>
+    where
+        F: FnMut(&[Block]) -> Result<Vec<BlockPut>, Error> + Send + 'static,
+    {
+        let batch_size = batch_size.max(1);
+        let (sender, mut receiver) = mpsc::unbounded_channel::<(Block, Reply)>();
+
+        let thread = std::thread::Builder::new()
+            .name(name.into())
+            .spawn(move || {
+                let mut blocks = Vec::with_capacity(batch_size);
+                let mut replies = Vec::with_capacity(batch_size);
+
+                while let Some((block, reply)) = receiver.blocking_recv() {
+                    blocks.push(block);
+                    replies.push(reply);
+
+                    while blocks.len() < batch_size {
+                        match receiver.try_recv() {
+                            Ok((block, reply)) => {
+                                blocks.push(block);
+                                replies.push(reply);
+                            }
+                            Err(_) => break,
+                        }
+                    }
+
+                    trace!(blocks = blocks.len(), "writing batch");
+
+                    match write(&blocks) {
+                        Ok(puts) => {
+                            for (reply, put) in replies.drain(..).zip(puts) {
+                                _ = reply.send(Ok(put));
+                            }
+                        }
+                        Err(e) => {
+                            let e = format!("{e:#}");
+                            for reply in replies.drain(..) {
+                                _ = reply.send(Err(e.clone()));
+                            }
+                        }
+                    }
+
+                    blocks.clear();
+                }
+            })?;
+
+        Ok(BatchWriter {
+            sender: Some(sender),
+            thread: Some(thread),
+        })
+    }
+
+    /// Queues the block for the next batch, resolving once the batch has been committed.
+    pub async fn put(&self, block: Block) -> Result<(Cid, BlockPut), Error> {
+        let cid = *block.cid();
+        let (tx, rx) = oneshot::channel();
+
+        self.sender
+            .as_ref()
+            .and_then(|sender| sender.send((block, tx)).ok())
+            .ok_or_else(|| anyhow::anyhow!("block store writer has stopped"))?;
+
+        match rx.await {
+            Ok(Ok(put)) => Ok((cid, put)),
+            Ok(Err(e)) => Err(anyhow::anyhow!("writing block {cid} failed: {e}")),
+            Err(_) => Err(anyhow::anyhow!("block store writer has stopped")),
+        }
+    }
+}
+
+impl Drop for BatchWriter {
+    fn drop(&mut self) {
+        // the thread exits once the queue is closed, releasing the database so that it can be
+        // opened again right away
+        drop(self.sender.take());
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+/// The totals of the logical and the physical sizes of the stored blocks, summed once when opening
+/// the database and updated after each committed transaction.
+#[derive(Debug, Default)]
+pub(crate) struct StoredSizes {
+    logical: AtomicUsize,
+    physical: AtomicUsize,
+}
+
+impl StoredSizes {
+    /// Returns the logical and the physical size of a stored block.
+    pub fn of(stored: &[u8]) -> (usize, usize) {
+        (compression::logical_len(stored, stored.len()), stored.len())
+    }
+
+    /// Returns the total of either the stored sizes or of the sizes of the original data.
+    pub fn total(&self, physical: bool) -> usize {
+        match physical {
+            true => self.physical.load(Ordering::Relaxed),
+            false => self.logical.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn add(&self, (logical, physical): (usize, usize)) {
+        self.logical.fetch_add(logical, Ordering::Relaxed);
+        self.physical.fetch_add(physical, Ordering::Relaxed);
+    }
+
+    pub fn sub(&self, (logical, physical): (usize, usize)) {
+        self.logical.fetch_sub(logical, Ordering::Relaxed);
+        self.physical.fetch_sub(physical, Ordering::Relaxed);
+    }
+}
+
+/// Sums the logical and the physical sizes.
+pub(crate) fn sum(sizes: impl IntoIterator<Item = (usize, usize)>) -> (usize, usize) {
+    sizes
+        .into_iter()
+        .fold((0, 0), |(logical, physical), (l, p)| {
+            (logical + l, physical + p)
+        })
+}
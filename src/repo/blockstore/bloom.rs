@@ -0,0 +1,213 @@
+//! Bloom filter answering the lookups of missing blocks without reaching another block store
+use crate::error::Error;
+use crate::repo::{BlockPut, BlockStore};
+use crate::Block;
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use libipld::Cid;
+use parking_lot::RwLock;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Block store answering [`BlockStore::contains`] and [`BlockStore::get`] for blocks which were
+/// never stored from a bloom filter, and only asking the inner block store for the blocks which
+/// might be stored.
+///
+/// The filter is filled with the blocks listed by the inner block store on [`BlockStore::init`]
+/// and with every block put afterwards, so the inner block store must not be written to other
+/// than through this one. Removed blocks cannot be cleared from the filter and are looked up from
+/// the inner block store until the filter is built again on the next start.
+///
+/// The filter is sized for `expected_blocks` with a false positive rate of 1%, which rises when
+/// more blocks are stored.
+pub struct BloomBlockStore {
+    inner: Box<dyn BlockStore>,
+    filter: RwLock<BloomFilter>,
+}
+
+impl std::fmt::Debug for BloomBlockStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BloomBlockStore")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl BloomBlockStore {
+    pub fn new(inner: Box<dyn BlockStore>, expected_blocks: usize) -> Self {
+        BloomBlockStore {
+            inner,
+            filter: RwLock::new(BloomFilter::new(expected_blocks, 0.01)),
+        }
+    }
+}
+
+struct BloomFilter {
+    bits: Vec<u64>,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    fn new(items: usize, false_positive_rate: f64) -> Self {
+        let items = items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let bit_len = (-items * false_positive_rate.ln() / (ln2 * ln2))
+            .ceil()
+            .max(64.0);
+        let hashes = ((bit_len / items) * ln2).round().clamp(1.0, 16.0) as u32;
+
+        BloomFilter {
+            bits: vec![0; (bit_len as usize + 63) / 64],
+            hashes,
+        }
+    }
+
+    /// Positions of the bits of the Cid, derived from two hashes as in "Less Hashing, Same
+    /// Performance: Building a Better Bloom Filter" by Kirsch and Mitzenmacher.
+    fn positions(&self, cid: &Cid) -> impl Iterator<Item = usize> {
+        let mut hasher = DefaultHasher::new();
+        Hash::hash(cid, &mut hasher);
+        let first = hasher.finish();
+        0xb10cu16.hash(&mut hasher);
+        let second = hasher.finish() | 1;
+
+        let bit_len = (self.bits.len() * 64) as u64;
+        (0..u64::from(self.hashes))
+            .map(move |i| (first.wrapping_add(i.wrapping_mul(second)) % bit_len) as usize)
+    }
+
+    fn insert(&mut self, cid: &Cid) {
+        for position in self.positions(cid) {
+            self.bits[position / 64] |= 1 << (position % 64);
+        }
+    }
+
+    fn may_contain(&self, cid: &Cid) -> bool {
+        self.positions(cid)
+            .all(|position| self.bits[position / 64] & (1 << (position % 64)) != 0)
+    }
+}
+
+#[async_trait]
+impl BlockStore for BloomBlockStore {
+    async fn init(&self) -> Result<(), Error> {
+        self.inner.init().await?;
+
+        let mut blocks = self.inner.list().await;
+        while let Some(cid) = blocks.next().await {
+            self.filter.write().insert(&cid);
+        }
+        Ok(())
+    }
+
+    async fn open(&self) -> Result<(), Error> {
+        self.inner.open().await
+    }
+
+    async fn contains(&self, cid: &Cid) -> Result<bool, Error> {
+        if !self.filter.read().may_contain(cid) {
+            return Ok(false);
+        }
+        self.inner.contains(cid).await
+    }
+
+    async fn get(&self, cid: &Cid) -> Result<Option<Block>, Error> {
+        if !self.filter.read().may_contain(cid) {
+            return Ok(None);
+        }
+        self.inner.get(cid).await
+    }
+
+    async fn size(&self, cid: &[Cid]) -> Result<Option<usize>, Error> {
+        self.inner.size(cid).await
+    }
+
+    async fn total_size(&self) -> Result<usize, Error> {
+        self.inner.total_size().await
+    }
+
+    async fn physical_size(&self, cid: &[Cid]) -> Result<Option<usize>, Error> {
+        self.inner.physical_size(cid).await
+    }
+
+    async fn total_physical_size(&self) -> Result<usize, Error> {
+        self.inner.total_physical_size().await
+    }
+
+    async fn put(&self, block: Block) -> Result<(Cid, BlockPut), Error> {
+        // added ahead of the put, so that the block is never reported missing once stored
+        self.filter.write().insert(block.cid());
+        self.inner.put(block).await
+    }
+
+    async fn remove(&self, cid: &Cid) -> Result<(), Error> {
+        self.inner.remove(cid).await
+    }
+
+    async fn remove_many(&self, blocks: BoxStream<'static, Cid>) -> BoxStream<'static, Cid> {
+        self.inner.remove_many(blocks).await
+    }
+
+    async fn list(&self) -> BoxStream<'static, Cid> {
+        self.inner.list().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::blockstore::memory::MemBlockStore;
+    use libipld::{
+        multihash::{Code, MultihashDigest},
+        IpldCodec,
+    };
+
+    fn block(data: &[u8]) -> Block {
+        let cid = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(data));
+        Block::new(cid, data.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        let mut filter = BloomFilter::new(1000, 0.01);
+        for i in 0..1000u32 {
+            filter.insert(block(&i.to_be_bytes()).cid());
+        }
+
+        for i in 0..1000u32 {
+            assert!(filter.may_contain(block(&i.to_be_bytes()).cid()));
+        }
+
+        let false_positives = (1000..11000u32)
+            .filter(|i| filter.may_contain(block(&i.to_be_bytes()).cid()))
+            .count();
+        assert!(false_positives < 300, "{false_positives} false positives");
+    }
+
+    #[tokio::test]
+    async fn filter_is_built_from_the_inner_store() {
+        let inner = MemBlockStore::new(Default::default());
+        let existing = block(b"existing");
+        inner.put(existing.clone()).await.unwrap();
+
+        let store = BloomBlockStore::new(Box::new(inner), 100);
+        store.init().await.unwrap();
+
+        assert!(store.contains(existing.cid()).await.unwrap());
+        assert_eq!(
+            store.get(existing.cid()).await.unwrap(),
+            Some(existing.clone())
+        );
+
+        let added = block(b"added");
+        assert!(!store.contains(added.cid()).await.unwrap());
+        store.put(added.clone()).await.unwrap();
+        assert!(store.contains(added.cid()).await.unwrap());
+
+        // still in the filter, but answered by the inner store
+        store.remove(existing.cid()).await.unwrap();
+        assert!(!store.contains(existing.cid()).await.unwrap());
+        assert_eq!(store.get(existing.cid()).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,340 @@
+//! Least recently used cache in front of another block store
+use crate::error::Error;
+use crate::repo::{BlockPut, BlockStore};
+use crate::Block;
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use libipld::Cid;
+use lru::LruCache;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// Block store keeping the most recently read and written blocks of another block store in
+/// memory, up to a total of `capacity` bytes of block data.
+///
+/// The cache is written through, so the blocks are always stored by the inner block store first.
+/// Blocks larger than the capacity are not cached. A block read from the inner block store while
+/// a block is being removed is not cached, so that a removed block is never served afterwards.
+pub struct LruBlockStore {
+    inner: Box<dyn BlockStore>,
+    cache: Arc<Mutex<LruCacheInner>>,
+}
+
+struct LruCacheInner {
+    blocks: LruCache<Cid, Block>,
+    size: usize,
+    capacity: usize,
+    // incremented on every removal, so that the blocks read from the inner block store before a
+    // removal are not cached after it
+    removals: u64,
+}
+
+impl std::fmt::Debug for LruBlockStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cache = self.cache.lock();
+        f.debug_struct("LruBlockStore")
+            .field("inner", &self.inner)
+            .field("size", &cache.size)
+            .field("capacity", &cache.capacity)
+            .finish()
+    }
+}
+
+impl LruBlockStore {
+    pub fn new(inner: Box<dyn BlockStore>, capacity: usize) -> Self {
+        let cache = LruCacheInner {
+            blocks: LruCache::unbounded(),
+            size: 0,
+            capacity,
+            removals: 0,
+        };
+
+        LruBlockStore {
+            inner,
+            cache: Arc::new(Mutex::new(cache)),
+        }
+    }
+
+    /// Returns the amount of bytes of block data held by the cache.
+    pub fn cached_size(&self) -> usize {
+        self.cache.lock().size
+    }
+}
+
+impl LruCacheInner {
+    fn insert(&mut self, block: Block) {
+        let len = block.data().len();
+        if len > self.capacity {
+            return;
+        }
+
+        if let Some(previous) = self.blocks.put(*block.cid(), block) {
+            self.size -= previous.data().len();
+        }
+        self.size += len;
+
+        while self.size > self.capacity {
+            match self.blocks.pop_lru() {
+                Some((_, evicted)) => self.size -= evicted.data().len(),
+                None => break,
+            }
+        }
+    }
+
+    fn remove(&mut self, cid: &Cid) {
+        self.removals += 1;
+        if let Some(block) = self.blocks.pop(cid) {
+            self.size -= block.data().len();
+        }
+    }
+}
+
+#[async_trait]
+impl BlockStore for LruBlockStore {
+    async fn init(&self) -> Result<(), Error> {
+        self.inner.init().await
+    }
+
+    async fn open(&self) -> Result<(), Error> {
+        self.inner.open().await
+    }
+
+    async fn contains(&self, cid: &Cid) -> Result<bool, Error> {
+        if self.cache.lock().blocks.contains(cid) {
+            return Ok(true);
+        }
+        self.inner.contains(cid).await
+    }
+
+    async fn get(&self, cid: &Cid) -> Result<Option<Block>, Error> {
+        let removals = {
+            let mut cache = self.cache.lock();
+            if let Some(block) = cache.blocks.get(cid) {
+                return Ok(Some(block.clone()));
+            }
+            cache.removals
+        };
+
+        let block = self.inner.get(cid).await?;
+        if let Some(block) = &block {
+            let mut cache = self.cache.lock();
+            // the block might have been removed since it was read
+            if cache.removals == removals {
+                cache.insert(block.clone());
+            }
+        }
+        Ok(block)
+    }
+
+    async fn size(&self, cid: &[Cid]) -> Result<Option<usize>, Error> {
+        self.inner.size(cid).await
+    }
+
+    async fn total_size(&self) -> Result<usize, Error> {
+        self.inner.total_size().await
+    }
+
+    async fn physical_size(&self, cid: &[Cid]) -> Result<Option<usize>, Error> {
+        self.inner.physical_size(cid).await
+    }
+
+    async fn total_physical_size(&self) -> Result<usize, Error> {
+        self.inner.total_physical_size().await
+    }
+
+    async fn put(&self, block: Block) -> Result<(Cid, BlockPut), Error> {
+        let put = self.inner.put(block.clone()).await?;
+        self.cache.lock().insert(block);
+        Ok(put)
+    }
+
+    async fn remove(&self, cid: &Cid) -> Result<(), Error> {
+        self.cache.lock().remove(cid);
+        let result = self.inner.remove(cid).await;
+        // evicting again, as the block could have been read and cached during the removal
+        self.cache.lock().remove(cid);
+        result
+    }
+
+    async fn remove_many(&self, blocks: BoxStream<'static, Cid>) -> BoxStream<'static, Cid> {
+        let cache = self.cache.clone();
+        // evicting ahead of the inner block store, so that a removed block is never served
+        let blocks = blocks.inspect(move |cid| cache.lock().remove(cid)).boxed();
+        let cache = self.cache.clone();
+        self.inner
+            .remove_many(blocks)
+            .await
+            .inspect(move |cid| cache.lock().remove(cid))
+            .boxed()
+    }
+
+    async fn list(&self) -> BoxStream<'static, Cid> {
+        self.inner.list().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::blockstore::memory::MemBlockStore;
+    use libipld::{
+        multihash::{Code, MultihashDigest},
+        IpldCodec,
+    };
+
+    fn block(data: &[u8]) -> Block {
+        let cid = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(data));
+        Block::new(cid, data.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used() {
+        let store = LruBlockStore::new(Box::new(MemBlockStore::new(Default::default())), 8);
+        store.init().await.unwrap();
+
+        let first = block(b"1111");
+        let second = block(b"2222");
+        let third = block(b"3333");
+        let large = block(b"too large for the cache");
+
+        store.put(first.clone()).await.unwrap();
+        store.put(second.clone()).await.unwrap();
+        assert_eq!(store.cached_size(), 8);
+
+        // reading the first block makes the second one the least recently used
+        assert_eq!(store.get(first.cid()).await.unwrap(), Some(first.clone()));
+        store.put(third.clone()).await.unwrap();
+        {
+            let cache = store.cache.lock();
+            assert!(cache.blocks.contains(first.cid()));
+            assert!(!cache.blocks.contains(second.cid()));
+            assert!(cache.blocks.contains(third.cid()));
+        }
+
+        // evicted and uncached blocks are still read from the inner block store
+        store.put(large.clone()).await.unwrap();
+        assert_eq!(store.cached_size(), 8);
+        assert_eq!(store.get(second.cid()).await.unwrap(), Some(second));
+        assert_eq!(store.get(large.cid()).await.unwrap(), Some(large));
+        assert_eq!(store.cached_size(), 8);
+    }
+
+    #[tokio::test]
+    async fn removed_blocks_are_evicted() {
+        let store = LruBlockStore::new(Box::new(MemBlockStore::new(Default::default())), 1024);
+        store.init().await.unwrap();
+
+        let first = block(b"1");
+        let second = block(b"2");
+        store.put(first.clone()).await.unwrap();
+        store.put(second.clone()).await.unwrap();
+
+        store.remove(first.cid()).await.unwrap();
+        assert!(!store.contains(first.cid()).await.unwrap());
+        assert_eq!(store.get(first.cid()).await.unwrap(), None);
+
+        let removed = store
+            .remove_many(futures::stream::iter(vec![*second.cid()]).boxed())
+            .await
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(removed, vec![*second.cid()]);
+        assert!(!store.contains(second.cid()).await.unwrap());
+        assert_eq!(store.cached_size(), 0);
+    }
+
+    /// Block store reading the blocks of another one, but returning them only once the gate is
+    /// released.
+    #[derive(Debug)]
+    struct GatedBlockStore {
+        inner: MemBlockStore,
+        gate: Arc<tokio::sync::Mutex<()>>,
+    }
+
+    #[async_trait]
+    impl BlockStore for GatedBlockStore {
+        async fn init(&self) -> Result<(), Error> {
+            self.inner.init().await
+        }
+
+        async fn open(&self) -> Result<(), Error> {
+            self.inner.open().await
+        }
+
+        async fn contains(&self, cid: &Cid) -> Result<bool, Error> {
+            self.inner.contains(cid).await
+        }
+
+        async fn get(&self, cid: &Cid) -> Result<Option<Block>, Error> {
+            let block = self.inner.get(cid).await;
+            let _gate = self.gate.lock().await;
+            block
+        }
+
+        async fn size(&self, cid: &[Cid]) -> Result<Option<usize>, Error> {
+            self.inner.size(cid).await
+        }
+
+        async fn total_size(&self) -> Result<usize, Error> {
+            self.inner.total_size().await
+        }
+
+        async fn put(&self, block: Block) -> Result<(Cid, BlockPut), Error> {
+            self.inner.put(block).await
+        }
+
+        async fn remove(&self, cid: &Cid) -> Result<(), Error> {
+            self.inner.remove(cid).await
+        }
+
+        async fn remove_many(&self, blocks: BoxStream<'static, Cid>) -> BoxStream<'static, Cid> {
+            self.inner.remove_many(blocks).await
+        }
+
+        async fn list(&self) -> BoxStream<'static, Cid> {
+            self.inner.list().await
+        }
+    }
+
+    #[tokio::test]
+    async fn blocks_read_during_removal_are_not_cached() {
+        let gate = Arc::new(tokio::sync::Mutex::new(()));
+        let inner = GatedBlockStore {
+            inner: MemBlockStore::new(Default::default()),
+            gate: gate.clone(),
+        };
+        let store = Arc::new(LruBlockStore::new(Box::new(inner), 1024));
+        store.init().await.unwrap();
+
+        for removal in [false, true] {
+            let removed = block(if removal { b"many" } else { b"one" });
+            store.inner.put(removed.clone()).await.unwrap();
+
+            // the read of the block completes only after it has been removed
+            let closed = gate.lock().await;
+            let read = tokio::spawn({
+                let store = store.clone();
+                let cid = *removed.cid();
+                async move { store.get(&cid).await }
+            });
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+            match removal {
+                false => store.remove(removed.cid()).await.unwrap(),
+                true => {
+                    store
+                        .remove_many(futures::stream::iter(vec![*removed.cid()]).boxed())
+                        .await
+                        .collect::<Vec<_>>()
+                        .await;
+                }
+            }
+            drop(closed);
+
+            assert_eq!(read.await.unwrap().unwrap(), Some(removed.clone()));
+            assert_eq!(store.get(removed.cid()).await.unwrap(), None);
+            assert_eq!(store.cached_size(), 0);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Compression of the block data written by the block stores.
+//!
+//! Compressed block data is stored with a header of the magic bytes `\0BLZ`, the algorithm and
+//! the length of the original data as a little endian `u64`, followed by the compressed data.
+//! Blocks which do not get smaller are stored as is, without the header, as are all blocks
+//! written without compression, so a block store can always read the blocks written with any
+//! other setting. As the stored bytes of a block without the header could begin with the same
+//! bytes by chance, the data read is always verified against the Cid, falling back to the stored
+//! bytes as is.
+use crate::error::Error;
+use crate::Block;
+use libipld::Cid;
+
+const MAGIC: &[u8; 4] = b"\0BLZ";
+
+/// Length of the header of the compressed block data.
+pub(crate) const HEADER_LEN: usize = MAGIC.len() + 1 + 8;
+
+// larger lengths in a header are not believed, as no block is anywhere near as large
+const MAX_ORIGINAL_LEN: u64 = 64 * 1024 * 1024;
+
+#[cfg(all(feature = "zstd_compression", not(target_arch = "wasm32")))]
+const ZSTD: u8 = 1;
+#[cfg(feature = "lz4_compression")]
+const LZ4: u8 = 2;
+
+/// Compression of the block data written to the block store.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// Blocks are written as is
+    #[default]
+    None,
+    /// [zstd](https://facebook.github.io/zstd/) at the given level, where 0 is the default level
+    /// of zstd. Requires the `zstd_compression` feature.
+    #[cfg(all(feature = "zstd_compression", not(target_arch = "wasm32")))]
+    Zstd { level: i32 },
+    /// [lz4](https://lz4.org/), faster but compressing less than zstd. Requires the
+    /// `lz4_compression` feature.
+    #[cfg(feature = "lz4_compression")]
+    Lz4,
+}
+
+impl Compression {
+    /// Returns the bytes to store for the block data, which are the data itself unless it is
+    /// compressed to fewer bytes.
+    pub(crate) fn encode(self, data: &[u8]) -> Vec<u8> {
+        let Some((algorithm, compressed)) = self.compress(data) else {
+            return data.to_vec();
+        };
+
+        if compressed.len() + HEADER_LEN >= data.len() {
+            return data.to_vec();
+        }
+
+        let mut stored = Vec::with_capacity(HEADER_LEN + compressed.len());
+        stored.extend_from_slice(MAGIC);
+        stored.push(algorithm);
+        stored.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        stored.extend_from_slice(&compressed);
+        stored
+    }
+
+    #[cfg_attr(
+        not(any(
+            all(feature = "zstd_compression", not(target_arch = "wasm32")),
+            feature = "lz4_compression"
+        )),
+        allow(unused_variables)
+    )]
+    fn compress(self, data: &[u8]) -> Option<(u8, Vec<u8>)> {
+        match self {
+            Compression::None => None,
+            #[cfg(all(feature = "zstd_compression", not(target_arch = "wasm32")))]
+            Compression::Zstd { level } => match zstd::bulk::compress(data, level) {
+                Ok(compressed) => Some((ZSTD, compressed)),
+                Err(e) => {
+                    warn!("zstd compression failed, storing the block uncompressed: {e}");
+                    None
+                }
+            },
+            #[cfg(feature = "lz4_compression")]
+            Compression::Lz4 => Some((LZ4, lz4_flex::block::compress(data))),
+        }
+    }
+}
+
+fn decompress(algorithm: u8, payload: &[u8], len: usize) -> Result<Vec<u8>, Error> {
+    #[cfg(all(feature = "zstd_compression", not(target_arch = "wasm32")))]
+    if algorithm == ZSTD {
+        return Ok(zstd::bulk::decompress(payload, len)?);
+    }
+    #[cfg(feature = "lz4_compression")]
+    if algorithm == LZ4 {
+        return Ok(lz4_flex::block::decompress(payload, len)?);
+    }
+
+    #[cfg(not(any(
+        all(feature = "zstd_compression", not(target_arch = "wasm32")),
+        feature = "lz4_compression"
+    )))]
+    let _ = (payload, len);
+
+    anyhow::bail!("unsupported compression algorithm {algorithm}, check the compression features")
+}
+
+/// Returns the algorithm and the length of the original data if the stored bytes begin with the
+/// header.
+fn header(stored: &[u8]) -> Option<(u8, usize)> {
+    let rest = stored.strip_prefix(MAGIC)?;
+    let (&algorithm, rest) = rest.split_first()?;
+    let len = u64::from_le_bytes(rest.get(..8)?.try_into().ok()?);
+    if len > MAX_ORIGINAL_LEN {
+        return None;
+    }
+    Some((algorithm, len as usize))
+}
+
+/// Returns the length of the original data of the stored bytes, given at least the first
+/// [`HEADER_LEN`] of the stored bytes and their total length.
+pub(crate) fn logical_len(stored_prefix: &[u8], stored_len: usize) -> usize {
+    match header(stored_prefix) {
+        Some((_, len)) => len,
+        None => stored_len,
+    }
+}
+
+/// Returns the block of the stored bytes, decompressing them if needed.
+pub(crate) fn decode(cid: Cid, stored: Vec<u8>) -> Result<Block, Error> {
+    let Some((algorithm, len)) = header(&stored) else {
+        return Block::new(cid, stored);
+    };
+
+    let decompressed = decompress(algorithm, &stored[HEADER_LEN..], len)
+        .map_err(|e| e.context(format!("decompressing block {cid} failed")));
+
+    match decompressed.and_then(|data| Block::new(cid, data)) {
+        Ok(block) => Ok(block),
+        // the uncompressed data happened to begin like the header
+        Err(e) => Block::new(cid, stored).map_err(|_| e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libipld::{
+        multihash::{Code, MultihashDigest},
+        IpldCodec,
+    };
+
+    fn block(data: &[u8]) -> Block {
+        let cid = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(data));
+        Block::new(cid, data.to_vec()).unwrap()
+    }
+
+    fn compressions() -> Vec<Compression> {
+        vec![
+            Compression::None,
+            #[cfg(all(feature = "zstd_compression", not(target_arch = "wasm32")))]
+            Compression::Zstd { level: 0 },
+            #[cfg(feature = "lz4_compression")]
+            Compression::Lz4,
+        ]
+    }
+
+    #[test]
+    fn roundtrip() {
+        let text = block(&b"{\"hello\": \"world\"}\n".repeat(100));
+        let short = block(b"short");
+        // begins like a header without being compressed
+        let lookalike = block(&[MAGIC.as_slice(), &[1, 5, 0, 0, 0, 0, 0, 0, 0, 0]].concat());
+
+        for compression in compressions() {
+            for block in [&text, &short] {
+                let stored = compression.encode(block.data());
+                assert_eq!(
+                    logical_len(&stored, stored.len()),
+                    block.data().len(),
+                    "{compression:?}"
+                );
+                assert_eq!(&decode(*block.cid(), stored).unwrap(), block);
+            }
+
+            let stored = compression.encode(lookalike.data());
+            assert_eq!(decode(*lookalike.cid(), stored).unwrap(), lookalike);
+
+            let stored = compression.encode(short.data());
+            assert_eq!(stored, short.data(), "incompressible blocks are kept as is");
+        }
+    }
+
+    #[cfg(feature = "lz4_compression")]
+    #[test]
+    fn compressed_data_is_verified() {
+        let text = block(&b"text ".repeat(100));
+        let mut stored = Compression::Lz4.encode(text.data());
+        assert!(stored.len() < text.data().len());
+
+        let last = stored.len() - 1;
+        stored[last] ^= 0xff;
+        assert!(decode(*text.cid(), stored).is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use super::compression::{self, Compression};
 use crate::error::Error;
 use crate::repo::paths::{block_path, filestem_to_block_cid};
 use crate::repo::{BlockPut, BlockStore};
@@ -6,6 +7,8 @@ use async_trait::async_trait;
 use futures::stream::{self, BoxStream};
 use futures::{StreamExt, TryFutureExt, TryStreamExt};
 use libipld::Cid;
+use parking_lot::Mutex;
+use std::collections::HashMap;
 use std::io::{self, ErrorKind, Read};
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -19,18 +22,35 @@ use tokio_stream::wrappers::ReadDirStream;
 #[derive(Debug)]
 pub struct FsBlockStore {
     inner: Arc<RwLock<FsBlockStoreInner>>,
+    compression: Compression,
 }
 
 #[derive(Debug)]
 struct FsBlockStoreInner {
     path: PathBuf,
+    /// Sizes of the original data of the blocks, which need the header of the compressed blocks
+    /// to be read. Loaded by the first `total_size` and kept up to date by the puts and removals.
+    logical_sizes: Mutex<Option<HashMap<Cid, usize>>>,
 }
 
 impl FsBlockStore {
     pub fn new(path: PathBuf) -> Self {
-        let inner = Arc::new(RwLock::new(FsBlockStoreInner { path }));
+        let inner = Arc::new(RwLock::new(FsBlockStoreInner {
+            path,
+            logical_sizes: Default::default(),
+        }));
+
+        FsBlockStore {
+            inner,
+            compression: Compression::None,
+        }
+    }
 
-        FsBlockStore { inner }
+    /// Compresses the blocks written from now on. Blocks are read regardless of the compression
+    /// they were written with.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
     }
 }
 
@@ -66,11 +86,21 @@ impl BlockStore for FsBlockStore {
         Ok(inner.total_size().await)
     }
 
+    async fn physical_size(&self, cid: &[Cid]) -> Result<Option<usize>, Error> {
+        let inner = &*self.inner.read().await;
+        Ok(inner.physical_size(cid).await)
+    }
+
+    async fn total_physical_size(&self) -> Result<usize, Error> {
+        let inner = &*self.inner.read().await;
+        Ok(inner.total_physical_size().await)
+    }
+
     //TODO: Allow multiple puts without holding a lock. We could probably hold a read lock instead
     //      and revert back to using a broadcast
     async fn put(&self, block: Block) -> Result<(Cid, BlockPut), Error> {
         let inner = &mut *self.inner.write().await;
-        inner.put(block).await
+        inner.put(block, self.compression).await
     }
 
     async fn remove(&self, cid: &Cid) -> Result<(), Error> {
@@ -86,6 +116,7 @@ impl BlockStore for FsBlockStore {
             for await cid in blocks
                 .map(move |cid| (cid, block_path(path.clone(), &cid)))
                 .filter_map(|(cid, path)| async move { fs::remove_file(path).await.ok().map(|_| cid) }) {
+                    inner.set_logical_size(cid, None);
                     yield cid;
                 }
         };
@@ -132,15 +163,20 @@ impl FsBlockStoreInner {
 
             let mut data = Vec::with_capacity(len as usize);
             file.read_to_end(&mut data)?;
-            let block = Block::new(cid, data)?;
+            let block = compression::decode(cid, data)?;
             Ok(Some(block))
         })
         .await?
     }
 
-    async fn put(&mut self, block: Block) -> Result<(Cid, BlockPut), Error> {
+    async fn put(
+        &mut self,
+        block: Block,
+        compression: Compression,
+    ) -> Result<(Cid, BlockPut), Error> {
         let target_path = block_path(self.path.clone(), block.cid());
         let cid = *block.cid();
+        let len = block.data().len();
 
         let je = tokio::task::spawn_blocking(move || {
             let sharded = target_path
@@ -155,11 +191,12 @@ impl FsBlockStoreInner {
                 .open(&target_path)?;
 
             let temp_path = target_path.with_extension("tmp");
+            let data = compression.encode(block.data());
 
-            match write_through_tempfile(target, &target_path, temp_path, block.data()) {
+            match write_through_tempfile(target, &target_path, temp_path, &data) {
                 Ok(()) => {
                     trace!("successfully wrote the block");
-                    Ok::<_, std::io::Error>(Ok(block.data().len()))
+                    Ok::<_, std::io::Error>(Ok(data.len()))
                 }
                 Err(e) => {
                     match std::fs::remove_file(&target_path) {
@@ -182,6 +219,7 @@ impl FsBlockStoreInner {
         match je {
             Ok(Ok(written)) => {
                 trace!(bytes = written, "block writing succeeded");
+                self.set_logical_size(cid, Some(len));
                 Ok((cid, BlockPut::NewBlock))
             }
             Ok(Err(e)) => {
@@ -200,6 +238,69 @@ impl FsBlockStoreInner {
     async fn size(&self, cids: &[Cid]) -> Option<usize> {
         let mut block_sizes = 0;
 
+        for cid in cids {
+            let cached = self
+                .logical_sizes
+                .lock()
+                .as_ref()
+                .map(|sizes| sizes.get(cid).copied());
+            match cached {
+                Some(size) => block_sizes += size.unwrap_or_default(),
+                None => {
+                    let path = block_path(self.path.clone(), cid);
+                    if let Ok(size) = logical_size(path).await {
+                        block_sizes += size;
+                    }
+                }
+            }
+        }
+
+        Some(block_sizes)
+    }
+
+    async fn total_size(&self) -> usize {
+        if let Some(sizes) = &*self.logical_sizes.lock() {
+            return sizes.values().sum();
+        }
+
+        let sizes = self
+            .list_stream()
+            .and_then(|blocks| async move {
+                let sizes = blocks
+                    .try_filter_map(|(cid, path)| async move {
+                        Ok(Some((cid, logical_size(path).await?)))
+                    })
+                    .try_collect::<HashMap<_, _>>()
+                    .await?;
+                Ok(sizes)
+            })
+            .await;
+
+        match sizes {
+            Ok(sizes) => {
+                let total = sizes.values().sum();
+                // the puts and removals are excluded by the lock held by the caller
+                *self.logical_sizes.lock() = Some(sizes);
+                total
+            }
+            Err(_) => 0,
+        }
+    }
+
+    /// Records the size of a new block, or the removal of a block with `None`, once the sizes
+    /// have been loaded.
+    fn set_logical_size(&self, cid: Cid, size: Option<usize>) {
+        if let Some(sizes) = &mut *self.logical_sizes.lock() {
+            match size {
+                Some(size) => sizes.insert(cid, size),
+                None => sizes.remove(&cid),
+            };
+        }
+    }
+
+    async fn physical_size(&self, cids: &[Cid]) -> Option<usize> {
+        let mut block_sizes = 0;
+
         for cid in cids {
             let path = block_path(self.path.clone(), cid);
             if let Ok(size) = fs::metadata(path).await.map(|m| m.len() as usize) {
@@ -210,7 +311,7 @@ impl FsBlockStoreInner {
         Some(block_sizes)
     }
 
-    async fn total_size(&self) -> usize {
+    async fn total_physical_size(&self) -> usize {
         self.list_stream()
             .and_then(|blocks| async move {
                 let list = blocks
@@ -230,7 +331,9 @@ impl FsBlockStoreInner {
     async fn remove(&mut self, cid: &Cid) -> Result<(), Error> {
         let path = block_path(self.path.clone(), cid);
         trace!(cid = %cid, "removing block after synchronizing");
-        fs::remove_file(path).await.map_err(anyhow::Error::from)
+        fs::remove_file(path).await?;
+        self.set_logical_size(*cid, None);
+        Ok(())
     }
 
     async fn list_stream(
@@ -280,6 +383,22 @@ impl FsBlockStoreInner {
     }
 }
 
+/// Returns the size of the original data of the block file, which is given in the header of
+/// compressed blocks.
+async fn logical_size(path: PathBuf) -> Result<usize, io::Error> {
+    use tokio::io::AsyncReadExt;
+
+    let mut file = fs::File::open(path).await?;
+    let len = file.metadata().await?.len() as usize;
+    let mut prefix = Vec::with_capacity(compression::HEADER_LEN);
+    (&mut file)
+        .take(compression::HEADER_LEN as u64)
+        .read_to_end(&mut prefix)
+        .await?;
+
+    Ok(compression::logical_len(&prefix, len))
+}
+
 fn write_through_tempfile(
     target: std::fs::File,
     target_path: impl AsRef<std::path::Path>,
@@ -502,4 +621,97 @@ mod tests {
         single.remove(&cid).await.unwrap();
         assert_eq!(single.list().await.collect::<Vec<_>>().await.len(), 0);
     }
+
+    #[tokio::test]
+    async fn cached_sizes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = FsBlockStore::new(tmp.path().into());
+        store.init().await.unwrap();
+
+        let blocks = [&b"1"[..], b"22", b"333", b"4444"].map(|data| {
+            let cid = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(data));
+            Block::new(cid, data.to_vec()).unwrap()
+        });
+
+        store.put(blocks[0].clone()).await.unwrap();
+        store.put(blocks[1].clone()).await.unwrap();
+        assert_eq!(store.total_size().await.unwrap(), 3);
+        assert!(store.inner.read().await.logical_sizes.lock().is_some());
+
+        // the sizes are kept up to date without reading the files again
+        store.put(blocks[1].clone()).await.unwrap();
+        store.put(blocks[2].clone()).await.unwrap();
+        store.put(blocks[3].clone()).await.unwrap();
+        assert_eq!(store.total_size().await.unwrap(), 10);
+        assert_eq!(
+            store
+                .size(&[*blocks[1].cid(), *blocks[3].cid()])
+                .await
+                .unwrap(),
+            Some(6)
+        );
+
+        store.remove(blocks[0].cid()).await.unwrap();
+        let removed = store
+            .remove_many(stream::iter(vec![*blocks[2].cid()]).boxed())
+            .await
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(removed, vec![*blocks[2].cid()]);
+        assert_eq!(store.total_size().await.unwrap(), 6);
+        assert_eq!(store.size(&[*blocks[2].cid()]).await.unwrap(), Some(0));
+
+        // matches the sizes read from the files
+        let reopened = FsBlockStore::new(tmp.path().into());
+        assert_eq!(reopened.total_size().await.unwrap(), 6);
+    }
+
+    #[cfg(feature = "lz4_compression")]
+    #[tokio::test]
+    async fn compressed_blocks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = FsBlockStore::new(tmp.path().into()).with_compression(Compression::Lz4);
+        store.init().await.unwrap();
+
+        let data = b"{\"compressible\": true}\n".repeat(100);
+        let cid = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(&data));
+        let compressible = Block::new(cid, data).unwrap();
+        let data = b"1".to_vec();
+        let cid = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(&data));
+        let small = Block::new(cid, data).unwrap();
+
+        store.put(compressible.clone()).await.unwrap();
+        store.put(small.clone()).await.unwrap();
+
+        let stored = std::fs::read(block_path(tmp.path().into(), compressible.cid())).unwrap();
+        assert!(stored.len() < compressible.data().len());
+
+        assert_eq!(
+            store.get(compressible.cid()).await.unwrap(),
+            Some(compressible.clone())
+        );
+        assert_eq!(store.get(small.cid()).await.unwrap(), Some(small.clone()));
+
+        assert_eq!(
+            store.size(&[*compressible.cid()]).await.unwrap(),
+            Some(compressible.data().len())
+        );
+        assert_eq!(
+            store.physical_size(&[*compressible.cid()]).await.unwrap(),
+            Some(stored.len())
+        );
+        assert_eq!(
+            store.total_size().await.unwrap(),
+            compressible.data().len() + 1
+        );
+        assert_eq!(store.total_physical_size().await.unwrap(), stored.len() + 1);
+
+        // blocks written with compression are still read without it
+        let store = FsBlockStore::new(tmp.path().into());
+        store.init().await.unwrap();
+        assert_eq!(
+            store.get(compressible.cid()).await.unwrap(),
+            Some(compressible)
+        );
+    }
 }
@@ -1,5 +1,18 @@
 #[cfg(not(target_arch = "wasm32"))]
+#[cfg(any(feature = "redb_block_store", feature = "sled_block_store"))]
+mod batch;
+pub mod bloom;
+pub mod cache;
+pub mod compression;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod flatfs;
 #[cfg(target_arch = "wasm32")]
 pub mod idb;
 pub mod memory;
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "redb_block_store")]
+pub mod redb;
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "sled_block_store")]
+pub mod sled;
+pub mod tiered;
@@ -0,0 +1,451 @@
+//! [`redb`] backed block store
+use super::batch::{self, BatchWriter, StoredSizes};
+use super::compression::{self, Compression};
+use crate::error::Error;
+use crate::repo::{BlockPut, BlockStore};
+use crate::Block;
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use libipld::Cid;
+use redb::{Database, ReadableTable, TableDefinition};
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+
+const BLOCKTABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("blocks");
+
+/// Block store keeping the blocks in a single [`redb`] database file, keyed by the bytes of their
+/// Cid.
+///
+/// Puts are committed in batches of up to `batch_size` blocks and removals of
+/// [`BlockStore::remove_many`] in transactions of the same size.
+///
+/// [`redb`]: https://github.com/cberner/redb
+#[derive(Debug)]
+pub struct RedbBlockStore {
+    path: PathBuf,
+    batch_size: usize,
+    compression: Compression,
+    db: OnceLock<Arc<Database>>,
+    writer: OnceLock<BatchWriter>,
+    stored: Arc<StoredSizes>,
+}
+
+impl RedbBlockStore {
+    pub fn new(path: PathBuf, batch_size: usize) -> Self {
+        RedbBlockStore {
+            path,
+            batch_size: batch_size.max(1),
+            compression: Compression::None,
+            db: Default::default(),
+            writer: Default::default(),
+            stored: Default::default(),
+        }
+    }
+
+    /// Compresses the blocks written from now on. Blocks are read regardless of the compression
+    /// they were written with.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    fn get_db(&self) -> Arc<Database> {
+        let db = self.db.get().cloned();
+        db.expect("Blockstore to be initialized")
+    }
+
+    /// Sums the sizes of the blocks, either as stored or of their original data.
+    async fn sizes(&self, cids: &[Cid], physical: bool) -> Result<usize, Error> {
+        let db = self.get_db();
+        let cids = cids.to_vec();
+        tokio::task::spawn_blocking(move || {
+            let read_tx = db.begin_read()?;
+            let table = read_tx.open_table(BLOCKTABLE)?;
+            let mut size = 0;
+            for cid in cids {
+                if let Some(item) = table.get(cid.to_bytes().as_slice())? {
+                    size += stored_size(item.value(), physical);
+                }
+            }
+            Ok::<_, Error>(size)
+        })
+        .await?
+    }
+}
+
+fn stored_size(stored: &[u8], physical: bool) -> usize {
+    match physical {
+        true => stored.len(),
+        false => compression::logical_len(stored, stored.len()),
+    }
+}
+
+fn write_blocks(
+    db: &Database,
+    blocks: &[Block],
+    compression: Compression,
+    stored: &StoredSizes,
+) -> Result<Vec<BlockPut>, Error> {
+    let tx = db.begin_write()?;
+    let mut puts = Vec::with_capacity(blocks.len());
+    let mut written = Vec::with_capacity(blocks.len());
+    {
+        let mut table = tx.open_table(BLOCKTABLE)?;
+        for block in blocks {
+            let key = block.cid().to_bytes();
+            // the same block can be queued more than once within a batch
+            if table.get(key.as_slice())?.is_some() {
+                puts.push(BlockPut::Existed);
+                continue;
+            }
+            let data = compression.encode(block.data());
+            table.insert(key.as_slice(), data.as_slice())?;
+            written.push(StoredSizes::of(&data));
+            puts.push(BlockPut::NewBlock);
+        }
+    }
+    tx.commit()?;
+    stored.add(batch::sum(written));
+    Ok(puts)
+}
+
+fn remove_blocks(db: &Database, cids: &[Cid], stored: &StoredSizes) -> Result<Vec<Cid>, Error> {
+    let tx = db.begin_write()?;
+    let mut removed = Vec::with_capacity(cids.len());
+    let mut freed = Vec::with_capacity(cids.len());
+    {
+        let mut table = tx.open_table(BLOCKTABLE)?;
+        for cid in cids {
+            if let Some(value) = table.remove(cid.to_bytes().as_slice())? {
+                freed.push(StoredSizes::of(value.value()));
+                removed.push(*cid);
+            }
+        }
+    }
+    tx.commit()?;
+    stored.sub(batch::sum(freed));
+    Ok(removed)
+}
+
+#[async_trait]
+impl BlockStore for RedbBlockStore {
+    async fn init(&self) -> Result<(), Error> {
+        tokio::fs::create_dir_all(&self.path).await?;
+
+        let path = self.path.join("ipfs_blockstore.db");
+        let stored = self.stored.clone();
+        let db = tokio::task::spawn_blocking(move || {
+            let db = Arc::new(Database::create(path)?);
+            let initial_tx = db.begin_write()?;
+            {
+                _ = initial_tx.open_table(BLOCKTABLE)?;
+            }
+            initial_tx.commit()?;
+
+            {
+                let read_tx = db.begin_read()?;
+                let table = read_tx.open_table(BLOCKTABLE)?;
+                for item in table.iter()? {
+                    let (_, value) = item?;
+                    stored.add(StoredSizes::of(value.value()));
+                }
+            }
+
+            Ok::<_, Error>(db)
+        })
+        .await??;
+
+        let writer = BatchWriter::spawn("redb-blockstore", self.batch_size, {
+            let db = db.clone();
+            let compression = self.compression;
+            let stored = self.stored.clone();
+            move |blocks| write_blocks(&db, blocks, compression, &stored)
+        })?;
+
+        match (self.db.set(db), self.writer.set(writer)) {
+            (Ok(()), Ok(())) => Ok(()),
+            _ => Err(anyhow::anyhow!("failed to init redb")),
+        }
+    }
+
+    async fn open(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn contains(&self, cid: &Cid) -> Result<bool, Error> {
+        let db = self.get_db();
+        let key = cid.to_bytes();
+        tokio::task::spawn_blocking(move || {
+            let read_tx = db.begin_read()?;
+            let table = read_tx.open_table(BLOCKTABLE)?;
+            let item = table.get(key.as_slice())?;
+            Ok::<_, Error>(item.is_some())
+        })
+        .await?
+    }
+
+    async fn get(&self, cid: &Cid) -> Result<Option<Block>, Error> {
+        let db = self.get_db();
+        let cid = *cid;
+        tokio::task::spawn_blocking(move || {
+            let read_tx = db.begin_read()?;
+            let table = read_tx.open_table(BLOCKTABLE)?;
+            let Some(item) = table.get(cid.to_bytes().as_slice())? else {
+                return Ok(None);
+            };
+            let block = compression::decode(cid, item.value().to_vec())?;
+            Ok::<_, Error>(Some(block))
+        })
+        .await?
+    }
+
+    async fn size(&self, cids: &[Cid]) -> Result<Option<usize>, Error> {
+        self.sizes(cids, false).await.map(Some)
+    }
+
+    async fn total_size(&self) -> Result<usize, Error> {
+        Ok(self.stored.total(false))
+    }
+
+    async fn physical_size(&self, cids: &[Cid]) -> Result<Option<usize>, Error> {
+        self.sizes(cids, true).await.map(Some)
+    }
+
+    async fn total_physical_size(&self) -> Result<usize, Error> {
+        Ok(self.stored.total(true))
+    }
+
+    async fn put(&self, block: Block) -> Result<(Cid, BlockPut), Error> {
+        let writer = self.writer.get().expect("Blockstore to be initialized");
+        writer.put(block).await
+    }
+
+    async fn remove(&self, cid: &Cid) -> Result<(), Error> {
+        let db = self.get_db();
+        let cid = *cid;
+        let stored = self.stored.clone();
+        let removed =
+            tokio::task::spawn_blocking(move || remove_blocks(&db, &[cid], &stored)).await??;
+        match removed.is_empty() {
+            false => Ok(()),
+            true => Err(std::io::Error::from(std::io::ErrorKind::NotFound).into()),
+        }
+    }
+
+    async fn remove_many(&self, blocks: BoxStream<'static, Cid>) -> BoxStream<'static, Cid> {
+        let db = self.get_db();
+        let batch_size = self.batch_size;
+        let stored = self.stored.clone();
+
+        let stream = async_stream::stream! {
+            let mut batches = blocks.chunks(batch_size);
+            while let Some(cids) = batches.next().await {
+                let db = db.clone();
+                let stored = stored.clone();
+                match tokio::task::spawn_blocking(move || remove_blocks(&db, &cids, &stored)).await {
+                    Ok(Ok(removed)) => {
+                        for cid in removed {
+                            yield cid;
+                        }
+                    }
+                    Ok(Err(e)) => error!("removing blocks failed: {e}"),
+                    Err(e) => error!("removing blocks failed: {e}"),
+                }
+            }
+        };
+
+        stream.boxed()
+    }
+
+    async fn list(&self) -> BoxStream<'static, Cid> {
+        use tokio_stream::wrappers::UnboundedReceiverStream;
+        let span = tracing::Span::current();
+        let db = self.get_db();
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let _t = tokio::task::spawn_blocking(move || {
+            let span = tracing::trace_span!(parent: &span, "blocking");
+            let _g = span.enter();
+            let Ok(read_tx) = db.begin_read() else {
+                return;
+            };
+            let Ok(table) = read_tx.open_table(BLOCKTABLE) else {
+                return;
+            };
+            let Ok(iter) = table.iter() else {
+                return;
+            };
+
+            for (key, _) in iter.filter_map(|res| res.ok()) {
+                if let Ok(cid) = Cid::try_from(key.value()) {
+                    _ = tx.send(cid);
+                }
+            }
+        });
+
+        UnboundedReceiverStream::new(rx).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libipld::{
+        multihash::{Code, MultihashDigest},
+        IpldCodec,
+    };
+
+    fn block(data: &[u8]) -> Block {
+        let cid = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(data));
+        Block::new(cid, data.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_redb_blockstore() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = RedbBlockStore::new(tmp.path().into(), 16);
+        let block = block(b"1");
+        let cid = *block.cid();
+
+        store.init().await.unwrap();
+        store.open().await.unwrap();
+
+        assert!(!store.contains(&cid).await.unwrap());
+        assert_eq!(store.get(&cid).await.unwrap(), None);
+        assert!(store.remove(&cid).await.is_err());
+
+        assert_eq!(
+            store.put(block.clone()).await.unwrap(),
+            (cid, BlockPut::NewBlock)
+        );
+        assert_eq!(
+            store.put(block.clone()).await.unwrap(),
+            (cid, BlockPut::Existed)
+        );
+        assert!(store.contains(&cid).await.unwrap());
+        assert_eq!(store.get(&cid).await.unwrap(), Some(block.clone()));
+        assert_eq!(store.size(&[cid]).await.unwrap(), Some(1));
+        assert_eq!(store.total_size().await.unwrap(), 1);
+
+        store.remove(&cid).await.unwrap();
+        assert!(!store.contains(&cid).await.unwrap());
+        assert_eq!(store.get(&cid).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn concurrent_puts_are_batched() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = Arc::new(RedbBlockStore::new(tmp.path().into(), 8));
+        store.init().await.unwrap();
+
+        // every block is put twice, only one of which may create it
+        let puts = (0..100u32)
+            .chain(0..100u32)
+            .map(|i| {
+                let store = store.clone();
+                tokio::spawn(async move { store.put(block(&i.to_be_bytes())).await })
+            })
+            .collect::<Vec<_>>();
+
+        let mut new_blocks = 0;
+        for put in puts {
+            if let (_, BlockPut::NewBlock) = put.await.unwrap().unwrap() {
+                new_blocks += 1;
+            }
+        }
+        assert_eq!(new_blocks, 100);
+        assert_eq!(store.list().await.count().await, 100);
+
+        let cids = (0..50u32)
+            .map(|i| *block(&i.to_be_bytes()).cid())
+            .collect::<Vec<_>>();
+        let removed = store
+            .remove_many(futures::stream::iter(cids.clone()).boxed())
+            .await
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(removed, cids);
+        assert_eq!(store.list().await.count().await, 50);
+    }
+
+    #[tokio::test]
+    async fn blocks_persist() {
+        let tmp = tempfile::tempdir().unwrap();
+        let block = block(b"persisted");
+
+        let store = RedbBlockStore::new(tmp.path().into(), 16);
+        store.init().await.unwrap();
+        store.put(block.clone()).await.unwrap();
+        drop(store);
+
+        let store = RedbBlockStore::new(tmp.path().into(), 16);
+        store.init().await.unwrap();
+        assert_eq!(store.get(block.cid()).await.unwrap(), Some(block));
+    }
+
+    #[tokio::test]
+    async fn running_totals() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (a, b, c) = (block(b"a"), block(b"bb"), block(b"ccc"));
+
+        let store = RedbBlockStore::new(tmp.path().into(), 16);
+        store.init().await.unwrap();
+        for block in [&a, &b, &c, &a] {
+            store.put(block.clone()).await.unwrap();
+        }
+        assert_eq!(store.total_size().await.unwrap(), 6);
+        assert_eq!(store.total_physical_size().await.unwrap(), 6);
+
+        store.remove(a.cid()).await.unwrap();
+        let removed = store
+            .remove_many(futures::stream::iter([*b.cid(), *a.cid()]).boxed())
+            .await
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(removed, [*b.cid()]);
+        assert_eq!(store.total_size().await.unwrap(), 3);
+        drop(store);
+
+        // summed again when opening the database
+        let store = RedbBlockStore::new(tmp.path().into(), 16);
+        store.init().await.unwrap();
+        assert_eq!(store.total_size().await.unwrap(), 3);
+        assert_eq!(store.total_physical_size().await.unwrap(), 3);
+    }
+
+    #[cfg(feature = "zstd_compression")]
+    #[tokio::test]
+    async fn compressed_blocks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = RedbBlockStore::new(tmp.path().into(), 16)
+            .with_compression(Compression::Zstd { level: 0 });
+        store.init().await.unwrap();
+
+        let compressible = block(&b"{\"compressible\": true}\n".repeat(100));
+        let small = block(b"1");
+        store.put(compressible.clone()).await.unwrap();
+        store.put(small.clone()).await.unwrap();
+
+        assert_eq!(
+            store.get(compressible.cid()).await.unwrap(),
+            Some(compressible.clone())
+        );
+        assert_eq!(store.get(small.cid()).await.unwrap(), Some(small));
+
+        let logical = compressible.data().len();
+        let physical = store
+            .physical_size(&[*compressible.cid()])
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(physical < logical);
+        assert_eq!(
+            store.size(&[*compressible.cid()]).await.unwrap(),
+            Some(logical)
+        );
+        assert_eq!(store.total_size().await.unwrap(), logical + 1);
+        assert_eq!(store.total_physical_size().await.unwrap(), physical + 1);
+    }
+}
@@ -0,0 +1,468 @@
+//! [`sled`] backed block store
+use super::batch::{self, BatchWriter, StoredSizes};
+use super::compression::{self, Compression};
+use crate::error::Error;
+use crate::repo::{BlockPut, BlockStore};
+use crate::Block;
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use libipld::Cid;
+use sled::transaction::{ConflictableTransactionError, TransactionError};
+use sled::{Config as DbConfig, Db, Mode as DbMode, Tree};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+/// Block store keeping the blocks in a [`sled`] tree, keyed by the bytes of their Cid.
+///
+/// Puts are committed in batches of up to `batch_size` blocks and removals of
+/// [`BlockStore::remove_many`] in transactions of the same size.
+///
+/// [`sled`]: https://github.com/spacejam/sled
+#[derive(Debug)]
+pub struct SledBlockStore {
+    path: PathBuf,
+    batch_size: usize,
+    compression: Compression,
+    // kept for flushing the whole database on drop
+    db: OnceLock<Db>,
+    blocks: OnceLock<Tree>,
+    writer: OnceLock<BatchWriter>,
+    stored: Arc<StoredSizes>,
+}
+
+impl SledBlockStore {
+    pub fn new(path: PathBuf, batch_size: usize) -> Self {
+        SledBlockStore {
+            path,
+            batch_size: batch_size.max(1),
+            compression: Compression::None,
+            db: Default::default(),
+            blocks: Default::default(),
+            writer: Default::default(),
+            stored: Default::default(),
+        }
+    }
+
+    /// Compresses the blocks written from now on. Blocks are read regardless of the compression
+    /// they were written with.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    fn get_tree(&self) -> Tree {
+        let tree = self.blocks.get().cloned();
+        tree.expect("Blockstore to be initialized")
+    }
+
+    /// Sums the sizes of the blocks, either as stored or of their original data.
+    async fn sizes(&self, cids: &[Cid], physical: bool) -> Result<usize, Error> {
+        let tree = self.get_tree();
+        let cids = cids.to_vec();
+        tokio::task::spawn_blocking(move || {
+            let mut size = 0;
+            for cid in cids {
+                if let Some(data) = tree.get(cid.to_bytes())? {
+                    size += stored_size(&data, physical);
+                }
+            }
+            Ok::<_, Error>(size)
+        })
+        .await?
+    }
+}
+
+fn stored_size(stored: &[u8], physical: bool) -> usize {
+    match physical {
+        true => stored.len(),
+        false => compression::logical_len(stored, stored.len()),
+    }
+}
+
+/// Opens the database, waiting for a while for the database of a store dropped just before to be
+/// released, as sled releases the lock of the database only once its background work completes.
+fn open(path: &Path) -> Result<Db, sled::Error> {
+    let mut attempts = 0;
+    loop {
+        match DbConfig::new()
+            .mode(DbMode::HighThroughput)
+            .path(path)
+            .open()
+        {
+            // sled reports the lock failure with the kind `Other`
+            Err(sled::Error::Io(e))
+                if e.to_string().starts_with("could not acquire lock") && attempts < 50 =>
+            {
+                attempts += 1;
+                std::thread::sleep(std::time::Duration::from_millis(20));
+            }
+            res => return res,
+        }
+    }
+}
+
+fn write_blocks(
+    tree: &Tree,
+    blocks: &[Block],
+    compression: Compression,
+    stored: &StoredSizes,
+) -> Result<Vec<BlockPut>, Error> {
+    // compressed ahead of the transaction, which can run more than once
+    let encoded = blocks
+        .iter()
+        .map(|block| compression.encode(block.data()))
+        .collect::<Vec<_>>();
+
+    let puts = tree.transaction(|tx| {
+        // the closure is retried on conflicts, so the outcome is collected anew every time
+        let mut puts = Vec::with_capacity(blocks.len());
+        let mut written = Vec::with_capacity(blocks.len());
+        for (block, data) in blocks.iter().zip(&encoded) {
+            let key = block.cid().to_bytes();
+            // the same block can be queued more than once within a batch
+            if tx.get(&key)?.is_some() {
+                puts.push(BlockPut::Existed);
+                continue;
+            }
+            tx.insert(key, data.as_slice())?;
+            written.push(StoredSizes::of(data));
+            puts.push(BlockPut::NewBlock);
+        }
+        Ok::<_, ConflictableTransactionError<Error>>((puts, written))
+    });
+
+    match puts {
+        Ok((puts, written)) => {
+            stored.add(batch::sum(written));
+            Ok(puts)
+        }
+        Err(TransactionError::Abort(e)) => Err(e),
+        Err(TransactionError::Storage(e)) => Err(e.into()),
+    }
+}
+
+fn remove_blocks(tree: &Tree, cids: &[Cid], stored: &StoredSizes) -> Result<Vec<Cid>, Error> {
+    let removed = tree.transaction(|tx| {
+        let mut removed = Vec::with_capacity(cids.len());
+        let mut freed = Vec::with_capacity(cids.len());
+        for cid in cids {
+            if let Some(data) = tx.remove(cid.to_bytes())? {
+                freed.push(StoredSizes::of(&data));
+                removed.push(*cid);
+            }
+        }
+        Ok::<_, ConflictableTransactionError<Error>>((removed, freed))
+    });
+
+    match removed {
+        Ok((removed, freed)) => {
+            stored.sub(batch::sum(freed));
+            Ok(removed)
+        }
+        Err(TransactionError::Abort(e)) => Err(e),
+        Err(TransactionError::Storage(e)) => Err(e.into()),
+    }
+}
+
+#[async_trait]
+impl BlockStore for SledBlockStore {
+    async fn init(&self) -> Result<(), Error> {
+        let path = self.path.clone();
+        let stored = self.stored.clone();
+        let (db, tree) = tokio::task::spawn_blocking(move || {
+            let db = open(&path)?;
+            let tree = db.open_tree("blocks")?;
+
+            for data in tree.iter().values() {
+                stored.add(StoredSizes::of(&data?));
+            }
+
+            Ok::<_, Error>((db, tree))
+        })
+        .await??;
+
+        let writer = BatchWriter::spawn("sled-blockstore", self.batch_size, {
+            let tree = tree.clone();
+            let compression = self.compression;
+            let stored = self.stored.clone();
+            move |blocks| write_blocks(&tree, blocks, compression, &stored)
+        })?;
+
+        match (
+            self.db.set(db),
+            self.blocks.set(tree),
+            self.writer.set(writer),
+        ) {
+            (Ok(()), Ok(()), Ok(())) => Ok(()),
+            _ => Err(anyhow::anyhow!("failed to init sled")),
+        }
+    }
+
+    async fn open(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn contains(&self, cid: &Cid) -> Result<bool, Error> {
+        let tree = self.get_tree();
+        let key = cid.to_bytes();
+        tokio::task::spawn_blocking(move || tree.contains_key(key).map_err(Error::from)).await?
+    }
+
+    async fn get(&self, cid: &Cid) -> Result<Option<Block>, Error> {
+        let tree = self.get_tree();
+        let cid = *cid;
+        tokio::task::spawn_blocking(move || {
+            let Some(data) = tree.get(cid.to_bytes())? else {
+                return Ok(None);
+            };
+            let block = compression::decode(cid, data.to_vec())?;
+            Ok::<_, Error>(Some(block))
+        })
+        .await?
+    }
+
+    async fn size(&self, cids: &[Cid]) -> Result<Option<usize>, Error> {
+        self.sizes(cids, false).await.map(Some)
+    }
+
+    async fn total_size(&self) -> Result<usize, Error> {
+        Ok(self.stored.total(false))
+    }
+
+    async fn physical_size(&self, cids: &[Cid]) -> Result<Option<usize>, Error> {
+        self.sizes(cids, true).await.map(Some)
+    }
+
+    async fn total_physical_size(&self) -> Result<usize, Error> {
+        Ok(self.stored.total(true))
+    }
+
+    async fn put(&self, block: Block) -> Result<(Cid, BlockPut), Error> {
+        let writer = self.writer.get().expect("Blockstore to be initialized");
+        writer.put(block).await
+    }
+
+    async fn remove(&self, cid: &Cid) -> Result<(), Error> {
+        let tree = self.get_tree();
+        let cid = *cid;
+        let stored = self.stored.clone();
+        let removed =
+            tokio::task::spawn_blocking(move || remove_blocks(&tree, &[cid], &stored)).await??;
+        match removed.is_empty() {
+            false => Ok(()),
+            true => Err(std::io::Error::from(std::io::ErrorKind::NotFound).into()),
+        }
+    }
+
+    async fn remove_many(&self, blocks: BoxStream<'static, Cid>) -> BoxStream<'static, Cid> {
+        let tree = self.get_tree();
+        let batch_size = self.batch_size;
+        let stored = self.stored.clone();
+
+        let stream = async_stream::stream! {
+            let mut batches = blocks.chunks(batch_size);
+            while let Some(cids) = batches.next().await {
+                let tree = tree.clone();
+                let stored = stored.clone();
+                match tokio::task::spawn_blocking(move || remove_blocks(&tree, &cids, &stored)).await {
+                    Ok(Ok(removed)) => {
+                        for cid in removed {
+                            yield cid;
+                        }
+                    }
+                    Ok(Err(e)) => error!("removing blocks failed: {e}"),
+                    Err(e) => error!("removing blocks failed: {e}"),
+                }
+            }
+        };
+
+        stream.boxed()
+    }
+
+    async fn list(&self) -> BoxStream<'static, Cid> {
+        let tree = self.get_tree();
+
+        let stream = async_stream::stream! {
+            for key in tree.iter().keys().flatten() {
+                if let Ok(cid) = Cid::try_from(key.as_ref()) {
+                    yield cid;
+                }
+            }
+        };
+
+        stream.boxed()
+    }
+}
+
+impl Drop for SledBlockStore {
+    fn drop(&mut self) {
+        if let Some(db) = self.db.get() {
+            if let Err(e) = db.flush() {
+                error!("flushing the block store failed: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libipld::{
+        multihash::{Code, MultihashDigest},
+        IpldCodec,
+    };
+    use std::sync::Arc;
+
+    fn block(data: &[u8]) -> Block {
+        let cid = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(data));
+        Block::new(cid, data.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sled_blockstore() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledBlockStore::new(tmp.path().into(), 16);
+        let block = block(b"1");
+        let cid = *block.cid();
+
+        store.init().await.unwrap();
+        store.open().await.unwrap();
+
+        assert!(!store.contains(&cid).await.unwrap());
+        assert_eq!(store.get(&cid).await.unwrap(), None);
+        assert!(store.remove(&cid).await.is_err());
+
+        assert_eq!(
+            store.put(block.clone()).await.unwrap(),
+            (cid, BlockPut::NewBlock)
+        );
+        assert_eq!(
+            store.put(block.clone()).await.unwrap(),
+            (cid, BlockPut::Existed)
+        );
+        assert!(store.contains(&cid).await.unwrap());
+        assert_eq!(store.get(&cid).await.unwrap(), Some(block.clone()));
+        assert_eq!(store.size(&[cid]).await.unwrap(), Some(1));
+        assert_eq!(store.total_size().await.unwrap(), 1);
+
+        store.remove(&cid).await.unwrap();
+        assert!(!store.contains(&cid).await.unwrap());
+        assert_eq!(store.get(&cid).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn concurrent_puts_are_batched() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = Arc::new(SledBlockStore::new(tmp.path().into(), 8));
+        store.init().await.unwrap();
+
+        // every block is put twice, only one of which may create it
+        let puts = (0..100u32)
+            .chain(0..100u32)
+            .map(|i| {
+                let store = store.clone();
+                tokio::spawn(async move { store.put(block(&i.to_be_bytes())).await })
+            })
+            .collect::<Vec<_>>();
+
+        let mut new_blocks = 0;
+        for put in puts {
+            if let (_, BlockPut::NewBlock) = put.await.unwrap().unwrap() {
+                new_blocks += 1;
+            }
+        }
+        assert_eq!(new_blocks, 100);
+        assert_eq!(store.list().await.count().await, 100);
+
+        let cids = (0..50u32)
+            .map(|i| *block(&i.to_be_bytes()).cid())
+            .collect::<Vec<_>>();
+        let removed = store
+            .remove_many(futures::stream::iter(cids.clone()).boxed())
+            .await
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(removed, cids);
+        assert_eq!(store.list().await.count().await, 50);
+    }
+
+    #[tokio::test]
+    async fn blocks_persist() {
+        let tmp = tempfile::tempdir().unwrap();
+        let block = block(b"persisted");
+
+        let store = SledBlockStore::new(tmp.path().into(), 16);
+        store.init().await.unwrap();
+        store.put(block.clone()).await.unwrap();
+        drop(store);
+
+        let store = SledBlockStore::new(tmp.path().into(), 16);
+        store.init().await.unwrap();
+        assert_eq!(store.get(block.cid()).await.unwrap(), Some(block));
+    }
+
+    #[tokio::test]
+    async fn running_totals() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (a, b, c) = (block(b"a"), block(b"bb"), block(b"ccc"));
+
+        let store = SledBlockStore::new(tmp.path().into(), 16);
+        store.init().await.unwrap();
+        for block in [&a, &b, &c, &a] {
+            store.put(block.clone()).await.unwrap();
+        }
+        assert_eq!(store.total_size().await.unwrap(), 6);
+        assert_eq!(store.total_physical_size().await.unwrap(), 6);
+
+        store.remove(a.cid()).await.unwrap();
+        let removed = store
+            .remove_many(futures::stream::iter([*b.cid(), *a.cid()]).boxed())
+            .await
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(removed, [*b.cid()]);
+        assert_eq!(store.total_size().await.unwrap(), 3);
+        drop(store);
+
+        // summed again when opening the database
+        let store = SledBlockStore::new(tmp.path().into(), 16);
+        store.init().await.unwrap();
+        assert_eq!(store.total_size().await.unwrap(), 3);
+        assert_eq!(store.total_physical_size().await.unwrap(), 3);
+    }
+
+    #[cfg(feature = "zstd_compression")]
+    #[tokio::test]
+    async fn compressed_blocks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = SledBlockStore::new(tmp.path().into(), 16)
+            .with_compression(Compression::Zstd { level: 0 });
+        store.init().await.unwrap();
+
+        let compressible = block(&b"{\"compressible\": true}\n".repeat(100));
+        let small = block(b"1");
+        store.put(compressible.clone()).await.unwrap();
+        store.put(small.clone()).await.unwrap();
+
+        assert_eq!(
+            store.get(compressible.cid()).await.unwrap(),
+            Some(compressible.clone())
+        );
+        assert_eq!(store.get(small.cid()).await.unwrap(), Some(small));
+
+        let logical = compressible.data().len();
+        let physical = store
+            .physical_size(&[*compressible.cid()])
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(physical < logical);
+        assert_eq!(
+            store.size(&[*compressible.cid()]).await.unwrap(),
+            Some(logical)
+        );
+        assert_eq!(store.total_size().await.unwrap(), logical + 1);
+        assert_eq!(store.total_physical_size().await.unwrap(), physical + 1);
+    }
+}
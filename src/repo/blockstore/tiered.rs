@@ -0,0 +1,219 @@
+//! Two tiers of block stores, a writable primary one and a read-only secondary one
+use crate::error::Error;
+use crate::repo::{BlockPut, BlockStore};
+use crate::Block;
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use libipld::Cid;
+use std::collections::HashSet;
+
+/// Block store writing to a primary block store and reading the blocks missing from it from a
+/// secondary block store, such as one shared by several nodes over a network file system.
+///
+/// The secondary block store is only read from: blocks are put into and removed from the primary
+/// block store only, and [`BlockStore::total_size`] only counts the primary block store, which is
+/// what the storage limits of the repo apply to. Blocks read from the secondary block store can
+/// optionally be copied into the primary one with [`TieredBlockStore::copy_on_read`].
+#[derive(Debug)]
+pub struct TieredBlockStore {
+    primary: Box<dyn BlockStore>,
+    secondary: Box<dyn BlockStore>,
+    copy_on_read: bool,
+}
+
+impl TieredBlockStore {
+    pub fn new(primary: Box<dyn BlockStore>, secondary: Box<dyn BlockStore>) -> Self {
+        TieredBlockStore {
+            primary,
+            secondary,
+            copy_on_read: false,
+        }
+    }
+
+    /// Copies the blocks read from the secondary block store into the primary block store.
+    /// Defaults to false.
+    pub fn copy_on_read(mut self, copy: bool) -> Self {
+        self.copy_on_read = copy;
+        self
+    }
+
+    /// Sums the sizes of the blocks from the tier holding each of them.
+    async fn sizes(&self, cids: &[Cid], physical: bool) -> Result<Option<usize>, Error> {
+        let mut primary = Vec::with_capacity(cids.len());
+        let mut secondary = vec![];
+        for cid in cids {
+            match self.primary.contains(cid).await? {
+                true => primary.push(*cid),
+                false => secondary.push(*cid),
+            }
+        }
+
+        let (primary, secondary) = match physical {
+            true => (
+                self.primary.physical_size(&primary).await?,
+                self.secondary.physical_size(&secondary).await?,
+            ),
+            false => (
+                self.primary.size(&primary).await?,
+                self.secondary.size(&secondary).await?,
+            ),
+        };
+
+        Ok(primary
+            .zip(secondary)
+            .map(|(primary, secondary)| primary + secondary))
+    }
+}
+
+#[async_trait]
+impl BlockStore for TieredBlockStore {
+    async fn init(&self) -> Result<(), Error> {
+        self.primary.init().await?;
+        self.secondary.init().await
+    }
+
+    async fn open(&self) -> Result<(), Error> {
+        self.primary.open().await?;
+        self.secondary.open().await
+    }
+
+    async fn contains(&self, cid: &Cid) -> Result<bool, Error> {
+        if self.primary.contains(cid).await? {
+            return Ok(true);
+        }
+        self.secondary.contains(cid).await
+    }
+
+    async fn get(&self, cid: &Cid) -> Result<Option<Block>, Error> {
+        if let Some(block) = self.primary.get(cid).await? {
+            return Ok(Some(block));
+        }
+
+        let block = self.secondary.get(cid).await?;
+        if let (Some(block), true) = (&block, self.copy_on_read) {
+            if let Err(e) = self.primary.put(block.clone()).await {
+                warn!(%cid, "copying block from the secondary block store failed: {e}");
+            }
+        }
+        Ok(block)
+    }
+
+    async fn size(&self, cid: &[Cid]) -> Result<Option<usize>, Error> {
+        self.sizes(cid, false).await
+    }
+
+    async fn total_size(&self) -> Result<usize, Error> {
+        self.primary.total_size().await
+    }
+
+    async fn physical_size(&self, cid: &[Cid]) -> Result<Option<usize>, Error> {
+        self.sizes(cid, true).await
+    }
+
+    async fn total_physical_size(&self) -> Result<usize, Error> {
+        self.primary.total_physical_size().await
+    }
+
+    async fn put(&self, block: Block) -> Result<(Cid, BlockPut), Error> {
+        self.primary.put(block).await
+    }
+
+    async fn remove(&self, cid: &Cid) -> Result<(), Error> {
+        self.primary.remove(cid).await
+    }
+
+    async fn remove_many(&self, blocks: BoxStream<'static, Cid>) -> BoxStream<'static, Cid> {
+        self.primary.remove_many(blocks).await
+    }
+
+    async fn list(&self) -> BoxStream<'static, Cid> {
+        let primary = self.primary.list().await;
+        let secondary = self.secondary.list().await;
+
+        let stream = async_stream::stream! {
+            let mut listed = HashSet::new();
+            for await cid in primary {
+                listed.insert(cid);
+                yield cid;
+            }
+            for await cid in secondary {
+                if !listed.contains(&cid) {
+                    yield cid;
+                }
+            }
+        };
+
+        stream.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::blockstore::memory::MemBlockStore;
+    use libipld::{
+        multihash::{Code, MultihashDigest},
+        IpldCodec,
+    };
+
+    fn block(data: &[u8]) -> Block {
+        let cid = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(data));
+        Block::new(cid, data.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reads_fall_back_to_the_secondary_store() {
+        let secondary = MemBlockStore::new(Default::default());
+        let shared = block(b"shared");
+        secondary.put(shared.clone()).await.unwrap();
+
+        let store = TieredBlockStore::new(
+            Box::new(MemBlockStore::new(Default::default())),
+            Box::new(secondary),
+        );
+        store.init().await.unwrap();
+
+        let local = block(b"local");
+        store.put(local.clone()).await.unwrap();
+
+        assert!(store.contains(shared.cid()).await.unwrap());
+        assert_eq!(store.get(shared.cid()).await.unwrap(), Some(shared.clone()));
+        assert_eq!(
+            store.size(&[*shared.cid(), *local.cid()]).await.unwrap(),
+            Some(11)
+        );
+        assert_eq!(store.total_size().await.unwrap(), 5);
+
+        let mut listed = store.list().await.collect::<Vec<_>>().await;
+        listed.sort();
+        let mut expected = vec![*shared.cid(), *local.cid()];
+        expected.sort();
+        assert_eq!(listed, expected);
+
+        // the secondary store is never written to
+        assert!(store.remove(shared.cid()).await.is_err());
+        assert!(store.contains(shared.cid()).await.unwrap());
+        store.remove(local.cid()).await.unwrap();
+        assert!(!store.contains(local.cid()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn copy_on_read() {
+        let secondary = MemBlockStore::new(Default::default());
+        let shared = block(b"shared");
+        secondary.put(shared.clone()).await.unwrap();
+
+        let store = TieredBlockStore::new(
+            Box::new(MemBlockStore::new(Default::default())),
+            Box::new(secondary),
+        )
+        .copy_on_read(true);
+        store.init().await.unwrap();
+
+        assert!(!store.primary.contains(shared.cid()).await.unwrap());
+        assert_eq!(store.get(shared.cid()).await.unwrap(), Some(shared.clone()));
+        assert!(store.primary.contains(shared.cid()).await.unwrap());
+        assert_eq!(store.total_size().await.unwrap(), 6);
+    }
+}
@@ -329,6 +329,59 @@ macro_rules! pinstore_interface_tests {
                 // go-ipfs it's different than path resolving
                 assert_eq!(e.to_string(), "already pinned recursively");
             }
+
+            #[tokio::test]
+            async fn update_recursive_pin() {
+                use libipld::multihash::{Code, MultihashDigest};
+
+                let repo = DSTestContext::with($factory).await;
+
+                let cid = |data: &[u8]| Cid::new_v1(0x55, Code::Sha2_256.digest(data));
+                let (old, new) = (cid(b"old"), cid(b"new"));
+                let (shared, removed, added) = (cid(b"shared"), cid(b"removed"), cid(b"added"));
+
+                repo.insert_recursive_pin(
+                    &old,
+                    futures::stream::iter(vec![Ok(shared), Ok(removed)]).boxed(),
+                )
+                .await
+                .unwrap();
+
+                repo.update_recursive_pin(&old, &[shared, removed], &new, &[shared, added])
+                    .await
+                    .unwrap();
+
+                assert!(repo
+                    .query(vec![new], Some(PinMode::Recursive))
+                    .await
+                    .is_ok());
+                assert!(!repo.is_pinned(&old).await.unwrap());
+                assert!(!repo.is_pinned(&removed).await.unwrap());
+                assert!(repo.is_pinned(&shared).await.unwrap());
+                assert!(repo.is_pinned(&added).await.unwrap());
+
+                // the old version is no longer pinned, and nothing changes
+                assert!(repo
+                    .update_recursive_pin(&old, &[shared, removed], &new, &[shared, added])
+                    .await
+                    .is_err());
+                assert!(repo
+                    .query(vec![new], Some(PinMode::Recursive))
+                    .await
+                    .is_ok());
+                assert!(repo.is_pinned(&shared).await.unwrap());
+
+                // the blocks are no longer pinned after removing the new version
+                repo.remove_recursive_pin(
+                    &new,
+                    futures::stream::iter(vec![Ok(shared), Ok(added)]).boxed(),
+                )
+                .await
+                .unwrap();
+
+                let pins = repo.list(None).await.try_collect::<Vec<_>>().await.unwrap();
+                assert!(pins.is_empty(), "{:?}", pins);
+            }
         }
     };
 }
@@ -126,7 +126,11 @@ impl FsDataStore {
         if path.is_dir() {
             return Ok(None);
         }
-        tokio::fs::read(path).await.map(Some)
+        match tokio::fs::read(path).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
     }
 }
 
@@ -214,6 +218,19 @@ impl DataStore for FsDataStore {
         let data_path = self.path.join("data");
         build_kv(&data_path, &data_path)
     }
+
+    async fn iter_prefix(&self, prefix: &[u8]) -> BoxStream<'static, (Vec<u8>, Vec<u8>)> {
+        let data_path = self.path.join("data");
+        // only the directory of the segments of the prefix preceding the last one is walked
+        let dir = match String::from_utf8_lossy(prefix).rsplit_once('/') {
+            Some((dir, _)) => data_path.join(dir.trim_start_matches('/')),
+            None => data_path.clone(),
+        };
+        let prefix = prefix.to_vec();
+        build_kv(&data_path, dir)
+            .filter(move |(key, _)| futures::future::ready(key.starts_with(&prefix)))
+            .boxed()
+    }
 }
 
 // PinStore is a trait from ipfs::repo implemented on FsDataStore defined at ipfs::repo::fs or
@@ -291,7 +308,7 @@ impl PinStore for FsDataStore {
 
         let permit = Semaphore::acquire_owned(Arc::clone(&self.lock)).await?;
 
-        let mut path = pin_path(self.path.join("pins"), target);
+        let path = pin_path(self.path.join("pins"), target);
 
         let span = tracing::Span::current();
 
@@ -299,48 +316,7 @@ impl PinStore for FsDataStore {
             let _permit = permit; // again move to the threadpool thread
             let _entered = span.enter();
 
-            std::fs::create_dir_all(path.parent().expect("shard parent has to exist"))?;
-            let count = set.len();
-            let cids = set.into_iter().map(|cid| cid.to_string());
-
-            path.set_extension("recursive_temp");
-
-            let file = std::fs::File::create(&path)?;
-
-            match sync_write_recursive_pin(file, count, cids) {
-                Ok(_) => {
-                    let final_path = path.with_extension("recursive");
-                    std::fs::rename(&path, final_path)?
-                }
-                Err(e) => {
-                    let removed = std::fs::remove_file(&path);
-
-                    match removed {
-                        Ok(_) => debug!("cleaned up ok after botched recursive pin write"),
-                        Err(e) => warn!("failed to cleanup temporary file: {}", e),
-                    }
-
-                    return Err(e);
-                }
-            }
-
-            // if we got this far, we have now written and renamed the recursive_temp into place.
-            // now we just need to remove the direct pin, if it exists
-
-            path.set_extension("direct");
-
-            match std::fs::remove_file(&path) {
-                Ok(_) => { /* good */ }
-                Err(e) if e.kind() == std::io::ErrorKind::NotFound => { /* good as well */ }
-                Err(e) => {
-                    warn!(
-                        "failed to remove direct pin when adding recursive {:?}: {}",
-                        path, e
-                    );
-                }
-            }
-
-            Ok::<_, Error>(())
+            sync_insert_recursive_pin(path, set)
         })
         .await??;
 
@@ -435,6 +411,52 @@ impl PinStore for FsDataStore {
         Ok(())
     }
 
+    async fn update_recursive_pin(
+        &self,
+        old: &Cid,
+        _: &[Cid],
+        new: &Cid,
+        new_referenced: &[Cid],
+    ) -> Result<(), Error> {
+        let set = new_referenced
+            .iter()
+            .copied()
+            .collect::<std::collections::BTreeSet<_>>();
+
+        let permit = Semaphore::acquire_owned(Arc::clone(&self.lock)).await?;
+
+        let (old, new) = (*old, *new);
+        let mut old_path = pin_path(self.path.join("pins"), &old);
+        let mut new_path = pin_path(self.path.join("pins"), &new);
+
+        let span = tracing::Span::current();
+
+        // no other pin is written while holding the permit, though both of the pins remain if
+        // interrupted by a crash in between
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let _entered = span.enter();
+
+            if sync_read_direct_or_recursive(&mut old_path) != Some(PinMode::Recursive) {
+                anyhow::bail!("{old} is not pinned recursively");
+            }
+
+            if sync_read_direct_or_recursive(&mut new_path) == Some(PinMode::Recursive) {
+                anyhow::bail!("{new} is already pinned recursively");
+            }
+
+            sync_insert_recursive_pin(new_path, set)?;
+
+            old_path.set_extension("recursive");
+            std::fs::remove_file(&old_path)?;
+
+            Ok(())
+        })
+        .await??;
+
+        Ok(())
+    }
+
     async fn list(
         &self,
         requirement: Option<PinMode>,
@@ -737,6 +759,55 @@ fn sync_read_direct_or_recursive(block_path: &mut PathBuf) -> Option<PinMode> {
     None
 }
 
+/// Writes the recursive pin file of the pin at `path` in place, replacing the direct pin if any.
+fn sync_insert_recursive_pin(
+    mut path: PathBuf,
+    set: std::collections::BTreeSet<Cid>,
+) -> Result<(), Error> {
+    std::fs::create_dir_all(path.parent().expect("shard parent has to exist"))?;
+    let count = set.len();
+    let cids = set.into_iter().map(|cid| cid.to_string());
+
+    path.set_extension("recursive_temp");
+
+    let file = std::fs::File::create(&path)?;
+
+    match sync_write_recursive_pin(file, count, cids) {
+        Ok(_) => {
+            let final_path = path.with_extension("recursive");
+            std::fs::rename(&path, final_path)?
+        }
+        Err(e) => {
+            let removed = std::fs::remove_file(&path);
+
+            match removed {
+                Ok(_) => debug!("cleaned up ok after botched recursive pin write"),
+                Err(e) => warn!("failed to cleanup temporary file: {}", e),
+            }
+
+            return Err(e);
+        }
+    }
+
+    // if we got this far, we have now written and renamed the recursive_temp into place.
+    // now we just need to remove the direct pin, if it exists
+
+    path.set_extension("direct");
+
+    match std::fs::remove_file(&path) {
+        Ok(_) => { /* good */ }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => { /* good as well */ }
+        Err(e) => {
+            warn!(
+                "failed to remove direct pin when adding recursive {:?}: {}",
+                path, e
+            );
+        }
+    }
+
+    Ok(())
+}
+
 fn sync_write_recursive_pin(
     file: std::fs::File,
     count: usize,
@@ -802,4 +873,49 @@ mod test {
         drop(store);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn missing_key_is_none() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let store = FsDataStore::new(tmp.path().into());
+
+        store.init().await.unwrap();
+        store.open().await.unwrap();
+
+        // same as the other data stores, which the mfs relies on to create the root on first use
+        assert_eq!(store.get(b"/local/filesroot").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn iter_prefix() {
+        use futures::StreamExt;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let store = FsDataStore::new(tmp.path().into());
+
+        store.init().await.unwrap();
+        store.open().await.unwrap();
+
+        for key in ["/a/b/1", "/a/b/2", "/a/bc", "/a/c/1", "/d"] {
+            store.put(key.as_bytes(), b"value").await.unwrap();
+        }
+
+        let keys = |prefix: &'static str| {
+            let store = &store;
+            async move {
+                let mut keys = store
+                    .iter_prefix(prefix.as_bytes())
+                    .await
+                    .map(|(key, _)| String::from_utf8(key).unwrap())
+                    .collect::<Vec<_>>()
+                    .await;
+                keys.sort();
+                keys
+            }
+        };
+
+        assert_eq!(keys("/a/b/").await, ["/a/b/1", "/a/b/2"]);
+        assert_eq!(keys("/a/b").await, ["/a/b/1", "/a/b/2", "/a/bc"]);
+        assert_eq!(keys("/x/").await, Vec::<String>::new());
+    }
 }
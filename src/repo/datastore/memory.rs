@@ -103,6 +103,77 @@ impl MemDataStore {
             Entry::Vacant(_) => Err(anyhow::anyhow!("not pinned")),
         }
     }
+
+    fn insert_recursive(
+        g: &mut OwnedMutexGuard<HashMap<Vec<u8>, Vec<u8>>>,
+        target: &Cid,
+        refs: &[Cid],
+    ) -> Result<(), Error> {
+        // this must fail if it is already fully pinned
+        Self::insert_pin(g, target, &PinKind::RecursiveIntention)?;
+
+        let target_v1 = if target.version() == cid::Version::V1 {
+            target.to_owned()
+        } else {
+            // this is one more allocation
+            Cid::new_v1(target.codec(), target.hash().to_owned())
+        };
+
+        let kind = PinKind::IndirectFrom(&target_v1);
+        for next in refs {
+            // no rollback, nothing
+            Self::insert_pin(g, next, &kind)?;
+        }
+
+        let kind = PinKind::Recursive(refs.len() as u64);
+        Self::insert_pin(g, target, &kind)?;
+
+        Ok(())
+    }
+
+    fn remove_recursive(
+        g: &mut OwnedMutexGuard<HashMap<Vec<u8>, Vec<u8>>>,
+        target: &Cid,
+        refs: &[Cid],
+    ) -> Result<(), Error> {
+        let doc: PinDocument = match g.get(&target.to_bytes()) {
+            Some(raw) => serde_json::from_slice(raw)?,
+            // well we know it's not pinned at all but this is the general error message
+            None => return Err(anyhow::anyhow!("not pinned or pinned indirectly")),
+        };
+
+        let kind = match doc.pick_kind() {
+            Some(Ok(kind @ PinKind::Recursive(_)))
+            | Some(Ok(kind @ PinKind::RecursiveIntention)) => kind,
+            Some(Ok(PinKind::Direct)) => {
+                Self::remove_pin(g, target, &PinKind::Direct)?;
+                return Ok(());
+            }
+            Some(Ok(PinKind::IndirectFrom(cid))) => {
+                return Err(anyhow::anyhow!("pinned indirectly through {}", cid))
+            }
+            // same here as above with the same message
+            _ => return Err(anyhow::anyhow!("not pinned or pinned indirectly")),
+        };
+
+        // this must fail if it is already fully pinned
+        Self::remove_pin(g, target, &kind.as_ref())?;
+
+        let target_v1 = if target.version() == cid::Version::V1 {
+            target.to_owned()
+        } else {
+            // this is one more allocation
+            Cid::new_v1(target.codec(), target.hash().to_owned())
+        };
+
+        let kind = PinKind::IndirectFrom(&target_v1);
+        for next in refs {
+            // no rollback, nothing
+            Self::remove_pin(g, next, &kind)?;
+        }
+
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -137,89 +208,51 @@ impl PinStore for MemDataStore {
     async fn insert_recursive_pin(
         &self,
         target: &Cid,
-        mut refs: crate::repo::References<'_>,
+        refs: crate::repo::References<'_>,
     ) -> Result<(), Error> {
         use futures::stream::TryStreamExt;
 
-        let mut g = Mutex::lock_owned(Arc::clone(&self.pin)).await;
-
-        // this must fail if it is already fully pinned
-        Self::insert_pin(&mut g, target, &PinKind::RecursiveIntention)?;
-
-        let target_v1 = if target.version() == cid::Version::V1 {
-            target.to_owned()
-        } else {
-            // this is one more allocation
-            Cid::new_v1(target.codec(), target.hash().to_owned())
-        };
-
         // collect these before even if they are many ... not sure if this is a good idea but, the
         // inmem version doesn't need to be all that great. this could be for nothing, if the root
         // was already pinned.
+        let refs = refs.try_collect::<Vec<_>>().await?;
 
-        let mut count = 0;
-        let kind = PinKind::IndirectFrom(&target_v1);
-        while let Some(next) = refs.try_next().await? {
-            // no rollback, nothing
-            Self::insert_pin(&mut g, &next, &kind)?;
-            count += 1;
-        }
-
-        let kind = PinKind::Recursive(count as u64);
-        Self::insert_pin(&mut g, target, &kind)?;
-
-        Ok(())
+        let mut g = Mutex::lock_owned(Arc::clone(&self.pin)).await;
+        Self::insert_recursive(&mut g, target, &refs)
     }
 
     async fn remove_recursive_pin(
         &self,
         target: &Cid,
-        mut refs: crate::repo::References<'_>,
+        refs: crate::repo::References<'_>,
     ) -> Result<(), Error> {
         use futures::TryStreamExt;
 
-        let mut g = Mutex::lock_owned(Arc::clone(&self.pin)).await;
-
-        let doc: PinDocument = match g.get(&target.to_bytes()) {
-            Some(raw) => match serde_json::from_slice(raw) {
-                Ok(doc) => doc,
-                Err(e) => return Err(e.into()),
-            },
-            // well we know it's not pinned at all but this is the general error message
-            None => return Err(anyhow::anyhow!("not pinned or pinned indirectly")),
-        };
+        let refs = refs.try_collect::<Vec<_>>().await?;
 
-        let kind = match doc.pick_kind() {
-            Some(Ok(kind @ PinKind::Recursive(_)))
-            | Some(Ok(kind @ PinKind::RecursiveIntention)) => kind,
-            Some(Ok(PinKind::Direct)) => {
-                Self::remove_pin(&mut g, target, &PinKind::Direct)?;
-                return Ok(());
-            }
-            Some(Ok(PinKind::IndirectFrom(cid))) => {
-                return Err(anyhow::anyhow!("pinned indirectly through {}", cid))
-            }
-            // same here as above with the same message
-            _ => return Err(anyhow::anyhow!("not pinned or pinned indirectly")),
-        };
+        let mut g = Mutex::lock_owned(Arc::clone(&self.pin)).await;
+        Self::remove_recursive(&mut g, target, &refs)
+    }
 
-        // this must fail if it is already fully pinned
-        Self::remove_pin(&mut g, target, &kind.as_ref())?;
+    async fn update_recursive_pin(
+        &self,
+        old: &Cid,
+        old_referenced: &[Cid],
+        new: &Cid,
+        new_referenced: &[Cid],
+    ) -> Result<(), Error> {
+        let mut g = Mutex::lock_owned(Arc::clone(&self.pin)).await;
 
-        let target_v1 = if target.version() == cid::Version::V1 {
-            target.to_owned()
-        } else {
-            // this is one more allocation
-            Cid::new_v1(target.codec(), target.hash().to_owned())
-        };
+        // the documents are restored if either of the pins fails to be updated
+        let backup = g.clone();
+        let result = Self::insert_recursive(&mut g, new, new_referenced)
+            .and_then(|_| Self::remove_recursive(&mut g, old, old_referenced));
 
-        let kind = PinKind::IndirectFrom(&target_v1);
-        while let Some(next) = refs.try_next().await? {
-            // no rollback, nothing
-            Self::remove_pin(&mut g, &next, &kind)?;
+        if result.is_err() {
+            *g = backup;
         }
 
-        Ok(())
+        result
     }
 
     async fn list(
@@ -361,6 +394,22 @@ impl DataStore for MemDataStore {
 
         stream.boxed()
     }
+
+    async fn iter_prefix(
+        &self,
+        prefix: &[u8],
+    ) -> futures::stream::BoxStream<'static, (Vec<u8>, Vec<u8>)> {
+        let list = self
+            .inner
+            .lock()
+            .await
+            .iter()
+            .filter(|(k, _)| k.starts_with(prefix))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect::<Vec<_>>();
+
+        futures::stream::iter(list).boxed()
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -163,6 +163,52 @@ impl DataStore for RedbDataStore {
 
         UnboundedReceiverStream::new(rx).boxed()
     }
+
+    async fn iter_prefix(
+        &self,
+        prefix: &[u8],
+    ) -> futures::stream::BoxStream<'static, (Vec<u8>, Vec<u8>)> {
+        use tokio_stream::wrappers::UnboundedReceiverStream;
+        let span = tracing::Span::current();
+        let db = self.get_db();
+        let prefix = prefix.to_vec();
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let _t = tokio::task::spawn_blocking(move || {
+            let span = tracing::trace_span!(parent: &span, "blocking");
+            let _g = span.enter();
+            let read_tx = match db.begin_read() {
+                Ok(r) => r,
+                Err(_) => {
+                    return;
+                }
+            };
+            let table = match read_tx.open_table(DATATABLE) {
+                Ok(r) => r,
+                Err(_) => {
+                    return;
+                }
+            };
+
+            let iter = match table.range(prefix.as_slice()..) {
+                Ok(r) => r,
+                Err(_) => {
+                    return;
+                }
+            };
+
+            for (k, v) in iter.filter_map(|res| res.ok()) {
+                let (key, val) = (k.value(), v.value());
+                if !key.starts_with(&prefix) {
+                    break;
+                }
+                _ = tx.send((key.to_vec(), val.to_vec()));
+            }
+        });
+
+        UnboundedReceiverStream::new(rx).boxed()
+    }
 }
 
 #[async_trait]
@@ -350,6 +396,86 @@ impl PinStore for RedbDataStore {
         .await?
     }
 
+    async fn update_recursive_pin(
+        &self,
+        old: &Cid,
+        old_referenced: &[Cid],
+        new: &Cid,
+        new_referenced: &[Cid],
+    ) -> Result<(), Error> {
+        let old_set = old_referenced.iter().copied().collect::<BTreeSet<_>>();
+        let new_set = new_referenced.iter().copied().collect::<BTreeSet<_>>();
+
+        let old = old.to_owned();
+        let new = new.to_owned();
+        let db = self.get_db().to_owned();
+
+        let span = tracing::Span::current();
+
+        tokio::task::spawn_blocking(move || {
+            let span = tracing::trace_span!(parent: &span, "blocking");
+            let _g = span.enter();
+
+            // nothing is written unless the transaction is committed
+            let tx = db.begin_write()?;
+            {
+                let mut table = tx.open_table(PINTABLE)?;
+
+                match get_pinned_mode(Either::Right(&mut table), &old)? {
+                    Some((PinMode::Recursive, key)) => {
+                        table.remove(key.as_bytes())?;
+                    }
+                    _ => anyhow::bail!("{old} is not pinned recursively"),
+                }
+
+                match get_pinned_mode(Either::Right(&mut table), &new)? {
+                    Some((PinMode::Recursive, _)) => {
+                        anyhow::bail!("{new} is already pinned recursively")
+                    }
+                    Some((_, key)) => {
+                        table.remove(key.as_bytes())?;
+                    }
+                    None => {}
+                }
+
+                let recursive_key = get_pin_key(&new, &PinMode::Recursive);
+                table.insert(recursive_key.as_bytes(), recursive_value())?;
+
+                let old_value = indirect_value(&old);
+                let new_value = indirect_value(&new);
+
+                // the blocks pinned through the old pin are either pinned through the new pin or
+                // no longer pinned
+                for cid in old_set.iter().chain(&new_set) {
+                    let key = match get_pinned_mode(Either::Right(&mut table), cid)? {
+                        Some((PinMode::Indirect, key)) => {
+                            let through_old = table
+                                .get(key.as_bytes())?
+                                .is_some_and(|value| value.value() == old_value.as_bytes());
+                            if !through_old {
+                                continue;
+                            }
+                            key
+                        }
+                        Some(_) => continue,
+                        None if new_set.contains(cid) => get_pin_key(cid, &PinMode::Indirect),
+                        None => continue,
+                    };
+
+                    if new_set.contains(cid) {
+                        table.insert(key.as_bytes(), new_value.as_bytes())?;
+                    } else {
+                        table.remove(key.as_bytes())?;
+                    }
+                }
+            }
+
+            tx.commit()?;
+            Ok::<_, anyhow::Error>(())
+        })
+        .await?
+    }
+
     async fn list(
         &self,
         requirement: Option<PinMode>,
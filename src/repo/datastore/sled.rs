@@ -115,6 +115,23 @@ impl DataStore for SledDataStore {
 
         stream.boxed()
     }
+
+    async fn iter_prefix(
+        &self,
+        prefix: &[u8],
+    ) -> futures::stream::BoxStream<'static, (Vec<u8>, Vec<u8>)> {
+        let db = self.get_db().to_owned();
+        let prefix = prefix.to_owned();
+
+        let stream = async_stream::stream! {
+            let iter = db.scan_prefix(prefix);
+            for (k, v) in iter.flatten() {
+                yield (k.to_vec(), v.to_vec());
+            }
+        };
+
+        stream.boxed()
+    }
 }
 
 // in the transactional parts of the [`Infallible`] is used to signal there is no additional
@@ -311,6 +328,84 @@ impl PinStore for SledDataStore {
         launder(res)
     }
 
+    async fn update_recursive_pin(
+        &self,
+        old: &Cid,
+        old_referenced: &[Cid],
+        new: &Cid,
+        new_referenced: &[Cid],
+    ) -> Result<(), Error> {
+        use ConflictableTransactionError::Abort;
+        let old_set = old_referenced.iter().copied().collect::<BTreeSet<_>>();
+        let new_set = new_referenced.iter().copied().collect::<BTreeSet<_>>();
+
+        let old = old.to_owned();
+        let new = new.to_owned();
+        let db = self.get_db().to_owned();
+
+        let span = tracing::Span::current();
+
+        let res = tokio::task::spawn_blocking(move || {
+            let span = tracing::trace_span!(parent: &span, "blocking");
+            let _g = span.enter();
+
+            db.transaction(|tx_tree| {
+                match get_pinned_mode(tx_tree, &old)? {
+                    Some((PinMode::Recursive, key)) => {
+                        tx_tree.remove(key.as_str())?;
+                    }
+                    _ => return Err(Abort(anyhow::anyhow!("{old} is not pinned recursively"))),
+                }
+
+                match get_pinned_mode(tx_tree, &new)? {
+                    Some((PinMode::Recursive, _)) => {
+                        return Err(Abort(anyhow::anyhow!(
+                            "{new} is already pinned recursively"
+                        )))
+                    }
+                    Some((_, key)) => {
+                        tx_tree.remove(key.as_str())?;
+                    }
+                    None => {}
+                }
+
+                let recursive_key = get_pin_key(&new, &PinMode::Recursive);
+                tx_tree.insert(recursive_key.as_str(), recursive_value())?;
+
+                let old_value = indirect_value(&old);
+                let new_value = indirect_value(&new);
+
+                // the blocks pinned through the old pin are either pinned through the new pin or
+                // no longer pinned
+                for cid in old_set.iter().chain(&new_set) {
+                    let key = match get_pinned_mode(tx_tree, cid)? {
+                        Some((PinMode::Indirect, key)) => {
+                            if tx_tree.get(key.as_str())?.as_deref() != Some(old_value.as_bytes()) {
+                                continue;
+                            }
+                            key
+                        }
+                        Some(_) => continue,
+                        None if new_set.contains(cid) => get_pin_key(cid, &PinMode::Indirect),
+                        None => continue,
+                    };
+
+                    if new_set.contains(cid) {
+                        tx_tree.insert(key.as_str(), new_value.as_str())?;
+                    } else {
+                        tx_tree.remove(key.as_str())?;
+                    }
+                }
+
+                tx_tree.flush();
+                Ok(())
+            })
+        })
+        .await?;
+
+        launder(res)
+    }
+
     async fn list(
         &self,
         requirement: Option<PinMode>,
@@ -0,0 +1,332 @@
+//! References to the blocks of files added without copying them into the block store, like the
+//! filestore of kubo.
+//!
+//! Adding a file with [`UnixfsAdd::nocopy`](crate::unixfs::UnixfsAdd::nocopy) records each raw
+//! leaf as the path, offset and length of its data within the file. The references are kept by
+//! the [`DataStore`] as json documents under `/filestore/<cid>`, and the blocks are read from the
+//! files when requested. Blocks whose files have been changed or removed since are treated as
+//! missing.
+
+use std::path::PathBuf;
+
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use libipld::Cid;
+use serde::{Deserialize, Serialize};
+
+use super::{BlockPut, DataStore, Repo};
+use crate::error::Error;
+use crate::Block;
+
+const FILESTORE_PREFIX: &str = "/filestore/";
+
+/// Location of the data of a block within a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileRef {
+    /// Absolute path of the file
+    pub path: PathBuf,
+    /// Offset of the data within the file
+    pub offset: u64,
+    /// Length of the data in bytes
+    pub length: u64,
+}
+
+/// Outcome of verifying a filestore reference against its file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilestoreStatus {
+    /// The data read from the file matches the Cid.
+    Ok,
+    /// The file has been modified or truncated since it was added.
+    Changed,
+    /// The file no longer exists.
+    NoFile,
+    /// The file could not be read.
+    FileError(String),
+}
+
+/// A filestore reference along with the outcome of verifying it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilestoreEntry {
+    pub cid: Cid,
+    pub reference: FileRef,
+    pub status: FilestoreStatus,
+}
+
+fn reference_key(cid: &Cid) -> String {
+    format!("{FILESTORE_PREFIX}{cid}")
+}
+
+pub(crate) async fn get_reference<D: DataStore + ?Sized>(
+    store: &D,
+    cid: &Cid,
+) -> Result<Option<FileRef>, Error> {
+    match store.get(reference_key(cid).as_bytes()).await? {
+        Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+        None => Ok(None),
+    }
+}
+
+pub(crate) async fn contains<D: DataStore + ?Sized>(store: &D, cid: &Cid) -> Result<bool, Error> {
+    store.contains(reference_key(cid).as_bytes()).await
+}
+
+pub(crate) async fn list_references<D: DataStore + ?Sized>(
+    store: &D,
+) -> BoxStream<'static, Result<(Cid, FileRef), Error>> {
+    store
+        .iter_prefix(FILESTORE_PREFIX.as_bytes())
+        .await
+        .filter_map(|(key, value)| async move {
+            let key = String::from_utf8(key).ok()?;
+            let cid = key.strip_prefix(FILESTORE_PREFIX)?;
+            let entry = Cid::try_from(cid)
+                .map_err(Error::from)
+                .and_then(|cid| Ok((cid, serde_json::from_slice(&value)?)));
+            Some(entry)
+        })
+        .boxed()
+}
+
+/// Records the block as a reference to its file unless the block store already has a copy.
+pub(crate) async fn put(
+    repo: &Repo,
+    block: &Block,
+    reference: &FileRef,
+) -> Result<(Cid, BlockPut), Error> {
+    let cid = *block.cid();
+
+    if repo.inner.block_store.contains(&cid).await? {
+        return Ok((cid, BlockPut::Existed));
+    }
+
+    let data_store = &repo.inner.data_store;
+    let existed = contains(&**data_store, &cid).await?;
+
+    // the latest reference wins, as the previous file might have been moved since
+    let value = serde_json::to_vec(reference)?;
+    data_store
+        .put(reference_key(&cid).as_bytes(), &value)
+        .await?;
+
+    match existed {
+        true => Ok((cid, BlockPut::Existed)),
+        false => Ok((cid, BlockPut::NewBlock)),
+    }
+}
+
+/// Reads the block from its file. The block is missing if the file no longer matches the Cid, so
+/// that it can be fetched from the network like any other missing block.
+pub(crate) async fn get(repo: &Repo, cid: &Cid) -> Result<Option<Block>, Error> {
+    let Some(reference) = get_reference(&*repo.inner.data_store, cid).await? else {
+        return Ok(None);
+    };
+
+    match read(cid, &reference).await {
+        Ok(block) => Ok(Some(block)),
+        Err(status @ (FilestoreStatus::Changed | FilestoreStatus::NoFile)) => {
+            tracing::warn!(
+                "filestore: {cid} is missing from {}: {status:?}",
+                reference.path.display()
+            );
+            Ok(None)
+        }
+        Err(status) => Err(anyhow::anyhow!(
+            "filestore: cannot read {cid} from {}: {status:?}",
+            reference.path.display()
+        )),
+    }
+}
+
+/// Removes the references of the blocks, returning the ones which were found.
+pub(crate) async fn remove_many(repo: &Repo, cids: &[Cid]) -> Result<Vec<Cid>, Error> {
+    let data_store = &repo.inner.data_store;
+    let mut removed = vec![];
+
+    for cid in cids {
+        let key = reference_key(cid);
+        // removing a missing key is an error for some of the datastores
+        if data_store.contains(key.as_bytes()).await? {
+            data_store.remove(key.as_bytes()).await?;
+            removed.push(*cid);
+        }
+    }
+
+    Ok(removed)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn read(cid: &Cid, reference: &FileRef) -> Result<Block, FilestoreStatus> {
+    use std::io::{ErrorKind, SeekFrom};
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    let io_status = |e: std::io::Error| match e.kind() {
+        ErrorKind::NotFound => FilestoreStatus::NoFile,
+        ErrorKind::UnexpectedEof => FilestoreStatus::Changed,
+        _ => FilestoreStatus::FileError(e.to_string()),
+    };
+
+    let mut file = tokio::fs::File::open(&reference.path)
+        .await
+        .map_err(io_status)?;
+    file.seek(SeekFrom::Start(reference.offset))
+        .await
+        .map_err(io_status)?;
+
+    let length = usize::try_from(reference.length).map_err(|_| FilestoreStatus::Changed)?;
+    let mut data = vec![0; length];
+    file.read_exact(&mut data).await.map_err(io_status)?;
+
+    Block::new(*cid, data).map_err(|_| FilestoreStatus::Changed)
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn read(_: &Cid, _: &FileRef) -> Result<Block, FilestoreStatus> {
+    Err(FilestoreStatus::FileError(
+        "files cannot be read on this platform".into(),
+    ))
+}
+
+impl Repo {
+    /// Lists the blocks which are stored as references to files.
+    pub async fn filestore_list(&self) -> BoxStream<'static, Result<(Cid, FileRef), Error>> {
+        list_references(&*self.inner.data_store).await
+    }
+
+    /// Reads the blocks which are stored as references to files, reporting the ones whose files
+    /// have been changed or removed since they were added.
+    pub async fn filestore_verify(&self) -> BoxStream<'static, Result<FilestoreEntry, Error>> {
+        list_references(&*self.inner.data_store)
+            .await
+            .then(|entry| async move {
+                let (cid, reference) = entry?;
+                let status = match read(&cid, &reference).await {
+                    Ok(_) => FilestoreStatus::Ok,
+                    Err(status) => status,
+                };
+                Ok(FilestoreEntry {
+                    cid,
+                    reference,
+                    status,
+                })
+            })
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileRef, FilestoreStatus};
+    use crate::repo::Repo;
+    use crate::Block;
+    use futures::{StreamExt, TryStreamExt};
+    use libipld::multihash::{Code, MultihashDigest};
+    use libipld::Cid;
+
+    const RAW: u64 = 0x55;
+
+    fn raw_block(data: &[u8]) -> Block {
+        let cid = Cid::new_v1(RAW, Code::Sha2_256.digest(data));
+        Block::new_unchecked(cid, data.to_vec())
+    }
+
+    #[tokio::test]
+    async fn reads_blocks_from_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data");
+        std::fs::write(&path, b"0123456789").unwrap();
+
+        let repo = Repo::new_memory();
+        let block = raw_block(b"3456");
+        let reference = FileRef {
+            path: path.clone(),
+            offset: 3,
+            length: 4,
+        };
+
+        let cid = repo
+            .put_block(block.clone())
+            .reference(reference.clone())
+            .await
+            .unwrap();
+        assert!(repo.contains(&cid).await.unwrap());
+        assert_eq!(repo.get_block_now(&cid).await.unwrap(), Some(block));
+        assert_eq!(
+            repo.list_blocks().await.collect::<Vec<_>>().await,
+            vec![cid]
+        );
+
+        let listed = repo
+            .filestore_list()
+            .await
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(listed, vec![(cid, reference)]);
+
+        let verified = repo
+            .filestore_verify()
+            .await
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(verified[0].status, FilestoreStatus::Ok);
+
+        std::fs::write(&path, b"0123xxx789").unwrap();
+        let verified = repo
+            .filestore_verify()
+            .await
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(verified[0].status, FilestoreStatus::Changed);
+        assert_eq!(repo.get_block_now(&cid).await.unwrap(), None);
+
+        std::fs::write(&path, b"0123").unwrap();
+        let verified = repo
+            .filestore_verify()
+            .await
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(verified[0].status, FilestoreStatus::Changed);
+
+        std::fs::remove_file(&path).unwrap();
+        let verified = repo
+            .filestore_verify()
+            .await
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(verified[0].status, FilestoreStatus::NoFile);
+        assert_eq!(repo.get_block_now(&cid).await.unwrap(), None);
+
+        assert_eq!(repo.remove_block(&cid, false).await.unwrap(), vec![cid]);
+        assert!(!repo.contains(&cid).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn copies_are_kept() {
+        let repo = Repo::new_memory();
+        let block = raw_block(b"copied");
+        repo.put_block(block.clone()).await.unwrap();
+
+        let reference = FileRef {
+            path: "/nonexistent".into(),
+            offset: 0,
+            length: 6,
+        };
+        repo.put_block(block.clone())
+            .reference(reference)
+            .await
+            .unwrap();
+
+        let listed = repo
+            .filestore_list()
+            .await
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert!(listed.is_empty());
+        assert_eq!(repo.get_block_now(block.cid()).await.unwrap(), Some(block));
+    }
+}
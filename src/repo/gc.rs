@@ -0,0 +1,740 @@
+//! Incremental mark-and-sweep garbage collection of the blocks, see [`RepoGarbageCollect`].
+//!
+//! The blocks reachable from the recursive pins, the root of the mutable file system and the
+//! registered [`RootSet`]s are first marked without holding the gc lock. The remaining blocks are
+//! then removed in batches, each holding the gc lock only for the duration of the batch, so that
+//! adding content is not stalled for the whole run. Each batch also holds the lock of the mutable
+//! file system, and marks the roots of the mutable file system which changed and the root sets
+//! which were registered since the previous batch.
+//!
+//! While a collection is running, the blocks stored or read through the repo are recorded and kept
+//! by the sweep, which covers the blocks which are stored and pinned after they have been marked.
+
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::future::IntoFuture;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::{FutureExt, SinkExt, StreamExt};
+use libipld::Cid;
+use tracing::Span;
+use tracing_futures::Instrument;
+use web_time::Instant;
+
+use super::{inlined_block, PinMode, Repo, RepoEvent, IDENTITY};
+use crate::error::Error;
+
+const DEFAULT_BATCH_SIZE: usize = 1024;
+
+/// Set of additional roots kept by the garbage collector along with every block reachable from
+/// them, registered with [`Repo::register_root_set`].
+#[async_trait]
+pub trait RootSet: Debug + Send + Sync + 'static {
+    /// Returns the roots at the time the garbage collector runs.
+    async fn roots(&self) -> Result<Vec<Cid>, Error>;
+}
+
+#[async_trait]
+impl RootSet for Vec<Cid> {
+    async fn roots(&self) -> Result<Vec<Cid>, Error> {
+        Ok(self.clone())
+    }
+}
+
+/// Outcome of a garbage collection.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// Blocks which were removed, or would have been removed in a dry run.
+    pub removed: Vec<Cid>,
+    /// Total size of the removed blocks in bytes, as stored by the block store.
+    pub bytes_freed: usize,
+    /// Number of reachable blocks which were kept.
+    pub marked: usize,
+    /// Time spent collecting.
+    pub duration: Duration,
+    /// False if the collection was stopped early because of the maximum duration.
+    pub completed: bool,
+    pub dry_run: bool,
+}
+
+/// Set of the marked blocks, keyed by the first 16 bytes of the multihash digest of their `Cid`
+/// so that blocks stored under a different codec or version are kept as well. Blocks inlined with
+/// the identity hash are never stored, and are not tracked.
+#[derive(Debug, Default)]
+pub(crate) struct MarkSet {
+    keys: HashSet<[u8; 16]>,
+}
+
+impl MarkSet {
+    fn key(cid: &Cid) -> [u8; 16] {
+        let digest = cid.hash().digest();
+        let mut key = [0u8; 16];
+        let len = digest.len().min(key.len());
+        key[..len].copy_from_slice(&digest[..len]);
+        key
+    }
+
+    /// Returns true if the block was not marked yet.
+    pub(crate) fn insert(&mut self, cid: &Cid) -> bool {
+        self.keys.insert(Self::key(cid))
+    }
+
+    pub(crate) fn contains(&self, cid: &Cid) -> bool {
+        self.keys.contains(&Self::key(cid))
+    }
+
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+}
+
+/// Blocks marked by a collection, along with the roots of the mutable file system and the root sets
+/// they were marked from.
+#[derive(Debug, Default)]
+struct Marks {
+    blocks: MarkSet,
+    mfs_roots: HashSet<Cid>,
+    root_sets: Vec<Arc<dyn RootSet>>,
+}
+
+/// Removes the blocks which are not reachable from the pins, the mutable file system and the
+/// registered [`RootSet`]s. Only one collection runs at a time, a collection started while another
+/// one is running waits for it to complete.
+pub struct RepoGarbageCollect {
+    repo: Repo,
+    dry_run: bool,
+    max_duration: Option<Duration>,
+    batch_size: usize,
+    span: Option<Span>,
+}
+
+impl RepoGarbageCollect {
+    pub fn new(repo: Repo) -> Self {
+        Self {
+            repo,
+            dry_run: false,
+            max_duration: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            span: None,
+        }
+    }
+
+    /// Report the blocks which would be removed without removing them
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Stop collecting once the duration has elapsed, leaving the remaining blocks for the next run
+    pub fn max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// Number of blocks removed while holding the gc lock. Defaults to 1024.
+    pub fn batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Set tracing span
+    pub fn span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+}
+
+impl IntoFuture for RepoGarbageCollect {
+    type Output = Result<GcReport, Error>;
+    type IntoFuture = BoxFuture<'static, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        let span = self.span.unwrap_or(Span::current());
+        let span = debug_span!(parent: &span, "gc", dry_run = self.dry_run);
+        let repo = self.repo;
+        let dry_run = self.dry_run;
+        let batch_size = self.batch_size;
+        let max_duration = self.max_duration;
+
+        async move {
+            let started = Instant::now();
+            let expired = || max_duration.is_some_and(|max| started.elapsed() >= max);
+
+            // the barrier of a collection is shared by the whole repo
+            let _running = repo.inner.gc_running.lock().await;
+            let _barrier = Barrier::start(&repo);
+
+            let mut report = GcReport {
+                dry_run,
+                ..Default::default()
+            };
+
+            let Some(mut marks) = mark(&repo, &expired).await? else {
+                report.duration = started.elapsed();
+                return Ok(report);
+            };
+
+            report.marked = marks.blocks.len();
+            trace!(marked = report.marked, "marked reachable blocks");
+
+            let mut blocks = repo.list_blocks().await;
+            let mut batch = Vec::with_capacity(batch_size);
+            let mut exhausted = false;
+
+            while !exhausted {
+                if expired() {
+                    report.duration = started.elapsed();
+                    return Ok(report);
+                }
+
+                batch.clear();
+                while batch.len() < batch_size {
+                    match blocks.next().await {
+                        Some(cid) if !marks.blocks.contains(&cid) => batch.push(cid),
+                        Some(_) => {}
+                        None => {
+                            exhausted = true;
+                            break;
+                        }
+                    }
+                }
+
+                sweep(&repo, &batch, &mut marks, dry_run, &mut report).await?;
+            }
+
+            report.completed = true;
+            report.duration = started.elapsed();
+            debug!(
+                removed = report.removed.len(),
+                bytes_freed = report.bytes_freed,
+                "gc finished"
+            );
+            Ok(report)
+        }
+        .instrument(span)
+        .boxed()
+    }
+}
+
+/// Marks the blocks reachable from the roots, returning `None` if the maximum duration elapsed.
+async fn mark(repo: &Repo, expired: &impl Fn() -> bool) -> Result<Option<Marks>, Error> {
+    let mut marked = MarkSet::default();
+    let mut pending = vec![];
+    // marked after walking, as marking them first would skip walking them from recursive pins
+    let mut kept = vec![];
+
+    let mut pins = repo.list_pins(None).await;
+    while let Some(result) = pins.next().await {
+        // failing to list the pins must not lead to removing pinned blocks
+        let (cid, mode) = result?;
+        match mode {
+            PinMode::Recursive => pending.push(cid),
+            PinMode::Direct | PinMode::Indirect => kept.push(cid),
+        }
+    }
+
+    let mfs_roots = crate::mfs::local_roots(repo).await?;
+    pending.extend(mfs_roots.iter().copied());
+
+    let root_sets = repo
+        .inner
+        .root_sets
+        .lock()
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    for set in &root_sets {
+        pending.extend(set.roots().await?);
+    }
+
+    if !walk(repo, pending, &mut marked, expired).await? {
+        return Ok(None);
+    }
+
+    for cid in &kept {
+        marked.insert(cid);
+    }
+
+    Ok(Some(Marks {
+        blocks: marked,
+        mfs_roots: mfs_roots.into_iter().collect(),
+        root_sets,
+    }))
+}
+
+/// Marks the roots of the mutable file system which changed and the root sets which were
+/// registered since the blocks were marked.
+async fn mark_changed_roots(repo: &Repo, marks: &mut Marks) -> Result<(), Error> {
+    let mut pending = vec![];
+
+    for root in crate::mfs::local_roots(repo).await? {
+        if marks.mfs_roots.insert(root) {
+            pending.push(root);
+        }
+    }
+
+    let root_sets = repo
+        .inner
+        .root_sets
+        .lock()
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    for set in root_sets {
+        if marks.root_sets.iter().any(|seen| Arc::ptr_eq(seen, &set)) {
+            continue;
+        }
+        pending.extend(set.roots().await?);
+        marks.root_sets.push(set);
+    }
+
+    walk(repo, pending, &mut marks.blocks, &|| false).await?;
+    Ok(())
+}
+
+/// Marks the blocks reachable from the pending blocks, returning false if the maximum duration
+/// elapsed.
+async fn walk(
+    repo: &Repo,
+    mut pending: Vec<Cid>,
+    marked: &mut MarkSet,
+    expired: &impl Fn() -> bool,
+) -> Result<bool, Error> {
+    while let Some(cid) = pending.pop() {
+        if expired() {
+            return Ok(false);
+        }
+
+        let block = match inlined_block(&cid) {
+            Some(block) => block,
+            None => {
+                if !marked.insert(&cid) {
+                    continue;
+                }
+                match repo.inner.block_store.get(&cid).await? {
+                    Some(block) => block,
+                    None => continue,
+                }
+            }
+        };
+
+        let mut references = HashSet::new();
+        // blocks which cannot be decoded have no references to follow
+        if block.references(&mut references).is_ok() {
+            pending.extend(references);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Removes the unmarked blocks of the batch while holding the gc lock.
+async fn sweep(
+    repo: &Repo,
+    batch: &[Cid],
+    marks: &mut Marks,
+    dry_run: bool,
+    report: &mut GcReport,
+) -> Result<(), Error> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+
+    // mfs operations hold their lock over storing blocks and updating the root
+    let _m = repo.inner.mfs_lock.lock().await;
+    let _g = repo.inner.gclock.write().await;
+
+    mark_changed_roots(repo, marks).await?;
+
+    // keep the blocks stored or used since the collection started
+    let batch = {
+        let barrier = repo.inner.gc_barrier.lock();
+        batch
+            .iter()
+            .filter(|cid| !marks.blocks.contains(cid))
+            .filter(|cid| match barrier.as_ref() {
+                Some(barrier) => !barrier.contains(cid),
+                None => true,
+            })
+            .copied()
+            .collect::<Vec<_>>()
+    };
+
+    if batch.is_empty() {
+        return Ok(());
+    }
+
+    let size = repo
+        .inner
+        .block_store
+        .physical_size(&batch)
+        .await?
+        .unwrap_or_default();
+
+    if dry_run {
+        report.bytes_freed += size;
+        report.removed.extend(batch);
+        return Ok(());
+    }
+
+    let mut removed = repo
+        .inner
+        .block_store
+        .remove_many(futures::stream::iter(batch.clone()).boxed())
+        .await
+        .collect::<Vec<_>>()
+        .await;
+
+    // the blocks which failed to be removed are still stored, and are not counted
+    let kept = batch
+        .iter()
+        .filter(|cid| !removed.contains(cid))
+        .copied()
+        .collect::<Vec<_>>();
+    let size = if kept.is_empty() {
+        size
+    } else {
+        let kept_size = repo
+            .inner
+            .block_store
+            .physical_size(&kept)
+            .await?
+            .unwrap_or_default();
+        size.saturating_sub(kept_size)
+    };
+
+    // blocks of files added without copying only have their references removed
+    let unreferenced = super::filestore::remove_many(repo, &batch).await?;
+    for cid in unreferenced {
+        if !removed.contains(&cid) {
+            removed.push(cid);
+        }
+    }
+
+    for cid in &removed {
+        // notify ipfs task about the removed blocks
+        if let Some(mut events) = repo.repo_channel() {
+            let _ = events.send(RepoEvent::RemovedBlock(*cid)).await;
+        }
+    }
+
+    report.bytes_freed += size;
+    report.removed.extend(removed);
+    Ok(())
+}
+
+/// Records the blocks stored or read through the repo until dropped.
+struct Barrier<'a> {
+    repo: &'a Repo,
+}
+
+impl<'a> Barrier<'a> {
+    fn start(repo: &'a Repo) -> Self {
+        *repo.inner.gc_barrier.lock() = Some(MarkSet::default());
+        Barrier { repo }
+    }
+}
+
+impl Drop for Barrier<'_> {
+    fn drop(&mut self) {
+        self.repo.inner.gc_barrier.lock().take();
+    }
+}
+
+impl Repo {
+    /// Keeps the block if a collection is running.
+    pub(crate) fn gc_protect(&self, cid: &Cid) {
+        if cid.hash().code() == IDENTITY {
+            return;
+        }
+
+        if let Some(barrier) = self.inner.gc_barrier.lock().as_mut() {
+            barrier.insert(cid);
+        }
+    }
+
+    /// Registers a set of roots kept by the garbage collector, replacing the set previously
+    /// registered under the same name.
+    pub fn register_root_set(&self, name: impl Into<String>, set: impl RootSet) {
+        self.inner
+            .root_sets
+            .lock()
+            .insert(name.into(), Arc::new(set));
+    }
+
+    /// Removes a set of roots registered with [`Repo::register_root_set`], returning true if it
+    /// was registered.
+    pub fn unregister_root_set(&self, name: &str) -> bool {
+        self.inner.root_sets.lock().remove(name).is_some()
+    }
+
+    /// Removes the blocks which are not reachable from the pins, the mutable file system or the
+    /// registered root sets.
+    pub fn gc(&self) -> RepoGarbageCollect {
+        RepoGarbageCollect::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::IntoFuture;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use futures::stream::BoxStream;
+    use futures::StreamExt;
+    use libipld::multihash::{Code, MultihashDigest};
+    use libipld::{ipld, Cid, IpldCodec};
+
+    use crate::error::Error;
+    use crate::repo::blockstore::memory::MemBlockStore;
+    use crate::repo::datastore::memory::MemDataStore;
+    use crate::repo::{lock, BlockPut, BlockStore, Repo};
+    use crate::Block;
+
+    /// Block store failing to remove one of the blocks.
+    #[derive(Debug)]
+    struct StuckBlockStore {
+        inner: MemBlockStore,
+        stuck: Cid,
+    }
+
+    #[async_trait]
+    impl BlockStore for StuckBlockStore {
+        async fn init(&self) -> Result<(), Error> {
+            self.inner.init().await
+        }
+        async fn open(&self) -> Result<(), Error> {
+            self.inner.open().await
+        }
+        async fn contains(&self, cid: &Cid) -> Result<bool, Error> {
+            self.inner.contains(cid).await
+        }
+        async fn get(&self, cid: &Cid) -> Result<Option<Block>, Error> {
+            self.inner.get(cid).await
+        }
+        async fn size(&self, cid: &[Cid]) -> Result<Option<usize>, Error> {
+            self.inner.size(cid).await
+        }
+        async fn total_size(&self) -> Result<usize, Error> {
+            self.inner.total_size().await
+        }
+        async fn put(&self, block: Block) -> Result<(Cid, BlockPut), Error> {
+            self.inner.put(block).await
+        }
+        async fn remove(&self, cid: &Cid) -> Result<(), Error> {
+            self.inner.remove(cid).await
+        }
+        async fn remove_many(&self, blocks: BoxStream<'static, Cid>) -> BoxStream<'static, Cid> {
+            let stuck = self.stuck;
+            let blocks = blocks.filter(move |cid| futures::future::ready(*cid != stuck));
+            self.inner.remove_many(blocks.boxed()).await
+        }
+        async fn list(&self) -> BoxStream<'static, Cid> {
+            self.inner.list().await
+        }
+    }
+
+    /// Block store holding the first listing of the blocks until the gate is released.
+    #[derive(Debug)]
+    struct GatedBlockStore {
+        inner: MemBlockStore,
+        gate: Arc<tokio::sync::Mutex<()>>,
+        listing: Arc<tokio::sync::Notify>,
+        listed: AtomicBool,
+    }
+
+    #[async_trait]
+    impl BlockStore for GatedBlockStore {
+        async fn init(&self) -> Result<(), Error> {
+            self.inner.init().await
+        }
+        async fn open(&self) -> Result<(), Error> {
+            self.inner.open().await
+        }
+        async fn contains(&self, cid: &Cid) -> Result<bool, Error> {
+            self.inner.contains(cid).await
+        }
+        async fn get(&self, cid: &Cid) -> Result<Option<Block>, Error> {
+            self.inner.get(cid).await
+        }
+        async fn size(&self, cid: &[Cid]) -> Result<Option<usize>, Error> {
+            self.inner.size(cid).await
+        }
+        async fn total_size(&self) -> Result<usize, Error> {
+            self.inner.total_size().await
+        }
+        async fn put(&self, block: Block) -> Result<(Cid, BlockPut), Error> {
+            self.inner.put(block).await
+        }
+        async fn remove(&self, cid: &Cid) -> Result<(), Error> {
+            self.inner.remove(cid).await
+        }
+        async fn remove_many(&self, blocks: BoxStream<'static, Cid>) -> BoxStream<'static, Cid> {
+            self.inner.remove_many(blocks).await
+        }
+        async fn list(&self) -> BoxStream<'static, Cid> {
+            if !self.listed.swap(true, Ordering::SeqCst) {
+                self.listing.notify_one();
+                let _gate = self.gate.lock().await;
+            }
+            self.inner.list().await
+        }
+    }
+
+    fn raw_block(data: &[u8]) -> Block {
+        let cid = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(data));
+        Block::new_unchecked(cid, data.to_vec())
+    }
+
+    fn cbor_block(ipld: libipld::Ipld) -> Block {
+        Block::encode(libipld::cbor::DagCborCodec, Code::Sha2_256, &ipld).unwrap()
+    }
+
+    #[tokio::test]
+    async fn keeps_reachable_blocks() {
+        let repo = Repo::new_memory();
+        repo.init().await.unwrap();
+
+        let leaf = raw_block(b"leaf");
+        let root = cbor_block(ipld!({ "leaf": *leaf.cid() }));
+        let registered_leaf = raw_block(b"registered");
+        let registered = cbor_block(ipld!([*registered_leaf.cid()]));
+        let garbage = raw_block(b"garbage");
+
+        for block in [&leaf, &root, &registered_leaf, &registered, &garbage] {
+            repo.put_block(block.clone()).await.unwrap();
+        }
+
+        repo.pin(root.cid()).recursive().local().await.unwrap();
+        repo.register_root_set("test", vec![*registered.cid()]);
+
+        let report = repo.gc().dry_run().await.unwrap();
+        assert!(report.completed && report.dry_run);
+        assert_eq!(report.removed, vec![*garbage.cid()]);
+        assert_eq!(report.bytes_freed, garbage.data().len());
+        assert_eq!(report.marked, 4);
+        assert!(repo.contains(garbage.cid()).await.unwrap());
+
+        let report = repo.gc().batch_size(1).await.unwrap();
+        assert!(report.completed && !report.dry_run);
+        assert_eq!(report.removed, vec![*garbage.cid()]);
+        assert!(!repo.contains(garbage.cid()).await.unwrap());
+
+        assert!(repo.unregister_root_set("test"));
+        let mut removed = repo.gc().await.unwrap().removed;
+        removed.sort();
+        let mut expected = vec![*registered.cid(), *registered_leaf.cid()];
+        expected.sort();
+        assert_eq!(removed, expected);
+
+        assert!(repo.contains(leaf.cid()).await.unwrap());
+        assert!(repo.contains(root.cid()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn counts_only_removed_blocks() {
+        let stuck = raw_block(b"stuck");
+        let garbage = raw_block(b"garbage");
+
+        let repo = Repo::new_raw(
+            Box::new(StuckBlockStore {
+                inner: MemBlockStore::new(Default::default()),
+                stuck: *stuck.cid(),
+            }),
+            Box::new(MemDataStore::new(Default::default())),
+            Box::new(lock::MemLock),
+        );
+        repo.init().await.unwrap();
+
+        for block in [&stuck, &garbage] {
+            repo.put_block(block.clone()).await.unwrap();
+        }
+
+        let report = repo.gc().await.unwrap();
+        assert_eq!(report.removed, vec![*garbage.cid()]);
+        assert_eq!(report.bytes_freed, garbage.data().len());
+        assert!(repo.contains(stuck.cid()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn stops_after_max_duration() {
+        let repo = Repo::new_memory();
+        repo.init().await.unwrap();
+
+        let garbage = raw_block(b"garbage");
+        repo.put_block(garbage.clone()).await.unwrap();
+
+        let report = repo.gc().max_duration(Duration::ZERO).await.unwrap();
+        assert!(!report.completed);
+        assert!(report.removed.is_empty());
+        assert!(repo.contains(garbage.cid()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn concurrent_collections_keep_blocks_stored_while_collecting() {
+        let gate = Arc::new(tokio::sync::Mutex::new(()));
+        let listing = Arc::new(tokio::sync::Notify::new());
+        let repo = Repo::new_raw(
+            Box::new(GatedBlockStore {
+                inner: MemBlockStore::new(Default::default()),
+                gate: gate.clone(),
+                listing: listing.clone(),
+                listed: AtomicBool::new(false),
+            }),
+            Box::new(MemDataStore::new(Default::default())),
+            Box::new(lock::MemLock),
+        );
+        repo.init().await.unwrap();
+
+        // the first collection is held after marking, before listing the blocks to sweep
+        let closed = gate.lock().await;
+        let first = tokio::spawn(repo.gc().into_future());
+        listing.notified().await;
+
+        // the second collection runs meanwhile
+        let second = tokio::spawn(repo.gc().into_future());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let block = raw_block(b"stored during gc");
+        repo.put_block(block.clone()).await.unwrap();
+        drop(closed);
+
+        let report = first.await.unwrap().unwrap();
+        assert!(report.removed.is_empty());
+        second.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_blocks_stored_while_collecting() {
+        let repo = Repo::new_memory();
+        repo.init().await.unwrap();
+
+        let block = raw_block(b"stored during gc");
+        repo.put_block(block.clone()).await.unwrap();
+
+        // the block is stored again after the gc started, before the sweep
+        let barrier = super::Barrier::start(&repo);
+        repo.put_block(block.clone()).await.unwrap();
+
+        let mut report = Default::default();
+        super::sweep(
+            &repo,
+            &[*block.cid()],
+            &mut Default::default(),
+            false,
+            &mut report,
+        )
+        .await
+        .unwrap();
+        drop(barrier);
+
+        assert!(report.removed.is_empty());
+        assert!(repo.contains(block.cid()).await.unwrap());
+    }
+}
@@ -15,7 +15,7 @@ use libipld::{Ipld, IpldCodec};
 use libp2p::identity::PeerId;
 use parking_lot::{Mutex, RwLock};
 use std::borrow::Borrow;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::future::IntoFuture;
 #[allow(unused_imports)]
 use std::path::Path;
@@ -33,7 +33,16 @@ mod common_tests;
 
 pub mod blockstore;
 pub mod datastore;
+pub mod filestore;
+mod gc;
 pub mod lock;
+mod pin;
+mod verify;
+
+pub use filestore::{FileRef, FilestoreEntry, FilestoreStatus};
+pub use gc::{GcReport, RepoGarbageCollect, RootSet};
+pub use pin::PinMetadata;
+pub use verify::{RepoVerify, VerifyReport};
 
 /// Path mangling done for pins and blocks
 #[cfg(not(target_arch = "wasm32"))]
@@ -79,6 +88,16 @@ pub trait BlockStore: Debug + Send + Sync + 'static {
     async fn size(&self, cid: &[Cid]) -> Result<Option<usize>, Error>;
     /// Get a total size of the block store
     async fn total_size(&self) -> Result<usize, Error>;
+    /// Get the size of the blocks as stored, which is less than [`BlockStore::size`] for
+    /// compressed blocks
+    async fn physical_size(&self, cid: &[Cid]) -> Result<Option<usize>, Error> {
+        self.size(cid).await
+    }
+    /// Get the total size of the block store as stored, which is less than
+    /// [`BlockStore::total_size`] for compressed blocks
+    async fn total_physical_size(&self) -> Result<usize, Error> {
+        self.total_size().await
+    }
     /// Inserts a block in the blockstore.
     async fn put(&self, block: Block) -> Result<(Cid, BlockPut), Error>;
     /// Removes a block from the blockstore.
@@ -104,6 +123,36 @@ pub trait DataStore: PinStore + Debug + Send + Sync + 'static {
     async fn remove(&self, key: &[u8]) -> Result<(), Error>;
     /// Iterate over the k/v of the datastore
     async fn iter(&self) -> futures::stream::BoxStream<'static, (Vec<u8>, Vec<u8>)>;
+    /// Iterate over the k/v of the datastore whose key starts with the prefix.
+    async fn iter_prefix(
+        &self,
+        prefix: &[u8],
+    ) -> futures::stream::BoxStream<'static, (Vec<u8>, Vec<u8>)> {
+        let prefix = prefix.to_vec();
+        self.iter()
+            .await
+            .filter(move |(key, _)| futures::future::ready(key.starts_with(&prefix)))
+            .boxed()
+    }
+
+    /// Returns the metadata of a direct or recursive pin.
+    async fn pin_metadata(&self, target: &Cid) -> Result<Option<PinMetadata>, Error> {
+        pin::get_metadata(self, target).await
+    }
+    /// Stores the metadata of a direct or recursive pin, replacing the previous metadata.
+    async fn set_pin_metadata(&self, target: &Cid, metadata: &PinMetadata) -> Result<(), Error> {
+        pin::set_metadata(self, target, metadata).await
+    }
+    /// Removes the metadata of a pin, if any.
+    async fn remove_pin_metadata(&self, target: &Cid) -> Result<(), Error> {
+        pin::remove_metadata(self, target).await
+    }
+    /// Lists the metadata of the pins.
+    async fn list_pin_metadata(
+        &self,
+    ) -> futures::stream::BoxStream<'static, Result<(Cid, PinMetadata), Error>> {
+        pin::list_metadata(self).await
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -209,6 +258,63 @@ pub trait PinStore: Debug + Send + Sync + Unpin + 'static {
         referenced: References<'_>,
     ) -> Result<(), Error>;
 
+    /// Replaces the recursive pin of `old` with a recursive pin of `new`, given the blocks
+    /// referenced by each of them. The default implementation inserts the new pin before removing
+    /// the old one along with the blocks no longer referenced, and removes the new pin again if
+    /// the old one cannot be removed.
+    async fn update_recursive_pin(
+        &self,
+        old: &Cid,
+        old_referenced: &[Cid],
+        new: &Cid,
+        new_referenced: &[Cid],
+    ) -> Result<(), Error> {
+        if self
+            .query(vec![*old], Some(PinMode::Recursive))
+            .await
+            .is_err()
+        {
+            anyhow::bail!("{old} is not pinned recursively");
+        }
+
+        if self
+            .query(vec![*new], Some(PinMode::Recursive))
+            .await
+            .is_ok()
+        {
+            anyhow::bail!("{new} is already pinned recursively");
+        }
+
+        let references = |cids: Vec<Cid>| -> References<'static> {
+            futures::stream::iter(cids.into_iter().map(Ok)).boxed()
+        };
+
+        self.insert_recursive_pin(new, references(new_referenced.to_vec()))
+            .await?;
+
+        let new_set = new_referenced.iter().collect::<HashSet<_>>();
+        let unreferenced = old_referenced
+            .iter()
+            .filter(|cid| !new_set.contains(cid))
+            .copied()
+            .collect();
+
+        if let Err(e) = self
+            .remove_recursive_pin(old, references(unreferenced))
+            .await
+        {
+            if let Err(e) = self
+                .remove_recursive_pin(new, references(new_referenced.to_vec()))
+                .await
+            {
+                warn!("failed to remove the new pin {new} after failing to update the pin: {e}");
+            }
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
     async fn list(
         &self,
         mode: Option<PinMode>,
@@ -339,6 +445,17 @@ pub(crate) struct RepoInner {
     pub(crate) subscriptions: Mutex<SubscriptionsMap>,
     lockfile: Box<dyn Lock>,
     pub(crate) gclock: tokio::sync::RwLock<()>,
+    pub(crate) mfs_lock: tokio::sync::Mutex<()>,
+    /// Root of the mutable file system written without flushing
+    pub(crate) mfs_unflushed: Mutex<Option<Cid>>,
+    /// Held while updating the ipns records published locally, so that republishing a record does
+    /// not overwrite one published meanwhile
+    pub(crate) ipns_lock: tokio::sync::Mutex<()>,
+    /// Held for the duration of a garbage collection, so that only one runs at a time
+    gc_running: tokio::sync::Mutex<()>,
+    /// Blocks stored or read while the garbage collector is running
+    gc_barrier: Mutex<Option<gc::MarkSet>>,
+    root_sets: Mutex<HashMap<String, Arc<dyn RootSet>>>,
 }
 
 #[cfg(feature = "beetle_bitswap")]
@@ -371,13 +488,11 @@ impl libp2p_bitswap_next::BitswapStore for Repo {
     type Params = libipld::DefaultParams;
 
     async fn contains(&mut self, cid: &Cid) -> anyhow::Result<bool> {
-        self.inner.block_store.contains(cid).await
+        Repo::contains(self, cid).await
     }
 
     async fn get(&mut self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
-        self.inner
-            .block_store
-            .get(cid)
+        self.get_block_now(cid)
             .await
             .map(|block| block.map(|block| block.data().to_vec()))
     }
@@ -401,6 +516,16 @@ impl libp2p_bitswap_next::BitswapStore for Repo {
     }
 }
 
+/// Multihash code of the identity hash, which inlines the block into the cid.
+const IDENTITY: u64 = 0x00;
+
+/// Returns the block inlined into the cid with the identity hash. Such blocks are never stored in
+/// the block store, as they are always available.
+fn inlined_block(cid: &Cid) -> Option<Block> {
+    (cid.hash().code() == IDENTITY)
+        .then(|| Block::new_unchecked(*cid, cid.hash().digest().to_vec()))
+}
+
 /// Events used to communicate to the swarm on repo changes.
 #[derive(Debug)]
 pub enum RepoEvent {
@@ -414,6 +539,49 @@ pub enum RepoEvent {
     RemovedBlock(Cid),
 }
 
+/// Backend of the block store of [`StorageType::Disk`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DiskBlockStore {
+    /// A file per block, see [`FsBlockStore`](blockstore::flatfs::FsBlockStore)
+    #[default]
+    Flatfs,
+    /// A [`redb`](https://github.com/cberner/redb) database, requires the `redb_block_store`
+    /// feature
+    #[cfg(feature = "redb_block_store")]
+    Redb,
+    /// A [`sled`](https://github.com/spacejam/sled) database, requires the `sled_block_store`
+    /// feature
+    #[cfg(feature = "sled_block_store")]
+    Sled,
+}
+
+/// Options of [`StorageType::Disk`], set with
+/// [`IpfsOptions::disk_options`](crate::IpfsOptions::disk_options).
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DiskOptions {
+    /// Backend of the block store, stored in the `blockstore` directory of the repo
+    pub block_store: DiskBlockStore,
+    /// Compression of the blocks written to the block store. Blocks are read regardless of the
+    /// compression they were written with, so this can be changed for an existing repo.
+    pub compression: blockstore::compression::Compression,
+    /// Maximum amount of blocks written or removed in a single transaction by the database
+    /// backends. Puts happening at the same time are committed together.
+    pub write_batch_size: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for DiskOptions {
+    fn default() -> Self {
+        Self {
+            block_store: DiskBlockStore::default(),
+            compression: Default::default(),
+            write_batch_size: 256,
+        }
+    }
+}
+
 impl Repo {
     pub fn new(repo_type: &mut StorageType) -> Self {
         match repo_type {
@@ -450,6 +618,12 @@ impl Repo {
             lockfile,
             max_storage_size: Default::default(),
             gclock: Default::default(),
+            mfs_lock: Default::default(),
+            mfs_unflushed: Default::default(),
+            ipns_lock: Default::default(),
+            gc_running: Default::default(),
+            gc_barrier: Default::default(),
+            root_sets: Default::default(),
         };
         Repo {
             inner: Arc::new(inner),
@@ -458,6 +632,11 @@ impl Repo {
 
     #[cfg(not(target_arch = "wasm32"))]
     pub fn new_fs(path: impl AsRef<Path>) -> Self {
+        Self::new_fs_with(path, DiskOptions::default())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new_fs_with(path: impl AsRef<Path>, options: DiskOptions) -> Self {
         let path = path.as_ref().to_path_buf();
         let mut blockstore_path = path.clone();
         let mut datastore_path = path.clone();
@@ -466,7 +645,22 @@ impl Repo {
         datastore_path.push("datastore");
         lockfile_path.push("repo_lock");
 
-        let block_store = Box::new(blockstore::flatfs::FsBlockStore::new(blockstore_path));
+        let block_store: Box<dyn BlockStore> = match options.block_store {
+            DiskBlockStore::Flatfs => Box::new(
+                blockstore::flatfs::FsBlockStore::new(blockstore_path)
+                    .with_compression(options.compression),
+            ),
+            #[cfg(feature = "redb_block_store")]
+            DiskBlockStore::Redb => Box::new(
+                blockstore::redb::RedbBlockStore::new(blockstore_path, options.write_batch_size)
+                    .with_compression(options.compression),
+            ),
+            #[cfg(feature = "sled_block_store")]
+            DiskBlockStore::Sled => Box::new(
+                blockstore::sled::SledBlockStore::new(blockstore_path, options.write_batch_size)
+                    .with_compression(options.compression),
+            ),
+        };
         #[cfg(not(any(feature = "sled_data_store", feature = "redb_data_store")))]
         let data_store = Box::new(datastore::flatfs::FsDataStore::new(datastore_path));
         #[cfg(feature = "sled_data_store")]
@@ -705,6 +899,13 @@ impl Repo {
         self.inner.block_store.total_size().await
     }
 
+    /// Get the total size of the block store as stored, which is less than
+    /// [`Repo::get_total_size`] for compressed blocks
+    #[inline]
+    pub async fn get_total_physical_size(&self) -> Result<usize, Error> {
+        self.inner.block_store.total_physical_size().await
+    }
+
     pub(crate) async fn get_blocks_with_session(
         &self,
         session: impl Into<Option<u64>>,
@@ -805,17 +1006,41 @@ impl Repo {
 
     /// Retrieves a block from the block store if it's available locally.
     pub async fn get_block_now(&self, cid: &Cid) -> Result<Option<Block>, Error> {
-        self.inner.block_store.get(cid).await
+        if let Some(block) = inlined_block(cid) {
+            return Ok(Some(block));
+        }
+        let block = match self.inner.block_store.get(cid).await? {
+            Some(block) => Some(block),
+            None => filestore::get(self, cid).await?,
+        };
+        if block.is_some() {
+            self.gc_protect(cid);
+        }
+        Ok(block)
     }
 
     /// Check to determine if blockstore contain a block
     pub async fn contains(&self, cid: &Cid) -> Result<bool, Error> {
-        self.inner.block_store.contains(cid).await
+        if cid.hash().code() == IDENTITY {
+            return Ok(true);
+        }
+        if self.inner.block_store.contains(cid).await? {
+            return Ok(true);
+        }
+        filestore::contains(&*self.inner.data_store, cid).await
     }
 
-    /// Lists the blocks in the blockstore.
+    /// Lists the blocks in the blockstore, followed by the blocks referenced in the filestore.
     pub async fn list_blocks(&self) -> BoxStream<'static, Cid> {
-        self.inner.block_store.list().await
+        let references = filestore::list_references(&*self.inner.data_store)
+            .await
+            .filter_map(|entry| async move { entry.ok().map(|(cid, _)| cid) });
+        self.inner
+            .block_store
+            .list()
+            .await
+            .chain(references)
+            .boxed()
     }
 
     /// Remove block from the block store.
@@ -836,24 +1061,28 @@ impl Repo {
             false => BTreeSet::from_iter(std::iter::once(*cid)),
         };
 
-        let list = stream::iter(
-            FuturesOrdered::from_iter(list.into_iter().map(|cid| async move { cid }))
-                .filter_map(|cid| async move {
-                    (!self.is_pinned(&cid).await.unwrap_or_default()).then_some(cid)
-                })
-                .collect::<Vec<Cid>>()
-                .await,
-        )
-        .boxed();
+        let candidates = FuturesOrdered::from_iter(list.into_iter().map(|cid| async move { cid }))
+            .filter_map(|cid| async move {
+                (!self.is_pinned(&cid).await.unwrap_or_default()).then_some(cid)
+            })
+            .collect::<Vec<Cid>>()
+            .await;
 
-        let removed = self
+        let mut removed = self
             .inner
             .block_store
-            .remove_many(list)
+            .remove_many(stream::iter(candidates.clone()).boxed())
             .await
             .collect::<Vec<_>>()
             .await;
 
+        let unreferenced = filestore::remove_many(self, &candidates).await?;
+        for cid in unreferenced {
+            if !removed.contains(&cid) {
+                removed.push(cid);
+            }
+        }
+
         for cid in &removed {
             // notify ipfs task about the removed blocks
             if let Some(mut events) = self.repo_channel() {
@@ -964,39 +1193,6 @@ impl Repo {
         self.inner.data_store.remove_recursive_pin(cid, refs).await
     }
 
-    /// Function to perform a basic cleanup of unpinned blocks
-    pub(crate) async fn cleanup(&self) -> Result<Vec<Cid>, Error> {
-        let repo = self.clone();
-
-        let blocks = repo.list_blocks().await;
-        let pins = repo
-            .list_pins(None)
-            .await
-            .filter_map(|result| futures::future::ready(result.map(|(cid, _)| cid).ok()))
-            .collect::<Vec<_>>()
-            .await;
-
-        let stream = async_stream::stream! {
-            for await cid in blocks {
-                if pins.contains(&cid) {
-                    continue;
-                }
-                yield cid;
-            }
-        }
-        .boxed();
-
-        let removed_blocks = self
-            .inner
-            .block_store
-            .remove_many(stream)
-            .await
-            .collect::<Vec<_>>()
-            .await;
-
-        Ok(removed_blocks)
-    }
-
     /// Checks if a `Cid` is pinned.
     pub async fn is_pinned(&self, cid: &Cid) -> Result<bool, Error> {
         self.inner.data_store.is_pinned(cid).await
@@ -1043,6 +1239,7 @@ pub struct RepoPutBlock {
     block: Block,
     span: Option<Span>,
     broadcast_on_new_block: bool,
+    reference: Option<FileRef>,
 }
 
 impl RepoPutBlock {
@@ -1052,6 +1249,7 @@ impl RepoPutBlock {
             block,
             span: None,
             broadcast_on_new_block: true,
+            reference: None,
         }
     }
 
@@ -1064,6 +1262,13 @@ impl RepoPutBlock {
         self.span = Some(span);
         self
     }
+
+    /// Records the block as a reference to the file containing its data instead of storing a
+    /// copy, see [`filestore`]. The block is kept as is if the block store already has it.
+    pub fn reference(mut self, reference: FileRef) -> Self {
+        self.reference = Some(reference);
+        self
+    }
 }
 
 impl IntoFuture for RepoPutBlock {
@@ -1074,8 +1279,17 @@ impl IntoFuture for RepoPutBlock {
         let span = self.span.unwrap_or(Span::current());
         let span = debug_span!(parent: &span, "put_block", cid = %block.cid());
         async move {
+            if block.cid().hash().code() == IDENTITY {
+                // the block is always available from the cid itself
+                return Ok(*block.cid());
+            }
+
             let _guard = self.repo.inner.gclock.read().await;
-            let (cid, res) = self.repo.inner.block_store.put(block.clone()).await?;
+            let (cid, res) = match &self.reference {
+                Some(reference) => filestore::put(&self.repo, &block, reference).await?,
+                None => self.repo.inner.block_store.put(block.clone()).await?,
+            };
+            self.repo.gc_protect(&cid);
 
             if let BlockPut::NewBlock = res {
                 if self.broadcast_on_new_block {
@@ -1220,6 +1434,8 @@ pub struct RepoInsertPin {
     timeout: Option<Duration>,
     local: bool,
     refs: crate::refs::IpldRefs,
+    name: Option<String>,
+    metadata: BTreeMap<String, String>,
 }
 
 impl RepoInsertPin {
@@ -1233,9 +1449,23 @@ impl RepoInsertPin {
             timeout: None,
             refs: Default::default(),
             span: None,
+            name: None,
+            metadata: BTreeMap::new(),
         }
     }
 
+    /// Name of the pin, which can be queried with [`Repo::pins_by_name`]
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Key/value metadata stored along with the pin
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
     /// Recursively pin blocks
     pub fn recursive(mut self) -> Self {
         self.recursive = true;
@@ -1312,6 +1542,8 @@ impl std::future::IntoFuture for RepoInsertPin {
         let span = debug_span!(parent: &span, "insert_pin", cid = %cid, recursive);
         let providers = self.providers;
         let timeout = self.timeout;
+        let name = self.name;
+        let metadata = self.metadata;
         async move {
             // Although getting a block adds a guard, we will add a read guard here a head of time so we can hold it throughout this future
             let _g = repo.inner.gclock.read().await;
@@ -1335,7 +1567,7 @@ impl std::future::IntoFuture for RepoInsertPin {
 
                 repo.insert_recursive_pin(&cid, st).await?
             }
-            Ok(())
+            repo.update_pin_metadata(&cid, name, metadata).await
         }
         .instrument(span)
         .boxed()
@@ -1389,7 +1621,7 @@ impl std::future::IntoFuture for RepoRemovePin {
         async move {
             let _g = repo.inner.gclock.read().await;
             if !recursive {
-                repo.remove_direct_pin(&cid).await
+                repo.remove_direct_pin(&cid).await?;
             } else {
                 // start walking refs of the root after loading it
 
@@ -1410,8 +1642,9 @@ impl std::future::IntoFuture for RepoRemovePin {
                     .into_stream()
                     .boxed();
 
-                repo.remove_recursive_pin(&cid, st).await
+                repo.remove_recursive_pin(&cid, st).await?;
             }
+            repo.remove_pin_metadata(&cid).await
         }
         .instrument(span)
         .boxed()
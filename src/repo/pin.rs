@@ -0,0 +1,514 @@
+//! Names and metadata of the pins, and updating a recursive pin to a new version of a DAG.
+//!
+//! The metadata is kept by the [`DataStore`] next to the pins. The default implementation stores
+//! it as json documents under `/pins/metadata/<cid>`, which works with every datastore.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use libipld::Cid;
+use serde::{Deserialize, Serialize};
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use super::{DataStore, PinMode, Repo};
+use crate::error::Error;
+
+const METADATA_PREFIX: &str = "/pins/metadata/";
+
+/// Name, metadata and creation time of a direct or recursive pin.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinMetadata {
+    /// Name of the pin
+    pub name: Option<String>,
+    /// Arbitrary key/value metadata
+    pub metadata: BTreeMap<String, String>,
+    /// Creation time of the pin, in seconds since the unix epoch
+    pub created: u64,
+}
+
+impl PinMetadata {
+    /// Creates metadata without a name for a pin created now.
+    pub fn new() -> Self {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        Self {
+            name: None,
+            metadata: BTreeMap::new(),
+            created,
+        }
+    }
+}
+
+impl Default for PinMetadata {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) fn metadata_key(cid: &Cid) -> String {
+    format!("{METADATA_PREFIX}{cid}")
+}
+
+pub(crate) async fn get_metadata<D: DataStore + ?Sized>(
+    store: &D,
+    target: &Cid,
+) -> Result<Option<PinMetadata>, Error> {
+    match store.get(metadata_key(target).as_bytes()).await? {
+        Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+        None => Ok(None),
+    }
+}
+
+pub(crate) async fn set_metadata<D: DataStore + ?Sized>(
+    store: &D,
+    target: &Cid,
+    metadata: &PinMetadata,
+) -> Result<(), Error> {
+    let value = serde_json::to_vec(metadata)?;
+    store.put(metadata_key(target).as_bytes(), &value).await
+}
+
+pub(crate) async fn remove_metadata<D: DataStore + ?Sized>(
+    store: &D,
+    target: &Cid,
+) -> Result<(), Error> {
+    let key = metadata_key(target);
+    // removing a missing key is an error for some of the datastores
+    match store.contains(key.as_bytes()).await? {
+        true => store.remove(key.as_bytes()).await,
+        false => Ok(()),
+    }
+}
+
+pub(crate) async fn list_metadata<D: DataStore + ?Sized>(
+    store: &D,
+) -> BoxStream<'static, Result<(Cid, PinMetadata), Error>> {
+    store
+        .iter()
+        .await
+        .filter_map(|(key, value)| async move {
+            let key = String::from_utf8(key).ok()?;
+            let cid = key.strip_prefix(METADATA_PREFIX)?;
+            let entry = Cid::try_from(cid)
+                .map_err(Error::from)
+                .and_then(|cid| Ok((cid, serde_json::from_slice(&value)?)));
+            Some(entry)
+        })
+        .boxed()
+}
+
+impl Repo {
+    /// Returns the name, metadata and creation time of a direct or recursive pin.
+    pub async fn pin_metadata(&self, cid: &Cid) -> Result<Option<PinMetadata>, Error> {
+        self.inner.data_store.pin_metadata(cid).await
+    }
+
+    /// Returns the pins with a name containing `name`, like `ipfs pin ls --name` of kubo.
+    pub async fn pins_by_name(&self, name: &str) -> Result<Vec<(Cid, PinMetadata)>, Error> {
+        let mut entries = self.inner.data_store.list_pin_metadata().await;
+        let mut pins = vec![];
+
+        while let Some(entry) = entries.next().await {
+            let (cid, metadata) = entry?;
+            if metadata
+                .name
+                .as_deref()
+                .is_some_and(|pin_name| pin_name.contains(name))
+            {
+                pins.push((cid, metadata));
+            }
+        }
+
+        Ok(pins)
+    }
+
+    /// Stores the metadata after pinning, keeping the creation time of an existing pin.
+    pub(crate) async fn update_pin_metadata(
+        &self,
+        cid: &Cid,
+        name: Option<String>,
+        metadata: BTreeMap<String, String>,
+    ) -> Result<(), Error> {
+        let data_store = &self.inner.data_store;
+        let existing = data_store.pin_metadata(cid).await?;
+        let unchanged = existing.is_some() && name.is_none() && metadata.is_empty();
+
+        if unchanged {
+            return Ok(());
+        }
+
+        let mut pin_metadata = existing.unwrap_or_default();
+        if name.is_some() {
+            pin_metadata.name = name;
+        }
+        pin_metadata.metadata.extend(metadata);
+
+        data_store.set_pin_metadata(cid, &pin_metadata).await
+    }
+
+    /// Removes the metadata after unpinning, unless the block remains pinned recursively.
+    pub(crate) async fn remove_pin_metadata(&self, cid: &Cid) -> Result<(), Error> {
+        let data_store = &self.inner.data_store;
+        if data_store
+            .query(vec![*cid], Some(PinMode::Recursive))
+            .await
+            .is_ok()
+        {
+            return Ok(());
+        }
+        data_store.remove_pin_metadata(cid).await
+    }
+
+    /// Pins `new` recursively in place of `old`, which has to be pinned recursively, like
+    /// `ipfs pin update` of kubo. The name and metadata of the old pin are carried over.
+    ///
+    /// Like the `dagutils` of kubo, only the blocks of the new version which are not part of the
+    /// old version are fetched and walked, as the blocks shared with the old version are available
+    /// locally. The new version is kept by the garbage collector while it is fetched, and the old
+    /// pin is replaced with the new one in a single update of the [`PinStore`](super::PinStore).
+    pub async fn update_pin(&self, old: &Cid, new: &Cid) -> Result<(), Error> {
+        if self
+            .query_pins(vec![*old], PinMode::Recursive)
+            .await
+            .is_err()
+        {
+            anyhow::bail!("{old} is not pinned recursively");
+        }
+
+        if old == new {
+            return Ok(());
+        }
+
+        if self
+            .query_pins(vec![*new], PinMode::Recursive)
+            .await
+            .is_ok()
+        {
+            // the new version is already pinned along with its blocks
+            return self.remove_pin(old).recursive().await;
+        }
+
+        let _kept = KeptRoot::new(self, *new);
+
+        let old_references = self.local_references(old).await?;
+        let new_referenced = self.updated_references(new, &old_references).await?;
+        let old_referenced = old_references
+            .into_keys()
+            .filter(|cid| cid != old)
+            .collect::<Vec<_>>();
+
+        self.inner
+            .data_store
+            .update_recursive_pin(old, &old_referenced, new, &new_referenced)
+            .await?;
+
+        if let Some(metadata) = self.pin_metadata(old).await? {
+            self.update_pin_metadata(new, metadata.name, metadata.metadata)
+                .await?;
+        }
+        self.remove_pin_metadata(old).await
+    }
+
+    /// Returns the references of each of the blocks reachable from the root which are available
+    /// locally, including the root. The blocks which are not available have no references.
+    async fn local_references(&self, root: &Cid) -> Result<HashMap<Cid, Vec<Cid>>, Error> {
+        let mut references = HashMap::new();
+        let mut pending = vec![*root];
+
+        while let Some(cid) = pending.pop() {
+            if references.contains_key(&cid) {
+                continue;
+            }
+
+            let mut links = HashSet::new();
+            if let Some(block) = self.get_block_now(&cid).await? {
+                // blocks which cannot be decoded have no references to follow
+                let _ = block.references(&mut links);
+            }
+
+            let links = links.into_iter().collect::<Vec<_>>();
+            pending.extend(links.iter().copied());
+            references.insert(cid, links);
+        }
+
+        Ok(references)
+    }
+
+    /// Returns the blocks referenced by the root of a new version, fetching only the blocks which
+    /// are not part of the previous version, given the references of its blocks.
+    async fn updated_references(
+        &self,
+        root: &Cid,
+        previous: &HashMap<Cid, Vec<Cid>>,
+    ) -> Result<Vec<Cid>, Error> {
+        let mut visited = HashSet::new();
+        let mut pending = vec![*root];
+
+        while let Some(cid) = pending.pop() {
+            if !visited.insert(cid) {
+                continue;
+            }
+
+            if let Some(links) = previous.get(&cid) {
+                pending.extend(links.iter().copied());
+                continue;
+            }
+
+            let block = self.get_block(&cid, &[], false).await?;
+            let mut links = HashSet::new();
+            let _ = block.references(&mut links);
+            pending.extend(links);
+        }
+
+        visited.remove(root);
+        Ok(visited.into_iter().collect())
+    }
+}
+
+/// Keeps the blocks reachable from a root from being collected until dropped.
+struct KeptRoot<'a> {
+    repo: &'a Repo,
+    name: String,
+}
+
+impl<'a> KeptRoot<'a> {
+    fn new(repo: &'a Repo, root: Cid) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let name = format!("update_pin/{id}");
+        repo.register_root_set(name.clone(), vec![root]);
+        Self { repo, name }
+    }
+}
+
+impl Drop for KeptRoot<'_> {
+    fn drop(&mut self) {
+        self.repo.unregister_root_set(&self.name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::IntoFuture;
+    use std::time::Duration;
+
+    use libipld::multihash::Code;
+    use libipld::{ipld, Cid};
+
+    use crate::repo::Repo;
+    use crate::Block;
+
+    fn block(ipld: libipld::Ipld) -> Block {
+        Block::encode(libipld::cbor::DagCborCodec, Code::Sha2_256, &ipld).unwrap()
+    }
+
+    async fn put(repo: &Repo, ipld: libipld::Ipld) -> Cid {
+        repo.put_block(block(ipld)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn named_pins() {
+        let repo = Repo::new_memory();
+        repo.init().await.unwrap();
+
+        let first = put(&repo, ipld!("first")).await;
+        let second = put(&repo, ipld!("second")).await;
+        let unnamed = put(&repo, ipld!("unnamed")).await;
+
+        repo.pin(&first)
+            .name("website v1")
+            .metadata("env", "prod")
+            .await
+            .unwrap();
+        repo.pin(&second)
+            .recursive()
+            .name("website v2")
+            .await
+            .unwrap();
+        repo.pin(&unnamed).await.unwrap();
+
+        let metadata = repo.pin_metadata(&first).await.unwrap().unwrap();
+        assert_eq!(metadata.name.as_deref(), Some("website v1"));
+        assert_eq!(metadata.metadata["env"], "prod");
+        assert!(metadata.created > 0);
+
+        // pinning again keeps the creation time and the existing metadata
+        repo.pin(&first).metadata("owner", "ops").await.unwrap();
+        let updated = repo.pin_metadata(&first).await.unwrap().unwrap();
+        assert_eq!(updated.created, metadata.created);
+        assert_eq!(updated.name, metadata.name);
+        assert_eq!(updated.metadata.len(), 2);
+
+        let unnamed_metadata = repo.pin_metadata(&unnamed).await.unwrap().unwrap();
+        assert!(unnamed_metadata.name.is_none());
+
+        let mut found = repo
+            .pins_by_name("website")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(cid, _)| cid)
+            .collect::<Vec<_>>();
+        found.sort();
+        let mut expected = vec![first, second];
+        expected.sort();
+        assert_eq!(found, expected);
+
+        assert_eq!(repo.pins_by_name("v2").await.unwrap()[0].0, second);
+
+        repo.remove_pin(&second).recursive().await.unwrap();
+        assert!(repo.pin_metadata(&second).await.unwrap().is_none());
+        assert!(repo.pins_by_name("v2").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn update_pin() {
+        let repo = Repo::new_memory();
+        repo.init().await.unwrap();
+
+        let shared = put(&repo, ipld!("shared")).await;
+        let removed = put(&repo, ipld!("removed")).await;
+        let added = put(&repo, ipld!("added")).await;
+        let old = put(&repo, ipld!([shared, removed])).await;
+        let new = put(&repo, ipld!([shared, added])).await;
+
+        repo.pin(&old).recursive().name("versioned").await.unwrap();
+
+        assert!(repo.update_pin(&shared, &new).await.is_err());
+
+        repo.update_pin(&old, &new).await.unwrap();
+
+        assert!(!repo.is_pinned(&old).await.unwrap());
+        assert!(!repo.is_pinned(&removed).await.unwrap());
+        assert!(repo.is_pinned(&shared).await.unwrap());
+        assert!(repo.is_pinned(&added).await.unwrap());
+
+        let metadata = repo.pin_metadata(&new).await.unwrap().unwrap();
+        assert_eq!(metadata.name.as_deref(), Some("versioned"));
+        assert!(repo.pin_metadata(&old).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn update_pin_fetches_only_new_blocks() {
+        let repo = Repo::new_memory();
+        repo.init().await.unwrap();
+
+        let missing = block(ipld!("missing"));
+        let shared = put(&repo, ipld!([*missing.cid()])).await;
+        let added = put(&repo, ipld!("added")).await;
+        let old = put(&repo, ipld!([shared])).await;
+        let new = put(&repo, ipld!([shared, added])).await;
+
+        repo.put_block(missing.clone()).await.unwrap();
+        repo.pin(&old).recursive().await.unwrap();
+        // the old version is pinned without one of its blocks, which has to be fetched if the
+        // blocks shared with the new version are walked again
+        repo.inner.block_store.remove(missing.cid()).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), repo.update_pin(&old, &new))
+            .await
+            .expect("only the blocks of the new version are fetched")
+            .unwrap();
+
+        assert!(repo.is_pinned(&new).await.unwrap());
+        assert!(repo.is_pinned(missing.cid()).await.unwrap());
+        assert!(!repo.is_pinned(&old).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn update_pin_with_contended_gc() {
+        let repo = Repo::new_memory();
+        repo.init().await.unwrap();
+
+        let shared = put(&repo, ipld!("shared")).await;
+        let added = put(&repo, ipld!("added")).await;
+        let garbage = put(&repo, ipld!("garbage")).await;
+        let old = put(&repo, ipld!([shared])).await;
+        let new = put(&repo, ipld!([shared, added])).await;
+
+        repo.pin(&old).recursive().await.unwrap();
+        // the new version is stored before the update, and kept until then
+        repo.register_root_set("new", vec![new]);
+
+        // the gc waits to sweep behind the guard, and blocks the other readers of the gc lock
+        let guard = repo.gc_guard().await;
+        let gc = tokio::spawn(repo.gc().into_future());
+        let update = tokio::spawn({
+            let repo = repo.clone();
+            async move { repo.update_pin(&old, &new).await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(guard);
+
+        let report = tokio::time::timeout(Duration::from_secs(10), gc)
+            .await
+            .expect("the gc is not blocked by the update")
+            .unwrap()
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(10), update)
+            .await
+            .expect("the update is not blocked by the gc")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(report.removed, vec![garbage]);
+        assert!(repo.is_pinned(&new).await.unwrap());
+        assert!(!repo.is_pinned(&old).await.unwrap());
+        for cid in [new, shared, added] {
+            assert!(repo.contains(&cid).await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn update_pin_with_concurrent_gc() {
+        let repo = Repo::new_memory();
+        repo.init().await.unwrap();
+
+        let mut old = put(&repo, ipld!(0)).await;
+        repo.pin(&old).recursive().await.unwrap();
+
+        let gc = tokio::spawn({
+            let repo = repo.clone();
+            async move {
+                for _ in 0..20 {
+                    repo.gc().await.unwrap();
+                    tokio::task::yield_now().await;
+                }
+            }
+        });
+
+        for version in 1..20 {
+            let leaf = block(ipld!(version));
+            let new = block(ipld!([old, *leaf.cid()]));
+            // the new version is stored before the update, and kept until then. the root is
+            // stored first so that the leaf is reachable from it once stored
+            repo.register_root_set("new", vec![*new.cid()]);
+            for block in [new.clone(), leaf] {
+                repo.put_block(block).await.unwrap();
+            }
+            repo.update_pin(&old, new.cid()).await.unwrap();
+            old = *new.cid();
+        }
+        repo.unregister_root_set("new");
+
+        gc.await.unwrap();
+        repo.gc().await.unwrap();
+
+        // every version references the previous ones
+        let mut pending = vec![old];
+        let mut count = 0;
+        while let Some(cid) = pending.pop() {
+            let block = repo.get_block_now(&cid).await.unwrap().expect("kept");
+            let mut links = std::collections::HashSet::new();
+            block.references(&mut links).unwrap();
+            pending.extend(links);
+            count += 1;
+        }
+        assert_eq!(count, 1 + 2 * 19);
+    }
+}
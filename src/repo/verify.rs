@@ -0,0 +1,561 @@
+//! Integrity verification of the blocks and the recursive pins, see [`RepoVerify`].
+//!
+//! Every stored block is read back and hashed with the multihash of its `Cid`, and the blocks
+//! reachable from each recursive pin are checked to be stored. Corrupt blocks can optionally be
+//! removed and fetched again from the network, along with the blocks missing from the pins.
+//!
+//! The blocks are checked without holding the gc lock, so the gc keeps running during the
+//! verification. The blocks found corrupt or missing are checked again while holding it, leaving
+//! out the ones which were removed or stored again in the meantime.
+
+use std::collections::HashSet;
+use std::future::IntoFuture;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::{FutureExt, SinkExt, StreamExt};
+use libipld::multihash::{Code, MultihashDigest};
+use libipld::{Cid, Ipld, IpldCodec};
+use libp2p::PeerId;
+use tracing::Span;
+use tracing_futures::Instrument;
+use web_time::Instant;
+
+use super::{PinMode, Repo, RepoEvent};
+use crate::error::Error;
+use crate::refs::{Edge, IpldRefs, IpldRefsError};
+use crate::Block;
+
+/// Outcome of verifying the repo.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Number of blocks which were read back and hashed.
+    pub checked: usize,
+    /// Blocks which cannot be read or whose data does not match the multihash of their `Cid`.
+    pub corrupt: Vec<Cid>,
+    /// Blocks listed by the block store which cannot be found, and blocks reachable from the
+    /// recursive pins which are not stored.
+    pub missing: Vec<Cid>,
+    /// Recursive pins which do not have every block reachable from them stored intact.
+    pub incomplete_pins: Vec<Cid>,
+    /// Corrupt blocks which were removed.
+    pub removed: Vec<Cid>,
+    /// Corrupt or missing blocks which were fetched from the network.
+    pub refetched: Vec<Cid>,
+    /// Time spent verifying.
+    pub duration: Duration,
+}
+
+impl VerifyReport {
+    /// Returns true if no corrupt or missing blocks were found.
+    pub fn is_intact(&self) -> bool {
+        self.corrupt.is_empty() && self.missing.is_empty() && self.incomplete_pins.is_empty()
+    }
+}
+
+/// Verifies the stored blocks against their multihash and the completeness of the recursive pins.
+pub struct RepoVerify {
+    repo: Repo,
+    repair: bool,
+    refetch: bool,
+    providers: Vec<PeerId>,
+    timeout: Option<Duration>,
+    span: Option<Span>,
+}
+
+impl RepoVerify {
+    pub fn new(repo: Repo) -> Self {
+        Self {
+            repo,
+            repair: false,
+            refetch: false,
+            providers: vec![],
+            timeout: None,
+            span: None,
+        }
+    }
+
+    /// Remove the corrupt blocks, including the pinned ones
+    pub fn repair(mut self) -> Self {
+        self.repair = true;
+        self
+    }
+
+    /// Remove the corrupt blocks and fetch them again from the network, along with the blocks
+    /// missing from the recursive pins. Requires the repo to be online.
+    pub fn refetch(mut self) -> Self {
+        self.repair = true;
+        self.refetch = true;
+        self
+    }
+
+    /// Peer that may contain the blocks to refetch
+    pub fn provider(mut self, peer_id: PeerId) -> Self {
+        if !self.providers.contains(&peer_id) {
+            self.providers.push(peer_id);
+        }
+        self
+    }
+
+    /// List of peers that may contain the blocks to refetch
+    pub fn providers(mut self, providers: &[PeerId]) -> Self {
+        self.providers = providers.into();
+        self
+    }
+
+    /// Duration to fetch each block from the network before timing out
+    pub fn timeout(mut self, duration: Duration) -> Self {
+        self.timeout = Some(duration);
+        self
+    }
+
+    /// Set tracing span
+    pub fn span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+}
+
+impl IntoFuture for RepoVerify {
+    type Output = Result<VerifyReport, Error>;
+    type IntoFuture = BoxFuture<'static, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        let span = self.span.unwrap_or(Span::current());
+        let span =
+            debug_span!(parent: &span, "verify", repair = self.repair, refetch = self.refetch);
+        let repo = self.repo;
+        let repair = self.repair;
+        let refetch = self.refetch;
+        let providers = self.providers;
+        let timeout = self.timeout;
+
+        async move {
+            if refetch && !repo.is_online() {
+                anyhow::bail!("refetching blocks requires the repo to be online");
+            }
+
+            let started = Instant::now();
+            let mut report = VerifyReport::default();
+
+            scan(&repo, &mut report).await;
+            recheck_scanned(&repo, &mut report).await;
+
+            let scanned_missing = report.missing.len();
+            check_pins(&repo, &mut report).await?;
+            recheck_pins(&repo, &mut report, scanned_missing).await;
+
+            debug!(
+                checked = report.checked,
+                corrupt = report.corrupt.len(),
+                missing = report.missing.len(),
+                incomplete_pins = report.incomplete_pins.len(),
+                "verified blocks"
+            );
+
+            if repair {
+                report.removed = remove(&repo, &report.corrupt).await?;
+            }
+
+            if refetch {
+                fetch(&repo, &providers, timeout, &mut report).await;
+            }
+
+            report.duration = started.elapsed();
+            Ok(report)
+        }
+        .instrument(span)
+        .boxed()
+    }
+}
+
+/// Reads back and hashes every block listed by the repo.
+async fn scan(repo: &Repo, report: &mut VerifyReport) {
+    let mut blocks = repo.list_blocks().await;
+
+    while let Some(cid) = blocks.next().await {
+        report.checked += 1;
+        match read(repo, &cid).await {
+            Ok(Some(block)) => match verify_hash(&cid, block.data()) {
+                Ok(true) => {}
+                Ok(false) => {
+                    warn!(%cid, "block does not match its multihash");
+                    report.corrupt.push(cid);
+                }
+                Err(e) => {
+                    warn!(%cid, "cannot hash block: {e}");
+                    report.corrupt.push(cid);
+                }
+            },
+            Ok(None) => {
+                warn!(%cid, "listed block is missing");
+                report.missing.push(cid);
+            }
+            Err(e) => {
+                // the block stores verify the blocks they read
+                warn!(%cid, "cannot read block: {e}");
+                report.corrupt.push(cid);
+            }
+        }
+    }
+}
+
+/// Reads the block without protecting it from a running gc, as it is not in use by the node.
+async fn read(repo: &Repo, cid: &Cid) -> Result<Option<Block>, Error> {
+    match repo.inner.block_store.get(cid).await? {
+        Some(block) => Ok(Some(block)),
+        None => super::filestore::get(repo, cid).await,
+    }
+}
+
+/// Checks the corrupt and missing blocks found by [`scan`] again while holding the gc lock, as
+/// they might have been removed since they were listed.
+async fn recheck_scanned(repo: &Repo, report: &mut VerifyReport) {
+    let _g = repo.inner.gclock.read().await;
+
+    let mut corrupt = Vec::with_capacity(report.corrupt.len());
+    for cid in std::mem::take(&mut report.corrupt) {
+        match read(repo, &cid).await {
+            Ok(Some(block)) if matches!(verify_hash(&cid, block.data()), Ok(true)) => {
+                debug!(%cid, "block was stored again after it was found corrupt");
+            }
+            Ok(None) if !repo.contains(&cid).await.unwrap_or(true) => {
+                debug!(%cid, "corrupt block was removed while verifying");
+            }
+            _ => corrupt.push(cid),
+        }
+    }
+    report.corrupt = corrupt;
+
+    // a listed block which cannot be found is only missing if it is still stored
+    let mut missing = Vec::with_capacity(report.missing.len());
+    for cid in std::mem::take(&mut report.missing) {
+        match read(repo, &cid).await {
+            Ok(Some(_)) => {}
+            _ if repo.contains(&cid).await.unwrap_or(true) => missing.push(cid),
+            _ => debug!(%cid, "listed block was removed while verifying"),
+        }
+    }
+    report.missing = missing;
+}
+
+/// Checks the blocks found missing by [`check_pins`] after the first `scanned` missing blocks
+/// again while holding the gc lock, along with the incomplete pins, as the blocks might have been
+/// stored or the pins removed in the meantime.
+async fn recheck_pins(repo: &Repo, report: &mut VerifyReport, scanned: usize) {
+    let _g = repo.inner.gclock.read().await;
+
+    let found = report.missing.split_off(scanned);
+    for cid in found {
+        match repo.contains(&cid).await {
+            Ok(true) => debug!(%cid, "missing block was stored while verifying"),
+            _ => report.missing.push(cid),
+        }
+    }
+
+    let mut incomplete_pins = Vec::with_capacity(report.incomplete_pins.len());
+    for cid in std::mem::take(&mut report.incomplete_pins) {
+        match repo.query_pins(vec![cid], PinMode::Recursive).await {
+            Ok(_) => incomplete_pins.push(cid),
+            Err(_) => debug!(pin = %cid, "incomplete pin was removed while verifying"),
+        }
+    }
+    report.incomplete_pins = incomplete_pins;
+}
+
+/// Returns true if the data hashes to the multihash of the `Cid`.
+fn verify_hash(cid: &Cid, data: &[u8]) -> Result<bool, Error> {
+    let code = Code::try_from(cid.hash().code())?;
+    Ok(code.digest(data) == *cid.hash())
+}
+
+/// Walks every recursive pin, recording the pins which are missing some of their blocks.
+async fn check_pins(repo: &Repo, report: &mut VerifyReport) -> Result<(), Error> {
+    let corrupt = report.corrupt.iter().copied().collect::<HashSet<_>>();
+    let mut reported = corrupt
+        .iter()
+        .chain(&report.missing)
+        .copied()
+        .collect::<HashSet<_>>();
+
+    let mut pins = repo.list_pins(PinMode::Recursive).await;
+    let mut roots = vec![];
+    while let Some(result) = pins.next().await {
+        let (cid, _) = result?;
+        roots.push(cid);
+    }
+
+    for cid in roots {
+        let block = match repo.get_block_now(&cid).await {
+            Ok(Some(block)) if !corrupt.contains(&cid) => block,
+            Ok(Some(_)) | Err(_) => {
+                report.incomplete_pins.push(cid);
+                continue;
+            }
+            Ok(None) => {
+                if reported.insert(cid) {
+                    report.missing.push(cid);
+                }
+                report.incomplete_pins.push(cid);
+                continue;
+            }
+        };
+
+        // blocks which cannot be decoded have no references to check
+        let Ok(ipld) = block.decode::<IpldCodec, Ipld>() else {
+            continue;
+        };
+
+        let mut refs = IpldRefs::default()
+            .with_only_unique()
+            .with_existing_blocks()
+            .with_missing_reported()
+            .refs_of_resolved(repo, vec![(cid, ipld)])
+            .boxed();
+
+        let mut complete = true;
+        while let Some(result) = refs.next().await {
+            match result {
+                Ok(Edge { destination, .. }) => {
+                    if corrupt.contains(&destination) {
+                        complete = false;
+                    }
+                }
+                Err(IpldRefsError::BlockNotFound(missing)) => {
+                    complete = false;
+                    if reported.insert(missing) {
+                        report.missing.push(missing);
+                    }
+                }
+                // undecodable blocks are stored, they just have no references to follow
+                Err(IpldRefsError::Loading(e)) => trace!(pin = %cid, "skipping block: {e}"),
+            }
+        }
+
+        if !complete {
+            warn!(%cid, "recursive pin is incomplete");
+            report.incomplete_pins.push(cid);
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the blocks while holding the gc lock, returning the ones which were removed.
+async fn remove(repo: &Repo, cids: &[Cid]) -> Result<Vec<Cid>, Error> {
+    if cids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let _g = repo.inner.gclock.write().await;
+
+    let mut removed = repo
+        .inner
+        .block_store
+        .remove_many(futures::stream::iter(cids.to_vec()).boxed())
+        .await
+        .collect::<Vec<_>>()
+        .await;
+
+    // blocks of files added without copying only have their references removed
+    let unreferenced = super::filestore::remove_many(repo, cids).await?;
+    for cid in unreferenced {
+        if !removed.contains(&cid) {
+            removed.push(cid);
+        }
+    }
+
+    for cid in &removed {
+        // notify ipfs task about the removed blocks
+        if let Some(mut events) = repo.repo_channel() {
+            let _ = events.send(RepoEvent::RemovedBlock(*cid)).await;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Fetches the removed blocks and the incomplete pins from the network. Blocks which cannot be
+/// fetched are left out of the report, and are found again by the next verification.
+async fn fetch(
+    repo: &Repo,
+    providers: &[PeerId],
+    timeout: Option<Duration>,
+    report: &mut VerifyReport,
+) {
+    for cid in &report.removed {
+        match repo
+            .get_block_with_session(None, cid, providers, false, timeout)
+            .await
+        {
+            Ok(_) => report.refetched.push(*cid),
+            Err(e) => warn!(%cid, "failed to refetch block: {e}"),
+        }
+    }
+
+    for cid in &report.incomplete_pins {
+        let mut fetch = repo.fetch(cid).recursive().providers(providers);
+        if let Some(timeout) = timeout {
+            fetch = fetch.timeout(timeout);
+        }
+        if let Err(e) = fetch.await {
+            warn!(pin = %cid, "failed to refetch pinned blocks: {e}");
+        }
+    }
+
+    for cid in &report.missing {
+        if repo.contains(cid).await.unwrap_or_default() {
+            report.refetched.push(*cid);
+        }
+    }
+}
+
+impl Repo {
+    /// Verifies the stored blocks against their multihash and checks that the recursive pins have
+    /// every block reachable from them stored.
+    pub fn verify(&self) -> RepoVerify {
+        RepoVerify::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use libipld::multihash::{Code, MultihashDigest};
+    use libipld::{ipld, Cid, IpldCodec};
+
+    use super::{recheck_pins, recheck_scanned, VerifyReport};
+    use crate::repo::Repo;
+    use crate::Block;
+
+    fn raw_block(data: &[u8]) -> Block {
+        let cid = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(data));
+        Block::new_unchecked(cid, data.to_vec())
+    }
+
+    fn cbor_block(ipld: libipld::Ipld) -> Block {
+        Block::encode(libipld::cbor::DagCborCodec, Code::Sha2_256, &ipld).unwrap()
+    }
+
+    #[tokio::test]
+    async fn intact_repo() {
+        let repo = Repo::new_memory();
+        repo.init().await.unwrap();
+
+        let leaf = raw_block(b"leaf");
+        let root = cbor_block(ipld!({ "leaf": *leaf.cid() }));
+        for block in [&leaf, &root] {
+            repo.put_block(block.clone()).await.unwrap();
+        }
+        repo.pin(root.cid()).recursive().local().await.unwrap();
+
+        let report = repo.verify().await.unwrap();
+        assert!(report.is_intact());
+        assert_eq!(report.checked, 2);
+    }
+
+    #[tokio::test]
+    async fn reports_and_removes_corrupt_blocks() {
+        let repo = Repo::new_memory();
+        repo.init().await.unwrap();
+
+        let leaf = raw_block(b"leaf");
+        let root = cbor_block(ipld!({ "leaf": *leaf.cid() }));
+        for block in [&leaf, &root] {
+            repo.put_block(block.clone()).await.unwrap();
+        }
+        repo.pin(root.cid()).recursive().local().await.unwrap();
+
+        // the stored bytes of the leaf rot
+        repo.inner.block_store.remove(leaf.cid()).await.unwrap();
+        let rotten = Block::new_unchecked(*leaf.cid(), b"lead".to_vec());
+        repo.inner.block_store.put(rotten).await.unwrap();
+
+        let report = repo.verify().await.unwrap();
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.corrupt, vec![*leaf.cid()]);
+        assert!(report.missing.is_empty());
+        assert_eq!(report.incomplete_pins, vec![*root.cid()]);
+        assert!(report.removed.is_empty());
+        assert!(repo.contains(leaf.cid()).await.unwrap());
+
+        let report = repo.verify().repair().await.unwrap();
+        assert_eq!(report.removed, vec![*leaf.cid()]);
+        assert!(!repo.contains(leaf.cid()).await.unwrap());
+
+        let report = repo.verify().await.unwrap();
+        assert!(report.corrupt.is_empty());
+        assert_eq!(report.missing, vec![*leaf.cid()]);
+        assert_eq!(report.incomplete_pins, vec![*root.cid()]);
+    }
+
+    #[tokio::test]
+    async fn removals_during_verification_are_not_reported() {
+        let repo = Repo::new_memory();
+        repo.init().await.unwrap();
+
+        let stored = raw_block(b"stored");
+        let removed = raw_block(b"removed");
+        let pinned = cbor_block(ipld!({ "leaf": *removed.cid() }));
+        repo.put_block(stored.clone()).await.unwrap();
+
+        // as found by the scan and the pin walk before the blocks were removed or stored again,
+        // and the pin was removed
+        let mut report = VerifyReport {
+            corrupt: vec![*stored.cid(), *removed.cid()],
+            missing: vec![*removed.cid()],
+            ..Default::default()
+        };
+        recheck_scanned(&repo, &mut report).await;
+        assert!(report.corrupt.is_empty());
+        assert!(report.missing.is_empty());
+
+        report.missing = vec![*stored.cid(), *removed.cid()];
+        report.incomplete_pins = vec![*pinned.cid()];
+        recheck_pins(&repo, &mut report, 0).await;
+        assert_eq!(report.missing, vec![*removed.cid()]);
+        assert!(report.incomplete_pins.is_empty());
+    }
+
+    #[tokio::test]
+    async fn refetches_corrupt_blocks() {
+        let a = crate::Node::new("a").await;
+        let b = crate::Node::new("b").await;
+        b.connect(a.addrs[0].clone()).await.unwrap();
+
+        let leaf = raw_block(b"leaf");
+        let root = cbor_block(ipld!({ "leaf": *leaf.cid() }));
+        for block in [&leaf, &root] {
+            a.put_block(block.clone()).await.unwrap();
+            b.put_block(block.clone()).await.unwrap();
+        }
+        b.insert_pin(root.cid()).recursive().local().await.unwrap();
+
+        let repo = b.repo();
+        repo.inner.block_store.remove(leaf.cid()).await.unwrap();
+        let rotten = Block::new_unchecked(*leaf.cid(), b"lead".to_vec());
+        repo.inner.block_store.put(rotten).await.unwrap();
+
+        let report = b
+            .verify()
+            .refetch()
+            .timeout(Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert_eq!(report.corrupt, vec![*leaf.cid()]);
+        assert_eq!(report.removed, vec![*leaf.cid()]);
+        assert_eq!(report.refetched, vec![*leaf.cid()]);
+
+        let report = b.verify().await.unwrap();
+        assert!(report.is_intact());
+    }
+
+    #[tokio::test]
+    async fn refetch_requires_online_repo() {
+        let repo = Repo::new_memory();
+        repo.init().await.unwrap();
+
+        assert!(repo.verify().refetch().await.is_err());
+    }
+}
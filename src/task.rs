@@ -5,8 +5,9 @@ use futures::{
         mpsc::{unbounded, Receiver, UnboundedSender},
         oneshot,
     },
+    future::BoxFuture,
     stream::Fuse,
-    FutureExt, StreamExt,
+    FutureExt, Stream, StreamExt, TryStreamExt,
 };
 
 #[cfg(feature = "beetle_bitswap")]
@@ -30,7 +31,7 @@ use tokio::task::JoinHandle;
 use wasm_timer::Interval;
 
 use std::{
-    collections::{hash_map::Entry, HashMap, HashSet},
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
     time::Duration,
 };
 
@@ -40,9 +41,12 @@ use std::task::{Context, Poll};
 use crate::{config::BOOTSTRAP_NODES, IpfsEvent, TSwarmEventFn};
 
 use crate::{
+    error::Error,
+    ipns::{self, IpnsOption, Republish},
+    keystore::Keystore,
     p2p::TSwarm,
-    repo::{Repo, RepoEvent},
-    AddPeerOpt,
+    repo::{PinMode, Repo, RepoEvent},
+    AddPeerOpt, RepoProvider, ReproviderConfig, ReproviderStats,
 };
 
 pub use crate::{p2p::BehaviourEvent, p2p::KadResult};
@@ -56,10 +60,11 @@ pub use libp2p::{
 use libp2p::{
     autonat,
     identify::{Event as IdentifyEvent, Info as IdentifyInfo},
+    identity::Keypair,
     kad::{
         AddProviderError, AddProviderOk, BootstrapError, BootstrapOk, Event as KademliaEvent,
         GetClosestPeersError, GetClosestPeersOk, GetProvidersError, GetProvidersOk, GetRecordError,
-        GetRecordOk, PutRecordError, PutRecordOk, QueryId, QueryResult::*, Record,
+        GetRecordOk, PutRecordError, PutRecordOk, QueryId, QueryResult::*, Quorum, Record,
     },
     rendezvous::{Cookie, Namespace},
     swarm::{ConnectionId, SwarmEvent},
@@ -105,6 +110,8 @@ pub(crate) struct IpfsTask<C: NetworkBehaviour<ToSwarm = void::Void>> {
     pub(crate) pending_disconnection: HashMap<PeerId, Vec<Channel<()>>>,
     pub(crate) pending_add_listener: HashMap<ListenerId, Channel<Multiaddr>>,
     pub(crate) pending_remove_listener: HashMap<ListenerId, Channel<()>>,
+    pub(crate) reprovider: Reprovider,
+    pub(crate) republisher: Republisher,
 }
 
 impl<C: NetworkBehaviour<ToSwarm = void::Void>> IpfsTask<C> {
@@ -139,6 +146,298 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void>> IpfsTask<C> {
             pending_connection: Default::default(),
             pending_add_listener: Default::default(),
             pending_remove_listener: Default::default(),
+            reprovider: Reprovider::new(repo, RepoProvider::None, Default::default(), vec![]),
+            republisher: Republisher::disabled(repo),
+        }
+    }
+}
+
+/// Time after start at which the ipns records are first republished.
+const REPUBLISH_INITIAL_DELAY: Duration = Duration::from_secs(60);
+
+/// Shortest time between republishing, for records published with very short lifetimes.
+const REPUBLISH_MIN_DELAY: Duration = Duration::from_secs(1);
+
+/// Republishes the ipns records published by the node and with the keys of the [`Keystore`]
+/// once half of their lifetime has passed, re-signing them so that they do not expire. The
+/// records are checked at least once every interval.
+pub(crate) struct Republisher {
+    repo: Repo,
+    keypair: Option<Keypair>,
+    keystore: Keystore,
+    interval: Option<Duration>,
+    timer: futures_timer::Delay,
+    pending: Option<BoxFuture<'static, (Vec<Republish>, Option<Duration>)>>,
+}
+
+impl Republisher {
+    pub(crate) fn new(
+        repo: &Repo,
+        keypair: &Keypair,
+        keystore: &Keystore,
+        interval: Option<Duration>,
+    ) -> Self {
+        Republisher {
+            repo: repo.clone(),
+            keypair: Some(keypair.clone()),
+            keystore: keystore.clone(),
+            interval,
+            timer: futures_timer::Delay::new(REPUBLISH_INITIAL_DELAY),
+            pending: None,
+        }
+    }
+
+    pub(crate) fn disabled(repo: &Repo) -> Self {
+        Republisher {
+            repo: repo.clone(),
+            keypair: None,
+            keystore: Keystore::in_memory(),
+            interval: None,
+            timer: futures_timer::Delay::new(REPUBLISH_INITIAL_DELAY),
+            pending: None,
+        }
+    }
+}
+
+impl Stream for Republisher {
+    type Item = Vec<Republish>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        let (Some(interval), Some(keypair)) = (this.interval, this.keypair.as_ref()) else {
+            return Poll::Pending;
+        };
+
+        loop {
+            let pending = match this.pending.as_mut() {
+                Some(pending) => pending,
+                None => {
+                    if this.timer.poll_unpin(cx).is_pending() {
+                        return Poll::Pending;
+                    }
+
+                    let repo = this.repo.clone();
+                    let keypair = keypair.clone();
+                    let keystore = this.keystore.clone();
+
+                    this.pending.insert(
+                        async move {
+                            let mut keypairs = match keystore.keypairs().await {
+                                Ok(keypairs) => keypairs,
+                                Err(e) => {
+                                    warn!("ipns: failed to list the keys to republish: {e}");
+                                    Vec::new()
+                                }
+                            };
+                            keypairs.push(keypair);
+                            ipns::republish(&repo, keypairs).await
+                        }
+                        .boxed(),
+                    )
+                }
+            };
+
+            let Poll::Ready((records, next)) = pending.poll_unpin(cx) else {
+                return Poll::Pending;
+            };
+
+            this.pending = None;
+
+            let next = next.map_or(interval, |next| next.min(interval).max(REPUBLISH_MIN_DELAY));
+            this.timer.reset(next);
+
+            if !records.is_empty() {
+                return Poll::Ready(Some(records));
+            }
+        }
+    }
+}
+
+/// Lists the blocks to announce with the given strategy.
+pub(crate) async fn reprovider_keys(
+    repo: &Repo,
+    strategy: RepoProvider,
+) -> Result<Vec<libipld::Cid>, Error> {
+    let mode = match strategy {
+        RepoProvider::None => return Ok(vec![]),
+        RepoProvider::All => return Ok(repo.list_blocks().await.collect().await),
+        RepoProvider::Pinned => None,
+        RepoProvider::Roots => Some(PinMode::Recursive),
+    };
+
+    let pins = repo
+        .list_pins(mode)
+        .await
+        .map_ok(|(cid, _)| cid)
+        .try_collect::<indexmap::IndexSet<_>>()
+        .await?;
+
+    Ok(pins.into_iter().collect())
+}
+
+/// Announces the blocks selected by the [`RepoProvider`] strategy on start and then after every
+/// interval. The blocks are yielded in batches to be provided by kademlia, the next batch being
+/// yielded once the queries of the previous one have finished.
+pub(crate) struct Reprovider {
+    repo: Repo,
+    strategy: RepoProvider,
+    config: ReproviderConfig,
+    interval: futures_timer::Delay,
+    requested: bool,
+    listing: Option<BoxFuture<'static, Result<Vec<libipld::Cid>, Error>>>,
+    queue: VecDeque<libipld::Cid>,
+    in_flight: HashSet<QueryId>,
+    started: Option<(web_time::Instant, web_time::SystemTime)>,
+    stats: ReproviderStats,
+    waiting: Vec<Channel<ReproviderStats>>,
+}
+
+impl Reprovider {
+    pub(crate) fn new(
+        repo: &Repo,
+        strategy: RepoProvider,
+        config: ReproviderConfig,
+        blocks: Vec<libipld::Cid>,
+    ) -> Self {
+        let mut reprovider = Reprovider {
+            repo: repo.clone(),
+            strategy,
+            config,
+            interval: futures_timer::Delay::new(config.interval),
+            requested: false,
+            listing: None,
+            queue: VecDeque::new(),
+            in_flight: HashSet::new(),
+            started: None,
+            stats: ReproviderStats::default(),
+            waiting: Vec::new(),
+        };
+
+        if strategy != RepoProvider::None {
+            reprovider.start(blocks);
+        }
+
+        reprovider
+    }
+
+    /// Starts a run unless one is in progress, replying once it has finished.
+    pub(crate) fn request(&mut self, ret: Channel<ReproviderStats>) {
+        if self.strategy == RepoProvider::None {
+            let _ = ret.send(Err(anyhow!("reprovider is disabled")));
+            return;
+        }
+
+        if !self.stats.running && self.listing.is_none() {
+            self.requested = true;
+        }
+
+        self.waiting.push(ret);
+    }
+
+    pub(crate) fn stats(&self) -> ReproviderStats {
+        self.stats.clone()
+    }
+
+    pub(crate) fn track(&mut self, id: QueryId) {
+        self.in_flight.insert(id);
+    }
+
+    pub(crate) fn failed(&mut self, count: usize) {
+        self.stats.failed += count;
+    }
+
+    /// Records the result of a finished query, if it was started by the reprovider.
+    pub(crate) fn complete(&mut self, id: &QueryId, success: bool) {
+        if !self.in_flight.remove(id) {
+            return;
+        }
+
+        match success {
+            true => self.stats.provided += 1,
+            false => self.stats.failed += 1,
+        }
+    }
+
+    fn start(&mut self, blocks: Vec<libipld::Cid>) {
+        self.started = Some((web_time::Instant::now(), web_time::SystemTime::now()));
+        self.stats.running = true;
+        self.stats.total = blocks.len();
+        self.stats.provided = 0;
+        self.stats.failed = 0;
+        self.queue = blocks.into();
+    }
+
+    fn finish(&mut self) {
+        if let Some((instant, time)) = self.started.take() {
+            self.stats.last_run = Some(time);
+            self.stats.last_run_duration = Some(instant.elapsed());
+        }
+
+        self.stats.running = false;
+        self.stats.runs += 1;
+        self.interval.reset(self.config.interval);
+
+        debug!(
+            provided = self.stats.provided,
+            failed = self.stats.failed,
+            "reprovider: run finished"
+        );
+
+        for ret in self.waiting.drain(..) {
+            let _ = ret.send(Ok(self.stats.clone()));
+        }
+    }
+}
+
+impl Stream for Reprovider {
+    type Item = Vec<libipld::Cid>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if this.strategy == RepoProvider::None {
+            return Poll::Pending;
+        }
+
+        loop {
+            if !this.stats.running && this.listing.is_none() {
+                let requested = std::mem::take(&mut this.requested);
+                if this.interval.poll_unpin(cx).is_pending() && !requested {
+                    return Poll::Pending;
+                }
+
+                let repo = this.repo.clone();
+                let strategy = this.strategy;
+                this.listing = Some(async move { reprovider_keys(&repo, strategy).await }.boxed());
+            }
+
+            if let Some(listing) = this.listing.as_mut() {
+                let Poll::Ready(result) = listing.poll_unpin(cx) else {
+                    return Poll::Pending;
+                };
+
+                this.listing = None;
+
+                match result {
+                    Ok(blocks) => this.start(blocks),
+                    Err(e) => {
+                        warn!("reprovider: failed to list the blocks to provide: {e}");
+                        this.start(vec![]);
+                    }
+                }
+            }
+
+            if !this.in_flight.is_empty() {
+                return Poll::Pending;
+            }
+
+            if !this.queue.is_empty() {
+                let len = this.config.batch_size.clamp(1, this.queue.len());
+                return Poll::Ready(Some(this.queue.drain(..len).collect()));
+            }
+
+            this.finish();
         }
     }
 }
@@ -189,6 +488,14 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void>> futures::Future for IpfsTask<C>
             }
         }
 
+        while let Poll::Ready(Some(blocks)) = self.reprovider.poll_next_unpin(cx) {
+            self.reprovide(blocks);
+        }
+
+        while let Poll::Ready(Some(records)) = self.republisher.poll_next_unpin(cx) {
+            self.republish(records);
+        }
+
         if self.timer.event_cleanup.poll_next_unpin(cx).is_ready() {
             self.pubsub_event_stream.retain(|ch| !ch.is_closed());
         }
@@ -242,6 +549,12 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void>> IpfsTask<C> {
                     }
                     self.handle_event(event);
                 },
+                Some(blocks) = self.reprovider.next() => {
+                    self.reprovide(blocks);
+                },
+                Some(records) = self.republisher.next() => {
+                    self.republish(records);
+                },
                 _ = &mut event_cleanup => {
                     self.pubsub_event_stream.retain(|ch| !ch.is_closed());
                     event_cleanup.reset(Duration::from_secs(60));
@@ -426,6 +739,10 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void>> IpfsTask<C> {
                             .and_then(|kad| kad.query(&id))
                             .is_none()
                         {
+                            if let StartProviding(result) = &result {
+                                self.reprovider.complete(&id, result.is_ok());
+                            }
+
                             match result {
                                 // these subscriptions return actual values
                                 GetClosestPeers(_) | GetProviders(_) | GetRecord(_) => {}
@@ -1056,6 +1373,56 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void>> IpfsTask<C> {
         }
     }
 
+    fn reprovide(&mut self, blocks: Vec<libipld::Cid>) {
+        let Some(kad) = self.swarm.behaviour_mut().kademlia.as_mut() else {
+            self.reprovider.failed(blocks.len());
+            return;
+        };
+
+        for cid in blocks {
+            match kad.start_providing(Key::from(cid.hash().to_bytes())) {
+                Ok(id) => self.reprovider.track(id),
+                Err(e) => {
+                    debug!("reprovider: can't provide {cid}: {e:?}");
+                    self.reprovider.failed(1);
+                }
+            }
+        }
+    }
+
+    fn republish(&mut self, records: Vec<Republish>) {
+        for Republish {
+            peer_id,
+            option,
+            record,
+        } in records
+        {
+            match option {
+                IpnsOption::DHT => {
+                    let Some(kad) = self.swarm.behaviour_mut().kademlia.as_mut() else {
+                        continue;
+                    };
+
+                    let key = [b"/ipns/".as_slice(), &peer_id.to_bytes()].concat();
+
+                    if let Err(e) = kad.put_record(Record::new(key, record), Quorum::One) {
+                        warn!("ipns: failed to republish record of {peer_id}: {e}");
+                    }
+                }
+                IpnsOption::PubSub => {
+                    let Some(pubsub) = self.swarm.behaviour_mut().pubsub.as_mut() else {
+                        continue;
+                    };
+
+                    if let Err(e) = pubsub.publish(ipns::pubsub::topic(&peer_id), record) {
+                        debug!("ipns: record of {peer_id} not republished over pubsub: {e}");
+                    }
+                }
+                IpnsOption::Local => {}
+            }
+        }
+    }
+
     fn handle_event(&mut self, event: IpfsEvent) {
         match event {
             IpfsEvent::Connect(target, ret) => {
@@ -1473,6 +1840,10 @@ impl<C: NetworkBehaviour<ToSwarm = void::Void>> IpfsTask<C> {
                 };
                 let _ = ret.send(future);
             }
+            IpfsEvent::Reprovide(ret) => self.reprovider.request(ret),
+            IpfsEvent::ReproviderStats(ret) => {
+                let _ = ret.send(self.reprovider.stats());
+            }
             IpfsEvent::DhtMode(mode, ret) => {
                 let Some(kad) = self.swarm.behaviour_mut().kademlia.as_mut() else {
                     let _ = ret.send(Err(anyhow!("kad protocol is disabled")));
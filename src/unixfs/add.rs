@@ -1,9 +1,12 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::HashSet;
 use std::{
     path::{Path, PathBuf},
     task::Poll,
 };
 
-use crate::{repo::Repo, Block};
+use crate::repo::{FileRef, Repo};
+use crate::Block;
 use bytes::Bytes;
 use either::Either;
 #[allow(unused_imports)]
@@ -12,7 +15,12 @@ use futures::{
     stream::{BoxStream, FusedStream},
     FutureExt, Stream, StreamExt, TryFutureExt,
 };
-use rust_unixfs::file::adder::{Chunker, FileAdderBuilder};
+use libipld::cid::Version;
+use libipld::multihash::Code;
+use libipld::Cid;
+use rust_unixfs::dir::builder::BufferingTreeBuilder;
+use rust_unixfs::file::adder::{Chunker, Collector, FileAdder, FileAdderBuilder};
+use rust_unixfs::{CidOptions, Metadata};
 #[cfg(not(target_arch = "wasm32"))]
 use tokio_util::io::ReaderStream;
 use tracing::{Instrument, Span};
@@ -42,15 +50,39 @@ impl From<&Path> for AddOpt {
     }
 }
 
+/// Handling of the symbolic links found when adding a directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Symlinks {
+    /// Adds the links as UnixFS symlinks with the same target.
+    #[default]
+    Preserve,
+    /// Adds the files and directories the links point to.
+    Follow,
+    /// Leaves the links out.
+    Skip,
+}
+
 #[must_use = "do nothing unless you `.await` or poll the stream"]
 pub struct UnixfsAdd {
     core: Option<Either<Ipfs, Repo>>,
     opt: Option<AddOpt>,
     span: Span,
     chunk: Chunker,
+    collector: Collector,
+    cid_version: Option<Version>,
+    raw_leaves: Option<bool>,
+    hash: Code,
+    inline: Option<usize>,
     pin: bool,
     provide: bool,
     wrap: bool,
+    hidden: bool,
+    ignore: Vec<String>,
+    symlinks: Symlinks,
+    empty_dirs: bool,
+    preserve_mode: bool,
+    preserve_mtime: bool,
+    nocopy: bool,
     stream: StatusStreamState,
 }
 
@@ -70,9 +102,21 @@ impl UnixfsAdd {
             opt: Some(opt),
             span: Span::current(),
             chunk: Chunker::Size(256 * 1024),
+            collector: Collector::default(),
+            cid_version: None,
+            raw_leaves: None,
+            hash: Code::Sha2_256,
+            inline: None,
             pin: true,
             provide: false,
             wrap: false,
+            hidden: false,
+            ignore: Vec::new(),
+            symlinks: Symlinks::default(),
+            empty_dirs: true,
+            preserve_mode: false,
+            preserve_mtime: false,
+            nocopy: false,
             stream: StatusStreamState::None,
         }
     }
@@ -87,6 +131,39 @@ impl UnixfsAdd {
         self
     }
 
+    /// Sets the layout of the file trees, either the default balanced or the trickle layout.
+    pub fn collector(mut self, collector: impl Into<Collector>) -> Self {
+        self.collector = collector.into();
+        self
+    }
+
+    /// Sets the Cid version of the created blocks. Unless set with [`UnixfsAdd::raw_leaves`],
+    /// version 1 also enables raw leaves.
+    pub fn cid_version(mut self, version: Version) -> Self {
+        self.cid_version = Some(version);
+        self
+    }
+
+    /// Stores the file contents in raw blocks instead of UnixFs File blocks.
+    pub fn raw_leaves(mut self, raw_leaves: bool) -> Self {
+        self.raw_leaves = Some(raw_leaves);
+        self
+    }
+
+    /// Sets the multihash of the created blocks. Any other than sha2-256 implies Cid version 1,
+    /// and cannot be used together with an explicit Cid version 0.
+    pub fn hash(mut self, hash: Code) -> Self {
+        self.hash = hash;
+        self
+    }
+
+    /// Inlines the blocks of at most `limit` bytes into their Cids with the identity multihash.
+    /// The limit is capped at 64 bytes.
+    pub fn inline(mut self, limit: usize) -> Self {
+        self.inline = Some(limit);
+        self
+    }
+
     pub fn pin(mut self, pin: bool) -> Self {
         self.pin = pin;
         self
@@ -101,6 +178,87 @@ impl UnixfsAdd {
         self.wrap = true;
         self
     }
+
+    /// Includes the files and directories whose names start with a dot when adding a directory.
+    /// They are left out by default.
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Leaves out the entries matching the gitignore style pattern when adding a directory.
+    /// Patterns without a slash match the names at any depth, others match the path relative to
+    /// the directory being added. A trailing slash only matches directories, and `*`, `?` and
+    /// `**` are supported as wildcards.
+    pub fn ignore(mut self, pattern: impl Into<String>) -> Self {
+        self.ignore.push(pattern.into());
+        self
+    }
+
+    /// Sets how the symbolic links are handled when adding a directory. The path being added
+    /// is always followed.
+    pub fn symlinks(mut self, symlinks: Symlinks) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    /// Keeps the directories without any entries when adding a directory. Defaults to true.
+    pub fn empty_dirs(mut self, keep: bool) -> Self {
+        self.empty_dirs = keep;
+        self
+    }
+
+    /// Records the permissions of the files, directories and symlinks added from disk in the
+    /// UnixFS metadata, like `--preserve-mode` in kubo.
+    pub fn preserve_mode(mut self, preserve: bool) -> Self {
+        self.preserve_mode = preserve;
+        self
+    }
+
+    /// Records the modification times of the files, directories and symlinks added from disk in
+    /// the UnixFS metadata, like `--preserve-mtime` in kubo.
+    pub fn preserve_mtime(mut self, preserve: bool) -> Self {
+        self.preserve_mtime = preserve;
+        self
+    }
+
+    /// Records the contents of the files added from disk as references to the files instead of
+    /// copying them into the block store, like `--nocopy` in kubo. The files must then be kept
+    /// as they are, see [`crate::repo::filestore`]. Requires raw leaves, which are enabled by
+    /// default when adding without copying.
+    pub fn nocopy(mut self, nocopy: bool) -> Self {
+        self.nocopy = nocopy;
+        self
+    }
+}
+
+impl UnixfsAdd {
+    /// Resolves the Cid options and whether or not to use raw leaves the same way as go-ipfs.
+    fn cid_options(&self) -> Result<(CidOptions, bool), anyhow::Error> {
+        let version = match (self.cid_version, self.hash) {
+            (Some(Version::V0), hash) if hash != Code::Sha2_256 => {
+                anyhow::bail!("cid version 0 only supports sha2-256")
+            }
+            (Some(version), _) => version,
+            (None, Code::Sha2_256) => Version::V0,
+            (None, _) => Version::V1,
+        };
+
+        let raw_leaves = self
+            .raw_leaves
+            .unwrap_or(self.nocopy || version == Version::V1);
+
+        if self.nocopy && !raw_leaves {
+            anyhow::bail!("nocopy requires raw leaves");
+        }
+
+        let options = CidOptions::default()
+            .with_version(version)
+            .with_hash(self.hash)
+            .with_inline_limit(self.inline);
+
+        Ok((options, raw_leaves))
+    }
 }
 
 impl Stream for UnixfsAdd {
@@ -121,151 +279,311 @@ impl Stream for UnixfsAdd {
                     };
                     let option = self.opt.take().expect("option already constructed");
                     let chunk = self.chunk;
+                    let collector = std::mem::take(&mut self.collector);
+                    let cid_options = self.cid_options();
                     let pin = self.pin;
                     let provide = self.provide;
                     let wrap = self.wrap;
+                    let nocopy = self.nocopy;
+                    #[cfg(not(target_arch = "wasm32"))]
+                    let filter = Filter {
+                        hidden: self.hidden,
+                        ignore: self
+                            .ignore
+                            .iter()
+                            .filter_map(|p| Pattern::parse(p))
+                            .collect(),
+                        symlinks: self.symlinks,
+                    };
+                    #[cfg(not(target_arch = "wasm32"))]
+                    let empty_dirs = self.empty_dirs;
+                    #[cfg(not(target_arch = "wasm32"))]
+                    let preserve = Preserve {
+                        mode: self.preserve_mode,
+                        mtime: self.preserve_mtime,
+                    };
 
                     let stream = async_stream::stream! {
                         let _g = repo.gc_guard().await;
 
                         let mut written = 0;
 
-                        let (name, total_size, mut stream) = match option {
-                            #[cfg(not(target_arch = "wasm32"))]
-                            AddOpt::File(path) => match tokio::fs::File::open(path.clone())
-                                .and_then(|file| async move {
-                                    let size = file.metadata().await?.len() as usize;
+                        let (cid_options, raw_leaves) = match cid_options {
+                            Ok(options) => options,
+                            Err(e) => {
+                                yield UnixfsStatus::FailedStatus { written, total_size: None, error: Some(e) };
+                                return;
+                            }
+                        };
 
-                                    let stream = ReaderStream::new(file);
+                        #[cfg(not(target_arch = "wasm32"))]
+                        let is_dir = match &option {
+                            AddOpt::File(path) => tokio::fs::metadata(path).await.map(|m| m.is_dir()).unwrap_or_default(),
+                            AddOpt::Stream { .. } => false,
+                        };
 
-                                    let name: Option<String> = path.file_name().map(|f| f.to_string_lossy().to_string());
+                        #[cfg(target_arch = "wasm32")]
+                        let is_dir = false;
 
-                                    Ok((name, Some(size), stream.boxed()))
-                                }).await {
-                                    Ok(s) => s,
+                        let (path, total_size) = match option {
+                            #[cfg(not(target_arch = "wasm32"))]
+                            AddOpt::File(root) if is_dir => {
+                                let (metadata, entries) = match walk_dir(&root, &filter, preserve).await {
+                                    Ok(walked) => walked,
                                     Err(e) => {
-                                        yield UnixfsStatus::FailedStatus { written, total_size: None, error: Some(anyhow::Error::from(e)) };
+                                        yield UnixfsStatus::FailedStatus { written, total_size: None, error: Some(e) };
                                         return;
                                     }
-                                },
-                            #[cfg(target_arch = "wasm32")]
-                            AddOpt::File(_) => {
-                                yield UnixfsStatus::FailedStatus { written, total_size: None, error: Some(anyhow::anyhow!("unimplemented")) };
-                                return;
-                            },
-                            AddOpt::Stream { name, total, stream } => (name, total, stream),
-                        };
+                                };
 
-                        let mut adder = FileAdderBuilder::default()
-                            .with_chunker(chunk)
-                            .build();
+                                let total_size = Some(entries.iter().map(|entry| match entry.kind {
+                                    EntryKind::File(size) => size as usize,
+                                    _ => 0,
+                                }).sum());
 
-                        yield UnixfsStatus::ProgressStatus { written, total_size };
+                                yield UnixfsStatus::ProgressStatus { written, total_size };
 
-                        while let Some(buffer) = stream.next().await {
-                            let buffer = match buffer {
-                                Ok(buf) => buf,
-                                Err(e) => {
-                                    yield UnixfsStatus::FailedStatus { written, total_size, error: Some(anyhow::Error::from(e)) };
-                                    return;
-                                }
-                            };
-
-                            let mut total = 0;
-                            while total < buffer.len() {
-                                let (blocks, consumed) = adder.push(&buffer[total..]);
-                                for (cid, block) in blocks {
-                                    let block = match Block::new(cid, block) {
-                                        Ok(block) => block,
+                                // the directory itself is the root unless wrapped, in which case
+                                // it is the only entry of the wrapping directory
+                                let prefix = match wrap {
+                                    true => match dir_name(&root).await {
+                                        Ok(name) => Some(name),
                                         Err(e) => {
                                             yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
                                             return;
                                         }
-                                    };
-                                    let _cid = match repo.put_block(block).await {
-                                        Ok(cid) => cid,
-                                        Err(e) => {
-                                            yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
-                                            return;
-                                        }
-                                    };
-                                }
-                                total += consumed;
-                                written += consumed;
-                            }
+                                    },
+                                    false => None,
+                                };
 
-                            yield UnixfsStatus::ProgressStatus { written, total_size };
-                        }
+                                let full_path = |relative: &str| match &prefix {
+                                    Some(prefix) => format!("{prefix}/{relative}"),
+                                    None => relative.to_string(),
+                                };
 
-                        let blocks = adder.finish();
-                        let mut last_cid = None;
+                                let mut opts = rust_unixfs::dir::builder::TreeOptions::default();
+                                opts.wrap_with_directory();
+                                opts.cid_options(cid_options);
 
-                        for (cid, block) in blocks {
-                            let block = match Block::new(cid, block) {
-                                Ok(block) => block,
-                                Err(e) => {
-                                    yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
-                                    return;
-                                }
-                            };
-                            let _cid = match repo.put_block(block).await {
-                                Ok(cid) => cid,
-                                Err(e) => {
-                                    yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
+                                let mut tree = rust_unixfs::dir::builder::BufferingTreeBuilder::new(opts);
+
+                                if let Err(e) = tree.set_metadata(prefix.as_deref().unwrap_or_default(), metadata) {
+                                    yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e.into()) };
                                     return;
                                 }
-                            };
-                            last_cid = Some(cid);
-                        }
 
-                        let cid = match last_cid {
-                            Some(cid) => cid,
-                            None => {
-                                yield UnixfsStatus::FailedStatus { written, total_size, error: None };
-                                return;
-                            }
-                        };
+                                // the directories which will exist even without being created
+                                // explicitly, as there are files or symlinks within
+                                let non_empty = match empty_dirs {
+                                    true => HashSet::new(),
+                                    false => non_empty_dirs(&entries),
+                                };
 
-                        let mut path = IpfsPath::from(cid);
+                                for entry in entries {
+                                    let name = full_path(&entry.relative);
 
-                        if wrap {
-                            if let Some(name) = name {
-                                let result = {
-                                    let repo = repo.clone();
-                                    async move {
-                                        let mut opts = rust_unixfs::dir::builder::TreeOptions::default();
-                                        opts.wrap_with_directory();
+                                    let (cid, size) = match entry.kind {
+                                        EntryKind::Directory => {
+                                            if empty_dirs || non_empty.contains(&entry.relative) {
+                                                if let Err(e) = tree.set_metadata(&name, entry.metadata) {
+                                                    yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e.into()) };
+                                                    return;
+                                                }
+                                            }
+                                            continue;
+                                        }
+                                        EntryKind::Symlink(target) => {
+                                            let mut block = Vec::new();
+                                            rust_unixfs::symlink::serialize_symlink_block_with_metadata(&target, &entry.metadata, &mut block);
+                                            let cid = cid_options.create(DAG_PB, &block);
+                                            let size = block.len();
 
-                                        let mut tree = rust_unixfs::dir::builder::BufferingTreeBuilder::new(opts);
-                                        tree.put_link(&name, cid, written as _)?;
+                                            if let Err(e) = repo.put_block(Block::new_unchecked(cid, block)).await {
+                                                yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
+                                                return;
+                                            }
 
-                                        let mut iter = tree.build();
-                                        let mut cids = Vec::new();
+                                            (cid, size)
+                                        }
+                                        EntryKind::File(_) => {
+                                            let stream = match tokio::fs::File::open(&entry.path).await {
+                                                Ok(file) => ReaderStream::new(file).boxed(),
+                                                Err(e) => {
+                                                    yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e.into()) };
+                                                    return;
+                                                }
+                                            };
+
+                                            let reference = match nocopy {
+                                                true => match NoCopy::new(&entry.path).await {
+                                                    Ok(reference) => Some(reference),
+                                                    Err(e) => {
+                                                        yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
+                                                        return;
+                                                    }
+                                                },
+                                                false => None,
+                                            };
 
-                                        while let Some(node) = iter.next_borrowed() {
-                                            let node = node?;
-                                            let block = Block::new(node.cid.to_owned(), node.block.into())?;
+                                            let adder = FileAdderBuilder::default()
+                                                .with_chunker(chunk)
+                                                .with_collector(collector.clone())
+                                                .with_cid_options(cid_options)
+                                                .with_raw_leaves(raw_leaves)
+                                                .with_metadata(entry.metadata)
+                                                .build();
 
-                                            repo.put_block(block).await?;
+                                            // the cumulative size of the blocks is what the
+                                            // directory links record as the size of the file
+                                            let mut added = None;
+                                            let mut file = add_file(&repo, adder, stream, reference);
 
-                                            cids.push(*node.cid);
+                                            while let Some(progress) = file.next().await {
+                                                match progress {
+                                                    Ok(FileProgress::Written(bytes)) => {
+                                                        written += bytes;
+                                                        yield UnixfsStatus::ProgressStatus { written, total_size };
+                                                    }
+                                                    Ok(FileProgress::Added(cid, size)) => added = Some((cid, size)),
+                                                    Err(e) => {
+                                                        yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
+                                                        return;
+                                                    }
+                                                }
+                                            }
+
+                                            match added {
+                                                Some(added) => added,
+                                                None => {
+                                                    yield UnixfsStatus::FailedStatus { written, total_size, error: None };
+                                                    return;
+                                                }
+                                            }
                                         }
-                                        let cid = cids.last().ok_or(anyhow::anyhow!("no cid available"))?;
-                                        let path = IpfsPath::from(*cid).sub_path(&name)?;
+                                    };
 
-                                        Ok::<_, anyhow::Error>(path)
+                                    if let Err(e) = tree.put_link(&name, cid, size as _) {
+                                        yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e.into()) };
+                                        return;
                                     }
-                                };
 
-                                path = match result.await {
-                                    Ok(path) => path,
+                                    yield UnixfsStatus::EntryStatus { path: entry.path, cid, written };
+                                }
+
+                                let result = put_tree(&repo, tree).await.and_then(|cid| match prefix {
+                                    Some(name) => IpfsPath::from(cid).sub_path(&name),
+                                    None => Ok(IpfsPath::from(cid)),
+                                });
+
+                                match result {
+                                    Ok(path) => (path, total_size),
                                     Err(e) => {
                                         yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
                                         return;
                                     }
+                                }
+                            }
+                            option => {
+                                let (name, total_size, metadata, stream, reference) = match option {
+                                    #[cfg(not(target_arch = "wasm32"))]
+                                    AddOpt::File(path) => match tokio::fs::File::open(path.clone())
+                                        .map_err(anyhow::Error::from)
+                                        .and_then(|file| async move {
+                                            let metadata = file.metadata().await?;
+                                            let size = metadata.len() as usize;
+
+                                            let stream = ReaderStream::new(file);
+
+                                            let name: Option<String> = path.file_name().map(|f| f.to_string_lossy().to_string());
+
+                                            let reference = match nocopy {
+                                                true => Some(NoCopy::new(&path).await?),
+                                                false => None,
+                                            };
+
+                                            Ok((name, Some(size), preserve.metadata(&metadata), stream.boxed(), reference))
+                                        }).await {
+                                            Ok(s) => s,
+                                            Err(e) => {
+                                                yield UnixfsStatus::FailedStatus { written, total_size: None, error: Some(e) };
+                                                return;
+                                            }
+                                        },
+                                    #[cfg(target_arch = "wasm32")]
+                                    AddOpt::File(_) => {
+                                        yield UnixfsStatus::FailedStatus { written, total_size: None, error: Some(anyhow::anyhow!("unimplemented")) };
+                                        return;
+                                    },
+                                    AddOpt::Stream { .. } if nocopy => {
+                                        yield UnixfsStatus::FailedStatus { written, total_size: None, error: Some(anyhow::anyhow!("nocopy requires adding from a file")) };
+                                        return;
+                                    }
+                                    AddOpt::Stream { name, total, stream } => (name, total, Metadata::default(), stream, None),
+                                };
+
+                                let adder = FileAdderBuilder::default()
+                                    .with_chunker(chunk)
+                                    .with_collector(collector)
+                                    .with_cid_options(cid_options)
+                                    .with_raw_leaves(raw_leaves)
+                                    .with_metadata(metadata)
+                                    .build();
+
+                                yield UnixfsStatus::ProgressStatus { written, total_size };
+
+                                let mut added = None;
+                                let mut file = add_file(&repo, adder, stream, reference);
+
+                                while let Some(progress) = file.next().await {
+                                    match progress {
+                                        Ok(FileProgress::Written(bytes)) => {
+                                            written += bytes;
+                                            yield UnixfsStatus::ProgressStatus { written, total_size };
+                                        }
+                                        Ok(FileProgress::Added(cid, _)) => added = Some(cid),
+                                        Err(e) => {
+                                            yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
+                                            return;
+                                        }
+                                    }
+                                }
+
+                                let cid = match added {
+                                    Some(cid) => cid,
+                                    None => {
+                                        yield UnixfsStatus::FailedStatus { written, total_size, error: None };
+                                        return;
+                                    }
                                 };
+
+                                let mut path = IpfsPath::from(cid);
+
+                                if wrap {
+                                    if let Some(name) = name {
+                                        let mut opts = rust_unixfs::dir::builder::TreeOptions::default();
+                                        opts.wrap_with_directory();
+                                        opts.cid_options(cid_options);
+
+                                        let mut tree = rust_unixfs::dir::builder::BufferingTreeBuilder::new(opts);
+
+                                        let result = match tree.put_link(&name, cid, written as _) {
+                                            Ok(()) => put_tree(&repo, tree).await.and_then(|cid| IpfsPath::from(cid).sub_path(&name)),
+                                            Err(e) => Err(e.into()),
+                                        };
+
+                                        path = match result {
+                                            Ok(path) => path,
+                                            Err(e) => {
+                                                yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
+                                                return;
+                                            }
+                                        };
+                                    }
+                                }
+
+                                (path, total_size)
                             }
-                        }
+                        };
 
                         let cid = path.root().cid().copied().expect("Cid is apart of the path");
 
@@ -344,3 +662,478 @@ impl FusedStream for UnixfsAdd {
         matches!(self.stream, StatusStreamState::Done) && self.core.is_none()
     }
 }
+
+/// Multicodec of the symlink blocks.
+#[cfg(not(target_arch = "wasm32"))]
+const DAG_PB: u64 = 0x70;
+
+/// Which entries of a directory are added.
+#[cfg(not(target_arch = "wasm32"))]
+struct Filter {
+    hidden: bool,
+    ignore: Vec<Pattern>,
+    symlinks: Symlinks,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Filter {
+    fn includes(&self, relative: &str, name: &str, is_dir: bool) -> bool {
+        if !self.hidden && name.starts_with('.') {
+            return false;
+        }
+
+        !self
+            .ignore
+            .iter()
+            .any(|pattern| pattern.matches(relative, name, is_dir))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+enum EntryKind {
+    File(u64),
+    Directory,
+    Symlink(String),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct DirEntry {
+    /// Path of the entry on the local filesystem.
+    path: PathBuf,
+    /// Path of the entry relative to the directory being added, separated with slashes.
+    relative: String,
+    kind: EntryKind,
+    metadata: Metadata,
+}
+
+/// Lists the entries within the directory depth first, ordered by name.
+#[cfg(not(target_arch = "wasm32"))]
+async fn walk_dir(
+    root: &Path,
+    filter: &Filter,
+    preserve: Preserve,
+) -> Result<(Metadata, Vec<DirEntry>), anyhow::Error> {
+    let mut entries = Vec::new();
+    let root_metadata = preserve.metadata(&tokio::fs::metadata(root).await?);
+
+    // the listings of the directories being walked along with their relative path and the
+    // canonical paths leading to them, which are used to detect cycles when following links
+    let root_canonical = tokio::fs::canonicalize(root).await?;
+    let mut pending = vec![(list_dir(root).await?, String::new(), vec![root_canonical])];
+
+    while let Some((children, parent, ancestors)) = pending.last_mut() {
+        let Some((path, name)) = children.pop() else {
+            pending.pop();
+            continue;
+        };
+
+        let relative = match parent.is_empty() {
+            true => name.clone(),
+            false => format!("{parent}/{name}"),
+        };
+
+        let mut metadata = tokio::fs::symlink_metadata(&path).await?;
+
+        if metadata.file_type().is_symlink() {
+            match filter.symlinks {
+                Symlinks::Skip => continue,
+                Symlinks::Preserve => {
+                    if filter.includes(&relative, &name, false) {
+                        let target = tokio::fs::read_link(&path).await?;
+                        let target = target.to_str().ok_or_else(|| {
+                            anyhow::anyhow!("target of {} is not valid utf-8", path.display())
+                        })?;
+
+                        let kind = EntryKind::Symlink(target.to_string());
+                        entries.push(DirEntry {
+                            path,
+                            relative,
+                            kind,
+                            metadata: preserve.metadata(&metadata),
+                        });
+                    }
+                    continue;
+                }
+                Symlinks::Follow => metadata = tokio::fs::metadata(&path).await?,
+            }
+        }
+
+        if !filter.includes(&relative, &name, metadata.is_dir()) {
+            continue;
+        }
+
+        if metadata.is_dir() {
+            let canonical = tokio::fs::canonicalize(&path).await?;
+
+            if ancestors.contains(&canonical) {
+                anyhow::bail!("symbolic link cycle at {}", path.display());
+            }
+
+            let mut ancestors = ancestors.clone();
+            ancestors.push(canonical);
+
+            let listing = list_dir(&path).await?;
+
+            entries.push(DirEntry {
+                path,
+                relative: relative.clone(),
+                kind: EntryKind::Directory,
+                metadata: preserve.metadata(&metadata),
+            });
+
+            pending.push((listing, relative, ancestors));
+        } else if metadata.is_file() {
+            entries.push(DirEntry {
+                path,
+                relative,
+                kind: EntryKind::File(metadata.len()),
+                metadata: preserve.metadata(&metadata),
+            });
+        }
+        // sockets, fifos and devices cannot be represented in UnixFS
+    }
+
+    Ok((root_metadata, entries))
+}
+
+/// Returns the relative paths of the directories containing files or symlinks.
+#[cfg(not(target_arch = "wasm32"))]
+fn non_empty_dirs(entries: &[DirEntry]) -> HashSet<String> {
+    entries
+        .iter()
+        .filter(|entry| !matches!(entry.kind, EntryKind::Directory))
+        .flat_map(|entry| {
+            entry
+                .relative
+                .match_indices('/')
+                .map(|(i, _)| entry.relative[..i].to_string())
+        })
+        .collect()
+}
+
+/// Which metadata of the files, directories and symlinks is recorded.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy)]
+struct Preserve {
+    mode: bool,
+    mtime: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Preserve {
+    fn metadata(&self, metadata: &std::fs::Metadata) -> Metadata {
+        let mut preserved = Metadata::default();
+
+        if self.mode {
+            preserved = preserved.with_mode(mode(metadata));
+        }
+
+        if self.mtime {
+            if let Ok(modified) = metadata.modified() {
+                let (seconds, nanos) = unix_time(modified);
+                preserved = preserved.with_mtime(seconds, nanos);
+            }
+        }
+
+        preserved
+    }
+}
+
+#[cfg(unix)]
+fn mode(metadata: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode()
+}
+
+/// Approximates the mode from the read-only attribute, the same way as go does on windows.
+#[cfg(all(not(unix), not(target_arch = "wasm32")))]
+fn mode(metadata: &std::fs::Metadata) -> u32 {
+    let mode = match metadata.is_dir() {
+        true => 0o777,
+        false => 0o666,
+    };
+
+    match metadata.permissions().readonly() {
+        true => mode & 0o555,
+        false => mode,
+    }
+}
+
+/// Returns the seconds since the unix epoch, which are negative for earlier times, and the
+/// fractional nanoseconds.
+#[cfg(not(target_arch = "wasm32"))]
+fn unix_time(time: std::time::SystemTime) -> (i64, u32) {
+    match time.duration_since(std::time::UNIX_EPOCH) {
+        Ok(since) => (since.as_secs() as i64, since.subsec_nanos()),
+        Err(e) => {
+            let before = e.duration();
+            match before.subsec_nanos() {
+                0 => (-(before.as_secs() as i64), 0),
+                nanos => (-(before.as_secs() as i64) - 1, 1_000_000_000 - nanos),
+            }
+        }
+    }
+}
+
+/// Returns the paths and names of the directory entries in reverse order, so that popping them
+/// yields them ordered by name.
+#[cfg(not(target_arch = "wasm32"))]
+async fn list_dir(path: &Path) -> Result<Vec<(PathBuf, String)>, anyhow::Error> {
+    let mut read_dir = tokio::fs::read_dir(path).await?;
+    let mut children = Vec::new();
+
+    while let Some(entry) = read_dir.next_entry().await? {
+        let name = entry.file_name().into_string().map_err(|_| {
+            anyhow::anyhow!("name of {} is not valid utf-8", entry.path().display())
+        })?;
+        children.push((entry.path(), name));
+    }
+
+    children.sort_unstable_by(|(_, a), (_, b)| b.cmp(a));
+    Ok(children)
+}
+
+/// Returns the name of the directory, resolving paths such as `.` which do not end in one.
+#[cfg(not(target_arch = "wasm32"))]
+async fn dir_name(path: &Path) -> Result<String, anyhow::Error> {
+    let name = match path.file_name() {
+        Some(name) => name.to_owned(),
+        None => tokio::fs::canonicalize(path)
+            .await?
+            .file_name()
+            .map(ToOwned::to_owned)
+            .ok_or_else(|| anyhow::anyhow!("{} has no name", path.display()))?,
+    };
+
+    Ok(name.to_string_lossy().into_owned())
+}
+
+/// Stores the blocks created by the adder, adding their sizes to `size` and returning the Cid
+/// of the last one.
+#[cfg(not(target_arch = "wasm32"))]
+/// Progress of adding the contents of a single file with [`add_file`].
+enum FileProgress {
+    /// A buffer of the given length was chunked and stored.
+    Written(usize),
+    /// The file was stored, with the Cid of its root and the cumulative size of its blocks.
+    Added(Cid, usize),
+}
+
+/// Chunks the contents of a file into blocks and stores them, reporting the progress after every
+/// buffer read from the stream. Used both for adding a single file and the files of a directory.
+fn add_file<'a>(
+    repo: &'a Repo,
+    mut adder: FileAdder,
+    mut stream: BoxStream<'a, std::io::Result<Bytes>>,
+    mut reference: Option<NoCopy>,
+) -> BoxStream<'a, Result<FileProgress, anyhow::Error>> {
+    async_stream::try_stream! {
+        let mut size = 0;
+
+        while let Some(buffer) = stream.next().await {
+            let buffer = buffer?;
+
+            let mut total = 0;
+            while total < buffer.len() {
+                let (blocks, consumed) = adder.push(&buffer[total..]);
+                put_blocks(repo, blocks, &mut size, &mut reference).await?;
+                total += consumed;
+            }
+
+            yield FileProgress::Written(total);
+        }
+
+        let cid = put_blocks(repo, adder.finish(), &mut size, &mut reference)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("no cid available"))?;
+
+        yield FileProgress::Added(cid, size);
+    }
+    .boxed()
+}
+
+/// Stores the blocks of the directory tree, returning the Cid of its root.
+async fn put_tree(repo: &Repo, tree: BufferingTreeBuilder) -> Result<Cid, anyhow::Error> {
+    let mut iter = tree.build();
+    let mut root = None;
+
+    while let Some(node) = iter.next_borrowed() {
+        let node = node?;
+        let block = Block::new_unchecked(node.cid.to_owned(), node.block.into());
+
+        repo.put_block(block).await?;
+
+        root = Some(*node.cid);
+    }
+
+    root.ok_or(anyhow::anyhow!("no cid available"))
+}
+
+async fn put_blocks(
+    repo: &Repo,
+    blocks: impl Iterator<Item = (Cid, Vec<u8>)>,
+    size: &mut usize,
+    reference: &mut Option<NoCopy>,
+) -> Result<Option<Cid>, anyhow::Error> {
+    let mut last = None;
+
+    for (cid, block) in blocks {
+        *size += block.len();
+        put_block(repo, cid, block, reference).await?;
+        last = Some(cid);
+    }
+
+    Ok(last)
+}
+
+/// Multicodec of the raw leaves.
+const RAW: u64 = 0x55;
+
+/// Position of the next raw leaf within the file being added without copying.
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+struct NoCopy {
+    path: PathBuf,
+    offset: u64,
+}
+
+impl NoCopy {
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn new(path: &Path) -> Result<Self, anyhow::Error> {
+        // the references outlive the working directory
+        let path = tokio::fs::canonicalize(path).await?;
+        Ok(NoCopy { path, offset: 0 })
+    }
+}
+
+/// Stores the block, or only the reference to its data when adding without copying. The raw
+/// leaves are created in the order of the file contents, the other blocks only link to them.
+async fn put_block(
+    repo: &Repo,
+    cid: Cid,
+    data: Vec<u8>,
+    reference: &mut Option<NoCopy>,
+) -> Result<Cid, anyhow::Error> {
+    let block = Block::new_unchecked(cid, data);
+
+    match reference {
+        Some(file) if cid.codec() == RAW => {
+            let length = block.data().len() as u64;
+            let reference = FileRef {
+                path: file.path.clone(),
+                offset: file.offset,
+                length,
+            };
+            file.offset += length;
+            repo.put_block(block).reference(reference).await
+        }
+        _ => repo.put_block(block).await,
+    }
+}
+
+/// Gitignore style pattern given to [`UnixfsAdd::ignore`].
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+struct Pattern {
+    glob: Vec<char>,
+    /// Patterns containing a slash are matched against the relative path instead of the name.
+    anchored: bool,
+    dir_only: bool,
+}
+
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+impl Pattern {
+    fn parse(pattern: &str) -> Option<Self> {
+        let pattern = pattern.trim();
+
+        if pattern.is_empty() || pattern.starts_with('#') {
+            return None;
+        }
+
+        let (pattern, dir_only) = match pattern.strip_suffix('/') {
+            Some(pattern) => (pattern, true),
+            None => (pattern, false),
+        };
+
+        let anchored = pattern.contains('/');
+        let glob = pattern.trim_start_matches('/').chars().collect();
+
+        Some(Pattern {
+            glob,
+            anchored,
+            dir_only,
+        })
+    }
+
+    fn matches(&self, relative: &str, name: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        let text = match self.anchored {
+            true => relative,
+            false => name,
+        };
+
+        glob_match(&self.glob, &text.chars().collect::<Vec<_>>())
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `**/` also matches when there are no directories in between
+            if let Some(rest) = rest.strip_prefix(&['/']) {
+                if glob_match(rest, text) {
+                    return true;
+                }
+            }
+            (0..=text.len()).any(|i| glob_match(rest, &text[i..]))
+        }
+        ['*', rest @ ..] => {
+            for i in 0..=text.len() {
+                if glob_match(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        ['?', rest @ ..] => {
+            matches!(text, [c, text @ ..] if *c != '/' && glob_match(rest, text))
+        }
+        [p, rest @ ..] => matches!(text, [c, text @ ..] if c == p && glob_match(rest, text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pattern;
+
+    fn ignored(pattern: &str, relative: &str, is_dir: bool) -> bool {
+        let name = relative.rsplit('/').next().unwrap();
+        Pattern::parse(pattern)
+            .unwrap()
+            .matches(relative, name, is_dir)
+    }
+
+    #[test]
+    fn ignore_patterns() {
+        assert!(ignored("*.log", "a/b/c.log", false));
+        assert!(!ignored("*.log", "a/b/c.txt", false));
+        assert!(ignored("c.???", "a/b/c.log", false));
+
+        assert!(ignored("target/", "a/target", true));
+        assert!(!ignored("target/", "a/target", false));
+
+        assert!(ignored("/a/*.txt", "a/b.txt", false));
+        assert!(!ignored("a/*.txt", "a/b/c.txt", false));
+        assert!(!ignored("a/*.txt", "b/a/c.txt", false));
+
+        assert!(ignored("a/**/c.txt", "a/c.txt", false));
+        assert!(ignored("a/**/c.txt", "a/b/b/c.txt", false));
+        assert!(ignored("**/b", "a/b", true));
+    }
+}
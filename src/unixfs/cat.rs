@@ -5,6 +5,7 @@ use either::Either;
 use futures::future::BoxFuture;
 use futures::stream::{BoxStream, FusedStream, Stream};
 use futures::{FutureExt, StreamExt, TryStreamExt};
+use libipld::IpldCodec;
 use libp2p::PeerId;
 use rust_unixfs::file::visit::IdleFileVisit;
 use std::ops::Range;
@@ -79,6 +80,12 @@ impl UnixfsCat {
         self
     }
 
+    /// Byte range of the file to read
+    pub fn range(mut self, range: Range<u64>) -> Self {
+        self.range = Some(range);
+        self
+    }
+
     pub fn providers(mut self, list: &[PeerId]) -> Self {
         self.providers = list.to_vec();
         self
@@ -194,7 +201,13 @@ impl Stream for UnixfsCat {
                         let mut cache = None;
                         // Start the visit from the root block. We need to move the both components as Options into the
                         // stream as we can't yet return them from this Future context.
-                        let (visit, bytes) = match visit.start(block.data()) {
+                        let started = if block.cid().codec() == <IpldCodec as Into<u64>>::into(IpldCodec::Raw) {
+                            visit.start_raw_leaf(block.data())
+                        } else {
+                            visit.start(block.data())
+                        };
+
+                        let (visit, bytes) = match started {
                             Ok((bytes, _, _, visit)) => {
                                 let bytes = if !bytes.is_empty() {
                                     Some(Bytes::copy_from_slice(bytes))
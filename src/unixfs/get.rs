@@ -1,3 +1,5 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Component;
 use std::{
     path::{Path, PathBuf},
     task::Poll,
@@ -10,6 +12,8 @@ use libp2p::PeerId;
 #[allow(unused_imports)]
 use rust_unixfs::walk::{ContinuedWalk, Walker};
 #[cfg(not(target_arch = "wasm32"))]
+use rust_unixfs::Metadata;
+#[cfg(not(target_arch = "wasm32"))]
 use tokio::io::AsyncWriteExt;
 use tracing::{Instrument, Span};
 
@@ -18,6 +22,8 @@ use crate::{dag::IpldDag, repo::Repo, Ipfs, IpfsPath};
 #[allow(unused_imports)]
 use super::{StatusStreamState, TraversalFailed, UnixfsStatus};
 
+/// Writes a file or a directory tree to the destination path, recreating the directories and
+/// symlinks within. The total size reported in the progress grows as the files are reached.
 #[must_use = "do nothing unless you `.await` or poll the stream"]
 pub struct UnixfsGet {
     core: Option<Either<Ipfs, Repo>>,
@@ -27,6 +33,9 @@ pub struct UnixfsGet {
     providers: Vec<PeerId>,
     local_only: bool,
     timeout: Option<Duration>,
+    confine_symlinks: bool,
+    preserve_mode: bool,
+    preserve_mtime: bool,
     stream: StatusStreamState,
 }
 
@@ -54,6 +63,9 @@ impl UnixfsGet {
             providers: Vec::new(),
             local_only: false,
             timeout: None,
+            confine_symlinks: true,
+            preserve_mode: false,
+            preserve_mtime: false,
             stream: StatusStreamState::None,
         }
     }
@@ -89,6 +101,30 @@ impl UnixfsGet {
         self.local_only = local;
         self
     }
+
+    /// Refuses to create the symlinks whose targets lead outside of the destination, failing the
+    /// whole operation instead, which is the default. The targets are resolved through the
+    /// symlinks already written, and nothing is written through a symlink leading outside of the
+    /// destination. Disabling this recreates the symlinks as they are.
+    pub fn confine_symlinks(mut self, confine: bool) -> Self {
+        self.confine_symlinks = confine;
+        self
+    }
+
+    /// Applies the permissions recorded in the UnixFS metadata to the written files and
+    /// directories, like `--preserve-mode` in kubo. Only the permission bits are applied, the
+    /// setuid, setgid and sticky bits are not.
+    pub fn preserve_mode(mut self, preserve: bool) -> Self {
+        self.preserve_mode = preserve;
+        self
+    }
+
+    /// Applies the modification times recorded in the UnixFS metadata to the written files,
+    /// directories and symlinks, like `--preserve-mtime` in kubo.
+    pub fn preserve_mtime(mut self, preserve: bool) -> Self {
+        self.preserve_mtime = preserve;
+        self
+    }
 }
 
 impl Stream for UnixfsGet {
@@ -122,25 +158,17 @@ impl Stream for UnixfsGet {
                     let local_only = self.local_only;
                     let timeout = self.timeout;
                     let dest = self.dest.clone();
+                    let confine_symlinks = self.confine_symlinks;
+                    let preserve_mode = self.preserve_mode;
+                    let preserve_mtime = self.preserve_mtime;
 
                     #[cfg(not(target_arch = "wasm32"))]
                     let stream = async_stream::stream! {
 
-
                         let mut cache = None;
                         let mut total_size = None;
                         let mut written = 0;
 
-                        let mut file = match tokio::fs::File::create(dest)
-                            .await
-                            .map_err(TraversalFailed::Io) {
-                                Ok(f) => f,
-                                Err(e) => {
-                                    yield UnixfsStatus::FailedStatus { written, total_size, error: Some(anyhow::Error::from(e)) };
-                                    return;
-                                }
-                            };
-
                         let block  = match dag
                             .resolve_with_session(session, path.clone(), true, &providers, local_only, timeout)
                             .await
@@ -154,9 +182,16 @@ impl Stream for UnixfsGet {
                         };
 
                         let cid = block.cid();
-                        let root_name = block.cid().to_string();
 
-                        let mut walker = Walker::new(*cid, root_name);
+                        // with an empty root name the paths are relative to the destination
+                        let mut walker = Walker::new(*cid, String::new());
+                        let mut file = None;
+                        // the metadata of the directories is applied once their entries have
+                        // been written
+                        let mut directories = Vec::new();
+                        // the symlinks are checked again once all have been written, as the
+                        // symlinks written later can change where the earlier ones lead
+                        let mut symlinks = Vec::new();
 
                         while walker.should_continue() {
                             let (next, _) = walker.pending_links();
@@ -171,12 +206,40 @@ impl Stream for UnixfsGet {
 
                             match walker.next(block_data, &mut cache) {
                                 Ok(ContinuedWalk::Bucket(..)) => {}
-                                Ok(ContinuedWalk::File(segment, _, _, _, size)) => {
-
+                                Ok(ContinuedWalk::File(segment, cid, relative, metadata, size)) => {
                                     if segment.is_first() {
-                                        total_size = Some(size as usize);
+                                        // the total grows as the files of a directory are reached
+                                        total_size = Some(total_size.unwrap_or_default() + size as usize);
                                         yield UnixfsStatus::ProgressStatus { written, total_size };
+
+                                        let target = match confined_destination(&dest, relative, confine_symlinks).await {
+                                            Ok(target) => target,
+                                            Err(e) => {
+                                                yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
+                                                return;
+                                            }
+                                        };
+
+                                        // replace a symlink written previously instead of writing through it
+                                        if let Err(e) = remove_symlink(&target).await {
+                                            yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
+                                            return;
+                                        }
+
+                                        match tokio::fs::File::create(&target).await {
+                                            Ok(f) => file = Some((f, target, metadata.clone())),
+                                            Err(e) => {
+                                                yield UnixfsStatus::FailedStatus { written, total_size, error: Some(anyhow::Error::from(e)) };
+                                                return;
+                                            }
+                                        }
                                     }
+
+                                    let Some((f, ..)) = file.as_mut() else {
+                                        yield UnixfsStatus::FailedStatus { written, total_size, error: Some(anyhow::anyhow!("file segment without a file")) };
+                                        return;
+                                    };
+
                                     // even if the largest of files can have 256 kB blocks and about the same
                                     // amount of content, try to consume it in small parts not to grow the buffers
                                     // too much.
@@ -188,25 +251,67 @@ impl Stream for UnixfsGet {
                                     while n < total {
                                         let next = &slice[n..];
                                         n += next.len();
-                                        if let Err(e) = file.write_all(next).await {
-                                            yield UnixfsStatus::FailedStatus { written, total_size, error: Some(anyhow::Error::from(e)) };
-                                            return;
-                                        }
-                                        if let Err(e) = file.sync_all().await {
+                                        if let Err(e) = f.write_all(next).await {
                                             yield UnixfsStatus::FailedStatus { written, total_size, error: Some(anyhow::Error::from(e)) };
                                             return;
                                         }
 
-                                        written += n;
+                                        written += next.len();
                                         yield UnixfsStatus::ProgressStatus { written, total_size };
                                     }
 
                                     if segment.is_last() {
-                                        yield UnixfsStatus::ProgressStatus { written, total_size };
+                                        if let Err(e) = f.sync_all().await {
+                                            yield UnixfsStatus::FailedStatus { written, total_size, error: Some(anyhow::Error::from(e)) };
+                                            return;
+                                        }
+
+                                        let Some((_, path, metadata)) = file.take() else {
+                                            unreachable!("the file was just written");
+                                        };
+
+                                        if let Err(e) = apply_metadata(&path, &metadata, preserve_mode, preserve_mtime, false).await {
+                                            yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
+                                            return;
+                                        }
+
+                                        yield UnixfsStatus::EntryStatus { path, cid: *cid, written };
+                                    }
+                                },
+                                Ok(ContinuedWalk::Directory(_, relative, metadata)) | Ok(ContinuedWalk::RootDirectory(_, relative, metadata)) => {
+                                    let result = match confined_destination(&dest, relative, confine_symlinks).await {
+                                        Ok(target) => tokio::fs::create_dir_all(&target)
+                                            .await
+                                            .map(|_| directories.push((target, metadata.clone())))
+                                            .map_err(anyhow::Error::from),
+                                        Err(e) => Err(e),
+                                    };
+
+                                    if let Err(e) = result {
+                                        yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
+                                        return;
+                                    }
+                                },
+                                Ok(ContinuedWalk::Symlink(target, cid, relative, metadata)) => {
+                                    let result = match confined_destination(&dest, relative, confine_symlinks).await {
+                                        Ok(link) => match create_symlink(&dest, &link, relative, target, confine_symlinks).await {
+                                            Ok(()) => {
+                                                symlinks.push((link.clone(), relative.to_path_buf()));
+                                                apply_metadata(&link, metadata, false, preserve_mtime, true).await.map(|_| link)
+                                            }
+                                            Err(e) => Err(e),
+                                        },
+                                        Err(e) => Err(e),
+                                    };
+
+                                    match result {
+                                        Ok(path) => yield UnixfsStatus::EntryStatus { path, cid: *cid, written },
+                                        Err(e) => {
+                                            yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
+                                            return;
+                                        }
                                     }
                                 },
-                                Ok(ContinuedWalk::Directory( .. )) | Ok(ContinuedWalk::RootDirectory( .. )) => {}, //TODO
-                                Ok(ContinuedWalk::Symlink( .. )) => {},
                                 Err(e) => {
                                     yield UnixfsStatus::FailedStatus { written, total_size, error: Some(anyhow::Error::from(e)) };
                                     return;
@@ -214,6 +319,22 @@ impl Stream for UnixfsGet {
                             };
                         };
 
+                        if confine_symlinks {
+                            for (link, relative) in &symlinks {
+                                if let Err(e) = confine(&dest, link, relative).await {
+                                    yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
+                                    return;
+                                }
+                            }
+                        }
+
+                        for (directory, metadata) in directories.iter().rev() {
+                            if let Err(e) = apply_metadata(directory, metadata, preserve_mode, preserve_mtime, false).await {
+                                yield UnixfsStatus::FailedStatus { written, total_size, error: Some(e) };
+                                return;
+                            }
+                        }
+
                         yield UnixfsStatus::CompletedStatus { path, written, total_size }
                     };
 
@@ -227,6 +348,9 @@ impl Stream for UnixfsGet {
                         _ = local_only;
                         _ = timeout;
                         _ = dest;
+                        _ = confine_symlinks;
+                        _ = preserve_mode;
+                        _ = preserve_mtime;
                         yield UnixfsStatus::FailedStatus { written: 0, total_size: None, error: Some(anyhow::anyhow!("unimplemented")) };
                     };
 
@@ -289,3 +413,221 @@ impl FusedStream for UnixfsGet {
         matches!(self.stream, StatusStreamState::Done) && self.core.is_none()
     }
 }
+
+/// Returns the path of the entry within the destination, refusing the names which would lead
+/// outside of it.
+#[cfg(not(target_arch = "wasm32"))]
+fn destination(dest: &Path, relative: &Path) -> Result<PathBuf, anyhow::Error> {
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        anyhow::bail!("invalid entry name {}", relative.display());
+    }
+
+    // joining an empty path would add a trailing separator
+    match relative.as_os_str().is_empty() {
+        true => Ok(dest.to_path_buf()),
+        false => Ok(dest.join(relative)),
+    }
+}
+
+/// Creates the symlink at `link`, the path of which is `relative` within the destination.
+#[cfg(not(target_arch = "wasm32"))]
+async fn create_symlink(
+    dest: &Path,
+    link: &Path,
+    relative: &Path,
+    target: &[u8],
+    confine: bool,
+) -> Result<(), anyhow::Error> {
+    let target = std::str::from_utf8(target)
+        .map_err(|_| anyhow::anyhow!("target of {} is not valid utf-8", relative.display()))?;
+    let target = Path::new(target);
+
+    if confine {
+        let resolved = link.parent().unwrap_or(link).join(target);
+        if !stays_within(&bound(dest, relative), &resolved).await? {
+            anyhow::bail!(
+                "target {} of {} leads outside of the destination",
+                target.display(),
+                relative.display()
+            );
+        }
+    }
+
+    // replace a symlink written previously, like files are overwritten
+    if tokio::fs::symlink_metadata(link).await.is_ok() {
+        tokio::fs::remove_file(link).await?;
+    }
+
+    #[cfg(unix)]
+    tokio::fs::symlink(target, link).await?;
+
+    #[cfg(windows)]
+    {
+        let resolved = link.parent().unwrap_or(link).join(target);
+        match tokio::fs::metadata(resolved).await {
+            Ok(metadata) if metadata.is_dir() => tokio::fs::symlink_dir(target, link).await?,
+            _ => tokio::fs::symlink_file(target, link).await?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the symlink at `path`, if any, so that the file written there is not written through it.
+#[cfg(not(target_arch = "wasm32"))]
+async fn remove_symlink(path: &Path) -> Result<(), anyhow::Error> {
+    match tokio::fs::symlink_metadata(path).await {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            tokio::fs::remove_file(path).await?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Applies the mode and the modification time recorded for the entry at `path`, of which only
+/// the modification time is applied to symlinks.
+#[cfg(not(target_arch = "wasm32"))]
+async fn apply_metadata(
+    path: &Path,
+    metadata: &Metadata,
+    mode: bool,
+    mtime: bool,
+    symlink: bool,
+) -> Result<(), anyhow::Error> {
+    if let (true, false, Some(mode)) = (mode, symlink, metadata.mode()) {
+        let mut permissions = tokio::fs::metadata(path).await?.permissions();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            // leave out the setuid, setgid and sticky bits
+            permissions.set_mode(mode & 0o777);
+        }
+
+        #[cfg(not(unix))]
+        permissions.set_readonly(mode & 0o222 == 0);
+
+        tokio::fs::set_permissions(path, permissions).await?;
+    }
+
+    if let (true, Some(mtime)) = (mtime, metadata.mtime_as_filetime()) {
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || match symlink {
+            true => filetime::set_symlink_file_times(&path, mtime, mtime),
+            false => filetime::set_file_mtime(&path, mtime),
+        })
+        .await??;
+    }
+
+    Ok(())
+}
+
+/// Returns the path of the entry within the destination like [`destination`], refusing to write
+/// through the symlinks leading outside of the destination when confining them, including a
+/// symlink written previously at the path of the entry.
+#[cfg(not(target_arch = "wasm32"))]
+async fn confined_destination(
+    dest: &Path,
+    relative: &Path,
+    confine: bool,
+) -> Result<PathBuf, anyhow::Error> {
+    let path = destination(dest, relative)?;
+
+    if confine && !relative.as_os_str().is_empty() && !stays_within(dest, &path).await? {
+        anyhow::bail!("{} leads outside of the destination", relative.display());
+    }
+
+    Ok(path)
+}
+
+/// Fails if the symlink at `link`, the path of which is `relative` within the destination, leads
+/// outside of the destination.
+#[cfg(not(target_arch = "wasm32"))]
+async fn confine(dest: &Path, link: &Path, relative: &Path) -> Result<(), anyhow::Error> {
+    match stays_within(&bound(dest, relative), link).await? {
+        true => Ok(()),
+        false => anyhow::bail!("{} leads outside of the destination", relative.display()),
+    }
+}
+
+/// Returns the directory the symlink at `relative` must lead within, which for a symlink written
+/// as the destination itself is the directory containing it.
+#[cfg(not(target_arch = "wasm32"))]
+fn bound(dest: &Path, relative: &Path) -> PathBuf {
+    match relative.as_os_str().is_empty() {
+        true => dest.parent().unwrap_or(dest).to_path_buf(),
+        false => dest.to_path_buf(),
+    }
+}
+
+/// Returns whether the path stays within the directory `bound` once the symlinks along the path
+/// have been followed.
+#[cfg(not(target_arch = "wasm32"))]
+async fn stays_within(bound: &Path, path: &Path) -> Result<bool, anyhow::Error> {
+    let bound = bound.to_path_buf();
+    let path = path.to_path_buf();
+
+    let within = tokio::task::spawn_blocking(move || {
+        let current = std::env::current_dir()?;
+        let bound = resolve(&current.join(bound))?;
+        let path = resolve(&current.join(path))?;
+        Ok::<_, std::io::Error>(path.starts_with(bound))
+    })
+    .await??;
+
+    Ok(within)
+}
+
+/// Resolves the absolute path the way the file system would, following the symlinks which exist
+/// and resolving the rest lexically, as the symlinks can lead to entries written later.
+#[cfg(not(target_arch = "wasm32"))]
+fn resolve(path: &Path) -> std::io::Result<PathBuf> {
+    // the same limit as in linux
+    const MAX_SYMLINKS: usize = 40;
+
+    let mut pending = path
+        .components()
+        .map(|component| component.as_os_str().to_owned())
+        .collect::<std::collections::VecDeque<_>>();
+    let mut resolved = PathBuf::new();
+    let mut followed = 0;
+
+    while let Some(part) = pending.pop_front() {
+        match Path::new(&part).components().next() {
+            Some(Component::Prefix(_) | Component::RootDir) => resolved.push(&part),
+            Some(Component::CurDir) | None => {}
+            Some(Component::ParentDir) => {
+                resolved.pop();
+            }
+            Some(Component::Normal(_)) => {
+                resolved.push(&part);
+
+                let is_symlink = std::fs::symlink_metadata(&resolved)
+                    .map(|metadata| metadata.file_type().is_symlink())
+                    .unwrap_or_default();
+
+                if is_symlink {
+                    followed += 1;
+                    if followed > MAX_SYMLINKS {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::Other,
+                            "too many levels of symbolic links",
+                        ));
+                    }
+
+                    let target = std::fs::read_link(&resolved)?;
+                    resolved.pop();
+                    for component in target.components().rev() {
+                        pending.push_front(component.as_os_str().to_owned());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(resolved)
+}
@@ -9,16 +9,33 @@ use futures::{
 use libipld::Cid;
 use libp2p::PeerId;
 use rust_unixfs::walk::{ContinuedWalk, Walker};
+use rust_unixfs::Metadata;
 use tracing::{Instrument, Span};
 
 use crate::{dag::IpldDag, repo::Repo, Ipfs, IpfsPath};
 
+/// Entry of the listing, with the mode and modification time recorded when the entry was added.
 #[derive(Debug)]
 pub enum Entry {
-    Error { error: anyhow::Error },
-    RootDirectory { cid: Cid, path: String },
-    Directory { cid: Cid, path: String },
-    File { cid: Cid, file: String, size: usize },
+    Error {
+        error: anyhow::Error,
+    },
+    RootDirectory {
+        cid: Cid,
+        path: String,
+        metadata: Metadata,
+    },
+    Directory {
+        cid: Cid,
+        path: String,
+        metadata: Metadata,
+    },
+    File {
+        cid: Cid,
+        file: String,
+        size: usize,
+        metadata: Metadata,
+    },
 }
 
 #[must_use = "do nothing unless you `.await` or poll the stream"]
@@ -169,18 +186,21 @@ impl Stream for UnixfsLs {
 
                             match walker.next(block_data, &mut cache) {
                                 Ok(ContinuedWalk::Bucket(..)) => {}
-                                Ok(ContinuedWalk::File(_, cid, path, _, size)) => {
-                                    let file = path.to_string_lossy().to_string().replace(&format!("{root_directory}/"), "");
-                                    yield Entry::File { cid: *cid, file, size: size as _ };
+                                Ok(ContinuedWalk::File(segment, cid, path, metadata, size)) => {
+                                    // the walker reports the metadata of the file with every segment
+                                    if segment.is_first() {
+                                        let file = path.to_string_lossy().to_string().replace(&format!("{root_directory}/"), "");
+                                        yield Entry::File { cid: *cid, file, size: size as _, metadata: metadata.clone() };
+                                    }
                                 },
-                                Ok(ContinuedWalk::RootDirectory( cid, path, _)) => {
+                                Ok(ContinuedWalk::RootDirectory( cid, path, metadata)) => {
                                     let path = path.to_string_lossy().to_string();
                                     root_directory.clone_from(&path);
-                                    yield Entry::RootDirectory { cid: *cid, path };
+                                    yield Entry::RootDirectory { cid: *cid, path, metadata: metadata.clone() };
                                 }
-                                Ok(ContinuedWalk::Directory( cid, path, _)) => {
+                                Ok(ContinuedWalk::Directory( cid, path, metadata)) => {
                                     let path = path.to_string_lossy().to_string().replace(&format!("{root_directory}/"), "");
-                                    yield Entry::Directory { cid: *cid, path };
+                                    yield Entry::Directory { cid: *cid, path, metadata: metadata.clone() };
                                 }
                                 Ok(ContinuedWalk::Symlink( .. )) => {},
                                 Err(error) => {
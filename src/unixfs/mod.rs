@@ -19,7 +19,7 @@ mod add;
 mod cat;
 mod get;
 mod ls;
-pub use add::UnixfsAdd;
+pub use add::{Symlinks, UnixfsAdd};
 pub use cat::{StartingPoint, UnixfsCat};
 pub use get::UnixfsGet;
 pub use ls::{Entry, UnixfsLs};
@@ -180,11 +180,18 @@ impl IpfsUnixfs {
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum UnixfsStatus {
     ProgressStatus {
         written: usize,
         total_size: Option<usize>,
     },
+    /// A file within the directory being processed has been completed.
+    EntryStatus {
+        path: PathBuf,
+        cid: Cid,
+        written: usize,
+    },
     CompletedStatus {
         path: IpfsPath,
         written: usize,
@@ -236,6 +243,16 @@ pub enum TraversalFailed {
 
 #[cfg(test)]
 mod tests {
+    use super::{Entry, UnixfsStatus};
+    use crate::repo::FilestoreStatus;
+    use crate::{IpfsPath, Node};
+    use bytes::Bytes;
+    use futures::StreamExt;
+    use libipld::cid::Version;
+    use libipld::multihash::{Code, MultihashDigest};
+    use libipld::{Cid, IpldCodec};
+    use rust_unixfs::file::adder::{Chunker, TrickleCollector};
+
     #[test]
     fn test_file_cid() {
         // note: old versions of `ipfs::unixfs::File` was an interface where user would provide the
@@ -264,4 +281,548 @@ mod tests {
             "matches cid from go-ipfs 0.6.0"
         );
     }
+
+    #[tokio::test]
+    async fn add_with_cid_version_1() {
+        let ipfs = Node::new("test_node").await;
+        let content = Bytes::from_static(b"foobar\n");
+
+        let path = ipfs
+            .add_unixfs(content.clone())
+            .cid_version(Version::V1)
+            .await
+            .unwrap();
+        let cid = *path.root().cid().unwrap();
+
+        // raw leaves are implied, and a single block file is the raw leaf itself
+        let expected = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(&content));
+        assert_eq!(cid, expected);
+        assert_eq!(ipfs.cat_unixfs(cid).await.unwrap(), content);
+
+        let path = ipfs
+            .add_unixfs((String::from("foobar.txt"), content.clone()))
+            .cid_version(Version::V1)
+            .wrap()
+            .await
+            .unwrap();
+
+        assert_eq!(path.root().cid().unwrap().version(), Version::V1);
+        assert_eq!(ipfs.cat_unixfs(path).await.unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn add_with_hash_and_inlining() {
+        let ipfs = Node::new("test_node").await;
+        let content = Bytes::from_static(b"foobar\n");
+
+        let path = ipfs
+            .add_unixfs(content.clone())
+            .chunk(Chunker::Size(4))
+            .hash(Code::Blake3_256)
+            .inline(4)
+            .await
+            .unwrap();
+        let cid = *path.root().cid().unwrap();
+
+        assert_eq!(cid.version(), Version::V1);
+        assert_eq!(cid.hash().code(), u64::from(Code::Blake3_256));
+
+        // the inlined leaves are not stored but are still readable
+        let leaf = Cid::new_v1(
+            IpldCodec::Raw.into(),
+            libipld::multihash::Multihash::wrap(0x00, b"foob").unwrap(),
+        );
+        let blocks = ipfs.repo().list_blocks().await.collect::<Vec<_>>().await;
+        assert!(!blocks.contains(&leaf));
+        assert_eq!(ipfs.cat_unixfs(cid).await.unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn cid_version_0_requires_sha2_256() {
+        let ipfs = Node::new("test_node").await;
+
+        let res = ipfs
+            .add_unixfs(Bytes::from_static(b"foobar\n"))
+            .cid_version(Version::V0)
+            .hash(Code::Sha2_512)
+            .await;
+
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn add_with_trickle_layout() {
+        let ipfs = Node::new("test_node").await;
+        let content = Bytes::from_static(b"foobar\n");
+
+        let path = ipfs
+            .add_unixfs(content.clone())
+            .chunk(Chunker::Size(2))
+            .collector(TrickleCollector::default())
+            .await
+            .unwrap();
+
+        // `ipfs add --trickle -s size-2` with go-ipfs
+        assert_eq!(
+            path.root().cid().unwrap().to_string(),
+            "QmWfQ48ChJUj4vWKFsUDe4646xCBmXgdmNfhjz9T7crywd"
+        );
+        assert_eq!(ipfs.cat_unixfs(path).await.unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn add_without_copying() {
+        let ipfs = Node::new("test_node").await;
+        let dir = tempfile::tempdir().unwrap();
+        let content = b"0123456789abcdefghij";
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("file"), content).unwrap();
+        std::fs::write(dir.path().join("sub/file"), b"nested").unwrap();
+
+        let copied = ipfs
+            .add_unixfs(dir.path().join("file"))
+            .chunk(Chunker::Size(8))
+            .raw_leaves(true)
+            .await
+            .unwrap();
+
+        let other = Node::new("test_node").await;
+        let path = other
+            .add_unixfs(dir.path().join("file"))
+            .chunk(Chunker::Size(8))
+            .nocopy(true)
+            .await
+            .unwrap();
+
+        // the same dag as when copying, but only the root is in the block store
+        assert_eq!(path, copied);
+        assert_eq!(other.cat_unixfs(path.clone()).await.unwrap(), &content[..]);
+        let references = other
+            .repo()
+            .filestore_list()
+            .await
+            .map(|entry| entry.unwrap().1)
+            .collect::<Vec<_>>()
+            .await;
+        let mut offsets = references
+            .iter()
+            .map(|reference| (reference.offset, reference.length))
+            .collect::<Vec<_>>();
+        offsets.sort();
+        assert_eq!(offsets, [(0, 8), (8, 8), (16, 4)]);
+        assert!(references[0].path.is_absolute());
+
+        let path = other.add_unixfs(dir.path()).nocopy(true).await.unwrap();
+        assert_eq!(
+            other
+                .cat_unixfs(path.sub_path("sub/file").unwrap())
+                .await
+                .unwrap(),
+            &b"nested"[..]
+        );
+
+        // changing the file is detected when reading it
+        std::fs::write(dir.path().join("file"), b"changed").unwrap();
+        let changed = other
+            .repo()
+            .filestore_verify()
+            .await
+            .filter(|entry| {
+                let changed = entry.as_ref().unwrap().status == FilestoreStatus::Changed;
+                async move { changed }
+            })
+            .count()
+            .await;
+        // the three leaves of the file and its single leaf when added along with the directory
+        assert_eq!(changed, 4);
+        assert!(other.cat_unixfs(copied.clone()).local().await.is_err());
+
+        let raw_leaves = other
+            .add_unixfs(dir.path().join("file"))
+            .nocopy(true)
+            .raw_leaves(false)
+            .await;
+        assert!(raw_leaves.is_err());
+
+        let stream = other
+            .add_unixfs(Bytes::from_static(b"streamed"))
+            .nocopy(true)
+            .await;
+        assert!(stream.is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn add_directory_with_symlink() {
+        let ipfs = Node::new("test_node").await;
+        let dir = tempfile::tempdir().unwrap();
+
+        let root = dir.path().join("foo_directory");
+        std::fs::create_dir_all(root.join("b")).unwrap();
+        std::fs::write(root.join("b/car"), b"car\n").unwrap();
+        std::os::unix::fs::symlink("b", root.join("a")).unwrap();
+
+        let path = ipfs.add_unixfs(root.clone()).await.unwrap();
+
+        // same tree as in the `symlinks_in_trees_rooted` test of rust-unixfs
+        assert_eq!(
+            path.to_string(),
+            "/ipfs/QmZDVQHwjHwA4SyzEDtJLNxmZeJVK1W8BWFAHV61x2Rs19"
+        );
+
+        let path = ipfs.add_unixfs(root).wrap().await.unwrap();
+        assert_eq!(
+            ipfs.cat_unixfs(path.sub_path("b/car").unwrap())
+                .await
+                .unwrap(),
+            &b"car\n"[..]
+        );
+    }
+
+    #[tokio::test]
+    async fn add_directory_with_filters() {
+        let ipfs = Node::new("test_node").await;
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        for (path, content) in [
+            ("a.txt", "a"),
+            (".hidden", "hidden"),
+            ("b.log", "log"),
+            ("sub/c.txt", "c"),
+            ("target/d.txt", "d"),
+        ] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        std::fs::create_dir(root.join("empty")).unwrap();
+
+        let list = |path: IpfsPath| {
+            let ipfs = ipfs.clone();
+            async move {
+                let mut names = ipfs
+                    .ls_unixfs(path)
+                    .filter_map(|entry| async move {
+                        match entry {
+                            Entry::File { file, .. } => Some(file),
+                            Entry::Directory { path, .. } => Some(path),
+                            _ => None,
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .await;
+                names.sort();
+                names
+            }
+        };
+
+        let mut stream = ipfs.add_unixfs(root).ignore("*.log").ignore("target/");
+        let mut added = Vec::new();
+        let mut path = None;
+
+        while let Some(status) = stream.next().await {
+            match status {
+                UnixfsStatus::EntryStatus { path, .. } => added.push(path),
+                UnixfsStatus::CompletedStatus {
+                    path: root,
+                    written,
+                    total_size,
+                } => {
+                    assert_eq!(written, 2);
+                    assert_eq!(total_size, Some(2));
+                    path = Some(root);
+                }
+                UnixfsStatus::FailedStatus { error, .. } => panic!("{error:?}"),
+                UnixfsStatus::ProgressStatus { .. } => {}
+            }
+        }
+
+        assert_eq!(added, [root.join("a.txt"), root.join("sub/c.txt")]);
+        assert_eq!(
+            list(path.unwrap()).await,
+            ["a.txt", "empty", "sub", "sub/c.txt"]
+        );
+
+        let path = ipfs
+            .add_unixfs(root)
+            .hidden(true)
+            .empty_dirs(false)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            list(path).await,
+            [
+                ".hidden",
+                "a.txt",
+                "b.log",
+                "sub",
+                "sub/c.txt",
+                "target",
+                "target/d.txt"
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn get_directory_tree() {
+        let ipfs = Node::new("test_node").await;
+        let dir = tempfile::tempdir().unwrap();
+
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("sub/empty")).unwrap();
+        std::fs::write(root.join("a.txt"), b"a").unwrap();
+        std::fs::write(root.join("sub/b.txt"), vec![1u8; 300 * 1024]).unwrap();
+        std::os::unix::fs::symlink("../a.txt", root.join("sub/link")).unwrap();
+
+        let path = ipfs.add_unixfs(root).await.unwrap();
+
+        let dest = dir.path().join("dest");
+        let mut stream = ipfs.get_unixfs(path, &dest);
+        let mut entries = Vec::new();
+
+        while let Some(status) = stream.next().await {
+            match status {
+                UnixfsStatus::EntryStatus { path, .. } => entries.push(path),
+                UnixfsStatus::CompletedStatus {
+                    written,
+                    total_size,
+                    ..
+                } => {
+                    assert_eq!(written, 1 + 300 * 1024);
+                    assert_eq!(total_size, Some(written));
+                }
+                UnixfsStatus::FailedStatus { error, .. } => panic!("{error:?}"),
+                UnixfsStatus::ProgressStatus { .. } => {}
+            }
+        }
+
+        entries.sort();
+        assert_eq!(
+            entries,
+            [
+                dest.join("a.txt"),
+                dest.join("sub/b.txt"),
+                dest.join("sub/link")
+            ]
+        );
+
+        assert_eq!(std::fs::read(dest.join("a.txt")).unwrap(), b"a");
+        assert_eq!(
+            std::fs::read(dest.join("sub/b.txt")).unwrap(),
+            vec![1u8; 300 * 1024]
+        );
+        assert_eq!(
+            std::fs::read_link(dest.join("sub/link")).unwrap(),
+            std::path::Path::new("../a.txt")
+        );
+        assert!(dest.join("sub/empty").is_dir());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn get_confined_symlinks() {
+        let ipfs = Node::new("test_node").await;
+        let dir = tempfile::tempdir().unwrap();
+
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::os::unix::fs::symlink("../a.txt", root.join("sub/inside")).unwrap();
+
+        let inside = ipfs.add_unixfs(root.clone()).await.unwrap();
+
+        std::os::unix::fs::symlink("../../secret", root.join("sub/outside")).unwrap();
+
+        let outside = ipfs.add_unixfs(root).await.unwrap();
+
+        ipfs.get_unixfs(inside, dir.path().join("inside"))
+            .confine_symlinks(true)
+            .await
+            .unwrap();
+
+        let res = ipfs
+            .get_unixfs(outside.clone(), dir.path().join("outside"))
+            .confine_symlinks(true)
+            .await;
+        assert!(res.is_err());
+
+        ipfs.get_unixfs(outside, dir.path().join("outside"))
+            .confine_symlinks(false)
+            .await
+            .unwrap();
+        assert!(dir.path().join("outside/sub/outside").is_symlink());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn get_confined_symlink_chains() {
+        let ipfs = Node::new("test_node").await;
+        let dir = tempfile::tempdir().unwrap();
+
+        // `chain` only stays within lexically, while `sub/up` leads to the root
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::os::unix::fs::symlink("..", root.join("sub/up")).unwrap();
+        std::os::unix::fs::symlink("sub/up/..", root.join("chain")).unwrap();
+
+        let path = ipfs.add_unixfs(root).await.unwrap();
+
+        let res = ipfs.get_unixfs(path.clone(), dir.path().join("dest")).await;
+        assert!(res.is_err());
+
+        // nor can the symlinks written later change where the earlier ones lead, as `a` only
+        // leads outside once `z` exists
+        let later = dir.path().join("later");
+        std::fs::create_dir_all(later.join("sub")).unwrap();
+        std::os::unix::fs::symlink("z/..", later.join("a")).unwrap();
+        std::os::unix::fs::symlink("..", later.join("sub/up")).unwrap();
+        std::os::unix::fs::symlink("sub/up", later.join("z")).unwrap();
+
+        let later = ipfs.add_unixfs(later).await.unwrap();
+        let res = ipfs.get_unixfs(later, dir.path().join("later_dest")).await;
+        assert!(res.is_err());
+
+        ipfs.get_unixfs(path, dir.path().join("dest"))
+            .confine_symlinks(false)
+            .await
+            .unwrap();
+        assert!(dir.path().join("dest/chain").is_symlink());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn get_confined_duplicate_names() {
+        let ipfs = Node::new("test_node").await;
+        let dir = tempfile::tempdir().unwrap();
+
+        let put = |data: Vec<u8>| {
+            let cid = Cid::new_v0(Code::Sha2_256.digest(&data)).unwrap();
+            crate::Block::new(cid, data).unwrap()
+        };
+
+        let symlink = |target: &str| {
+            let mut data = Vec::new();
+            rust_unixfs::symlink::serialize_symlink_block(target, &mut data);
+            put(data)
+        };
+
+        // `a` only leads outside once `b` exists, after which the file named `a` would be written
+        // through it
+        let a = symlink("b/../f");
+        let b = symlink(".");
+        let file = ipfs
+            .add_unixfs(Bytes::from_static(b"outside"))
+            .await
+            .unwrap();
+        let file = *file.root().cid().unwrap();
+
+        // encoded by hand, as the links are sorted by name when encoding the nodes
+        let mut data = Vec::new();
+        for (cid, name) in [(a.cid(), "a"), (b.cid(), "b"), (&file, "a")] {
+            let cid = cid.to_bytes();
+            let mut link = vec![0x0a, cid.len() as u8];
+            link.extend(cid);
+            link.extend([0x12, name.len() as u8]);
+            link.extend(name.as_bytes());
+            link.extend([0x18, 0x00]);
+
+            data.extend([0x12, link.len() as u8]);
+            data.extend(link);
+        }
+        data.extend([0x0a, 0x02, 0x08, 0x01]);
+        let root = put(data);
+
+        for block in [a, b, root.clone()] {
+            ipfs.put_block(block).await.unwrap();
+        }
+
+        let dest = dir.path().join("dest");
+        let res = ipfs.get_unixfs(IpfsPath::from(*root.cid()), &dest).await;
+        assert!(res.is_err());
+        assert!(!dir.path().join("f").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn preserve_mode_and_mtime() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let ipfs = Node::new("test_node").await;
+        let dir = tempfile::tempdir().unwrap();
+
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("sub/a.txt"), b"a").unwrap();
+        std::os::unix::fs::symlink("a.txt", root.join("sub/link")).unwrap();
+
+        let mode = |path: &std::path::Path, mode| {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap()
+        };
+        mode(&root.join("sub/a.txt"), 0o4600);
+        mode(&root.join("sub"), 0o750);
+
+        let mtime = filetime::FileTime::from_unix_time(1_000_000_000, 500);
+        filetime::set_file_mtime(root.join("sub/a.txt"), mtime).unwrap();
+        filetime::set_symlink_file_times(root.join("sub/link"), mtime, mtime).unwrap();
+        filetime::set_file_mtime(root.join("sub"), mtime).unwrap();
+
+        let plain = ipfs.add_unixfs(root.clone()).await.unwrap();
+        let path = ipfs
+            .add_unixfs(root)
+            .cid_version(Version::V1)
+            .preserve_mode(true)
+            .preserve_mtime(true)
+            .await
+            .unwrap();
+        assert_ne!(plain.root(), path.root());
+
+        let entries = ipfs.ls_unixfs(path.clone()).collect::<Vec<_>>().await;
+        let mut files = 0;
+
+        for entry in entries {
+            match entry {
+                Entry::File { file, metadata, .. } => {
+                    assert_eq!(file, "sub/a.txt");
+                    assert_eq!(metadata.mode(), Some(0o4600));
+                    assert_eq!(metadata.mtime(), Some((1_000_000_000, 500)));
+                    files += 1;
+                }
+                Entry::Directory { path, metadata, .. } => {
+                    assert_eq!(path, "sub");
+                    assert_eq!(metadata.mode(), Some(0o750));
+                    assert_eq!(metadata.mtime(), Some((1_000_000_000, 500)));
+                }
+                Entry::RootDirectory { metadata, .. } => assert!(metadata.mode().is_some()),
+                Entry::Error { error } => panic!("{error}"),
+            }
+        }
+        assert_eq!(files, 1);
+
+        let dest = dir.path().join("dest");
+        ipfs.get_unixfs(path, &dest)
+            .preserve_mode(true)
+            .preserve_mtime(true)
+            .await
+            .unwrap();
+
+        // the setuid bit is not restored
+        let file = std::fs::metadata(dest.join("sub/a.txt")).unwrap();
+        assert_eq!(file.permissions().mode() & 0o7777, 0o600);
+        assert_eq!(
+            filetime::FileTime::from_last_modification_time(&file),
+            mtime
+        );
+
+        let sub = std::fs::metadata(dest.join("sub")).unwrap();
+        assert_eq!(sub.permissions().mode() & 0o7777, 0o750);
+        assert_eq!(filetime::FileTime::from_last_modification_time(&sub), mtime);
+
+        let link = std::fs::symlink_metadata(dest.join("sub/link")).unwrap();
+        assert_eq!(
+            filetime::FileTime::from_last_modification_time(&link),
+            mtime
+        );
+    }
 }
@@ -0,0 +1,387 @@
+#![cfg(feature = "gateway")]
+
+use libipld::Cid;
+use reqwest::header::{
+    ACCEPT_RANGES, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_NONE_MATCH,
+    LOCATION, RANGE,
+};
+use reqwest::redirect::Policy;
+use reqwest::{Client, StatusCode};
+use rust_ipfs::car::CarImport;
+use rust_ipfs::dag::ResolvedNode;
+use rust_ipfs::ipns::IpnsOption;
+use rust_ipfs::{Block, Ipfs, Node};
+use rust_unixfs::dir::builder::{BufferingTreeBuilder, TreeOptions};
+
+const CONTENT: &str = "0123456789abcdefghijklmnopqrstuvwxyz";
+
+struct Site {
+    node: Node,
+    root: Cid,
+    _dir: tempfile::TempDir,
+}
+
+/// Adds a directory with a file, a directory with an index and a directory without one.
+async fn site() -> Site {
+    let dir = tempfile::tempdir().unwrap();
+    let site = dir.path().join("site");
+    std::fs::create_dir_all(site.join("docs")).unwrap();
+    std::fs::create_dir_all(site.join("assets")).unwrap();
+    std::fs::write(site.join("text.txt"), CONTENT).unwrap();
+    std::fs::write(site.join("docs/index.html"), "<p>docs</p>").unwrap();
+    std::fs::write(site.join("assets/a b.css"), "body {}").unwrap();
+    std::fs::write(site.join("assets/data"), b"plain text").unwrap();
+
+    let node = Node::new("gateway_node").await;
+    let path = node.add_unixfs(site.as_path()).await.unwrap();
+    let root = *path.root().cid().unwrap();
+
+    Site {
+        node,
+        root,
+        _dir: dir,
+    }
+}
+
+fn client() -> Client {
+    Client::builder().redirect(Policy::none()).build().unwrap()
+}
+
+#[tokio::test]
+async fn path_gateway() -> anyhow::Result<()> {
+    let Site { node, root, .. } = site().await;
+    let server = node
+        .gateway()
+        .local()
+        .listen("127.0.0.1:0".parse()?)
+        .await?;
+    let base = format!("http://{}", server.local_addr());
+    let client = client();
+
+    // files
+    let response = client
+        .get(format!("{base}/ipfs/{root}/text.txt"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[CONTENT_TYPE],
+        "text/plain; charset=utf-8"
+    );
+    assert_eq!(response.headers()[ACCEPT_RANGES], "bytes");
+    assert!(response.headers()[CACHE_CONTROL]
+        .to_str()?
+        .contains("immutable"));
+    let etag = response.headers()[ETAG].clone();
+    assert_eq!(response.text().await?, CONTENT);
+
+    let response = client
+        .get(format!("{base}/ipfs/{root}/text.txt"))
+        .header(IF_NONE_MATCH, etag)
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    let response = client
+        .head(format!("{base}/ipfs/{root}/text.txt"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_LENGTH], "36");
+
+    // ranges
+    let response = client
+        .get(format!("{base}/ipfs/{root}/text.txt"))
+        .header(RANGE, "bytes=10-15")
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.headers()[CONTENT_RANGE], "bytes 10-15/36");
+    assert_eq!(response.text().await?, "abcdef");
+
+    let response = client
+        .get(format!("{base}/ipfs/{root}/text.txt"))
+        .header(RANGE, "bytes=-3")
+        .send()
+        .await?;
+    assert_eq!(response.text().await?, "xyz");
+
+    let response = client
+        .get(format!("{base}/ipfs/{root}/text.txt"))
+        .header(RANGE, "bytes=100-")
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(response.headers()[CONTENT_RANGE], "bytes */36");
+
+    // content types
+    let response = client
+        .get(format!("{base}/ipfs/{root}/assets/a%20b.css"))
+        .send()
+        .await?;
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/css; charset=utf-8");
+    let response = client
+        .get(format!("{base}/ipfs/{root}/assets/data"))
+        .send()
+        .await?;
+    assert_eq!(
+        response.headers()[CONTENT_TYPE],
+        "text/plain; charset=utf-8"
+    );
+
+    // directories
+    let response = client
+        .get(format!("{base}/ipfs/{root}/docs"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(response.headers()[LOCATION], format!("/ipfs/{root}/docs/"));
+
+    let response = client
+        .get(format!("{base}/ipfs/{root}/docs/"))
+        .send()
+        .await?;
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+    assert_eq!(response.text().await?, "<p>docs</p>");
+
+    let response = client
+        .get(format!("{base}/ipfs/{root}/assets/"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let listing = response.text().await?;
+    assert!(listing.contains("<a href=\"a%20b.css\">a b.css</a>"));
+    assert!(listing.contains("<a href=\"data\">data</a>"));
+    assert!(listing.contains("<a href=\"../\">..</a>"));
+
+    let listing = client
+        .get(format!("{base}/ipfs/{root}/"))
+        .send()
+        .await?
+        .text()
+        .await?;
+    assert!(listing.contains("<a href=\"docs/\">docs/</a>"));
+    assert!(!listing.contains("index.html"));
+
+    // errors
+    let response = client
+        .get(format!("{base}/ipfs/{root}/missing"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = client.get(format!("{base}/ipfs/invalid")).send().await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = client.get(format!("{base}/other")).send().await?;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = client
+        .post(format!("{base}/ipfs/{root}/text.txt"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+    Ok(())
+}
+
+async fn resolve(node: &Node, path: String) -> Cid {
+    match node
+        .dag()
+        .resolve(path.parse().unwrap(), true, &[], true)
+        .await
+        .unwrap()
+    {
+        (ResolvedNode::Block(block), _) => *block.cid(),
+        _ => unreachable!(),
+    }
+}
+
+#[tokio::test]
+async fn listings_load_only_the_entries() -> anyhow::Result<()> {
+    let Site { node, root, .. } = site().await;
+    let server = node
+        .gateway()
+        .local()
+        .listen("127.0.0.1:0".parse()?)
+        .await?;
+    let base = format!("http://{}", server.local_addr());
+    let client = client();
+
+    // the blocks below the entries are not needed
+    let data = resolve(&node, format!("/ipfs/{root}/assets/data")).await;
+    node.remove_pin(&root).recursive().await?;
+    node.repo().remove_block(&data, false).await?;
+
+    let response = client.get(format!("{base}/ipfs/{root}/")).send().await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let listing = response.text().await?;
+    assert!(listing.contains("<a href=\"assets/\">assets/</a>"));
+    assert!(listing.contains(&format!(
+        "<a href=\"text.txt\">text.txt</a></td><td>{}</td><td>{}</td>",
+        resolve(&node, format!("/ipfs/{root}/text.txt")).await,
+        CONTENT.len()
+    )));
+
+    // the buckets of sharded directories are walked
+    let text = resolve(&node, format!("/ipfs/{root}/text.txt")).await;
+    let assets = resolve(&node, format!("/ipfs/{root}/assets")).await;
+    let mut opts = TreeOptions::default();
+    opts.wrap_with_directory();
+    opts.sharding_threshold(Some(1));
+    let mut builder = BufferingTreeBuilder::new(opts);
+    builder.put_link("assets", assets, 0)?;
+    for i in 0..100 {
+        builder.put_link(&format!("file-{i}"), text, CONTENT.len() as u64)?;
+    }
+    let mut sharded = None;
+    for node_block in builder.build() {
+        let node_block = node_block?;
+        sharded = Some(node_block.cid);
+        node.put_block(Block::new(node_block.cid, node_block.block.into_vec())?)
+            .await?;
+    }
+    let sharded = sharded.unwrap();
+
+    let response = client.get(format!("{base}/ipfs/{sharded}/")).send().await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let listing = response.text().await?;
+    assert!(listing.contains("<a href=\"assets/\">assets/</a>"));
+    for i in 0..100 {
+        assert!(listing.contains(&format!("<a href=\"file-{i}\">file-{i}</a>")));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn trustless_responses() -> anyhow::Result<()> {
+    let Site { node, root, .. } = site().await;
+    let server = node
+        .gateway()
+        .local()
+        .listen("127.0.0.1:0".parse()?)
+        .await?;
+    let base = format!("http://{}", server.local_addr());
+    let client = client();
+
+    let response = client
+        .get(format!("{base}/ipfs/{root}?format=raw"))
+        .send()
+        .await?;
+    assert_eq!(response.headers()[CONTENT_TYPE], "application/vnd.ipld.raw");
+    assert_eq!(response.headers()[ETAG], format!("\"{root}.raw\"").as_str());
+    let block = node.get_block(&root).await?;
+    assert_eq!(response.bytes().await?, block.data());
+
+    let response = client
+        .get(format!("{base}/ipfs/{root}"))
+        .header("accept", "application/vnd.ipld.car")
+        .send()
+        .await?;
+    assert_eq!(
+        response.headers()[CONTENT_TYPE],
+        "application/vnd.ipld.car; version=1"
+    );
+    let car = response.bytes().await?;
+
+    // the archive contains the whole dag
+    let other = Node::new("other_node").await;
+    let stream = futures::stream::iter([Ok::<_, std::io::Error>(car)]);
+    let roots = CarImport::new(other.repo(), stream).await?;
+    assert_eq!(roots, vec![root]);
+    let text = other
+        .cat_unixfs(format!("/ipfs/{root}/text.txt").parse::<rust_ipfs::IpfsPath>()?)
+        .await?;
+    assert_eq!(text, CONTENT.as_bytes());
+
+    let response = client
+        .get(format!("{base}/ipfs/{root}?format=tar"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn subdomain_gateway() -> anyhow::Result<()> {
+    let Site { node, root, .. } = site().await;
+    let server = node
+        .gateway()
+        .local()
+        .subdomain_host("localhost")
+        .listen("127.0.0.1:0".parse()?)
+        .await?;
+    let addr = server.local_addr();
+    let client = client();
+    let port = addr.port();
+    // subdomains are case-insensitive, so the cid is redirected to as base32
+    let root_v1 = Cid::new_v1(root.codec(), *root.hash());
+
+    let response = client
+        .get(format!("http://{addr}/ipfs/{root}/text.txt?x=1"))
+        .header("host", format!("localhost:{port}"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(
+        response.headers()[LOCATION],
+        format!("http://{root_v1}.ipfs.localhost:{port}/text.txt?x=1").as_str()
+    );
+
+    let response = client
+        .get(format!("http://{addr}/text.txt"))
+        .header("host", format!("{root_v1}.ipfs.localhost:{port}"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await?, CONTENT);
+
+    let peer_id = node.keypair().public().to_peer_id();
+    let response = client
+        .get(format!("http://{addr}/ipns/{peer_id}"))
+        .header("host", format!("localhost:{port}"))
+        .send()
+        .await?;
+    let location = response.headers()[LOCATION].to_str()?.to_string();
+    assert!(location.starts_with("http://k51"));
+    assert!(location.ends_with(&format!(".ipns.localhost:{port}")));
+
+    // path requests to other hosts are still served
+    let response = client
+        .get(format!("http://{addr}/ipfs/{root}/text.txt"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ipns_paths_are_mutable() -> anyhow::Result<()> {
+    let Site { node, root, .. } = site().await;
+    let ipfs: &Ipfs = &node;
+    let published = ipfs
+        .ipns()
+        .publish(
+            None,
+            &format!("/ipfs/{root}").parse()?,
+            Some(IpnsOption::Local),
+        )
+        .await?;
+
+    let server = node
+        .gateway()
+        .local()
+        .listen("127.0.0.1:0".parse()?)
+        .await?;
+    let base = format!("http://{}", server.local_addr());
+
+    let response = client()
+        .get(format!("{base}{published}/text.txt"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CACHE_CONTROL], "public, max-age=60");
+    assert_eq!(response.text().await?, CONTENT);
+
+    Ok(())
+}
@@ -17,7 +17,7 @@ async fn cleanup_unpinned_blocks() -> anyhow::Result<()> {
     let block = create_block();
     let cid = node.put_block(block).await?;
 
-    let removed = node.gc().await?;
+    let removed = node.gc().await?.removed;
 
     assert_eq!(removed[0], cid);
 
@@ -30,8 +30,23 @@ async fn gc_cleanup_attempt_of_pinned_blocks() -> anyhow::Result<()> {
     let block = create_block();
     let cid = node.put_block(block).await?;
     node.insert_pin(&cid).await?;
-    let removed = node.gc().await?;
+    let removed = node.gc().await?.removed;
     assert!(removed.is_empty());
 
     Ok(())
 }
+
+#[tokio::test]
+async fn gc_dry_run_keeps_blocks() -> anyhow::Result<()> {
+    let node = Node::new("gc_test_node").await;
+    let block = create_block();
+    let cid = node.put_block(block).await?;
+
+    let report = node.gc().dry_run().await?;
+    assert!(report.completed);
+    assert_eq!(report.removed, vec![cid]);
+    assert_eq!(report.bytes_freed, b"hello block\n".len());
+    assert!(node.repo().contains(&cid).await?);
+
+    Ok(())
+}
@@ -0,0 +1,343 @@
+#![cfg(feature = "remote_pinning")]
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use libipld::multihash::{Code, MultihashDigest};
+use libipld::{Cid, IpldCodec};
+use parking_lot::Mutex;
+use rust_ipfs::remote_pin::{RemotePin, RemotePinRequest, RemotePinStatus, RemotePinningService};
+use rust_ipfs::Node;
+use serde_json::json;
+
+const TOKEN: &str = "secret";
+
+/// In-memory stand-in of a pinning service, advancing the status of a request every time it is
+/// queried unless the pin is named "stuck".
+#[derive(Default)]
+struct PinningService {
+    requests: Mutex<Vec<RemotePinRequest>>,
+    next_id: Mutex<u32>,
+    /// Lists the pins created at the `before` time again
+    inclusive_before: bool,
+}
+
+type Service = State<Arc<PinningService>>;
+
+impl PinningService {
+    fn create(&self, pin: RemotePin) -> RemotePinRequest {
+        let mut next_id = self.next_id.lock();
+        *next_id += 1;
+        let request = RemotePinRequest {
+            request_id: format!("request-{}", *next_id),
+            status: RemotePinStatus::Queued,
+            created: format!("2024-01-01T00:00:{:02}Z", *next_id),
+            pin,
+            delegates: vec![],
+            info: Default::default(),
+        };
+        self.requests.lock().push(request.clone());
+        request
+    }
+}
+
+fn error(status: StatusCode, reason: &str) -> Response {
+    (status, Json(json!({ "error": { "reason": reason } }))).into_response()
+}
+
+fn authorized(headers: &HeaderMap) -> bool {
+    headers
+        .get("authorization")
+        .is_some_and(|value| value == format!("Bearer {TOKEN}").as_str())
+}
+
+async fn list(
+    State(service): Service,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    if !authorized(&headers) {
+        return error(StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+    }
+
+    let status = query
+        .get("status")
+        .map(String::as_str)
+        .unwrap_or("pinned")
+        .split(',')
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let limit = query
+        .get("limit")
+        .map(|limit| limit.parse().unwrap())
+        .unwrap_or(10);
+
+    let mut matching = service
+        .requests
+        .lock()
+        .iter()
+        .filter(|request| status.contains(&request.status.to_string()))
+        .filter(|request| {
+            query
+                .get("cid")
+                .map_or(true, |cids| cids.contains(&request.pin.cid.to_string()))
+        })
+        .filter(|request| {
+            query.get("name").map_or(true, |name| {
+                request.pin.name.as_deref() == Some(name.as_str())
+            })
+        })
+        .filter(|request| {
+            query
+                .get("before")
+                .map_or(true, |before| match service.inclusive_before {
+                    true => request.created <= *before,
+                    false => request.created < *before,
+                })
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    matching.sort_by(|a, b| b.created.cmp(&a.created));
+
+    let count = matching.len();
+    matching.truncate(limit);
+    Json(json!({ "count": count, "results": matching })).into_response()
+}
+
+async fn add(State(service): Service, headers: HeaderMap, Json(pin): Json<RemotePin>) -> Response {
+    if !authorized(&headers) {
+        return error(StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+    }
+    (StatusCode::ACCEPTED, Json(service.create(pin))).into_response()
+}
+
+async fn status(State(service): Service, headers: HeaderMap, Path(id): Path<String>) -> Response {
+    if !authorized(&headers) {
+        return error(StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+    }
+    let mut requests = service.requests.lock();
+    let Some(request) = requests.iter_mut().find(|request| request.request_id == id) else {
+        return error(StatusCode::NOT_FOUND, "NOT_FOUND");
+    };
+    if request.pin.name.as_deref() != Some("stuck") {
+        request.status = match request.status {
+            RemotePinStatus::Queued => RemotePinStatus::Pinning,
+            _ => RemotePinStatus::Pinned,
+        };
+    }
+    Json(request.clone()).into_response()
+}
+
+async fn replace(
+    State(service): Service,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(pin): Json<RemotePin>,
+) -> Response {
+    if !authorized(&headers) {
+        return error(StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+    }
+    let found = {
+        let mut requests = service.requests.lock();
+        let len = requests.len();
+        requests.retain(|request| request.request_id != id);
+        requests.len() != len
+    };
+    if !found {
+        return error(StatusCode::NOT_FOUND, "NOT_FOUND");
+    }
+    (StatusCode::ACCEPTED, Json(service.create(pin))).into_response()
+}
+
+async fn remove(State(service): Service, headers: HeaderMap, Path(id): Path<String>) -> Response {
+    if !authorized(&headers) {
+        return error(StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+    }
+    let mut requests = service.requests.lock();
+    let len = requests.len();
+    requests.retain(|request| request.request_id != id);
+    match requests.len() != len {
+        true => StatusCode::ACCEPTED.into_response(),
+        false => error(StatusCode::NOT_FOUND, "NOT_FOUND"),
+    }
+}
+
+async fn spawn_service(service: PinningService) -> String {
+    let app = Router::new()
+        .route("/psa/pins", get(list).post(add))
+        .route("/psa/pins/:id", get(status).post(replace).delete(remove))
+        .with_state(Arc::new(service));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    format!("http://{addr}/psa")
+}
+
+fn cid(data: &[u8]) -> Cid {
+    Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(data))
+}
+
+#[tokio::test]
+async fn remote_pins() -> anyhow::Result<()> {
+    let endpoint = spawn_service(Default::default()).await;
+    let node = Node::new("remote_pin_node").await;
+
+    assert!(node.remote_pins("service").is_err());
+    node.add_remote_pinning_service("service", RemotePinningService::new(&endpoint, TOKEN)?);
+    let remote = node.remote_pins("service")?;
+
+    let first = cid(b"first");
+    let second = cid(b"second");
+
+    // waits until pinned by polling the status
+    let request = remote
+        .add(&first)
+        .name("website")
+        .meta("env", "prod")
+        .poll_interval(Duration::from_millis(10))
+        .await?;
+    assert_eq!(request.status, RemotePinStatus::Pinned);
+    assert_eq!(request.pin.name.as_deref(), Some("website"));
+    assert_eq!(request.pin.meta["env"], "prod");
+    assert_eq!(request.pin.origins, node.addrs);
+
+    let queued = remote.add(&second).background().await?;
+    assert_eq!(queued.status, RemotePinStatus::Queued);
+
+    let pinned = remote.list().await?;
+    assert_eq!(pinned.len(), 1);
+    assert_eq!(pinned[0].pin.cid, first);
+
+    let queued_list = remote.list().status(RemotePinStatus::Queued).await?;
+    assert_eq!(queued_list, vec![queued.clone()]);
+
+    let by_name = remote
+        .list()
+        .name("website")
+        .status(RemotePinStatus::Pinned)
+        .status(RemotePinStatus::Queued)
+        .await?;
+    assert_eq!(by_name.len(), 1);
+
+    // replacing the pin returns a new request
+    let third = cid(b"third");
+    let replaced = remote
+        .replace(&request.request_id, &third)
+        .name("website")
+        .poll_interval(Duration::from_millis(10))
+        .await?;
+    assert_ne!(replaced.request_id, request.request_id);
+    assert_eq!(replaced.pin.cid, third);
+    assert!(remote.get(&request.request_id).await.is_err());
+
+    remote.remove(&queued.request_id).await?;
+    let error = remote.remove(&queued.request_id).await.unwrap_err();
+    assert!(error.to_string().contains("NOT_FOUND"));
+
+    // the listing is paginated
+    for i in 0..25u8 {
+        remote.add(&cid(&[i])).background().await?;
+    }
+    let all = remote
+        .list()
+        .status(RemotePinStatus::Queued)
+        .status(RemotePinStatus::Pinned)
+        .await?;
+    assert_eq!(all.len(), 26);
+    let limited = remote
+        .list()
+        .status(RemotePinStatus::Queued)
+        .limit(12)
+        .await?;
+    assert_eq!(limited.len(), 12);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unauthorized() -> anyhow::Result<()> {
+    let endpoint = spawn_service(Default::default()).await;
+    let node = Node::new("remote_pin_node").await;
+
+    node.add_remote_pinning_service("service", RemotePinningService::new(&endpoint, "wrong")?);
+    let error = node
+        .remote_pins("service")?
+        .add(&cid(b"first"))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("UNAUTHORIZED"));
+
+    assert!(node.remove_remote_pinning_service("service"));
+    assert!(node.remote_pinning_services().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn inclusive_pagination() -> anyhow::Result<()> {
+    let endpoint = spawn_service(PinningService {
+        inclusive_before: true,
+        ..Default::default()
+    })
+    .await;
+    let node = Node::new("remote_pin_node").await;
+
+    node.add_remote_pinning_service("service", RemotePinningService::new(&endpoint, TOKEN)?);
+    let remote = node.remote_pins("service")?;
+
+    for i in 0..25u8 {
+        remote.add(&cid(&[i])).background().await?;
+    }
+
+    // the last pin of every page is listed again by the service
+    let all = remote.list().status(RemotePinStatus::Queued).await?;
+    assert_eq!(all.len(), 25);
+    let cids = all
+        .iter()
+        .map(|request| request.pin.cid)
+        .collect::<HashSet<_>>();
+    assert_eq!(cids.len(), 25);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wait_timeout() -> anyhow::Result<()> {
+    let endpoint = spawn_service(Default::default()).await;
+    let node = Node::new("remote_pin_node").await;
+
+    node.add_remote_pinning_service("service", RemotePinningService::new(&endpoint, TOKEN)?);
+    let remote = node.remote_pins("service")?;
+
+    let error = remote
+        .add(&cid(b"first"))
+        .name("stuck")
+        .poll_interval(Duration::from_millis(10))
+        .timeout(Duration::from_millis(200))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("timed out"));
+
+    // the request is left on the service
+    let queued = remote.list().status(RemotePinStatus::Queued).await?;
+    assert_eq!(queued.len(), 1);
+    let error = remote
+        .wait(
+            &queued[0].request_id,
+            Duration::from_millis(10),
+            Some(Duration::from_millis(50)),
+        )
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("timed out"));
+
+    Ok(())
+}
@@ -77,6 +77,65 @@ pub fn resolve<'needle>(
     }
 }
 
+/// A link of a directory or of a single HAMT shard, as returned by [`links`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirectoryLink {
+    /// An entry of the directory, with the cumulative size of the linked tree.
+    Entry {
+        /// Name of the entry, without the bucket index of HAMT shards.
+        name: String,
+        /// The linked entry.
+        cid: Cid,
+        /// Cumulative size of the linked tree as recorded in the link.
+        total_size: u64,
+    },
+    /// A nested HAMT shard, which needs to be loaded to find the rest of the entries.
+    Bucket(Cid),
+}
+
+/// Returns the links of a `dag-pb` or UnixFS directory, or of a single HAMT shard. The entries of
+/// a sharded directory are found by loading all of the returned [`DirectoryLink::Bucket`]s in
+/// turn.
+#[allow(clippy::result_large_err)]
+pub fn links(block: &[u8]) -> Result<Vec<DirectoryLink>, ResolveError> {
+    let (links, sharded) = match FlatUnixFs::try_parse(block) {
+        Ok(hamt) if hamt.data.Type == UnixFsType::HAMTShard => {
+            (check_hamtshard_supported(hamt)?.links, true)
+        }
+        Ok(flat) if flat.data.Type == UnixFsType::Directory => {
+            (check_directory_supported(flat)?.links, false)
+        }
+        Err(ParsingFailed::InvalidUnixFs(_, PBNode { Links: links, .. }))
+        | Err(ParsingFailed::NoData(PBNode { Links: links, .. })) => (links, false),
+        Ok(other) => return Err(ResolveError::UnexpectedType(other.data.Type.into())),
+        Err(ParsingFailed::InvalidDagPb(e)) => return Err(ResolveError::Read(e)),
+    };
+
+    links
+        .into_iter()
+        .enumerate()
+        .map(|(nth, link)| {
+            let name = link.Name.as_deref().unwrap_or_default().to_owned();
+            let total_size = link.Tsize.unwrap_or_default();
+            let cid = try_convert_cid(nth, link)?;
+
+            Ok(match name.len() {
+                2 if sharded => DirectoryLink::Bucket(cid),
+                _ if sharded => DirectoryLink::Entry {
+                    name: name.get(2..).unwrap_or_default().to_owned(),
+                    cid,
+                    total_size,
+                },
+                _ => DirectoryLink::Entry {
+                    name,
+                    cid,
+                    total_size,
+                },
+            })
+        })
+        .collect()
+}
+
 fn try_convert_cid(nth: usize, link: PBLink<'_>) -> Result<Cid, InvalidCidInLink> {
     let hash = link.Hash.as_deref().unwrap_or_default();
     Cid::try_from(hash).map_err(|e| InvalidCidInLink::from((nth, link, e)))
@@ -213,7 +272,7 @@ impl MultipleMatchingLinks {
 #[cfg(test)]
 mod tests {
 
-    use super::{resolve, MaybeResolved};
+    use super::{links, resolve, DirectoryLink, MaybeResolved};
     use crate::test_support::FakeBlockstore;
     use core::convert::TryFrom;
     use hex_literal::hex;
@@ -297,4 +356,82 @@ mod tests {
             "QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL"
         );
     }
+
+    #[test]
+    fn links_of_sharded_directory() {
+        let blocks = FakeBlockstore::with_fixtures();
+
+        let block = blocks.get_by_str("QmQXUANxYGpkwMTWQUdZBPx9jqfFP7acNgL4FHRWkndKCe");
+        let root = links(block).unwrap();
+
+        assert_eq!(root.len(), 1);
+
+        let found = root
+            .iter()
+            .find_map(|link| match link {
+                DirectoryLink::Entry { name, cid, .. } if name == "non_sharded_dir" => Some(cid),
+                _ => None,
+            })
+            .unwrap();
+
+        let block = blocks.get_by_cid(found);
+        let nested = links(block).unwrap();
+
+        assert!(nested
+            .iter()
+            .any(|link| matches!(link, DirectoryLink::Entry { name, .. } if name == "foobar")));
+        assert!(links(&hex!("0a130802120d666f6f6261720a666f6f626172180d")[..]).is_err());
+    }
+
+    #[test]
+    fn links_of_built_shards() {
+        use super::builder::{BufferingTreeBuilder, TreeOptions};
+        use std::collections::{BTreeSet, HashMap};
+
+        let mut opts = TreeOptions::default();
+        opts.wrap_with_directory();
+        opts.sharding_threshold(Some(1));
+
+        let mut builder = BufferingTreeBuilder::new(opts);
+        let target = Cid::try_from("QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL").unwrap();
+        let expected = (0..300).map(|i| format!("{i:03}")).collect::<BTreeSet<_>>();
+
+        for name in &expected {
+            builder.put_link(name, target, 7).unwrap();
+        }
+
+        let mut blocks = HashMap::new();
+        let mut root = None;
+        for node in builder.build() {
+            let node = node.unwrap();
+            root = Some(node.cid);
+            blocks.insert(node.cid, node.block);
+        }
+
+        let mut found = BTreeSet::new();
+        let mut buckets = 0;
+        let mut pending = vec![root.unwrap()];
+
+        while let Some(cid) = pending.pop() {
+            for link in links(&blocks[&cid]).unwrap() {
+                match link {
+                    DirectoryLink::Entry {
+                        name,
+                        cid,
+                        total_size,
+                    } => {
+                        assert_eq!((cid, total_size), (target, 7));
+                        assert!(found.insert(name));
+                    }
+                    DirectoryLink::Bucket(cid) => {
+                        buckets += 1;
+                        pending.push(cid);
+                    }
+                }
+            }
+        }
+
+        assert!(buckets > 0);
+        assert_eq!(found, expected);
+    }
 }
@@ -1,3 +1,4 @@
+use crate::CidOptions;
 use core::fmt;
 use libipld::Cid;
 
@@ -13,6 +14,8 @@ pub use buffered::BufferingTreeBuilder;
 mod custom_pb;
 use custom_pb::CustomFlatUnixFs;
 
+mod hamt;
+
 enum Entry {
     Leaf(Leaf),
     Directory(DirBuilder),
@@ -55,6 +58,8 @@ impl fmt::Debug for Leaf {
 pub struct TreeOptions {
     block_size_limit: Option<u64>,
     wrap_with_directory: bool,
+    sharding_threshold: Option<u64>,
+    cid_options: CidOptions,
 }
 
 impl Default for TreeOptions {
@@ -62,6 +67,8 @@ impl Default for TreeOptions {
         TreeOptions {
             block_size_limit: Some(512 * 1024),
             wrap_with_directory: false,
+            sharding_threshold: Some(256 * 1024),
+            cid_options: CidOptions::default(),
         }
     }
 }
@@ -78,6 +85,20 @@ impl TreeOptions {
     pub fn wrap_with_directory(&mut self) {
         self.wrap_with_directory = true;
     }
+
+    /// Overrides the default HAMT sharding threshold of 256 KiB. Directories are built as HAMT
+    /// shards once the sum of their link name and Cid lengths reaches the threshold, which is the
+    /// same estimate go-ipfs uses. If the threshold is set to `None`, directories are never
+    /// sharded.
+    pub fn sharding_threshold(&mut self, threshold: Option<u64>) {
+        self.sharding_threshold = threshold;
+    }
+
+    /// Overrides the default options for creating the Cids of the directory blocks, which by
+    /// default are Cid version 0 with sha2-256 multihash.
+    pub fn cid_options(&mut self, cid_options: CidOptions) {
+        self.cid_options = cid_options;
+    }
 }
 
 /// Tree building failure cases.
@@ -127,9 +148,11 @@ impl std::error::Error for TreeBuildingFailed {}
 pub enum TreeConstructionFailed {
     /// Failed to serialize the protobuf node for the directory
     Protobuf(quick_protobuf::Error),
-    /// The resulting directory would be too large and HAMT sharding is yet to be implemented or
-    /// denied.
+    /// The resulting directory block would be too large, while HAMT sharding has been disabled.
     TooLargeBlock(u64),
+    /// The name of the directory entry shares the whole hash with another entry, so it cannot be
+    /// placed in a HAMT shard.
+    HashCollision(String),
 }
 
 impl fmt::Display for TreeConstructionFailed {
@@ -139,6 +162,7 @@ impl fmt::Display for TreeConstructionFailed {
         match self {
             Protobuf(e) => write!(fmt, "serialization failed: {e}"),
             TooLargeBlock(size) => write!(fmt, "attempted to create block of {size} bytes"),
+            HashCollision(name) => write!(fmt, "hash collision of HAMT shard entry {name:?}"),
         }
     }
 }
@@ -55,12 +55,18 @@ impl BufferingTreeBuilder {
     }
 
     /// Directories get "put" implicitly through the put files, and directories need to be adjusted
-    /// only when wanting them to have metadata.
+    /// only when wanting them to have metadata. An empty path sets the metadata of the directory
+    /// created with [`TreeOptions::wrap_with_directory`].
     pub fn set_metadata(
         &mut self,
         full_path: &str,
         metadata: Metadata,
     ) -> Result<(), TreeBuildingFailed> {
+        if full_path.is_empty() {
+            self.root_builder.set_metadata(metadata);
+            return Ok(());
+        }
+
         // create all paths along the way
         //
         // set if not set, error otherwise? FIXME: doesn't error atm
@@ -188,7 +194,9 @@ mod tests {
     use super::{
         super::OwnedTreeNode, BufferingTreeBuilder, Metadata, TreeBuildingFailed, TreeOptions,
     };
+    use crate::CidOptions;
     use core::convert::TryFrom;
+    use libipld::cid::Version;
     use libipld::multihash::{Code, MultihashDigest};
     use libipld::Cid;
 
@@ -343,6 +351,33 @@ mod tests {
         builder.put_link("a.txt/b.txt", some_cid(1), 1).unwrap();
     }
 
+    #[test]
+    fn metadata_is_rendered() {
+        use crate::pb::FlatUnixFs;
+
+        let mut opts = TreeOptions::default();
+        opts.wrap_with_directory();
+
+        let root = Metadata::default().with_mode(0o700);
+        let nested = Metadata::default().with_mtime(-1, 0);
+
+        let mut builder = BufferingTreeBuilder::new(opts);
+        builder.set_metadata("", root.clone()).unwrap();
+        builder.set_metadata("a", nested.clone()).unwrap();
+        builder.put_link("a/b.txt", some_cid(0), 1).unwrap();
+
+        let nodes = builder.build().collect::<Result<Vec<_>, _>>().unwrap();
+        let read = nodes
+            .iter()
+            .map(|node| {
+                let flat = FlatUnixFs::try_parse(&node.block).unwrap();
+                (node.path.as_str(), Metadata::from(&flat.data))
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(read, [("a", nested), ("", root)]);
+    }
+
     #[test]
     fn set_metadata_before_files() {
         let mut builder = BufferingTreeBuilder::default();
@@ -395,6 +430,137 @@ mod tests {
         verify_results(expected, actual);
     }
 
+    #[test]
+    fn dir_with_cid_options() {
+        let target =
+            Cid::try_from("bafyreihakpd7te5nbmlhdk5ntvcvhf2hmfgrvcwna2sddq5zz5342mcbli").unwrap();
+
+        let mut opts = TreeOptions::default();
+        opts.cid_options(CidOptions::default().with_version(Version::V1));
+        let mut builder = BufferingTreeBuilder::new(opts);
+        builder.put_link("a/b", target, 12).unwrap();
+
+        let actual = builder
+            .build()
+            .map(|res| res.map(|n| (n.path, n.cid, n.block)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        // same block as in `dir_with_cidv1_link`, only the Cid version differs
+        let v0 = Cid::try_from("QmPMDMPG8dbHDC9GuvqWr9pfruLnp4GZCAWrskwCmenVQa").unwrap();
+        let expected = vec![("a", Cid::new_v1(0x70, *v0.hash()).to_string())];
+
+        verify_results(expected, actual);
+    }
+
+    #[test]
+    fn sharded_dir_with_single_link() {
+        // the non-sharded directory of test_support
+        let target = Cid::try_from("QmYmmkD3dGZjuozuqSzDYjU4ZyhAgc4T4P4SUgY6qjzBi8").unwrap();
+
+        let mut opts = TreeOptions::default();
+        opts.wrap_with_directory();
+        opts.sharding_threshold(Some(1));
+        let mut builder = BufferingTreeBuilder::new(opts);
+        builder.put_link("non_sharded_dir", target, 67).unwrap();
+
+        let actual = builder
+            .build()
+            .map(|res| res.map(|n| (n.path, n.cid, n.block)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let expected = vec![("", "QmQXUANxYGpkwMTWQUdZBPx9jqfFP7acNgL4FHRWkndKCe")];
+
+        verify_results(expected, actual);
+    }
+
+    #[test]
+    fn sharded_dir_with_collisions() {
+        // the empty file of test_support, linked by all of the names
+        let target = Cid::try_from("QmbFMke1KXqnYyBBWxB74N4c5SBnJMVAiMNRcGu6x1AwQH").unwrap();
+
+        let mut opts = TreeOptions::default();
+        opts.wrap_with_directory();
+        opts.sharding_threshold(Some(1));
+        let mut builder = BufferingTreeBuilder::new(opts);
+
+        for n in [3, 4, 9, 16, 17, 25, 33, 34, 37, 38, 40, 41, 48, 49, 50, 58] {
+            builder
+                .put_link(&format!("long-named-file-{n:03}"), target, 6)
+                .unwrap();
+        }
+
+        let actual = builder
+            .build()
+            .map(|res| res.map(|n| (n.path, n.cid)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        // each of the eight buckets holds two entries, rendered as nested shards before the root
+        assert_eq!(actual.len(), 9);
+        assert!(actual.iter().all(|(path, _)| path.is_empty()));
+        assert_eq!(
+            actual.last().unwrap().1.to_string(),
+            "QmZbFPTnDBMWbQ6iBxQAhuhLz8Nu9XptYS96e7cuf5wvbk"
+        );
+    }
+
+    #[test]
+    fn sharded_dir_metadata() {
+        use crate::pb::FlatUnixFs;
+
+        let target = Cid::try_from("QmbFMke1KXqnYyBBWxB74N4c5SBnJMVAiMNRcGu6x1AwQH").unwrap();
+
+        let mut opts = TreeOptions::default();
+        opts.wrap_with_directory();
+        opts.sharding_threshold(Some(1));
+        let mut builder = BufferingTreeBuilder::new(opts);
+
+        let metadata = Metadata::default().with_mode(0o755).with_mtime(1, 0);
+        builder.set_metadata("", metadata.clone()).unwrap();
+
+        // the same names as in `sharded_dir_with_collisions`, for nested shards
+        for n in [3, 4, 9, 16, 17, 25, 33, 34, 37, 38, 40, 41, 48, 49, 50, 58] {
+            builder
+                .put_link(&format!("long-named-file-{n:03}"), target, 6)
+                .unwrap();
+        }
+
+        let nodes = builder.build().collect::<Result<Vec<_>, _>>().unwrap();
+        let read = nodes
+            .iter()
+            .map(|node| Metadata::from(&FlatUnixFs::try_parse(&node.block).unwrap().data))
+            .collect::<Vec<_>>();
+
+        // only the root shard, rendered last, records the metadata
+        let (root, nested) = read.split_last().unwrap();
+        assert_eq!(root, &metadata);
+        assert!(!nested.is_empty());
+        assert!(nested.iter().all(|metadata| metadata.is_empty()));
+    }
+
+    #[test]
+    fn sharding_threshold() {
+        // each link is estimated as the name length and 34 bytes for the cidv0, so the default
+        // threshold of 256 KiB is reached with 6899 links of four byte names
+        for (count, sharded) in [(6898, false), (6899, true)] {
+            let mut opts = TreeOptions::default();
+            opts.wrap_with_directory();
+            let mut builder = BufferingTreeBuilder::new(opts);
+
+            for n in 0..count {
+                builder
+                    .put_link(&format!("{n:04}"), some_cid(n), 1)
+                    .unwrap();
+            }
+
+            let nodes = builder.build().collect::<Result<Vec<_>, _>>().unwrap();
+
+            assert_eq!(nodes.len() > 1, sharded, "{count} links");
+        }
+    }
+
     fn verify_results(
         mut expected: Vec<(
             impl AsRef<str> + core::fmt::Debug,
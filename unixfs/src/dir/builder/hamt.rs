@@ -0,0 +1,225 @@
+//! HAMT sharded directory construction, following the layout used by go-ipfs.
+//!
+//! Entries are bucketed by the murmur3-x64-64 hash of their name, eight bits per level. A bucket
+//! holding a single entry links directly to it, with the link named by the uppercase hex bucket
+//! index followed by the entry name. A bucket with more entries links to a nested shard, named by
+//! the bucket index alone.
+
+use super::iter::render_node;
+use super::{Leaf, NamedLeaf, TreeConstructionFailed, TreeOptions};
+use crate::pb::{UnixFs, UnixFsType};
+use crate::Metadata;
+use alloc::borrow::Cow;
+use alloc::collections::VecDeque;
+
+/// Multicodec code of murmur3-x64-64, the only hash function supported for HAMT shards.
+pub(super) const HASH_TYPE: u64 = 0x22;
+
+/// Fanout of each shard; each level consumes eight bits of the hash.
+pub(super) const FANOUT: u64 = 256;
+
+/// Rendered shard waiting to be returned from the `PostOrderIterator`.
+pub(super) struct RenderedShard {
+    pub(super) leaf: Leaf,
+    pub(super) block: Vec<u8>,
+}
+
+/// Estimates the size of a directory block the way go-ipfs does when deciding whether or not to
+/// switch to a HAMT sharded directory.
+pub(super) fn estimated_size(links: &[Option<NamedLeaf>]) -> u64 {
+    links
+        .iter()
+        .flatten()
+        .map(|NamedLeaf(name, cid, _)| (name.len() + cid.encoded_len()) as u64)
+        .sum()
+}
+
+/// Renders the given directory entries as a HAMT. All of the shards will be pushed to `shards` in
+/// post order, the last one being the root shard, which is also returned. The metadata of the
+/// directory is only recorded in the root shard.
+pub(super) fn render_shards(
+    links: &[Option<NamedLeaf>],
+    metadata: &Metadata,
+    buffer: &mut Vec<u8>,
+    opts: &TreeOptions,
+    shards: &mut VecDeque<RenderedShard>,
+) -> Result<Leaf, TreeConstructionFailed> {
+    let mut entries = links
+        .iter()
+        .map(|link| {
+            // FIXME: same assumption as with the `CustomFlatUnixFs`
+            let link = link.as_ref().unwrap();
+            (hash(link.0.as_bytes()), link)
+        })
+        .collect::<Vec<_>>();
+
+    // sorting by the whole hash keeps the entries of each bucket next to each other on every level
+    entries.sort_unstable_by_key(|(hash, _)| *hash);
+
+    render_level(&entries, 0, Some(metadata), buffer, opts, shards)
+}
+
+fn render_level(
+    entries: &[([u8; 8], &NamedLeaf)],
+    depth: usize,
+    metadata: Option<&Metadata>,
+    buffer: &mut Vec<u8>,
+    opts: &TreeOptions,
+    shards: &mut VecDeque<RenderedShard>,
+) -> Result<Leaf, TreeConstructionFailed> {
+    let mut bitfield = [0u8; FANOUT as usize / 8];
+    let mut links = Vec::new();
+    let mut remaining = entries;
+
+    while let Some(((hash, _), _)) = remaining.split_first() {
+        let index = hash[depth];
+        let len = remaining
+            .iter()
+            .take_while(|(hash, _)| hash[depth] == index)
+            .count();
+        let (bucket, rest) = remaining.split_at(len);
+        remaining = rest;
+
+        // the bitfield is a big endian integer
+        bitfield[bitfield.len() - 1 - index as usize / 8] |= 1 << (index % 8);
+
+        match bucket {
+            [(_, NamedLeaf(name, cid, total_size))] => {
+                links.push(Some(NamedLeaf(
+                    format!("{index:02X}{name}"),
+                    *cid,
+                    *total_size,
+                )));
+            }
+            [(_, NamedLeaf(name, ..)), ..] if depth + 1 == hash.len() => {
+                return Err(TreeConstructionFailed::HashCollision(name.clone()));
+            }
+            _ => {
+                let shard = render_level(bucket, depth + 1, None, buffer, opts, shards)?;
+                links.push(Some(NamedLeaf(
+                    format!("{index:02X}"),
+                    shard.link,
+                    shard.total_size,
+                )));
+            }
+        }
+    }
+
+    // go-ipfs writes the bitfield with the leading zero bytes trimmed
+    let start = bitfield
+        .iter()
+        .position(|b| *b != 0)
+        .unwrap_or(bitfield.len());
+
+    let mut data = UnixFs {
+        Type: UnixFsType::HAMTShard,
+        Data: Some(Cow::Borrowed(&bitfield[start..])),
+        hashType: Some(HASH_TYPE),
+        fanout: Some(FANOUT),
+        ..Default::default()
+    };
+    if let Some(metadata) = metadata {
+        metadata.apply(&mut data);
+    }
+
+    let leaf = render_node(&links, data, buffer, opts)?;
+
+    shards.push_back(RenderedShard {
+        leaf: Leaf {
+            link: leaf.link,
+            total_size: leaf.total_size,
+        },
+        block: buffer.clone(),
+    });
+
+    Ok(leaf)
+}
+
+/// The murmur3 x64 128-bit hash with zero seed, truncated to the first 64 bits and returned as big
+/// endian bytes, which is what go-ipfs uses to bucket the entries.
+fn hash(data: &[u8]) -> [u8; 8] {
+    const C1: u64 = 0x87c3_7b91_1142_53d5;
+    const C2: u64 = 0x4cf5_ad43_2745_937f;
+
+    fn fmix(mut k: u64) -> u64 {
+        k ^= k >> 33;
+        k = k.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        k ^= k >> 33;
+        k = k.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        k ^ (k >> 33)
+    }
+
+    fn mix_k1(k1: u64) -> u64 {
+        k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2)
+    }
+
+    fn mix_k2(k2: u64) -> u64 {
+        k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1)
+    }
+
+    let mut h1 = 0u64;
+    let mut h2 = 0u64;
+
+    let mut blocks = data.chunks_exact(16);
+
+    for block in &mut blocks {
+        let k1 = u64::from_le_bytes(block[..8].try_into().unwrap());
+        let k2 = u64::from_le_bytes(block[8..].try_into().unwrap());
+
+        h1 ^= mix_k1(k1);
+        h1 = h1
+            .rotate_left(27)
+            .wrapping_add(h2)
+            .wrapping_mul(5)
+            .wrapping_add(0x52dc_e729);
+
+        h2 ^= mix_k2(k2);
+        h2 = h2
+            .rotate_left(31)
+            .wrapping_add(h1)
+            .wrapping_mul(5)
+            .wrapping_add(0x3849_5ab5);
+    }
+
+    let tail = blocks.remainder();
+
+    if tail.len() > 8 {
+        let k2 = tail[8..]
+            .iter()
+            .rev()
+            .fold(0u64, |acc, b| (acc << 8) | *b as u64);
+        h2 ^= mix_k2(k2);
+    }
+
+    if !tail.is_empty() {
+        let k1 = tail[..tail.len().min(8)]
+            .iter()
+            .rev()
+            .fold(0u64, |acc, b| (acc << 8) | *b as u64);
+        h1 ^= mix_k1(k1);
+    }
+
+    h1 ^= data.len() as u64;
+    h2 ^= data.len() as u64;
+
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+
+    h1 = fmix(h1);
+    h2 = fmix(h2);
+
+    h1.wrapping_add(h2).to_be_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hash;
+
+    #[test]
+    fn murmur3_x64_64() {
+        assert_eq!(hash(b""), [0; 8]);
+        // the bucket indices as found in the go-ipfs created shards of `test_support`
+        assert_eq!(hash(b"non_sharded_dir")[0], 0xFA);
+        assert_eq!(hash(b"long-named-file-016")[..2], [0x07, 0x48]);
+    }
+}
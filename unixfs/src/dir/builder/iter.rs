@@ -1,8 +1,11 @@
+use super::hamt::{self, RenderedShard};
 use super::{
     CustomFlatUnixFs, DirBuilder, Entry, Leaf, NamedLeaf, TreeConstructionFailed, TreeOptions,
 };
+use crate::pb::{UnixFs, UnixFsType};
+use crate::Metadata;
+use alloc::collections::VecDeque;
 use core::fmt;
-use libipld::multihash::{Code, Multihash};
 use libipld::Cid;
 use std::collections::HashMap;
 
@@ -20,6 +23,8 @@ pub struct PostOrderIterator {
     // in the event of mixed child nodes (leaves and nodes).
     persisted_cids: HashMap<u64, Vec<Option<NamedLeaf>>>,
     reused_children: Vec<Visited>,
+    // rendered HAMT shards of the latest directory, which are returned before continuing the visit
+    shards: VecDeque<RenderedShard>,
     cid: Option<Cid>,
     total_size: u64,
     // from TreeOptions
@@ -56,9 +61,11 @@ enum Visited {
         /// Leaves will be stored directly in this field when there are no DirBuilder descendants,
         /// in the `PostOrderIterator::persisted_cids` otherwise.
         leaves: LeafStorage,
+        metadata: Metadata,
     },
     PostRoot {
         leaves: LeafStorage,
+        metadata: Metadata,
     },
 }
 
@@ -72,98 +79,69 @@ impl PostOrderIterator {
             pending: vec![root],
             persisted_cids: Default::default(),
             reused_children: Vec::new(),
+            shards: Default::default(),
             cid: None,
             total_size: 0,
             opts,
         }
     }
 
+    /// Renders the directory either as a single block into `block_buffer` or as HAMT shards into
+    /// `shards`, depending on the configured sharding threshold.
     fn render_directory(
+        &mut self,
         links: &[Option<NamedLeaf>],
-        buffer: &mut Vec<u8>,
-        block_size_limit: &Option<u64>,
+        metadata: &Metadata,
     ) -> Result<Leaf, TreeConstructionFailed> {
-        use crate::pb::{UnixFs, UnixFsType};
-        use quick_protobuf::{BytesWriter, MessageWrite, Writer};
-        use sha2::{Digest, Sha256};
-
-        // FIXME: ideas on how to turn this into a HAMT sharding on some heuristic. we probably
-        // need to introduce states in to the "iterator":
-        //
-        // 1. bucketization
-        // 2. another post order visit of the buckets?
-        //
-        // the nested post order visit should probably re-use the existing infra ("message
-        // passing") and new ids can be generated by giving this iterator the counter from
-        // BufferedTreeBuilder.
-        //
-        // could also be that the HAMT shard building should start earlier, since the same
-        // heuristic can be detected *at* bufferedtreewriter. there the split would be easier, and
-        // this would "just" be a single node rendering, and not need any additional states..
-
-        let node = CustomFlatUnixFs {
-            links,
-            data: UnixFs {
-                Type: UnixFsType::Directory,
-                ..Default::default()
-            },
-        };
-
-        let size = node.get_size();
-
-        if let Some(limit) = block_size_limit {
-            let size = size as u64;
-            if *limit < size {
-                // FIXME: this could probably be detected at builder
-                return Err(TreeConstructionFailed::TooLargeBlock(size));
-            }
+        let shard = self
+            .opts
+            .sharding_threshold
+            .map(|threshold| hamt::estimated_size(links) >= threshold)
+            .unwrap_or(false);
+
+        if shard {
+            return hamt::render_shards(
+                links,
+                metadata,
+                &mut self.block_buffer,
+                &self.opts,
+                &mut self.shards,
+            );
         }
 
-        let cap = buffer.capacity();
-
-        if let Some(additional) = size.checked_sub(cap) {
-            buffer.reserve(additional);
-        }
-
-        if let Some(mut needed_zeroes) = size.checked_sub(buffer.len()) {
-            let zeroes = [0; 8];
+        let mut data = UnixFs {
+            Type: UnixFsType::Directory,
+            ..Default::default()
+        };
+        metadata.apply(&mut data);
 
-            while needed_zeroes > 8 {
-                buffer.extend_from_slice(&zeroes[..]);
-                needed_zeroes -= zeroes.len();
-            }
+        render_node(links, data, &mut self.block_buffer, &self.opts)
+    }
 
-            buffer.extend(core::iter::repeat(0).take(needed_zeroes));
+    /// Returns the latest rendered node, which is the next pending HAMT shard if there are any.
+    fn current(&mut self) -> TreeNode<'_> {
+        if let Some(RenderedShard { leaf, block }) = self.shards.pop_front() {
+            self.block_buffer = block;
+            self.cid = Some(leaf.link);
+            self.total_size = leaf.total_size;
         }
 
-        let mut writer = Writer::new(BytesWriter::new(&mut buffer[..]));
-        node.write_message(&mut writer)
-            .map_err(TreeConstructionFailed::Protobuf)?;
-
-        buffer.truncate(size);
-
-        let mh = Multihash::wrap(Code::Sha2_256.into(), &Sha256::digest(&buffer)).unwrap();
-        let cid = Cid::new_v0(mh).expect("sha2_256 is the correct multihash for cidv0");
-
-        let combined_from_links = links
-            .iter()
-            .map(|opt| {
-                opt.as_ref()
-                    .map(|NamedLeaf(_, _, total_size)| total_size)
-                    .unwrap()
-            })
-            .sum::<u64>();
-
-        Ok(Leaf {
-            link: cid,
-            total_size: buffer.len() as u64 + combined_from_links,
-        })
+        TreeNode {
+            path: self.full_path.as_str(),
+            cid: self.cid.as_ref().unwrap(),
+            total_size: self.total_size,
+            block: &self.block_buffer,
+        }
     }
 
     /// Construct the next dag-pb node, if any.
     ///
     /// Returns a `TreeNode` of the latest constructed tree node.
     pub fn next_borrowed(&mut self) -> Option<Result<TreeNode<'_>, TreeConstructionFailed>> {
+        if !self.shards.is_empty() {
+            return Some(Ok(self.current()));
+        }
+
         while let Some(visited) = self.pending.pop() {
             let (name, depth) = match &visited {
                 Visited::DescentRoot(_) => (None, 0),
@@ -187,7 +165,10 @@ impl PostOrderIterator {
                         leaves.into()
                     };
 
-                    self.pending.push(Visited::PostRoot { leaves });
+                    self.pending.push(Visited::PostRoot {
+                        leaves,
+                        metadata: node.metadata,
+                    });
                     self.pending.append(children);
                 }
                 Visited::Descent {
@@ -214,6 +195,7 @@ impl PostOrderIterator {
                         depth,
                         leaves,
                         index,
+                        metadata: node.metadata,
                     });
 
                     self.pending.append(children);
@@ -223,16 +205,11 @@ impl PostOrderIterator {
                     name,
                     leaves,
                     index,
+                    metadata,
                     ..
                 } => {
                     let leaves = leaves.into_inner(&mut self.persisted_cids);
-                    let buffer = &mut self.block_buffer;
-
-                    let leaf = match Self::render_directory(
-                        &leaves,
-                        buffer,
-                        &self.opts.block_size_limit,
-                    ) {
+                    let leaf = match self.render_directory(&leaves, &metadata) {
                         Ok(leaf) => leaf,
                         Err(e) => return Some(Err(e)),
                     };
@@ -258,27 +235,16 @@ impl PostOrderIterator {
                         }
                     }
 
-                    return Some(Ok(TreeNode {
-                        path: self.full_path.as_str(),
-                        cid: self.cid.as_ref().unwrap(),
-                        total_size: self.total_size,
-                        block: &self.block_buffer,
-                    }));
+                    return Some(Ok(self.current()));
                 }
-                Visited::PostRoot { leaves } => {
+                Visited::PostRoot { leaves, metadata } => {
                     let leaves = leaves.into_inner(&mut self.persisted_cids);
 
                     if !self.opts.wrap_with_directory {
                         break;
                     }
 
-                    let buffer = &mut self.block_buffer;
-
-                    let leaf = match Self::render_directory(
-                        &leaves,
-                        buffer,
-                        &self.opts.block_size_limit,
-                    ) {
+                    let leaf = match self.render_directory(&leaves, &metadata) {
                         Ok(leaf) => leaf,
                         Err(e) => return Some(Err(e)),
                     };
@@ -286,12 +252,7 @@ impl PostOrderIterator {
                     self.cid = Some(leaf.link);
                     self.total_size = leaf.total_size;
 
-                    return Some(Ok(TreeNode {
-                        path: self.full_path.as_str(),
-                        cid: self.cid.as_ref().unwrap(),
-                        total_size: self.total_size,
-                        block: &self.block_buffer,
-                    }));
+                    return Some(Ok(self.current()));
                 }
             }
         }
@@ -355,6 +316,68 @@ pub struct OwnedTreeNode {
     pub block: Box<[u8]>,
 }
 
+/// Renders a dag-pb node with the given links and unixfs data into the buffer, returning the
+/// link to the rendered block.
+pub(super) fn render_node(
+    links: &[Option<NamedLeaf>],
+    data: UnixFs<'_>,
+    buffer: &mut Vec<u8>,
+    opts: &TreeOptions,
+) -> Result<Leaf, TreeConstructionFailed> {
+    use quick_protobuf::{BytesWriter, MessageWrite, Writer};
+
+    let node = CustomFlatUnixFs { links, data };
+
+    let size = node.get_size();
+
+    if let Some(limit) = &opts.block_size_limit {
+        let size = size as u64;
+        if *limit < size {
+            // FIXME: this could probably be detected at builder
+            return Err(TreeConstructionFailed::TooLargeBlock(size));
+        }
+    }
+
+    let cap = buffer.capacity();
+
+    if let Some(additional) = size.checked_sub(cap) {
+        buffer.reserve(additional);
+    }
+
+    if let Some(mut needed_zeroes) = size.checked_sub(buffer.len()) {
+        let zeroes = [0; 8];
+
+        while needed_zeroes > 8 {
+            buffer.extend_from_slice(&zeroes[..]);
+            needed_zeroes -= zeroes.len();
+        }
+
+        buffer.extend(core::iter::repeat(0).take(needed_zeroes));
+    }
+
+    let mut writer = Writer::new(BytesWriter::new(&mut buffer[..]));
+    node.write_message(&mut writer)
+        .map_err(TreeConstructionFailed::Protobuf)?;
+
+    buffer.truncate(size);
+
+    let cid = opts.cid_options.create(crate::DAG_PB, buffer);
+
+    let combined_from_links = links
+        .iter()
+        .map(|opt| {
+            opt.as_ref()
+                .map(|NamedLeaf(_, _, total_size)| total_size)
+                .unwrap()
+        })
+        .sum::<u64>();
+
+    Ok(Leaf {
+        link: cid,
+        total_size: buffer.len() as u64 + combined_from_links,
+    })
+}
+
 fn update_full_path(
     (full_path, old_depth): (&mut String, &mut usize),
     name: Option<&str>,
@@ -16,6 +16,9 @@ pub mod visit;
 /// File adder capable of constructing UnixFs v1 trees
 pub mod adder;
 
+/// Modifying the content of existing files
+pub mod modify;
+
 /// Describes the errors which can happen during a visit or lower level block-by-block walking of
 /// the DAG.
 #[derive(Debug)]
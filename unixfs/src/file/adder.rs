@@ -1,25 +1,31 @@
-use libipld::multihash::{self, Multihash};
 use libipld::Cid;
 
+use super::modify::FileLink;
 use crate::pb::{FlatUnixFs, PBLink, UnixFs, UnixFsType};
+use crate::{CidOptions, Metadata};
 use alloc::borrow::Cow;
 use core::fmt;
 use quick_protobuf::{MessageWrite, Writer};
 
-use sha2::{Digest, Sha256};
+mod rabin;
+
+mod trickle;
+pub use trickle::TrickleCollector;
 
 /// File tree builder. Implements [`core::default::Default`] which tracks the recent defaults.
 ///
 /// Custom file tree builder can be created with [`FileAdder::builder()`] and configuring the
-/// chunker and collector.
+/// chunker, collector, Cid options and the use of raw leaves.
 ///
-/// Current implementation maintains an internal buffer for the block creation and uses a
-/// non-customizable hash function to produce Cid version 0 links. Currently does not support
-/// inline links.
+/// Current implementation maintains an internal buffer for the block creation and by default
+/// produces Cid version 0 links with sha2-256 multihash.
 #[derive(Default)]
 pub struct FileAdder {
     chunker: Chunker,
     collector: Collector,
+    cid_options: CidOptions,
+    raw_leaves: bool,
+    metadata: Metadata,
     block_buffer: Vec<u8>,
     // all unflushed links as a flat vec; this is compacted as we grow and need to create a link
     // block for the last N blocks, as decided by the collector.
@@ -86,6 +92,7 @@ impl fmt::Display for LinkFormatter<'_> {
 
 /// Represents an intermediate structure which will be serialized into link blocks as both PBLink
 /// and UnixFs::blocksize. Also holds `depth`, which helps with compaction of the link blocks.
+#[derive(Clone)]
 struct Link {
     /// Depth of this link. Zero is leaf, and anything above it is, at least for
     /// [`BalancedCollector`], the compacted link blocks.
@@ -115,6 +122,9 @@ impl fmt::Debug for Link {
 pub struct FileAdderBuilder {
     chunker: Chunker,
     collector: Collector,
+    cid_options: CidOptions,
+    raw_leaves: bool,
+    metadata: Metadata,
 }
 
 impl FileAdderBuilder {
@@ -131,18 +141,73 @@ impl FileAdderBuilder {
         }
     }
 
+    /// Configures the builder to use the given options for creating the Cids of the blocks.
+    pub fn with_cid_options(self, cid_options: CidOptions) -> Self {
+        FileAdderBuilder {
+            cid_options,
+            ..self
+        }
+    }
+
+    /// Configures the builder to create the leaves as raw blocks containing only the file
+    /// content instead of UnixFs File messages. Raw blocks always use Cid version 1.
+    pub fn with_raw_leaves(self, raw_leaves: bool) -> Self {
+        FileAdderBuilder { raw_leaves, ..self }
+    }
+
+    /// Configures the builder to write the given mode and modification time into the root block
+    /// of the file. As raw blocks cannot hold the metadata, a file consisting of a single raw leaf
+    /// will have an UnixFs File block as the root instead.
+    pub fn with_metadata(self, metadata: Metadata) -> Self {
+        FileAdderBuilder { metadata, ..self }
+    }
+
     /// Returns a new FileAdder
     pub fn build(self) -> FileAdder {
-        let FileAdderBuilder { chunker, collector } = self;
+        let FileAdderBuilder {
+            chunker,
+            collector,
+            cid_options,
+            raw_leaves,
+            metadata,
+        } = self;
 
         FileAdder {
             chunker,
             collector,
+            cid_options,
+            raw_leaves,
+            metadata,
             ..Default::default()
         }
     }
 }
 
+impl FileAdderBuilder {
+    /// Returns a new FileAdder continuing a file of the balanced layout after the given complete
+    /// subtrees, each given with its depth, zero being a leaf.
+    ///
+    /// The subtrees are the links to the left of the right spine of an existing tree, from the
+    /// root down, and pushing the content of the last leaf followed by the appended content will
+    /// produce the same tree as adding the whole content with the same options would.
+    pub fn build_with_subtrees(
+        self,
+        subtrees: impl IntoIterator<Item = (usize, FileLink)>,
+    ) -> FileAdder {
+        let mut adder = self.build();
+        adder.unflushed_links = subtrees
+            .into_iter()
+            .map(|(depth, link)| Link {
+                depth,
+                target: link.cid,
+                total_size: link.total_size,
+                file_size: link.file_size,
+            })
+            .collect();
+        adder
+    }
+}
+
 impl FileAdder {
     /// Returns a [`FileAdderBuilder`] for creating a non-default FileAdder.
     pub fn builder() -> FileAdderBuilder {
@@ -172,7 +237,14 @@ impl FileAdder {
             // blocks and user takes care of chunking (and buffering)?
             //
             // cat file | my_awesome_chunker | my_brilliant_collector
-            let leaf = Self::flush_buffered_leaf(accepted, &mut self.unflushed_links, false);
+            let leaf = Self::flush_buffered_leaf(
+                accepted,
+                &mut self.unflushed_links,
+                false,
+                self.collector.leaf_type(),
+                self.raw_leaves,
+                &self.cid_options,
+            );
             assert!(leaf.is_some(), "chunk completed, must produce a new block");
             self.block_buffer.clear();
             let links = self.flush_buffered_links(false);
@@ -198,6 +270,9 @@ impl FileAdder {
                     self.block_buffer.as_slice(),
                     &mut self.unflushed_links,
                     false,
+                    self.collector.leaf_type(),
+                    self.raw_leaves,
+                    &self.cid_options,
                 );
                 assert!(leaf.is_some(), "chunk completed, must produce a new block");
                 self.block_buffer.clear();
@@ -216,11 +291,46 @@ impl FileAdder {
     /// Note: the API will hopefully evolve in a direction which will not allocate a new Vec for
     /// every block in the near-ish future.
     pub fn finish(mut self) -> impl Iterator<Item = (Cid, Vec<u8>)> {
-        let last_leaf =
-            Self::flush_buffered_leaf(&self.block_buffer, &mut self.unflushed_links, true);
+        // only create the empty leaf for an empty file if the collector represents it so
+        let last_leaf = Self::flush_buffered_leaf(
+            &self.block_buffer,
+            &mut self.unflushed_links,
+            self.collector.has_empty_leaf(),
+            self.collector.leaf_type(),
+            self.raw_leaves,
+            &self.cid_options,
+        );
         let root_links = self.flush_buffered_links(true);
         // should probably error if there is neither?
-        last_leaf.into_iter().chain(root_links)
+        let mut blocks = last_leaf.into_iter().chain(root_links).collect::<Vec<_>>();
+
+        if !self.metadata.is_empty() {
+            if let Some(root) = blocks.last_mut() {
+                *root = self.root_with_metadata(root);
+            }
+        }
+
+        blocks.into_iter()
+    }
+
+    /// Renders the root block again with the metadata included.
+    fn root_with_metadata(&self, (cid, block): &(Cid, Vec<u8>)) -> (Cid, Vec<u8>) {
+        let mut root = if cid.codec() == crate::RAW {
+            FlatUnixFs {
+                links: Vec::new(),
+                data: UnixFs {
+                    Type: UnixFsType::File,
+                    Data: (!block.is_empty()).then_some(Cow::Borrowed(block.as_slice())),
+                    filesize: Some(block.len() as u64),
+                    ..Default::default()
+                },
+            }
+        } else {
+            FlatUnixFs::try_parse(block).expect("the root block was just rendered")
+        };
+
+        self.metadata.apply(&mut root.data);
+        render_and_hash(&root, &self.cid_options)
     }
 
     /// Returns `None` when the input is empty but there are links, otherwise a new Cid and a
@@ -229,11 +339,28 @@ impl FileAdder {
         input: &[u8],
         unflushed_links: &mut Vec<Link>,
         finishing: bool,
+        leaf_type: UnixFsType,
+        raw_leaves: bool,
+        cid_options: &CidOptions,
     ) -> Option<(Cid, Vec<u8>)> {
         if input.is_empty() && (!finishing || !unflushed_links.is_empty()) {
             return None;
         }
 
+        if raw_leaves {
+            // the raw leaf is the content as is, also for the empty file
+            let cid = cid_options.create(crate::RAW, input);
+
+            unflushed_links.push(Link {
+                depth: 0,
+                target: cid,
+                total_size: input.len() as u64,
+                file_size: input.len() as u64,
+            });
+
+            return Some((cid, input.to_vec()));
+        }
+
         // for empty unixfs file the bytes is missing but filesize is present.
 
         let data = if !input.is_empty() {
@@ -247,7 +374,7 @@ impl FileAdder {
         let inner = FlatUnixFs {
             links: Vec::new(),
             data: UnixFs {
-                Type: UnixFsType::File,
+                Type: leaf_type,
                 Data: data,
                 filesize,
                 // no blocksizes as there are no links
@@ -255,7 +382,7 @@ impl FileAdder {
             },
         };
 
-        let (cid, vec) = render_and_hash(&inner);
+        let (cid, vec) = render_and_hash(&inner, cid_options);
 
         let total_size = vec.len();
 
@@ -273,7 +400,7 @@ impl FileAdder {
 
     fn flush_buffered_links(&mut self, finishing: bool) -> Vec<(Cid, Vec<u8>)> {
         self.collector
-            .flush_links(&mut self.unflushed_links, finishing)
+            .flush_links(&mut self.unflushed_links, finishing, &self.cid_options)
     }
 
     /// Test helper for collecting all of the produced blocks; probably not a good idea outside
@@ -305,7 +432,7 @@ impl FileAdder {
     }
 }
 
-fn render_and_hash(flat: &FlatUnixFs<'_>) -> (Cid, Vec<u8>) {
+fn render_and_hash(flat: &FlatUnixFs<'_>, cid_options: &CidOptions) -> (Cid, Vec<u8>) {
     // TODO: as shown in later dagger we don't really need to render the FlatUnixFs fully; we could
     // either just render a fixed header and continue with the body OR links, though the links are
     // a bit more complicated.
@@ -313,16 +440,30 @@ fn render_and_hash(flat: &FlatUnixFs<'_>) -> (Cid, Vec<u8>) {
     let mut writer = Writer::new(&mut out);
     flat.write_message(&mut writer)
         .expect("unsure how this could fail");
-    let mh = Multihash::wrap(multihash::Code::Sha2_256.into(), &Sha256::digest(&out)).unwrap();
-    let cid = Cid::new_v0(mh).expect("sha2_256 is the correct multihash for cidv0");
+    let cid = cid_options.create(crate::DAG_PB, &out);
     (cid, out)
 }
 
 /// Chunker strategy
+///
+/// The go-ipfs `buzhash` chunker is not supported, as its output depends on the exact byte hash
+/// table of go-ipfs-chunker.
 #[derive(Debug, Clone, Copy)]
 pub enum Chunker {
     /// Size based chunking
     Size(usize),
+    /// Content defined chunking using Rabin fingerprints, producing the same chunks as the go-ipfs
+    /// `rabin-{min}-{avg}-{max}` chunker. Chunks are at least `min` bytes, except for the last
+    /// one, and at most `max` bytes, averaging around the largest power of two not greater than
+    /// `avg`. The `min` should be at least 16 bytes, which is the size of the rolling window.
+    Rabin {
+        /// Minimum size of a chunk
+        min: usize,
+        /// Targeted average size of a chunk
+        avg: usize,
+        /// Maximum size of a chunk
+        max: usize,
+    },
 }
 
 impl Default for Chunker {
@@ -343,6 +484,10 @@ impl Chunker {
                 let ready = buffered.len() + l >= *max;
                 (accepted, ready)
             }
+            Rabin { min, avg, max } => {
+                let (l, ready) = rabin::accept(buffered, input, *min, *avg, *max);
+                (&input[..l], ready)
+            }
         }
     }
 
@@ -350,19 +495,21 @@ impl Chunker {
         use Chunker::*;
 
         match self {
-            Size(max) => *max,
+            Size(max) | Rabin { max, .. } => *max,
         }
     }
 }
 
 /// Collector or layout strategy. For more information, see the [Layout section of the spec].
-/// Currently only the default balanced collector/layout has been implemented.
+/// Both the default balanced and the trickle collector/layout have been implemented.
 ///
 /// [Layout section of the spec]: https://github.com/ipfs/specs/blob/master/UNIXFS.md#layout
 #[derive(Debug, Clone)]
 pub enum Collector {
     /// Balanced trees.
     Balanced(BalancedCollector),
+    /// Trickle trees.
+    Trickle(TrickleCollector),
 }
 
 impl Default for Collector {
@@ -372,13 +519,34 @@ impl Default for Collector {
 }
 
 impl Collector {
-    fn flush_links(&mut self, pending: &mut Vec<Link>, finishing: bool) -> Vec<(Cid, Vec<u8>)> {
+    fn flush_links(
+        &mut self,
+        pending: &mut Vec<Link>,
+        finishing: bool,
+        cid_options: &CidOptions,
+    ) -> Vec<(Cid, Vec<u8>)> {
         use Collector::*;
 
         match self {
-            Balanced(bc) => bc.flush_links(pending, finishing),
+            Balanced(bc) => bc.flush_links(pending, finishing, cid_options),
+            Trickle(tc) => tc.flush_links(pending, finishing, cid_options),
         }
     }
+
+    /// Returns the UnixFs type of the leaves, when not using raw leaves. go-ipfs uses `Raw` for
+    /// the leaves of trickle trees.
+    fn leaf_type(&self) -> UnixFsType {
+        match self {
+            Collector::Balanced(_) => UnixFsType::File,
+            Collector::Trickle(_) => UnixFsType::Raw,
+        }
+    }
+
+    /// Returns `true` if an empty file is represented by an empty leaf, instead of a link block
+    /// without any links.
+    fn has_empty_leaf(&self) -> bool {
+        matches!(self, Collector::Balanced(_))
+    }
 }
 
 /// BalancedCollector creates balanced UnixFs trees, most optimized for random access to different
@@ -436,7 +604,12 @@ impl BalancedCollector {
     /// In-place compression of the `pending` links to a balanced hierarchy. When `finishing`, the
     /// links will be compressed iteratively from the lowest level to produce a single root link
     /// block.
-    fn flush_links(&mut self, pending: &mut Vec<Link>, finishing: bool) -> Vec<(Cid, Vec<u8>)> {
+    fn flush_links(
+        &mut self,
+        pending: &mut Vec<Link>,
+        finishing: bool,
+        cid_options: &CidOptions,
+    ) -> Vec<(Cid, Vec<u8>)> {
         /*
 
         file    |- - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -|
@@ -555,7 +728,7 @@ impl BalancedCollector {
                         index + first_at
                     );
 
-                    Self::partition_link(
+                    partition_link(
                         link,
                         &mut reused_links,
                         &mut reused_blocksizes,
@@ -576,7 +749,7 @@ impl BalancedCollector {
                     },
                 };
 
-                let (cid, vec) = render_and_hash(&inner);
+                let (cid, vec) = render_and_hash(&inner, cid_options);
 
                 // start overwriting at the first index of this level, then continue forward on
                 // next iterations.
@@ -617,35 +790,41 @@ impl BalancedCollector {
 
         ret
     }
+}
 
-    /// Each link needs to be partitioned into the four mut arguments received by this function in
-    /// order to produce the expected UnixFs output.
-    fn partition_link(
-        link: &Link,
-        links: &mut Vec<PBLink<'static>>,
-        blocksizes: &mut Vec<u64>,
-        nested_size: &mut u64,
-        nested_total_size: &mut u64,
-    ) {
-        links.push(PBLink {
-            Hash: Some(link.target.to_bytes().into()),
-            Name: Some("".into()),
-            Tsize: Some(link.total_size),
-        });
-        blocksizes.push(link.file_size);
-        *nested_size += link.file_size;
-        *nested_total_size += link.total_size;
-    }
+/// Each link needs to be partitioned into the four mut arguments received by this function in
+/// order to produce the expected UnixFs output.
+fn partition_link(
+    link: &Link,
+    links: &mut Vec<PBLink<'static>>,
+    blocksizes: &mut Vec<u64>,
+    nested_size: &mut u64,
+    nested_total_size: &mut u64,
+) {
+    links.push(PBLink {
+        Hash: Some(link.target.to_bytes().into()),
+        Name: Some("".into()),
+        Tsize: Some(link.total_size),
+    });
+    blocksizes.push(link.file_size);
+    *nested_size += link.file_size;
+    *nested_total_size += link.total_size;
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::{BalancedCollector, Chunker, FileAdder};
+    use crate::pb::FlatUnixFs;
     use crate::test_support::FakeBlockstore;
+    use crate::walk::{ContinuedWalk, Walker};
+    use crate::{CidOptions, Metadata};
     use core::convert::TryFrom;
     use hex_literal::hex;
+    use libipld::cid::Version;
+    use libipld::multihash::{Code, MultihashDigest};
     use libipld::Cid;
+    use std::collections::HashMap;
 
     #[test]
     fn test_size_chunker() {
@@ -866,4 +1045,197 @@ mod tests {
 
         assert_eq!(blocks_count, 175);
     }
+
+    #[test]
+    fn rabin_chunker_shares_blocks_after_insertion() {
+        let chunker = Chunker::Rabin {
+            min: 1024,
+            avg: 4096,
+            max: 16384,
+        };
+
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let original = (0..256 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect::<Vec<_>>();
+
+        let mut modified = original.clone();
+        modified.splice(1000..1000, b"inserted near the start".iter().copied());
+
+        let leaves = |content: &[u8]| {
+            FileAdder::builder()
+                .with_chunker(chunker)
+                .build()
+                .collect_blocks(content, 0)
+                .into_iter()
+                .map(|(cid, _)| cid)
+                .collect::<std::collections::HashSet<_>>()
+        };
+
+        let original = leaves(&original);
+        let modified = leaves(&modified);
+
+        // the cut points are found again right after the insertion, so only the first leaves and
+        // the link blocks differ
+        let shared = original.intersection(&modified).count();
+        assert!(
+            shared + 4 >= original.len(),
+            "{shared} of {}",
+            original.len()
+        );
+    }
+
+    #[test]
+    fn raw_leaves_empty_file() {
+        let blocks = FileAdder::builder()
+            .with_raw_leaves(true)
+            .build()
+            .collect_blocks(b"", 0);
+
+        // same as `ipfs add --raw-leaves` with go-ipfs
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(
+            blocks[0].0.to_string(),
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+        );
+        assert!(blocks[0].1.is_empty());
+    }
+
+    #[test]
+    fn raw_leaves_multi_block_file() {
+        let content = b"foobar\n";
+        let blocks = FileAdder::builder()
+            .with_chunker(Chunker::Size(2))
+            .with_raw_leaves(true)
+            .build()
+            .collect_blocks(content, 0);
+
+        let (root, leaves) = blocks.split_last().unwrap();
+
+        assert_eq!(
+            leaves
+                .iter()
+                .map(|(_, block)| block.as_slice())
+                .collect::<Vec<_>>(),
+            [&b"fo"[..], b"ob", b"ar", b"\n"]
+        );
+        assert!(leaves.iter().all(|(cid, _)| cid.codec() == crate::RAW));
+
+        // the raw leaves are linked from a cidv0 root by default
+        assert_eq!(root.0.version(), Version::V0);
+        assert_eq!(read_file(&root.0, &blocks), content);
+    }
+
+    #[test]
+    fn cid_version_1_single_block_file() {
+        let options = CidOptions::default().with_version(Version::V1);
+        let blocks = FileAdder::builder()
+            .with_cid_options(options)
+            .build()
+            .collect_blocks(b"foobar\n", 0);
+
+        let v0 = Cid::try_from("QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL").unwrap();
+
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].0, Cid::new_v1(crate::DAG_PB, *v0.hash()));
+    }
+
+    #[test]
+    fn blake3_multi_block_file() {
+        let content = b"foobar\n";
+        let options = CidOptions::default().with_hash(Code::Blake3_256);
+        let blocks = FileAdder::builder()
+            .with_chunker(Chunker::Size(2))
+            .with_cid_options(options)
+            .build()
+            .collect_blocks(content, 0);
+
+        for (cid, block) in &blocks {
+            // cidv0 can only be used with sha2-256
+            assert_eq!(cid.version(), Version::V1);
+            assert_eq!(cid.hash(), &Code::Blake3_256.digest(block));
+        }
+
+        assert_eq!(read_file(&blocks.last().unwrap().0, &blocks), content);
+    }
+
+    #[test]
+    fn inlined_raw_leaves() {
+        let content = b"foobar\n";
+        let options = CidOptions::default().with_inline_limit(Some(4));
+        let blocks = FileAdder::builder()
+            .with_chunker(Chunker::Size(2))
+            .with_cid_options(options)
+            .with_raw_leaves(true)
+            .build()
+            .collect_blocks(content, 0);
+
+        let (root, leaves) = blocks.split_last().unwrap();
+
+        for (cid, block) in leaves {
+            assert_eq!(cid.hash().code(), crate::IDENTITY);
+            assert_eq!(cid.hash().digest(), block.as_slice());
+        }
+
+        // the root is too large to be inlined
+        assert_eq!(root.0.version(), Version::V0);
+        assert_eq!(read_file(&root.0, &blocks), content);
+    }
+
+    fn read_file(root: &Cid, blocks: &[(Cid, Vec<u8>)]) -> Vec<u8> {
+        let blocks = blocks.iter().cloned().collect::<HashMap<_, _>>();
+        let mut walker = Walker::new(*root, String::new());
+        let mut content = Vec::new();
+
+        while walker.should_continue() {
+            let (next, _) = walker.pending_links();
+            let block = &blocks[next];
+
+            match walker.next(block, &mut None).unwrap() {
+                ContinuedWalk::File(segment, ..) => content.extend_from_slice(segment.as_ref()),
+                x => unreachable!("{:?}", x),
+            }
+        }
+
+        content
+    }
+
+    #[test]
+    fn metadata_in_root_block() {
+        let metadata = Metadata::default()
+            .with_mode(0o100755)
+            .with_mtime(1_600_000_000, 42);
+
+        for (raw_leaves, chunk) in [(false, 256 * 1024), (true, 256 * 1024), (true, 2)] {
+            let blocks = FileAdder::builder()
+                .with_chunker(Chunker::Size(chunk))
+                .with_raw_leaves(raw_leaves)
+                .with_metadata(metadata.clone())
+                .build()
+                .collect_blocks(b"foobar\n", 0);
+
+            let (cid, block) = blocks.last().unwrap();
+            assert_eq!(cid.codec(), crate::DAG_PB);
+
+            let root = FlatUnixFs::try_parse(block).unwrap();
+            let read = Metadata::from(&root.data);
+
+            // the file type bits are left out
+            assert_eq!(read.mode(), Some(0o755));
+            assert_eq!(read.mtime(), Some((1_600_000_000, 42)));
+            assert_eq!(root.data.filesize, Some(7));
+
+            // only the root carries the metadata
+            for (_, block) in &blocks[..blocks.len() - 1] {
+                if let Ok(leaf) = FlatUnixFs::try_parse(block) {
+                    assert!(Metadata::from(&leaf.data).is_empty());
+                }
+            }
+        }
+    }
 }
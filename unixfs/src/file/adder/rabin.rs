@@ -0,0 +1,235 @@
+//! Rabin fingerprint based content defined chunking, producing the same chunks as go-ipfs
+//! `rabin-{min}-{avg}-{max}` chunker.
+//!
+//! The fingerprint is calculated over a sliding window of 16 bytes, which means the fingerprint
+//! at any position depends only on the window. This allows finding the next cut point without
+//! keeping any state between the calls, as the window can always be recovered from the already
+//! buffered bytes.
+
+/// The irreducible polynomial used by go-ipfs.
+const POLYNOMIAL: u64 = 0x003d_f305_dfb2_a805;
+
+/// Size of the sliding window in bytes.
+const WINDOW_SIZE: usize = 16;
+
+/// Degree of the polynomial less the eight bits, used to find the `MOD_TABLE` index.
+const POLYNOMIAL_SHIFT: u32 = degree(POLYNOMIAL) - 8;
+
+/// `OUT_TABLE[b]` is the fingerprint of `b` followed by `WINDOW_SIZE - 1` zero bytes, and is used
+/// to slide the byte `b` out of the window.
+const OUT_TABLE: [u64; 256] = out_table();
+
+/// `MOD_TABLE[b]` cancels out the eight bits above the degree of the polynomial, while reducing
+/// the fingerprint modulo the polynomial.
+const MOD_TABLE: [u64; 256] = mod_table();
+
+/// Returns the amount of `input` which belongs to the chunk started in `buffered` and whether the
+/// chunk was completed.
+pub(super) fn accept(
+    buffered: &[u8],
+    input: &[u8],
+    min: usize,
+    avg: usize,
+    max: usize,
+) -> (usize, bool) {
+    let byte = |at: usize| match at.checked_sub(buffered.len()) {
+        Some(at) => input[at],
+        None => buffered[at],
+    };
+
+    let total = (buffered.len() + input.len()).min(max);
+
+    // the chunk lengths up to buffered.len() have already been checked for a cut
+    let first = min.max(WINDOW_SIZE).max(buffered.len() + 1);
+
+    if first > total {
+        let accepted = total - buffered.len();
+        return (accepted, total == max);
+    }
+
+    // go-ipfs uses the floor of log2 of the average to find the mask bits
+    let mask = (1u64 << avg.max(1).ilog2()) - 1;
+
+    // the initial state is as if a single byte of one had been appended, which is slid out of the
+    // window with the last byte of the preheat
+    let mut state = 1;
+
+    for (n, at) in (first - WINDOW_SIZE..first).enumerate() {
+        if n == WINDOW_SIZE - 1 {
+            state ^= OUT_TABLE[1];
+        }
+        state = append(state, byte(at));
+    }
+
+    let mut len = first;
+
+    loop {
+        if state & mask == 0 || len >= max {
+            return (len - buffered.len(), true);
+        }
+
+        if len == total {
+            return (len - buffered.len(), false);
+        }
+
+        state ^= OUT_TABLE[byte(len - WINDOW_SIZE) as usize];
+        state = append(state, byte(len));
+        len += 1;
+    }
+}
+
+fn append(state: u64, b: u8) -> u64 {
+    let index = (state >> POLYNOMIAL_SHIFT) as usize;
+    ((state << 8) | b as u64) ^ MOD_TABLE[index]
+}
+
+const fn degree(x: u64) -> u32 {
+    63 - x.leading_zeros()
+}
+
+/// Remainder of the polynomial division of `x` by `d` over GF(2).
+const fn modulo(mut x: u64, d: u64) -> u64 {
+    while x != 0 && degree(x) >= degree(d) {
+        x ^= d << (degree(x) - degree(d));
+    }
+    x
+}
+
+const fn out_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut b = 0;
+    while b < 256 {
+        let mut hash = modulo(b as u64, POLYNOMIAL);
+        let mut i = 0;
+        while i < WINDOW_SIZE - 1 {
+            hash = modulo(hash << 8, POLYNOMIAL);
+            i += 1;
+        }
+        table[b] = hash;
+        b += 1;
+    }
+    table
+}
+
+const fn mod_table() -> [u64; 256] {
+    let k = degree(POLYNOMIAL);
+    let mut table = [0u64; 256];
+    let mut b = 0;
+    while b < 256 {
+        table[b] = modulo((b as u64) << k, POLYNOMIAL) | ((b as u64) << k);
+        b += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{accept, MOD_TABLE, OUT_TABLE};
+
+    #[test]
+    fn tables() {
+        assert_eq!(OUT_TABLE[..3], [0x0, 0x17fa63217c2ad7, 0x1207c39d4afdab]);
+        assert_eq!(MOD_TABLE[..3], [0x0, 0x3df305dfb2a805, 0x46150e60d7f80f]);
+    }
+
+    #[test]
+    fn cut_points_do_not_depend_on_buffering() {
+        let data = pseudorandom(512 * 1024);
+        let (min, avg, max) = (4 * 1024, 16 * 1024, 64 * 1024);
+
+        let whole = chunk_lengths(&data, data.len(), min, avg, max);
+        assert!(whole.len() > 1);
+        assert!(whole.iter().all(|len| *len <= max));
+        assert!(whole[..whole.len() - 1].iter().all(|len| *len >= min));
+
+        for step in [1, 7, 1000, 4096] {
+            assert_eq!(chunk_lengths(&data, step, min, avg, max), whole, "{step}");
+        }
+    }
+
+    #[test]
+    fn average_follows_the_mask() {
+        let data = pseudorandom(4 * 1024 * 1024);
+        let (min, max) = (1024, 64 * 1024);
+
+        // the mask has the bits of the largest power of two not greater than the average, and a
+        // cut is only looked for after the minimum, so chunks are expected to be min + 2^bits long
+        for (avg, mask) in [(4096, 4096), (6000, 4096), (8192, 8192)] {
+            let lengths = chunk_lengths(&data, data.len(), min, avg, max);
+            let mean = data.len() / lengths.len();
+            let expected = min + mask;
+            assert!(
+                mean > expected * 3 / 4 && mean < expected * 5 / 4,
+                "avg {avg}: mean {mean}, expected about {expected}"
+            );
+        }
+
+        // averages which round down to the same mask produce the same chunks
+        assert_eq!(
+            chunk_lengths(&data, data.len(), min, 4096, max),
+            chunk_lengths(&data, data.len(), min, 8191, max)
+        );
+    }
+
+    #[test]
+    fn cut_points_depend_only_on_the_window() {
+        let data = pseudorandom(256 * 1024);
+        let (min, avg, max) = (64, 1024, 64 * 1024);
+
+        let lengths = chunk_lengths(&data, data.len(), min, avg, max);
+        let cuts = lengths
+            .iter()
+            .scan(0, |end, len| {
+                *end += len;
+                Some(*end)
+            })
+            .collect::<Vec<_>>();
+
+        // changing a byte outside of the window preceding a cut point does not move the cut,
+        // given the minimum is reached again before it
+        let cut = cuts[cuts.len() / 2];
+        let previous = cuts[cuts.len() / 2 - 1];
+        assert!(cut - previous >= min + 16);
+
+        let mut changed = data.clone();
+        changed[previous] ^= 0xff;
+        let changed_lengths = chunk_lengths(&changed, changed.len(), min, avg, max);
+        assert_eq!(changed_lengths, lengths);
+    }
+
+    fn chunk_lengths(data: &[u8], step: usize, min: usize, avg: usize, max: usize) -> Vec<usize> {
+        let mut lengths = Vec::new();
+        let mut buffered = Vec::new();
+        let mut remaining = data;
+
+        while !remaining.is_empty() {
+            let input = &remaining[..step.min(remaining.len())];
+            let (accepted, ready) = accept(&buffered, input, min, avg, max);
+            buffered.extend_from_slice(&input[..accepted]);
+            remaining = &remaining[accepted..];
+
+            if ready {
+                lengths.push(buffered.len());
+                buffered.clear();
+            }
+        }
+
+        if !buffered.is_empty() {
+            lengths.push(buffered.len());
+        }
+
+        lengths
+    }
+
+    fn pseudorandom(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,379 @@
+//! Trickle layout, producing the same trees as the go-ipfs `--trickle` option.
+//!
+//! A trickle tree node first links up to `branching_factor` leaves, then `depth_repeat` subtrees
+//! of depth one, `depth_repeat` subtrees of depth two and so on, until the depth limit of the
+//! node is reached. The root node has no depth limit. This allows reading the beginning of a file
+//! while having fetched only a few blocks, and appending to the file by changing only the nodes
+//! along the rightmost path.
+
+use super::{partition_link, render_and_hash, Link};
+use crate::pb::{FlatUnixFs, UnixFs, UnixFsType};
+use crate::CidOptions;
+use core::fmt;
+use libipld::Cid;
+
+/// TrickleCollector creates trickle UnixFs trees, which are optimized for reading the file
+/// sequentially from the beginning and for appending.
+#[derive(Clone)]
+pub struct TrickleCollector {
+    branching_factor: usize,
+    depth_repeat: usize,
+    // the nodes under construction, starting from the root
+    stack: Vec<TrickleNode>,
+}
+
+impl fmt::Debug for TrickleCollector {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "TrickleCollector {{ branching_factor: {}, depth_repeat: {} }}",
+            self.branching_factor, self.depth_repeat
+        )
+    }
+}
+
+impl Default for TrickleCollector {
+    /// Returns a default collector which matches go-ipfs, with the same branching factor as the
+    /// [`super::BalancedCollector`] and each subtree depth repeated four times.
+    fn default() -> Self {
+        Self::with_branching_factor(174)
+    }
+}
+
+impl From<TrickleCollector> for super::Collector {
+    fn from(t: TrickleCollector) -> Self {
+        super::Collector::Trickle(t)
+    }
+}
+
+/// A trickle tree node under construction.
+#[derive(Clone)]
+struct TrickleNode {
+    /// `None` for the root, which has no depth limit.
+    max_depth: Option<usize>,
+    leaves: usize,
+    /// Depth of the subtrees currently being linked.
+    depth: usize,
+    /// Number of subtrees already linked at `depth`.
+    repeat: usize,
+    links: Vec<Link>,
+}
+
+impl TrickleNode {
+    fn new(max_depth: Option<usize>) -> Self {
+        TrickleNode {
+            max_depth,
+            leaves: 0,
+            depth: 1,
+            repeat: 0,
+            links: Vec::new(),
+        }
+    }
+
+    fn is_full(&self, branching_factor: usize) -> bool {
+        self.leaves == branching_factor && self.max_depth.is_some_and(|max| self.depth >= max)
+    }
+}
+
+impl TrickleCollector {
+    /// Configure Trickle collector with the given branching factor, which is the maximum number
+    /// of leaves linked from each node.
+    pub fn with_branching_factor(branching_factor: usize) -> Self {
+        assert!(branching_factor > 0);
+
+        Self {
+            branching_factor,
+            depth_repeat: 4,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Moves all of the `pending` leaves into the tree, returning the completed link blocks. When
+    /// `finishing`, all of the remaining nodes are completed and the root is left in `pending`.
+    pub(super) fn flush_links(
+        &mut self,
+        pending: &mut Vec<Link>,
+        finishing: bool,
+        cid_options: &CidOptions,
+    ) -> Vec<(Cid, Vec<u8>)> {
+        let mut ret = Vec::new();
+
+        if self.stack.is_empty() {
+            self.stack.push(TrickleNode::new(None));
+        }
+
+        for leaf in pending.drain(..) {
+            self.push_leaf(leaf, cid_options, &mut ret);
+        }
+
+        if finishing {
+            while let Some(node) = self.stack.pop() {
+                let link = render(&node.links, cid_options, &mut ret);
+
+                match self.stack.last_mut() {
+                    Some(parent) => parent.links.push(link),
+                    None => pending.push(link),
+                }
+            }
+        }
+
+        ret
+    }
+
+    fn push_leaf(&mut self, leaf: Link, cid_options: &CidOptions, ret: &mut Vec<(Cid, Vec<u8>)>) {
+        loop {
+            let top = self
+                .stack
+                .last_mut()
+                .expect("root is only popped when finishing");
+
+            if top.leaves < self.branching_factor {
+                top.leaves += 1;
+                top.links.push(leaf);
+                break;
+            }
+
+            // the leaf belongs to a new subtree of the current depth
+            let max_depth = Some(top.depth);
+            self.stack.push(TrickleNode::new(max_depth));
+        }
+
+        // complete the nodes which cannot link anything more, as go-ipfs does before reading
+        // the next chunk
+        while self
+            .stack
+            .last()
+            .is_some_and(|node| node.is_full(self.branching_factor))
+        {
+            let node = self.stack.pop().expect("checked above");
+            let link = render(&node.links, cid_options, ret);
+
+            let parent = self.stack.last_mut().expect("root is never full");
+            parent.links.push(link);
+            parent.repeat += 1;
+
+            if parent.repeat == self.depth_repeat {
+                parent.repeat = 0;
+                parent.depth += 1;
+            }
+        }
+    }
+}
+
+/// Renders a link block for the given links, returning the link to it.
+fn render(links: &[Link], cid_options: &CidOptions, ret: &mut Vec<(Cid, Vec<u8>)>) -> Link {
+    let mut pb_links = Vec::with_capacity(links.len());
+    let mut blocksizes = Vec::with_capacity(links.len());
+    let mut nested_size = 0;
+    let mut nested_total_size = 0;
+
+    for link in links {
+        partition_link(
+            link,
+            &mut pb_links,
+            &mut blocksizes,
+            &mut nested_size,
+            &mut nested_total_size,
+        );
+    }
+
+    let inner = FlatUnixFs {
+        links: pb_links,
+        data: UnixFs {
+            Type: UnixFsType::File,
+            filesize: Some(nested_size),
+            blocksizes,
+            ..Default::default()
+        },
+    };
+
+    let (cid, vec) = render_and_hash(&inner, cid_options);
+
+    let link = Link {
+        depth: 1 + links.iter().map(|link| link.depth).max().unwrap_or(0),
+        target: cid,
+        total_size: nested_total_size + vec.len() as u64,
+        file_size: nested_size,
+    };
+
+    ret.push((cid, vec));
+
+    link
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrickleCollector;
+    use crate::file::adder::{Chunker, FileAdder};
+    use crate::pb::FlatUnixFs;
+    use crate::test_support::FakeBlockstore;
+    use core::convert::TryFrom;
+    use libipld::Cid;
+    use std::collections::HashMap;
+
+    #[test]
+    fn foobar_trickle() {
+        let blocks = FakeBlockstore::with_fixtures();
+
+        let received = FileAdder::builder()
+            .with_chunker(Chunker::Size(2))
+            .with_collector(TrickleCollector::default())
+            .build()
+            .collect_blocks(b"foobar\n", 0);
+
+        // `ipfs add --trickle -s size-2` with go-ipfs 0.5
+        let (root, _) = received.last().unwrap();
+        assert_eq!(
+            root.to_string(),
+            "QmWfQ48ChJUj4vWKFsUDe4646xCBmXgdmNfhjz9T7crywd"
+        );
+
+        for (cid, block) in &received {
+            assert_eq!(blocks.get_by_cid(cid), block.as_slice());
+        }
+    }
+
+    #[test]
+    fn empty_file() {
+        let received = FileAdder::builder()
+            .with_collector(TrickleCollector::default())
+            .build()
+            .collect_blocks(b"", 0);
+
+        // same as the balanced empty file, but created as a link block without links
+        assert_eq!(received.len(), 1);
+        assert_eq!(
+            received[0].0.to_string(),
+            "QmbFMke1KXqnYyBBWxB74N4c5SBnJMVAiMNRcGu6x1AwQH"
+        );
+    }
+
+    #[test]
+    fn tree_structure() {
+        let branching_factor = 2;
+
+        for len in 1..=100 {
+            let content = (0..len).map(|i| i as u8).collect::<Vec<_>>();
+
+            let received = FileAdder::builder()
+                .with_chunker(Chunker::Size(1))
+                .with_collector(TrickleCollector::with_branching_factor(branching_factor))
+                .build()
+                .collect_blocks(&content, 0);
+
+            let (root, _) = received.last().unwrap();
+            let blocks = received.iter().cloned().collect::<HashMap<_, _>>();
+
+            let mut remaining = len;
+            let expected = expected_shape(&mut remaining, None, branching_factor);
+
+            assert_eq!(shape(root, &blocks), expected, "{len} leaves");
+        }
+    }
+
+    #[test]
+    fn default_branching_factor_over_several_levels() {
+        // the root links 174 leaves and four subtrees of 174 leaves each at depth one, followed
+        // by two subtrees of depth two and a third one which is left partially filled
+        let leaves = 174 * 5 + 174 * 5 * 2 + 100;
+        let content = (0..leaves).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+        let received = FileAdder::builder()
+            .with_chunker(Chunker::Size(1))
+            .with_collector(TrickleCollector::default())
+            .build()
+            .collect_blocks(&content, 0);
+
+        let (root, _) = received.last().unwrap();
+        let blocks = received.iter().cloned().collect::<HashMap<_, _>>();
+
+        let mut remaining = leaves;
+        let expected = expected_shape(&mut remaining, None, 174);
+        let Shape::Node(children) = &expected else {
+            unreachable!()
+        };
+        assert_eq!(children.len(), 174 + 4 + 3);
+        assert_eq!(shape(root, &blocks), expected);
+
+        let mut read = Vec::new();
+        assert_eq!(file_size(root, &blocks, &mut read), leaves as u64);
+        assert_eq!(read, content);
+    }
+
+    /// Returns the file size of the node after checking it against the sizes of the linked
+    /// nodes, and appends the content of the leaves.
+    fn file_size(cid: &Cid, blocks: &HashMap<Cid, Vec<u8>>, read: &mut Vec<u8>) -> u64 {
+        let flat = FlatUnixFs::try_from(blocks[cid].as_slice()).unwrap();
+
+        if let Some(data) = flat.data.Data.as_deref() {
+            read.extend_from_slice(data);
+        }
+
+        let sizes = flat
+            .links
+            .iter()
+            .map(|link| {
+                let cid = Cid::try_from(link.Hash.as_deref().unwrap()).unwrap();
+                file_size(&cid, blocks, read)
+            })
+            .collect::<Vec<_>>();
+
+        if !flat.links.is_empty() {
+            assert_eq!(flat.data.blocksizes, sizes);
+        }
+        let filesize = flat.data.filesize.unwrap();
+        assert_eq!(
+            filesize,
+            flat.data.Data.map_or(0, |data| data.len() as u64) + sizes.iter().sum::<u64>()
+        );
+        filesize
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Shape {
+        Leaf,
+        Node(Vec<Shape>),
+    }
+
+    /// Straightforward restatement of the recursive go-ipfs trickle layout.
+    fn expected_shape(remaining: &mut usize, max_depth: Option<usize>, bf: usize) -> Shape {
+        let mut children = Vec::new();
+
+        while children.len() < bf && *remaining > 0 {
+            children.push(Shape::Leaf);
+            *remaining -= 1;
+        }
+
+        let mut depth = 1;
+
+        while !matches!(max_depth, Some(max) if depth >= max) && *remaining > 0 {
+            for _ in 0..4 {
+                if *remaining == 0 {
+                    break;
+                }
+                children.push(expected_shape(remaining, Some(depth), bf));
+            }
+            depth += 1;
+        }
+
+        Shape::Node(children)
+    }
+
+    fn shape(cid: &Cid, blocks: &HashMap<Cid, Vec<u8>>) -> Shape {
+        let flat = FlatUnixFs::try_from(blocks[cid].as_slice()).unwrap();
+
+        if flat.links.is_empty() && flat.data.Data.is_some() {
+            return Shape::Leaf;
+        }
+
+        Shape::Node(
+            flat.links
+                .iter()
+                .map(|link| {
+                    let cid = Cid::try_from(link.Hash.as_deref().unwrap()).unwrap();
+                    shape(&cid, blocks)
+                })
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,293 @@
+//! Support for modifying existing files without adding the whole file again.
+//!
+//! A [`FileNode`] is a single parsed block of a file tree. Overwriting a range of a file only
+//! requires rendering the leaves covering the range and the link blocks above them again, and
+//! appending to a file created with the balanced layout only requires the blocks on the right
+//! spine of the tree, see [`FileAdderBuilder::build_with_subtrees`].
+//!
+//! [`FileAdderBuilder::build_with_subtrees`]: crate::file::adder::FileAdderBuilder::build_with_subtrees
+
+use super::{FileError, FileReadFailed};
+use crate::pb::{FlatUnixFs, PBLink, UnixFs, UnixFsType};
+use crate::{CidOptions, InvalidCidInLink, Metadata, UnexpectedNodeType};
+use alloc::borrow::Cow;
+use core::convert::TryFrom;
+use libipld::Cid;
+use quick_protobuf::{MessageWrite, Writer};
+
+/// A link from a file block to a subtree of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileLink {
+    /// The root of the linked subtree
+    pub cid: Cid,
+    /// Cumulative size of the blocks of the linked subtree, the dag-pb `Tsize`
+    pub total_size: u64,
+    /// Size of the file content in the linked subtree, the UnixFs `blocksize`
+    pub file_size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// Raw block of only the content
+    RawBlock,
+    /// dag-pb block with an UnixFs message of the given type, either File or Raw
+    UnixFs(UnixFsType),
+}
+
+/// A parsed block of a UnixFs file tree, which can be modified and rendered again.
+#[derive(Debug, Clone)]
+pub struct FileNode {
+    kind: Kind,
+    data: Vec<u8>,
+    links: Vec<FileLink>,
+    metadata: Metadata,
+}
+
+impl FileNode {
+    /// Parses a raw block or a dag-pb block of UnixFs type File or Raw.
+    pub fn parse(cid: &Cid, block: &[u8]) -> Result<Self, FileReadFailed> {
+        if cid.codec() == crate::RAW {
+            return Ok(FileNode {
+                kind: Kind::RawBlock,
+                data: block.to_vec(),
+                links: Vec::new(),
+                metadata: Metadata::default(),
+            });
+        }
+
+        let flat = FlatUnixFs::try_from(block)?;
+
+        if !matches!(flat.data.Type, UnixFsType::File | UnixFsType::Raw) {
+            return Err(FileReadFailed::UnexpectedType(UnexpectedNodeType::from(
+                flat.data.Type,
+            )));
+        }
+
+        if flat.links.len() != flat.data.blocksizes.len() {
+            return Err(FileReadFailed::File(FileError::LinksAndBlocksizesMismatch));
+        }
+
+        let links = flat
+            .links
+            .iter()
+            .zip(&flat.data.blocksizes)
+            .enumerate()
+            .map(|(nth, (link, &file_size))| {
+                let hash = link.Hash.as_deref().unwrap_or_default();
+                Cid::try_from(hash)
+                    .map(|cid| FileLink {
+                        cid,
+                        total_size: link.Tsize.unwrap_or_default(),
+                        file_size,
+                    })
+                    .map_err(|e| {
+                        FileReadFailed::InvalidCid(InvalidCidInLink::from((nth, link.clone(), e)))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(FileNode {
+            kind: Kind::UnixFs(flat.data.Type),
+            data: flat.data.Data.as_deref().unwrap_or_default().to_vec(),
+            links,
+            metadata: Metadata::from(&flat.data),
+        })
+    }
+
+    /// Returns true for a raw block, which only holds content.
+    pub fn is_raw_block(&self) -> bool {
+        self.kind == Kind::RawBlock
+    }
+
+    /// Returns true for a dag-pb block of UnixFs type File, as created for the leaves and link
+    /// blocks of the balanced layout.
+    pub fn is_file(&self) -> bool {
+        self.kind == Kind::UnixFs(UnixFsType::File)
+    }
+
+    /// The content held in the block itself, which precedes the content of the linked subtrees.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The links to the subtrees, in the order of the content.
+    pub fn links(&self) -> &[FileLink] {
+        &self.links
+    }
+
+    /// The mode and the modification time, only found in the root block of a file.
+    pub fn metadata(&self) -> Metadata {
+        self.metadata.clone()
+    }
+
+    /// Size of the content of the file tree rooted at this block.
+    pub fn file_size(&self) -> u64 {
+        self.data.len() as u64 + self.links.iter().map(|l| l.file_size).sum::<u64>()
+    }
+
+    /// Overwrites the content held in the block starting at `offset`.
+    ///
+    /// # Panics
+    ///
+    /// If the overwritten range extends past the content held in the block.
+    pub fn overwrite_data(&mut self, offset: usize, bytes: &[u8]) {
+        self.data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Replaces the link at `index` after the linked subtree has been modified.
+    pub fn set_link(&mut self, index: usize, link: FileLink) {
+        self.links[index] = link;
+    }
+
+    /// Renders the block with the given options, returning the link to it.
+    pub fn render(&self, cid_options: &CidOptions) -> (FileLink, Vec<u8>) {
+        let file_size = self.file_size();
+
+        let (cid, block) = match self.kind {
+            Kind::RawBlock => {
+                let cid = cid_options.create(crate::RAW, &self.data);
+                (cid, self.data.clone())
+            }
+            Kind::UnixFs(ty) => {
+                let links = self
+                    .links
+                    .iter()
+                    .map(|link| PBLink {
+                        Hash: Some(link.cid.to_bytes().into()),
+                        Name: Some("".into()),
+                        Tsize: Some(link.total_size),
+                    })
+                    .collect();
+
+                let mut data = UnixFs {
+                    Type: ty,
+                    Data: (!self.data.is_empty()).then_some(Cow::Borrowed(self.data.as_slice())),
+                    filesize: Some(file_size),
+                    blocksizes: self.links.iter().map(|l| l.file_size).collect(),
+                    ..Default::default()
+                };
+                self.metadata.apply(&mut data);
+
+                let flat = FlatUnixFs { links, data };
+                let mut out = Vec::with_capacity(flat.get_size());
+                let mut writer = Writer::new(&mut out);
+                flat.write_message(&mut writer)
+                    .expect("unsure how this could fail");
+                (cid_options.create(crate::DAG_PB, &out), out)
+            }
+        };
+
+        let total_size = block.len() as u64 + self.links.iter().map(|l| l.total_size).sum::<u64>();
+
+        let link = FileLink {
+            cid,
+            total_size,
+            file_size,
+        };
+
+        (link, block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileNode;
+    use crate::file::adder::{BalancedCollector, Chunker, FileAdder, FileAdderBuilder};
+    use crate::CidOptions;
+    use libipld::Cid;
+    use std::collections::HashMap;
+
+    fn builder() -> FileAdderBuilder {
+        FileAdder::builder()
+            .with_chunker(Chunker::Size(4))
+            .with_collector(BalancedCollector::with_branching_factor(3))
+    }
+
+    fn add(mut adder: FileAdder, mut content: &[u8]) -> Vec<(Cid, Vec<u8>)> {
+        let mut blocks = Vec::new();
+        while !content.is_empty() {
+            let (produced, consumed) = adder.push(content);
+            blocks.extend(produced);
+            content = &content[consumed..];
+        }
+        blocks.extend(adder.finish());
+        blocks
+    }
+
+    #[test]
+    fn append_after_subtrees_matches_adding_everything() {
+        let content = (0..=255u8).cycle().take(200).collect::<Vec<_>>();
+
+        for len in 0..content.len() {
+            let blocks = add(builder().build(), &content[..len]);
+            let root = blocks.last().unwrap().0;
+            let blocks = blocks.into_iter().collect::<HashMap<_, _>>();
+
+            // walk down the right spine of the tree, collecting the complete subtrees
+            let mut subtrees = Vec::new();
+            let mut spine = Vec::new();
+            let mut cid = root;
+            loop {
+                let node = FileNode::parse(&cid, &blocks[&cid]).unwrap();
+                let Some((last, rest)) = node.links().split_last() else {
+                    spine.push(node);
+                    break;
+                };
+                subtrees.push(rest.to_vec());
+                cid = last.cid;
+                spine.push(node);
+            }
+
+            let height = subtrees.len();
+            let subtrees = subtrees
+                .into_iter()
+                .enumerate()
+                .flat_map(|(level, links)| links.into_iter().map(move |l| (height - level - 1, l)));
+
+            let leaf = spine.last().unwrap().data().to_vec();
+            let adder = builder().build_with_subtrees(subtrees);
+            let appended = [leaf.as_slice(), &content[len..]].concat();
+            let appended = add(adder, &appended);
+
+            let expected = add(builder().build(), &content);
+            assert_eq!(
+                appended.last().unwrap().0,
+                expected.last().unwrap().0,
+                "appending to {len} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn unmodified_blocks_render_the_same() {
+        let content = vec![7u8; 256 * 1024 * 3 + 5];
+        let blocks = add(FileAdder::default(), &content);
+
+        for (cid, block) in &blocks {
+            let node = FileNode::parse(cid, block).unwrap();
+            let (link, rendered) = node.render(&CidOptions::default());
+            assert_eq!(&link.cid, cid);
+            assert_eq!(&rendered, block);
+        }
+
+        let (root, block) = blocks.last().unwrap();
+        let node = FileNode::parse(root, block).unwrap();
+        assert_eq!(node.file_size(), content.len() as u64);
+        assert_eq!(node.links().len(), 4);
+        assert!(node.is_file());
+    }
+
+    #[test]
+    fn overwrite_leaf() {
+        let (cid, block) = add(FileAdder::default(), b"foobar").pop().unwrap();
+
+        let mut node = FileNode::parse(&cid, &block).unwrap();
+        node.overwrite_data(3, b"xx");
+        let (link, _) = node.render(&CidOptions::default());
+
+        let (expected, block) = add(FileAdder::default(), b"fooxxr").pop().unwrap();
+        assert_eq!(link.cid, expected);
+        assert_eq!(link.total_size, block.len() as u64);
+        assert_eq!(link.file_size, 6);
+    }
+}
@@ -79,6 +79,24 @@ impl<'a> FileReader<'a> {
         Self::from_parts(inner, 0, metadata)
     }
 
+    /// Method for starting the file traversal from a raw leaf block, which holds the file content
+    /// as is.
+    pub fn from_raw_leaf(data: &'a [u8]) -> Self {
+        Self::from_raw_parts(0, data, Metadata::default(), data.len() as u64)
+    }
+
+    fn from_raw_parts(offset: u64, data: &'a [u8], metadata: Metadata, file_size: u64) -> Self {
+        FileReader {
+            offset,
+            end: Ending::Chunk(offset + data.len() as u64),
+            links: Vec::new(),
+            data,
+            blocksizes: Vec::new(),
+            metadata,
+            file_size,
+        }
+    }
+
     pub(crate) fn from_parsed(inner: FlatUnixFs<'a>) -> Result<Self, FileReadFailed> {
         let metadata = Metadata::from(&inner.data);
         Self::from_parts(inner, 0, metadata)
@@ -212,6 +230,24 @@ impl Traversal {
         FileReader::from_continued(self, tree_range.start, next_block)
     }
 
+    /// Continues the walk on the merkle tree with the given raw leaf block contents. Otherwise
+    /// the same as [`Traversal::continue_walk`].
+    pub fn continue_raw_leaf<'a>(
+        self,
+        next_block: &'a [u8],
+        tree_range: &Range<u64>,
+    ) -> Result<FileReader<'a>, FileReadFailed> {
+        self.last_ending
+            .check_is_suitable_next(self.last_offset, tree_range)?;
+
+        Ok(FileReader::from_raw_parts(
+            tree_range.start,
+            next_block,
+            self.metadata,
+            self.file_size,
+        ))
+    }
+
     /// Returns the total size of the file.
     pub fn file_size(&self) -> u64 {
         self.file_size
@@ -34,6 +34,16 @@ impl IdleFileVisit {
         self.start_from_reader(fr, &mut None)
     }
 
+    /// Begins the visitation from a raw leaf block, which is a single block file holding the
+    /// contents as is.
+    ///
+    /// Returns the same tuple as [`IdleFileVisit::start`], though there will never be a
+    /// `FileVisit` to continue the walk.
+    pub fn start_raw_leaf(self, block: &'_ [u8]) -> Result<FileVisitResult<'_>, FileReadFailed> {
+        let fr = FileReader::from_raw_leaf(block);
+        self.start_from_reader(fr, &mut None)
+    }
+
     pub(crate) fn start_from_parsed<'a>(
         self,
         block: FlatUnixFs<'a>,
@@ -155,13 +165,17 @@ impl FileVisit {
         cache: &mut Option<Cache>,
     ) -> Result<(&'a [u8], Option<Self>), FileReadFailed> {
         let traversal = self.state;
-        let (_, range) = self
+        let (cid, range) = self
             .pending
             .pop()
             .expect("User called continue_walk there must have been a next link");
 
         // interesting, validation doesn't trigger if the range is the same?
-        let fr = traversal.continue_walk(next, &range)?;
+        let fr = if cid.codec() == crate::RAW {
+            traversal.continue_raw_leaf(next, &range)?
+        } else {
+            traversal.continue_walk(next, &range)?
+        };
         let (content, traversal) = fr.content();
         match content {
             FileContent::Bytes(content) => {
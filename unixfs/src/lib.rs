@@ -12,6 +12,9 @@ extern crate alloc;
 
 use alloc::borrow::Cow;
 use core::fmt;
+use libipld::cid::Version;
+use libipld::multihash::{Code, Multihash, MultihashDigest};
+use libipld::Cid;
 
 /// File support.
 pub mod file;
@@ -35,6 +38,15 @@ pub mod walk;
 #[cfg(test)]
 pub(crate) mod test_support;
 
+/// Multicodec code of dag-pb blocks.
+pub(crate) const DAG_PB: u64 = 0x70;
+
+/// Multicodec code of raw blocks, used for the raw leaves of files.
+pub(crate) const RAW: u64 = 0x55;
+
+/// Multihash code of the identity hash, used for inlined blocks.
+pub(crate) const IDENTITY: u64 = 0x00;
+
 /// A link could not be transformed into a Cid.
 #[derive(Debug)]
 #[non_exhaustive]
@@ -161,6 +173,39 @@ impl Metadata {
         self.mtime()
             .map(|(seconds, nanos)| filetime::FileTime::from_unix_time(seconds, nanos))
     }
+
+    /// Returns the metadata with the given mode, of which only the lowest 12 bits for the
+    /// permissions, sticky bit, set user id and set group id are kept as in go-ipfs.
+    pub fn with_mode(self, mode: u32) -> Self {
+        Metadata {
+            mode: Some(mode & 0o7777),
+            ..self
+        }
+    }
+
+    /// Returns the metadata with the given modification time as seconds since the unix epoch and
+    /// the fractional nanoseconds.
+    pub fn with_mtime(self, seconds: i64, nanos: u32) -> Self {
+        Metadata {
+            mtime: Some((seconds, nanos)),
+            ..self
+        }
+    }
+
+    /// Returns true if neither the mode nor the modification time has been specified.
+    pub fn is_empty(&self) -> bool {
+        self.mode.is_none() && self.mtime.is_none()
+    }
+
+    /// Writes the metadata into the UnixFs message.
+    pub(crate) fn apply(&self, data: &mut UnixFs<'_>) {
+        data.mode = self.mode;
+        // zero fractional nanoseconds are left out as required by the spec
+        data.mtime = self.mtime.map(|(seconds, nanos)| pb::unixfs::UnixTime {
+            Seconds: seconds,
+            FractionalNanoseconds: (nanos != 0).then_some(nanos),
+        });
+    }
 }
 
 impl<'a> From<&'a UnixFs<'_>> for Metadata {
@@ -174,3 +219,82 @@ impl<'a> From<&'a UnixFs<'_>> for Metadata {
         Metadata { mode, mtime }
     }
 }
+
+/// Options controlling how the Cids of the created UnixFs blocks are formed, following the
+/// semantics of the `--cid-version`, `--hash` and `--inline` options of `ipfs add` in go-ipfs.
+///
+/// The default creates Cid version 0 links with sha2-256 multihash and no inlining.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CidOptions {
+    version: Version,
+    hash: Code,
+    inline_limit: Option<usize>,
+}
+
+impl Default for CidOptions {
+    fn default() -> Self {
+        CidOptions {
+            version: Version::V0,
+            hash: Code::Sha2_256,
+            inline_limit: None,
+        }
+    }
+}
+
+impl CidOptions {
+    /// The maximum amount of bytes which can be inlined with the identity multihash.
+    pub const MAX_INLINE_LIMIT: usize = 64;
+
+    /// Configures the Cid version. Version 0 can only be used for dag-pb blocks hashed with
+    /// sha2-256, so version 1 will be used for any other blocks regardless of this setting.
+    pub fn with_version(self, version: Version) -> Self {
+        CidOptions { version, ..self }
+    }
+
+    /// Configures the multihash used to hash the blocks, for example `Code::Blake3_256` or
+    /// `Code::Sha2_512`.
+    pub fn with_hash(self, hash: Code) -> Self {
+        CidOptions { hash, ..self }
+    }
+
+    /// Configures the blocks of at most `limit` bytes to be inlined into the Cid by using the
+    /// identity multihash. The limit is capped at [`CidOptions::MAX_INLINE_LIMIT`].
+    pub fn with_inline_limit(self, limit: Option<usize>) -> Self {
+        CidOptions {
+            inline_limit: limit.map(|limit| limit.min(Self::MAX_INLINE_LIMIT)),
+            ..self
+        }
+    }
+
+    /// Returns the configured Cid version.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Returns the configured multihash.
+    pub fn hash(&self) -> Code {
+        self.hash
+    }
+
+    /// Returns the configured inlining limit, if any.
+    pub fn inline_limit(&self) -> Option<usize> {
+        self.inline_limit
+    }
+
+    /// Creates the Cid for the given `block` of the multicodec `codec`.
+    pub fn create(&self, codec: u64, block: &[u8]) -> Cid {
+        if self.inline_limit.map(|limit| block.len() <= limit) == Some(true) {
+            let mh = Multihash::wrap(IDENTITY, block).expect("inline limit is capped at 64");
+            return Cid::new_v1(codec, mh);
+        }
+
+        let mh = self.hash.digest(block);
+
+        match self.version {
+            Version::V0 if codec == DAG_PB && self.hash == Code::Sha2_256 => {
+                Cid::new_v0(mh).expect("sha2_256 is the correct multihash for cidv0")
+            }
+            _ => Cid::new_v1(codec, mh),
+        }
+    }
+}
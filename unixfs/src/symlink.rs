@@ -4,6 +4,7 @@
 //! this is wrong.
 
 use crate::pb::{FlatUnixFs, UnixFs, UnixFsType};
+use crate::Metadata;
 use alloc::borrow::Cow;
 use quick_protobuf::{MessageWrite, Writer};
 
@@ -11,12 +12,22 @@ use quick_protobuf::{MessageWrite, Writer};
 /// `target_path` is valid relative unix path relative to the place in which this is used but
 /// targets validity cannot really be judged.
 pub fn serialize_symlink_block(target_path: &str, block_buffer: &mut Vec<u8>) {
+    serialize_symlink_block_with_metadata(target_path, &Metadata::default(), block_buffer)
+}
+
+/// Appends a dag-pb block for a symlink to the given target_path, like
+/// [`serialize_symlink_block`], with the given mode and modification time.
+pub fn serialize_symlink_block_with_metadata(
+    target_path: &str,
+    metadata: &Metadata,
+    block_buffer: &mut Vec<u8>,
+) {
     // should this fail or not? protobuf encoding cannot fail here, however we might create a too
     // large block but what's the limit?
     //
     // why not return a (Cid, Vec<u8>) like usually with cidv0? well...
 
-    let node = FlatUnixFs {
+    let mut node = FlatUnixFs {
         links: Vec::new(),
         data: UnixFs {
             Type: UnixFsType::Symlink,
@@ -24,6 +35,7 @@ pub fn serialize_symlink_block(target_path: &str, block_buffer: &mut Vec<u8>) {
             ..Default::default()
         },
     };
+    metadata.apply(&mut node.data);
 
     let mut writer = Writer::new(block_buffer);
     node.write_message(&mut writer).expect("unexpected failure");
@@ -150,6 +150,18 @@ impl Walker {
             return Ok(ContinuedWalk::File(segment, cid, path, metadata, *sz));
         }
 
+        if next.as_ref().map(|(cid, ..)| cid.codec()) == Some(crate::RAW) {
+            // raw leaves are single block files without any UnixFs message
+            let started = IdleFileVisit::default().start_raw_leaf(bytes)?;
+            return Ok(Self::visit_file(
+                current,
+                next,
+                pending,
+                should_continue,
+                started,
+            ));
+        }
+
         let flat = FlatUnixFs::try_from(bytes)?;
         let metadata = Metadata::from(&flat.data);
 
@@ -236,37 +248,13 @@ impl Walker {
                 })
             }
             UnixFsType::Raw | UnixFsType::File => {
-                let (bytes, file_size, metadata, step) =
-                    IdleFileVisit::default().start_from_parsed(flat, cache)?;
-                let (cid, name, depth) = next.take().expect("validated at new and earlier");
-                let file_continues = step.is_some();
-
-                match current {
-                    None => {
-                        let ie =
-                            InnerEntry::new_root_file(cid, metadata, &name, step, file_size, depth);
-                        *current = Some(ie);
-                    }
-                    Some(ie) => {
-                        ie.as_file(cid, &name, depth, metadata, step, file_size);
-                    }
-                };
-
-                let next_local = pending.pop();
-                if file_continues || next_local.is_some() {
-                    *next = next_local;
-                    *should_continue = true;
-                }
-
-                let segment = FileSegment::first(bytes, !file_continues);
-
-                let ie = current.as_ref().unwrap();
-                Ok(ContinuedWalk::File(
-                    segment,
-                    &ie.cid,
-                    &ie.path,
-                    &ie.metadata,
-                    file_size,
+                let started = IdleFileVisit::default().start_from_parsed(flat, cache)?;
+                Ok(Self::visit_file(
+                    current,
+                    next,
+                    pending,
+                    should_continue,
+                    started,
                 ))
             }
             UnixFsType::Metadata => Err(Error::UnsupportedType(flat.data.Type.into())),
@@ -304,6 +292,39 @@ impl Walker {
         }
     }
 
+    /// Moves to the file which was started from the block of `next`.
+    fn visit_file<'c>(
+        current: &'c mut Option<InnerEntry>,
+        next: &mut Option<(Cid, String, usize)>,
+        pending: &mut Vec<(Cid, String, usize)>,
+        should_continue: &mut bool,
+        (bytes, file_size, metadata, step): (&'c [u8], u64, Metadata, Option<FileVisit>),
+    ) -> ContinuedWalk<'c> {
+        let (cid, name, depth) = next.take().expect("validated at new and earlier");
+        let file_continues = step.is_some();
+
+        match current {
+            None => {
+                let ie = InnerEntry::new_root_file(cid, metadata, &name, step, file_size, depth);
+                *current = Some(ie);
+            }
+            Some(ie) => {
+                ie.as_file(cid, &name, depth, metadata, step, file_size);
+            }
+        };
+
+        let next_local = pending.pop();
+        if file_continues || next_local.is_some() {
+            *next = next_local;
+            *should_continue = true;
+        }
+
+        let segment = FileSegment::first(bytes, !file_continues);
+
+        let ie = current.as_ref().unwrap();
+        ContinuedWalk::File(segment, &ie.cid, &ie.path, &ie.metadata, file_size)
+    }
+
     /// Returns `true` if there are more links to walk over.
     pub fn should_continue(&self) -> bool {
         self.should_continue